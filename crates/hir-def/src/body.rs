@@ -19,7 +19,7 @@ use la_arena::{Arena, ArenaMap};
 use limit::Limit;
 use profile::Count;
 use rustc_hash::FxHashMap;
-use syntax::{ast, AstPtr, SyntaxNode, SyntaxNodePtr};
+use syntax::{ast, AstNode, AstPtr, SyntaxNode, SyntaxNodePtr};
 
 use crate::{
     attr::Attrs,
@@ -415,6 +415,44 @@ impl Body {
         pretty::print_body_hir(db, self, owner)
     }
 
+    /// Parses `text` as an expression to lower with [`Body::speculative_expr`]. Returns `None` if
+    /// it isn't a single expression, or if it contains items or macro calls, which have no `AstId`
+    /// outside of a file, or closures or async blocks, whose types are looked up in the body of
+    /// their owner.
+    pub fn parse_speculative_expr(text: &str) -> Option<ast::Expr> {
+        let text = text.trim();
+        let parse = ast::SourceFile::parse(&format!("const _: () = {text};"));
+        if !parse.errors().is_empty() {
+            return None;
+        }
+        let expr = parse.tree().syntax().descendants().find_map(ast::Expr::cast)?;
+        if expr.syntax().text() != text {
+            return None;
+        }
+        // Macro calls are items as well.
+        let is_supported = |node: SyntaxNode| match ast::BlockExpr::cast(node.clone()) {
+            Some(block) => !matches!(block.modifier(), Some(ast::BlockModifier::Async(_))),
+            None => !ast::Item::can_cast(node.kind()) && !ast::ClosureExpr::can_cast(node.kind()),
+        };
+        if !expr.syntax().descendants().all(is_supported) {
+            return None;
+        }
+        Some(expr)
+    }
+
+    /// Lowers `expr`, which isn't part of any file, as a body of `owner`, like an expression the
+    /// user evaluates in a scope of `owner`. The body isn't stored in the database, and has no
+    /// source map as its syntax isn't in the file of `owner`.
+    pub fn speculative_expr(db: &dyn DefDatabase, owner: DefWithBodyId, expr: ast::Expr) -> Body {
+        let module = owner.module(db);
+        let file_id = module.def_map(db)[module.local_id].definition_source(db).file_id;
+        let expander = Expander::new(db, file_id, module);
+        let params = None::<(ast::ParamList, std::iter::Empty<bool>)>;
+        let (mut body, _) = Body::new(db, expander, params, Some(expr), module.krate);
+        body.shrink_to_fit();
+        body
+    }
+
     fn new(
         db: &dyn DefDatabase,
         expander: Expander,
@@ -32,8 +32,8 @@ use crate::{
     builtin_type::{BuiltinFloat, BuiltinInt, BuiltinUint},
     db::DefDatabase,
     expr::{
-        dummy_expr_id, Array, Binding, BindingAnnotation, BindingId, ClosureKind, Expr, ExprId,
-        FloatTypeWrapper, Label, LabelId, Literal, MatchArm, Movability, Pat, PatId,
+        dummy_expr_id, Array, Binding, BindingAnnotation, BindingId, CaptureBy, ClosureKind, Expr,
+        ExprId, FloatTypeWrapper, Label, LabelId, Literal, MatchArm, Movability, Pat, PatId,
         RecordFieldPat, RecordLitField, Statement,
     },
     item_scope::BuiltinShadowMode,
@@ -511,6 +511,8 @@ impl ExprCollector<'_> {
                     ClosureKind::Closure
                 };
                 self.is_lowering_generator = prev_is_lowering_generator;
+                let capture_by =
+                    if e.move_token().is_some() { CaptureBy::Value } else { CaptureBy::Ref };
 
                 self.alloc_expr(
                     Expr::Closure {
@@ -519,6 +521,7 @@ impl ExprCollector<'_> {
                         ret_type,
                         body,
                         closure_kind,
+                        capture_by,
                     },
                     syntax_ptr,
                 )
@@ -590,7 +593,22 @@ impl ExprCollector<'_> {
                 let e = e.macro_call()?;
                 let macro_ptr = AstPtr::new(&e);
                 let id = self.collect_macro_call(e, macro_ptr, true, |this, expansion| {
-                    expansion.map(|it| this.collect_expr(it))
+                    expansion.map(|it| match it {
+                        ast::Expr::BlockExpr(block)
+                            if this
+                                .expander
+                                .current_file_id
+                                .is_offset_of_macro(this.db.upcast()) =>
+                        {
+                            this.collect_offset_of(block)
+                        }
+                        ast::Expr::BlockExpr(block)
+                            if this.expander.current_file_id.is_asm_macro(this.db.upcast()) =>
+                        {
+                            this.collect_inline_asm(block)
+                        }
+                        it => this.collect_expr(it),
+                    })
                 });
                 match id {
                     Some(id) => {
@@ -727,6 +745,31 @@ impl ExprCollector<'_> {
         self.alloc_expr(Expr::Match { expr, arms }, syntax_ptr)
     }
 
+    /// Lowers the expansion of `offset_of!(Container, a.b)`, which is
+    /// `{ let __ra_offset_of_container: Container; __ra_offset_of_container.a.b }`.
+    fn collect_offset_of(&mut self, e: BlockExpr) -> ExprId {
+        let syntax_ptr = AstPtr::new(&ast::Expr::from(e.clone()));
+        let container = e.statements().find_map(|s| match s {
+            ast::Stmt::LetStmt(l) => l.ty(),
+            _ => None,
+        });
+        let container = Interned::new(TypeRef::from_ast_opt(&self.ctx(), container));
+        let mut fields = vec![];
+        let mut expr = e.tail_expr();
+        while let Some(ast::Expr::FieldExpr(f)) = expr {
+            fields.push(f.field_access().map_or_else(Name::missing, |kind| kind.as_name()));
+            expr = f.expr();
+        }
+        fields.reverse();
+        self.alloc_expr(Expr::OffsetOf { container, fields: fields.into() }, syntax_ptr)
+    }
+
+    fn collect_inline_asm(&mut self, e: BlockExpr) -> ExprId {
+        let syntax_ptr = AstPtr::new(&ast::Expr::from(e.clone()));
+        let e = self.collect_block(e);
+        self.alloc_expr(Expr::InlineAsm { e }, syntax_ptr)
+    }
+
     fn collect_macro_call<F, T, U>(
         &mut self,
         mcall: ast::MacroCall,
@@ -822,6 +865,19 @@ impl ExprCollector<'_> {
                     expansion.statements().for_each(|stmt| this.collect_stmt(statements, stmt));
                     expansion.expr().and_then(|expr| match expr {
                         ast::Expr::MacroExpr(mac) => this.collect_macro_as_stmt(statements, mac),
+                        ast::Expr::BlockExpr(block)
+                            if this
+                                .expander
+                                .current_file_id
+                                .is_offset_of_macro(this.db.upcast()) =>
+                        {
+                            Some(this.collect_offset_of(block))
+                        }
+                        ast::Expr::BlockExpr(block)
+                            if this.expander.current_file_id.is_asm_macro(this.db.upcast()) =>
+                        {
+                            Some(this.collect_inline_asm(block))
+                        }
                         expr => Some(this.collect_expr(expr)),
                     })
                 }
@@ -891,10 +947,11 @@ impl ExprCollector<'_> {
         block: ast::BlockExpr,
         mk_block: impl FnOnce(Option<BlockId>, Box<[Statement]>, Option<ExprId>) -> Expr,
     ) -> ExprId {
-        let file_local_id = self.ast_id_map.ast_id(&block);
-        let ast_id = AstId::new(self.expander.current_file_id, file_local_id);
-
-        let block_id = if ItemTree::block_has_items(self.db, ast_id.file_id, &block) {
+        // Only blocks with items get an `AstId`, so that the blocks of expression snippets, which
+        // aren't in the `AstIdMap` of any file, can be lowered.
+        let file_id = self.expander.current_file_id;
+        let block_id = if ItemTree::block_has_items(self.db, file_id, &block) {
+            let ast_id = AstId::new(file_id, self.ast_id_map.ast_id(&block));
             Some(self.db.intern_block(BlockLoc {
                 ast_id,
                 module: self.expander.def_map.module_id(self.expander.module),
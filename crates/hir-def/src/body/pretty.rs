@@ -5,7 +5,9 @@ use std::fmt::{self, Write};
 use syntax::ast::HasName;
 
 use crate::{
-    expr::{Array, BindingAnnotation, BindingId, ClosureKind, Literal, Movability, Statement},
+    expr::{
+        Array, BindingAnnotation, BindingId, CaptureBy, ClosureKind, Literal, Movability, Statement,
+    },
     pretty::{print_generic_args, print_path, print_type_ref},
     type_ref::TypeRef,
 };
@@ -125,6 +127,23 @@ impl<'a> Printer<'a> {
         match expr {
             Expr::Missing => w!(self, "�"),
             Expr::Underscore => w!(self, "_"),
+            Expr::InlineAsm { e } => {
+                w!(self, "builtin#asm(");
+                self.print_expr(*e);
+                w!(self, ")");
+            }
+            Expr::OffsetOf { container, fields } => {
+                w!(self, "builtin#offset_of(");
+                self.print_type_ref(container);
+                w!(self, ", ");
+                for (i, field) in fields.iter().enumerate() {
+                    if i != 0 {
+                        w!(self, ".");
+                    }
+                    w!(self, "{}", field);
+                }
+                w!(self, ")");
+            }
             Expr::Path(path) => self.print_path(path),
             Expr::If { condition, then_branch, else_branch } => {
                 w!(self, "if ");
@@ -355,7 +374,7 @@ impl<'a> Printer<'a> {
                 self.print_expr(*index);
                 w!(self, "]");
             }
-            Expr::Closure { args, arg_types, ret_type, body, closure_kind } => {
+            Expr::Closure { args, arg_types, ret_type, body, closure_kind, capture_by } => {
                 match closure_kind {
                     ClosureKind::Generator(Movability::Static) => {
                         w!(self, "static ");
@@ -365,6 +384,9 @@ impl<'a> Printer<'a> {
                     }
                     _ => (),
                 }
+                if let CaptureBy::Value = capture_by {
+                    w!(self, "move ");
+                }
                 w!(self, "|");
                 for (i, (pat, ty)) in args.iter().zip(arg_types.iter()).enumerate() {
                     if i != 0 {
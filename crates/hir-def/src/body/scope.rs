@@ -86,7 +86,7 @@ impl ExprScopes {
 }
 
 impl ExprScopes {
-    fn new(body: &Body) -> ExprScopes {
+    pub(crate) fn new(body: &Body) -> ExprScopes {
         let mut scopes =
             ExprScopes { scopes: Arena::default(), scope_by_expr: FxHashMap::default() };
         let mut root = scopes.root_scope();
@@ -233,6 +233,7 @@ pub enum Expr {
         ret_type: Option<Interned<TypeRef>>,
         body: ExprId,
         closure_kind: ClosureKind,
+        capture_by: CaptureBy,
     },
     Tuple {
         exprs: Box<[ExprId]>,
@@ -241,6 +242,16 @@ pub enum Expr {
     Array(Array),
     Literal(Literal),
     Underscore,
+    /// `offset_of!(container, fields)`, which is a builtin macro.
+    OffsetOf {
+        container: Interned<TypeRef>,
+        fields: Box<[Name]>,
+    },
+    /// `asm!(..)` or `llvm_asm!(..)`, which are builtin macros. `e` is their expansion, which only
+    /// keeps the template strings.
+    InlineAsm {
+        e: ExprId,
+    },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -250,6 +261,16 @@ pub enum ClosureKind {
     Async,
 }
 
+/// Whether a closure captures its environment by reference or, if it is a `move` closure, by
+/// value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureBy {
+    /// `move |x| y + x`
+    Value,
+    /// `|x| y + x`
+    Ref,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Movability {
     Static,
@@ -400,6 +421,8 @@ impl Expr {
             },
             Expr::Literal(_) => {}
             Expr::Underscore => {}
+            Expr::OffsetOf { .. } => {}
+            Expr::InlineAsm { e } => f(*e),
         }
     }
 }
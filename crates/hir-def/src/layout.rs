@@ -1,6 +1,6 @@
 //! Definitions needed for computing data layout of types.
 
-use std::cmp;
+use std::{cmp, fmt};
 
 use la_arena::{Idx, RawIdx};
 pub use rustc_abi::{
@@ -92,6 +92,27 @@ pub enum LayoutError {
     SizeOverflow,
     TargetLayoutNotAvailable,
     HasPlaceholder,
+    /// A type which contains itself without a pointer in between, so it would be infinitely large.
+    RecursiveTypeWithoutIndirection,
+    /// A sized layout was needed for a dynamically sized type.
+    Unsized,
     NotImplemented,
     Unknown,
 }
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutError::UserError(message) => f.write_str(message),
+            LayoutError::SizeOverflow => f.write_str("size overflow"),
+            LayoutError::TargetLayoutNotAvailable => f.write_str("target data layout unavailable"),
+            LayoutError::HasPlaceholder => f.write_str("generic type not fully substituted"),
+            LayoutError::RecursiveTypeWithoutIndirection => {
+                f.write_str("recursive without indirection")
+            }
+            LayoutError::Unsized => f.write_str("unsized type"),
+            LayoutError::NotImplemented => f.write_str("not implemented"),
+            LayoutError::Unknown => f.write_str("unknown error"),
+        }
+    }
+}
@@ -120,6 +120,31 @@ fn main() { ""; }
     );
 }
 
+#[test]
+fn test_offset_of_expand() {
+    check(
+        r#"
+#[rustc_builtin_macro]
+macro_rules! offset_of {() => {}}
+
+fn main() {
+    offset_of!(Foo<u8, u16>, a.0.b,);
+}
+"#,
+        expect![[r#"
+#[rustc_builtin_macro]
+macro_rules! offset_of {() => {}}
+
+fn main() {
+     {
+        let __ra_offset_of_container: Foo<u8, u16>;
+        __ra_offset_of_container.a.0.b
+    };
+}
+"#]],
+    );
+}
+
 #[test]
 fn test_assert_expand() {
     check(
@@ -201,12 +226,115 @@ macro_rules! format_args {
 }
 
 fn main() {
-    $crate::fmt::Arguments::new_v1(&[], &[$crate::fmt::ArgumentV1::new(&(arg1(a, b, c)), $crate::fmt::Display::fmt), $crate::fmt::ArgumentV1::new(&(arg2), $crate::fmt::Display::fmt), ]);
+    $crate::fmt::Arguments::new_v1(&["", " ", ], &[$crate::fmt::ArgumentV1::new(&(arg1(a, b, c)), $crate::fmt::Display::fmt), $crate::fmt::ArgumentV1::new(&(arg2), $crate::fmt::Debug::fmt), ]);
 }
 "#]],
     );
 }
 
+#[test]
+fn test_format_args_expand_with_captured_and_named_args() {
+    check(
+        r#"
+#[rustc_builtin_macro]
+macro_rules! format_args {
+    ($fmt:expr) => ({ /* compiler built-in */ });
+    ($fmt:expr, $($args:tt)*) => ({ /* compiler built-in */ })
+}
+#[rustc_builtin_macro]
+macro_rules! format_args_nl {
+    ($fmt:expr) => ({ /* compiler built-in */ });
+    ($fmt:expr, $($args:tt)*) => ({ /* compiler built-in */ })
+}
+
+fn main() {
+    format_args_nl!("{x} = {:?}", a);
+    format_args!("{x} {y:?} {}", a, y = b);
+}
+"#,
+        expect![[r##"
+#[rustc_builtin_macro]
+macro_rules! format_args {
+    ($fmt:expr) => ({ /* compiler built-in */ });
+    ($fmt:expr, $($args:tt)*) => ({ /* compiler built-in */ })
+}
+#[rustc_builtin_macro]
+macro_rules! format_args_nl {
+    ($fmt:expr) => ({ /* compiler built-in */ });
+    ($fmt:expr, $($args:tt)*) => ({ /* compiler built-in */ })
+}
+
+fn main() {
+    $crate::fmt::Arguments::new_v1(&["", " = ", "\n", ], &[$crate::fmt::ArgumentV1::new(&(x), $crate::fmt::Display::fmt), $crate::fmt::ArgumentV1::new(&(a), $crate::fmt::Debug::fmt), ]);
+    $crate::fmt::Arguments::new_v1(&[], &[$crate::fmt::ArgumentV1::new(&(a), $crate::fmt::Display::fmt), $crate::fmt::ArgumentV1::new(&(b), $crate::fmt::Debug::fmt), $crate::fmt::ArgumentV1::new(&(x), $crate::fmt::Display::fmt), ]);
+}
+"##]],
+    );
+}
+
+#[test]
+fn test_format_args_expand_with_implicit_captures() {
+    check(
+        r#"
+#[rustc_builtin_macro]
+macro_rules! format_args {
+    ($fmt:expr) => ({ /* compiler built-in */ });
+    ($fmt:expr, $($args:tt)*) => ({ /* compiler built-in */ })
+}
+
+fn main() {
+    format_args!("{x} {x:?}");
+    format_args!("{x}", x = a);
+    format_args!("{x:?} {} {x} {0:>4}", a);
+}
+"#,
+        expect![[r##"
+#[rustc_builtin_macro]
+macro_rules! format_args {
+    ($fmt:expr) => ({ /* compiler built-in */ });
+    ($fmt:expr, $($args:tt)*) => ({ /* compiler built-in */ })
+}
+
+fn main() {
+    $crate::fmt::Arguments::new_v1(&["", " ", ], &[$crate::fmt::ArgumentV1::new(&(x), $crate::fmt::Display::fmt), $crate::fmt::ArgumentV1::new(&(x), $crate::fmt::Debug::fmt), ]);
+    $crate::fmt::Arguments::new_v1(&["", ], &[$crate::fmt::ArgumentV1::new(&(a), $crate::fmt::Display::fmt), ]);
+    $crate::fmt::Arguments::new_v1(&[], &[$crate::fmt::ArgumentV1::new(&(a), $crate::fmt::Display::fmt), $crate::fmt::ArgumentV1::new(&(x), $crate::fmt::Debug::fmt), ]);
+}
+"##]],
+    );
+}
+
+#[test]
+fn test_format_args_expand_captures_have_no_span() {
+    // Goto definition and rename can't follow the captured variables of the format string through
+    // the expansion, as their identifiers are not in the macro call.
+    check(
+        r#"
+#[rustc_builtin_macro]
+macro_rules! format_args {
+    ($fmt:expr) => ({ /* compiler built-in */ });
+    ($fmt:expr, $($args:tt)*) => ({ /* compiler built-in */ })
+}
+
+fn main() {
+    format_args!/*+tokenids*/("{x} {}", a);
+}
+"#,
+        expect![[r##"
+#[rustc_builtin_macro]
+macro_rules! format_args {
+    ($fmt:expr) => ({ /* compiler built-in */ });
+    ($fmt:expr, $($args:tt)*) => ({ /* compiler built-in */ })
+}
+
+fn main() {
+    // format_args!/*+tokenids*/("{x} {}"#1,#2 a#3)
+$crate#4294967295::fmt#4294967295::Arguments#4294967295::new_v1#4294967295(&#4294967295[#4294967295""#4294967295,#4294967295 " "#4294967295,#4294967295 ]#4294967295,#4294967295 &#4294967295[$crate#4294967295::fmt#4294967295::ArgumentV1#4294967295::new#4294967295(&#4294967295(#4294967295x#4294967295)#4294967295,#4294967295 $crate#4294967295::fmt#4294967295::Display#4294967295::fmt#4294967295)#4294967295,#4294967295 $crate#4294967295::fmt#4294967295::ArgumentV1#4294967295::new#4294967295(&#4294967295(#4294967295a#3)#4294967295,#4294967295 $crate#4294967295::fmt#4294967295::Display#4294967295::fmt#4294967295)#4294967295,#4294967295 ]#4294967295)#4294967295;
+}
+"##]],
+    );
+}
+
 #[test]
 fn test_format_args_expand_with_comma_exprs() {
     check(
@@ -229,7 +357,7 @@ macro_rules! format_args {
 }
 
 fn main() {
-    $crate::fmt::Arguments::new_v1(&[], &[$crate::fmt::ArgumentV1::new(&(a::<A, B>()), $crate::fmt::Display::fmt), $crate::fmt::ArgumentV1::new(&(b), $crate::fmt::Display::fmt), ]);
+    $crate::fmt::Arguments::new_v1(&["", " ", ], &[$crate::fmt::ArgumentV1::new(&(a::<A, B>()), $crate::fmt::Display::fmt), $crate::fmt::ArgumentV1::new(&(b), $crate::fmt::Debug::fmt), ]);
 }
 "#]],
     );
@@ -1296,6 +1296,7 @@ ok!();
 fn test_vertical_bar_with_pat() {
     check(
         r#"
+//- /main.rs crate:main edition:2018
 macro_rules! m { (|$pat:pat| ) => { ok!(); } }
 m! { |x| }
  "#,
@@ -1306,6 +1307,37 @@ ok!();
     );
 }
 
+#[test]
+fn test_vertical_bar_with_pat_param() {
+    // Since edition 2021, `pat` matches or-patterns, so it can't be followed by `|`.
+    check(
+        r#"
+macro_rules! m { (|$pat:pat_param| ) => { ok!(); } }
+m! { |x| }
+ "#,
+        expect![[r#"
+macro_rules! m { (|$pat:pat_param| ) => { ok!(); } }
+ok!();
+ "#]],
+    );
+}
+
+#[test]
+fn test_or_pattern_with_pat() {
+    check(
+        r#"
+macro_rules! m { ($pat:pat) => { match () { $pat => () } } }
+fn f() { m!(A | B); }
+ "#,
+        expect![[r#"
+macro_rules! m { ($pat:pat) => { match () { $pat => () } } }
+fn f() { match () {
+    A|B=>()
+}; }
+ "#]],
+    );
+}
+
 #[test]
 fn test_dollar_crate_lhs_is_not_meta() {
     check(
@@ -9,7 +9,10 @@ use rustc_hash::FxHashSet;
 use smallvec::{smallvec, SmallVec};
 
 use crate::{
-    body::scope::{ExprScopes, ScopeId},
+    body::{
+        scope::{ExprScopes, ScopeId},
+        Body,
+    },
     builtin_type::BuiltinType,
     db::DefDatabase,
     expr::{BindingId, ExprId, LabelId},
@@ -544,6 +547,14 @@ impl Resolver {
         })
     }
 
+    /// The body whose locals are in scope and the innermost scope of its expressions, if any.
+    pub fn expr_scope(&self) -> Option<(DefWithBodyId, ScopeId)> {
+        self.scopes().find_map(|scope| match scope {
+            Scope::ExprScope(it) => Some((it.owner, it.scope_id)),
+            _ => None,
+        })
+    }
+
     pub fn body_owner(&self) -> Option<DefWithBodyId> {
         self.scopes().find_map(|scope| match scope {
             Scope::ExprScope(it) => Some(it.owner),
@@ -729,6 +740,31 @@ pub fn resolver_for_scope(
     resolver_for_scope_(db, scopes, scope_id, r, owner)
 }
 
+/// The resolver of `body`, a speculative body of `owner` lowered by [`Body::speculative_expr`] to
+/// be evaluated in `scope_id` of `owner`. The items and imports of the blocks enclosing `scope_id`
+/// are visible in `body`, but not the locals of `owner`, which have no value.
+pub fn resolver_for_speculative_body(
+    db: &dyn DefDatabase,
+    owner: DefWithBodyId,
+    scope_id: ScopeId,
+    body: &Body,
+) -> Resolver {
+    let mut r = owner.resolver(db);
+    let scopes = db.expr_scopes(owner);
+    let scope_chain = scopes.scope_chain(Some(scope_id)).collect::<Vec<_>>();
+    for scope in scope_chain.into_iter().rev() {
+        if let Some(def_map) = scopes.block(scope).and_then(|block| db.block_def_map(block)) {
+            let root = def_map.root();
+            r = r.push_block_scope(def_map, root);
+        }
+    }
+    let body_scopes = Arc::new(ExprScopes::new(body));
+    match body_scopes.scope_chain(body_scopes.scope_for(body.body_expr)).last() {
+        Some(root) => r.push_expr_scope(owner, body_scopes, root),
+        None => r,
+    }
+}
+
 fn resolver_for_scope_(
     db: &dyn DefDatabase,
     scopes: Arc<ExprScopes>,
@@ -4,7 +4,8 @@
 use std::fmt::Write;
 
 use hir_expand::{
-    name::{AsName, Name},
+    mod_path::{ModPath, PathKind},
+    name::{name, AsName, Name},
     AstId,
 };
 use intern::Interned;
@@ -13,7 +14,7 @@ use syntax::ast::{self, HasName};
 use crate::{
     body::LowerCtx,
     builtin_type::{BuiltinInt, BuiltinType, BuiltinUint},
-    expr::Literal,
+    expr::{ArithOp, Literal},
     path::Path,
 };
 
@@ -380,7 +381,9 @@ impl TypeBound {
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ConstRefOrPath {
     Scalar(ConstRef),
-    Path(Name),
+    Path(ModPath),
+    /// An arithmetic expression like `N * 2`, whose operands are constants or paths themselves.
+    BinaryOp(Box<ConstRefOrPath>, ArithOp, Box<ConstRefOrPath>),
 }
 
 impl std::fmt::Display for ConstRefOrPath {
@@ -388,6 +391,15 @@ impl std::fmt::Display for ConstRefOrPath {
         match self {
             ConstRefOrPath::Scalar(s) => s.fmt(f),
             ConstRefOrPath::Path(n) => n.fmt(f),
+            ConstRefOrPath::BinaryOp(lhs, op, rhs) => {
+                let operand = |f: &mut std::fmt::Formatter<'_>, x: &ConstRefOrPath| match x {
+                    ConstRefOrPath::BinaryOp(..) => write!(f, "({x})"),
+                    _ => x.fmt(f),
+                };
+                operand(f, lhs)?;
+                write!(f, " {op} ")?;
+                operand(f, rhs)
+            }
         }
     }
 }
@@ -405,8 +417,24 @@ impl ConstRefOrPath {
     fn from_expr(expr: ast::Expr) -> Self {
         match expr {
             ast::Expr::PathExpr(p) => {
-                match p.path().and_then(|x| x.segment()).and_then(|x| x.name_ref()) {
-                    Some(x) => Self::Path(x.as_name()),
+                // Only paths made of plain names (and `Self`) without generic args are supported,
+                // e.g. `N` or `Self::N`.
+                let segments = p.path().and_then(|path| {
+                    path.segments()
+                        .map(|segment| {
+                            if segment.generic_arg_list().is_some() {
+                                return None;
+                            }
+                            match segment.kind()? {
+                                ast::PathSegmentKind::Name(name) => Some(name.as_name()),
+                                ast::PathSegmentKind::SelfTypeKw => Some(name![Self]),
+                                _ => None,
+                            }
+                        })
+                        .collect::<Option<Vec<_>>>()
+                });
+                match segments {
+                    Some(segments) => Self::Path(ModPath::from_segments(PathKind::Plain, segments)),
                     None => Self::Scalar(ConstRef::Unknown),
                 }
             }
@@ -423,6 +451,22 @@ impl ConstRefOrPath {
                 }
                 _ => Self::from_expr_opt(prefix_expr.expr()),
             },
+            ast::Expr::BinExpr(bin_expr) => match bin_expr.op_kind() {
+                Some(ast::BinaryOp::ArithOp(op)) => Self::BinaryOp(
+                    Box::new(Self::from_expr_opt(bin_expr.lhs())),
+                    op,
+                    Box::new(Self::from_expr_opt(bin_expr.rhs())),
+                ),
+                _ => Self::Scalar(ConstRef::Unknown),
+            },
+            ast::Expr::ParenExpr(paren_expr) => Self::from_expr_opt(paren_expr.expr()),
+            // Blocks like `{ N }`, which const generic arguments need.
+            ast::Expr::BlockExpr(block)
+                if block.modifier().is_none()
+                    && block.stmt_list().map_or(false, |it| it.statements().next().is_none()) =>
+            {
+                Self::from_expr_opt(block.tail_expr())
+            }
             ast::Expr::Literal(literal) => Self::Scalar(match literal.kind() {
                 ast::LiteralKind::IntNumber(num) => {
                     num.value().map(ConstRef::UInt).unwrap_or(ConstRef::Unknown)
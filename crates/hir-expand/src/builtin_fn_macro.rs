@@ -92,9 +92,7 @@ register_builtin! {
     (stringify, Stringify) => stringify_expand,
     (format_args, FormatArgs) => format_args_expand,
     (const_format_args, ConstFormatArgs) => format_args_expand,
-    // format_args_nl only differs in that it adds a newline in the end,
-    // so we use the same stub expansion for now
-    (format_args_nl, FormatArgsNl) => format_args_expand,
+    (format_args_nl, FormatArgsNl) => format_args_nl_expand,
     (llvm_asm, LlvmAsm) => asm_expand,
     (asm, Asm) => asm_expand,
     (global_asm, GlobalAsm) => global_asm_expand,
@@ -104,6 +102,7 @@ register_builtin! {
     (unreachable, Unreachable) => unreachable_expand,
     (log_syntax, LogSyntax) => log_syntax_expand,
     (trace_macros, TraceMacros) => trace_macros_expand,
+    (offset_of, OffsetOf) => offset_of_expand,
 
     EAGER:
     (compile_error, CompileError) => compile_error_expand,
@@ -217,6 +216,47 @@ fn assert_expand(
     ExpandResult::ok(expanded)
 }
 
+fn offset_of_expand(
+    _db: &dyn ExpandDatabase,
+    _id: MacroCallId,
+    tt: &tt::Subtree,
+) -> ExpandResult<tt::Subtree> {
+    // We expand `offset_of!(Container, a.b)` to
+    // ```
+    // { let __ra_offset_of_container: Container; __ra_offset_of_container.a.b }
+    // ```
+    // so that the container type and the fields resolve like in any other expression. Body lowering
+    // recognizes this expansion and turns it into an `Expr::OffsetOf`.
+    let is_comma =
+        |tt: &tt::TokenTree| matches!(tt, tt::TokenTree::Leaf(tt::Leaf::Punct(p)) if p.char == ',');
+    let mut token_trees = &tt.token_trees[..];
+    if let [rest @ .., last] = token_trees {
+        if is_comma(last) {
+            token_trees = rest;
+        }
+    }
+    // Fields can't contain commas, but the container type can, e.g. `Foo<A, B>`.
+    let Some(comma) = token_trees.iter().rposition(is_comma) else {
+        return ExpandResult::with_err(
+            tt::Subtree::empty(),
+            mbe::ExpandError::NoMatchingRule.into(),
+        );
+    };
+    let container = tt::Subtree {
+        delimiter: tt::Delimiter::unspecified(),
+        token_trees: token_trees[..comma].to_vec(),
+    };
+    let fields = tt::Subtree {
+        delimiter: tt::Delimiter::unspecified(),
+        token_trees: token_trees[comma + 1..].to_vec(),
+    };
+    let expanded = quote! {{
+        let __ra_offset_of_container: #container;
+        __ra_offset_of_container.#fields
+    }};
+    ExpandResult::ok(expanded)
+}
+
 fn file_expand(
     _db: &dyn ExpandDatabase,
     _id: MacroCallId,
@@ -234,18 +274,39 @@ fn file_expand(
 }
 
 fn format_args_expand(
+    db: &dyn ExpandDatabase,
+    id: MacroCallId,
+    tt: &tt::Subtree,
+) -> ExpandResult<tt::Subtree> {
+    format_args_expand_general(db, id, tt, "")
+}
+
+fn format_args_nl_expand(
+    db: &dyn ExpandDatabase,
+    id: MacroCallId,
+    tt: &tt::Subtree,
+) -> ExpandResult<tt::Subtree> {
+    format_args_expand_general(db, id, tt, "\n")
+}
+
+fn format_args_expand_general(
     _db: &dyn ExpandDatabase,
     _id: MacroCallId,
     tt: &tt::Subtree,
+    end_string: &str,
 ) -> ExpandResult<tt::Subtree> {
-    // We expand `format_args!("", a1, a2)` to
+    // We expand `format_args!("{} {:?}", a1, a2)` to
     // ```
-    // $crate::fmt::Arguments::new_v1(&[], &[
-    //   $crate::fmt::ArgumentV1::new(&arg1,$crate::fmt::Display::fmt),
-    //   $crate::fmt::ArgumentV1::new(&arg2,$crate::fmt::Display::fmt),
+    // $crate::fmt::Arguments::new_v1(&["", " "], &[
+    //   $crate::fmt::ArgumentV1::new(&(a1), $crate::fmt::Display::fmt),
+    //   $crate::fmt::ArgumentV1::new(&(a2), $crate::fmt::Debug::fmt),
     // ])
     // ```,
-    // which is still not really correct, but close enough for now
+    // with one argument for each placeholder, which is either an argument of the macro or a
+    // variable captured by the format string. This is still not really correct, but close enough
+    // for now. When the placeholders don't use the arguments once each in order, or use
+    // formatting options, we use each argument once and leave the pieces empty, as the arguments
+    // can't be put between them.
     let mut args = parse_exprs_with_sep(tt, ',');
 
     if args.is_empty() {
@@ -254,27 +315,196 @@ fn format_args_expand(
             mbe::ExpandError::NoMatchingRule.into(),
         );
     }
+    let mut names = Vec::with_capacity(args.len());
     for arg in &mut args {
+        let mut name = None;
         // Remove `key =`.
         if matches!(arg.token_trees.get(1), Some(tt::TokenTree::Leaf(tt::Leaf::Punct(p))) if p.char == '=')
         {
             // but not with `==`
             if !matches!(arg.token_trees.get(2), Some(tt::TokenTree::Leaf(tt::Leaf::Punct(p))) if p.char == '=' )
             {
+                if let Some(tt::TokenTree::Leaf(tt::Leaf::Ident(ident))) = arg.token_trees.first() {
+                    name = Some(ident.text.clone());
+                }
                 arg.token_trees.drain(..2);
             }
         }
+        names.push(name);
     }
-    let _format_string = args.remove(0);
-    let arg_tts = args.into_iter().flat_map(|arg| {
-        quote! { #DOLLAR_CRATE::fmt::ArgumentV1::new(&(#arg), #DOLLAR_CRATE::fmt::Display::fmt), }
+    let format_string = args.remove(0);
+    names.remove(0);
+    let format_string = match &*format_string.token_trees {
+        [tt::TokenTree::Leaf(tt::Leaf::Literal(lit))] => unquote_str(lit),
+        _ => None,
+    };
+    let (pieces, placeholders) =
+        format_string.as_deref().and_then(parse_format_string).unwrap_or_default();
+
+    // Resolve the placeholders to the arguments, or to the captured variables.
+    let mut next = 0;
+    let resolved: Vec<_> = placeholders
+        .iter()
+        .map(|placeholder| {
+            let arg = match &placeholder.arg {
+                FormatArgRef::Next => {
+                    next += 1;
+                    Ok(next - 1)
+                }
+                &FormatArgRef::Index(i) => Ok(i),
+                FormatArgRef::Name(name) => {
+                    match names.iter().position(|it| it.as_ref() == Some(name)) {
+                        Some(i) => Ok(i),
+                        None => Err(name.clone()),
+                    }
+                }
+            };
+            (arg, placeholder.trait_)
+        })
+        .collect();
+    // Captured variables have no side effects, so they can be repeated and put anywhere, but the
+    // arguments have to be used once each, in order.
+    let is_simple = placeholders.iter().all(|it| it.simple)
+        && resolved.iter().filter_map(|(arg, _)| arg.as_ref().ok().copied()).eq(0..args.len());
+
+    let captured = |text: SmolStr| tt::Subtree {
+        delimiter: tt::Delimiter::unspecified(),
+        token_trees: vec![tt::TokenTree::Leaf(tt::Leaf::Ident(tt::Ident {
+            text,
+            span: tt::TokenId::unspecified(),
+        }))],
+    };
+    let args: Vec<(tt::Subtree, FormatTrait)> = if is_simple {
+        let mut args = args.into_iter();
+        resolved
+            .into_iter()
+            .map(|(arg, trait_)| match arg {
+                Ok(_) => (args.next().unwrap(), trait_),
+                Err(name) => (captured(name), trait_),
+            })
+            .collect()
+    } else {
+        // Each argument once, formatted like the first placeholder using it. An argument used by
+        // placeholders of different traits only gets the first one, so `format_args!("{0} {0:?}",
+        // x)` only requires `x: Display`.
+        let mut traits = vec![None; args.len()];
+        let mut captures: Vec<(SmolStr, FormatTrait)> = vec![];
+        for (arg, trait_) in resolved {
+            match arg {
+                Ok(i) => {
+                    if let Some(it) = traits.get_mut(i) {
+                        it.get_or_insert(trait_);
+                    }
+                }
+                Err(name) => {
+                    if !captures.iter().any(|(it, _)| *it == name) {
+                        captures.push((name, trait_));
+                    }
+                }
+            }
+        }
+        let args = args
+            .into_iter()
+            .zip(traits)
+            .map(|(arg, trait_)| (arg, trait_.unwrap_or(FormatTrait::Display)));
+        args.chain(captures.into_iter().map(|(name, trait_)| (captured(name), trait_))).collect()
+    };
+    let arg_tts = args.into_iter().flat_map(|(arg, trait_)| {
+        let trait_ = match trait_ {
+            FormatTrait::Debug => quote!(Debug),
+            FormatTrait::Display => quote!(Display),
+        };
+        quote! { #DOLLAR_CRATE::fmt::ArgumentV1::new(&(#arg), #DOLLAR_CRATE::fmt::#trait_::fmt), }
     }.token_trees);
+    let mut pieces = if is_simple { pieces } else { vec![] };
+    if is_simple && !end_string.is_empty() {
+        if pieces.len() > placeholders.len() {
+            pieces.last_mut().unwrap().push_str(end_string);
+        } else {
+            pieces.push(end_string.to_owned());
+        }
+    }
+    let piece_tts = pieces.into_iter().flat_map(|piece| quote! { #piece, }.token_trees);
     let expanded = quote! {
-        #DOLLAR_CRATE::fmt::Arguments::new_v1(&[], &[##arg_tts])
+        #DOLLAR_CRATE::fmt::Arguments::new_v1(&[##piece_tts], &[##arg_tts])
     };
     ExpandResult::ok(expanded)
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FormatTrait {
+    Display,
+    Debug,
+}
+
+#[derive(Debug)]
+enum FormatArgRef {
+    Next,
+    Index(usize),
+    Name(SmolStr),
+}
+
+#[derive(Debug)]
+struct FormatPlaceholder {
+    arg: FormatArgRef,
+    trait_: FormatTrait,
+    /// Whether the placeholder has no formatting options, other than `?`.
+    simple: bool,
+}
+
+/// Splits a format string into the literal pieces before each placeholder (and the text after the
+/// last one, if it isn't empty) and the placeholders.
+fn parse_format_string(s: &str) -> Option<(Vec<String>, Vec<FormatPlaceholder>)> {
+    let mut pieces = vec![];
+    let mut placeholders = vec![];
+    let mut piece = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                piece.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                piece.push('}');
+            }
+            '{' => {
+                let mut inner = String::new();
+                loop {
+                    match chars.next()? {
+                        '}' => break,
+                        c => inner.push(c),
+                    }
+                }
+                let (arg, spec) = inner.split_once(':').unwrap_or((&inner, ""));
+                let arg = arg.trim();
+                let arg = if arg.is_empty() {
+                    FormatArgRef::Next
+                } else if let Ok(i) = arg.parse() {
+                    FormatArgRef::Index(i)
+                } else {
+                    FormatArgRef::Name(arg.into())
+                };
+                let trait_ =
+                    if spec.ends_with('?') { FormatTrait::Debug } else { FormatTrait::Display };
+                placeholders.push(FormatPlaceholder {
+                    arg,
+                    trait_,
+                    simple: spec.is_empty() || spec == "?",
+                });
+                pieces.push(std::mem::take(&mut piece));
+            }
+            '}' => return None,
+            c => piece.push(c),
+        }
+    }
+    if !piece.is_empty() {
+        pieces.push(piece);
+    }
+    Some((pieces, placeholders))
+}
+
 fn asm_expand(
     _db: &dyn ExpandDatabase,
     _id: MacroCallId,
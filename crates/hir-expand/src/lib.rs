@@ -325,6 +325,33 @@ impl HirFileId {
         }
     }
 
+    /// Return whether this file is an expansion of the builtin `offset_of!` macro
+    pub fn is_offset_of_macro(&self, db: &dyn db::ExpandDatabase) -> bool {
+        match self.macro_file() {
+            Some(macro_file) => {
+                let loc: MacroCallLoc = db.lookup_intern_macro_call(macro_file.macro_call_id);
+                matches!(loc.def.kind, MacroDefKind::BuiltIn(BuiltinFnLikeExpander::OffsetOf, _))
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_asm_macro(&self, db: &dyn db::ExpandDatabase) -> bool {
+        match self.macro_file() {
+            Some(macro_file) => {
+                let loc: MacroCallLoc = db.lookup_intern_macro_call(macro_file.macro_call_id);
+                matches!(
+                    loc.def.kind,
+                    MacroDefKind::BuiltIn(
+                        BuiltinFnLikeExpander::Asm | BuiltinFnLikeExpander::LlvmAsm,
+                        _
+                    )
+                )
+            }
+            None => false,
+        }
+    }
+
     /// Return whether this file is an attr macro
     pub fn is_attr_macro(&self, db: &dyn db::ExpandDatabase) -> bool {
         match self.macro_file() {
@@ -263,6 +263,8 @@ macro_rules! __known_path {
     (core::future::Future) => {};
     (core::future::IntoFuture) => {};
     (core::ops::Try) => {};
+    (core::fmt::Debug) => {};
+    (core::fmt::Display) => {};
     ($path:path) => {
         compile_error!("Please register your known path in the path module")
     };
@@ -266,6 +266,7 @@ pub mod known {
         iter,
         ops,
         future,
+        fmt,
         result,
         boxed,
         option,
@@ -283,6 +284,7 @@ pub mod known {
         Ok,
         Future,
         IntoFuture,
+        Display,
         Result,
         Option,
         Output,
@@ -327,6 +329,7 @@ pub mod known {
         llvm_asm,
         log_syntax,
         module_path,
+        offset_of,
         option_env,
         std_panic,
         stringify,
@@ -357,6 +360,7 @@ pub mod known {
         call_once,
         call_mut,
         call,
+        drop,
         eq,
         ne,
         ge,
@@ -1,24 +1,36 @@
 //! Constant evaluation details
 
+use std::sync::Arc;
+
 use base_db::CrateId;
-use chalk_ir::{BoundVar, DebruijnIndex, GenericArgData};
+use chalk_ir::{BoundVar, DebruijnIndex, GenericArgData, Scalar};
 use hir_def::{
-    expr::Expr,
+    body::{scope::ScopeId, Body},
+    expr::{ArithOp, Expr},
     path::Path,
-    resolver::{Resolver, ValueNs},
+    resolver::{resolver_for_speculative_body, ResolveValueResult, Resolver, TypeNs, ValueNs},
     type_ref::ConstRef,
-    ConstId, EnumVariantId,
+    AssocItemId, ConstId, DefWithBodyId, EnumVariantId, FunctionId, ItemContainerId, Lookup,
+    StaticId,
 };
 use la_arena::{Idx, RawIdx};
 use stdx::never;
 
 use crate::{
-    db::HirDatabase, infer::InferenceContext, layout::layout_of_ty, lower::ParamLoweringMode,
-    to_placeholder_idx, utils::Generics, Const, ConstData, ConstScalar, ConstValue, GenericArg,
-    Interner, MemoryMap, Substitution, Ty, TyBuilder,
+    db::HirDatabase,
+    infer::{infer_speculative_body, InferenceContext},
+    layout::layout_of_ty,
+    lower::ParamLoweringMode,
+    to_placeholder_idx,
+    utils::{generics, Generics},
+    Const, ConstData, ConstScalar, ConstValue, GenericArg, Interner, MemoryMap, Substitution, Ty,
+    TyBuilder, TyKind,
 };
 
-use super::mir::{interpret_mir, lower_to_mir, pad16, MirEvalError, MirLowerError};
+use super::mir::{
+    interpret_mir, interpret_mir_with_limit, lower_speculative_body_to_mir, lower_to_mir, pad16,
+    run_enabled_passes, CoverageMode, MirEvalError, MirLowerError, MirSpan, PanicStrategy,
+};
 
 /// Extension trait for [`Const`]
 pub trait ConstExt {
@@ -52,6 +64,22 @@ impl ConstExt for Const {
 pub enum ConstEvalError {
     MirLowerError(MirLowerError),
     MirEvalError(MirEvalError),
+    /// The evaluated code has undefined behavior at `span`.
+    UndefinedBehavior {
+        kind: UndefinedBehaviorKind,
+        span: MirSpan,
+    },
+}
+
+/// The kinds of undefined behavior that the evaluator detects with the span of their cause. This
+/// is not exhaustive, and other undefined behavior is reported as [`MirEvalError::UndefinedBehavior`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UndefinedBehaviorKind {
+    /// A write through a pointer derived from a shared reference to a type without interior
+    /// mutability, e.g. one transmuted or cast to a mutable pointer.
+    WriteThroughSharedRef,
+    /// A write into memory that can't be mutated, like the memory of a constant.
+    WriteToReadOnly,
 }
 
 impl From<MirLowerError> for ConstEvalError {
@@ -69,6 +97,92 @@ impl From<MirEvalError> for ConstEvalError {
     }
 }
 
+/// Something that only exists at runtime, which an evaluated body reaches through the calls in
+/// [`RuntimeDependency::frames`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeOnlyCause {
+    /// A call to a function of an `extern` block.
+    ForeignFunction(FunctionId),
+    /// A read of a static of an `extern` block, like a `static mut` set by foreign code.
+    ExternStatic(StaticId),
+    InlineAsm,
+}
+
+/// A function called on the way from an evaluated body to a [`RuntimeOnlyCause`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DependencyFrame {
+    pub func: FunctionId,
+    /// The body and the span of the call of `func`, if it is known.
+    pub call_site: Option<(DefWithBodyId, MirSpan)>,
+    /// Whether `func` calls itself, directly or through other functions. The frames of these calls
+    /// are collapsed into this one.
+    pub recursive: bool,
+}
+
+/// Why evaluation failed, if it is because of something that only exists at runtime, see
+/// [`ConstEvalError::runtime_dependency`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeDependency {
+    /// The calls from the evaluated body to the function containing the cause, outermost first.
+    pub frames: Vec<DependencyFrame>,
+    pub cause: RuntimeOnlyCause,
+}
+
+impl ConstEvalError {
+    /// The chain of calls to the runtime-only construct which made the evaluation fail, if the
+    /// evaluation failed because of one.
+    pub fn runtime_dependency(&self, db: &dyn HirDatabase) -> Option<RuntimeDependency> {
+        let mut frames: Vec<DependencyFrame> = vec![];
+        let mut push_frame = |func, call_site| {
+            match frames.iter().position(|it: &DependencyFrame| it.func == func) {
+                // Drop the frames of the cycle, and keep the call which entered it.
+                Some(i) => {
+                    frames.truncate(i + 1);
+                    frames[i].recursive = true;
+                }
+                None => frames.push(DependencyFrame { func, call_site, recursive: false }),
+            }
+        };
+        let mut e = match self {
+            ConstEvalError::MirLowerError(MirLowerError::InlineAsm) => {
+                return Some(RuntimeDependency { frames, cause: RuntimeOnlyCause::InlineAsm });
+            }
+            ConstEvalError::MirEvalError(e) => e,
+            _ => return None,
+        };
+        let cause = loop {
+            match e {
+                MirEvalError::InFunction(func, next, call_site) => {
+                    push_frame(*func, *call_site);
+                    e = next;
+                }
+                // The failure of a constant used by the body.
+                MirEvalError::ConstEvalError(next) => match &**next {
+                    ConstEvalError::MirEvalError(next) => e = next,
+                    ConstEvalError::MirLowerError(MirLowerError::InlineAsm) => {
+                        break RuntimeOnlyCause::InlineAsm
+                    }
+                    _ => return None,
+                },
+                MirEvalError::MirLowerError(func, error) => {
+                    let container = func.lookup(db.upcast()).container;
+                    if matches!(container, ItemContainerId::ExternBlockId(_)) {
+                        break RuntimeOnlyCause::ForeignFunction(*func);
+                    }
+                    if *error != MirLowerError::InlineAsm {
+                        return None;
+                    }
+                    push_frame(*func, None);
+                    break RuntimeOnlyCause::InlineAsm;
+                }
+                MirEvalError::ExternStatic(st) => break RuntimeOnlyCause::ExternStatic(*st),
+                _ => return None,
+            }
+        };
+        Some(RuntimeDependency { frames, cause })
+    }
+}
+
 pub(crate) fn path_to_const(
     db: &dyn HirDatabase,
     resolver: &Resolver,
@@ -77,8 +191,8 @@ pub(crate) fn path_to_const(
     args_lazy: impl FnOnce() -> Generics,
     debruijn: DebruijnIndex,
 ) -> Option<Const> {
-    match resolver.resolve_path_in_value_ns_fully(db.upcast(), path) {
-        Some(ValueNs::GenericParam(p)) => {
+    match resolver.resolve_path_in_value_ns(db.upcast(), path) {
+        Some(ResolveValueResult::ValueNs(ValueNs::GenericParam(p))) => {
             let ty = db.const_param_ty(p);
             let args = args_lazy();
             let value = match mode {
@@ -100,6 +214,20 @@ pub(crate) fn path_to_const(
             };
             Some(ConstData { ty, value }.intern(Interner))
         }
+        Some(ResolveValueResult::Partial(TypeNs::SelfType(impl_id), 1)) => {
+            // `Self::N` inside an impl. We can only evaluate it if the impl is not generic.
+            let name = &path.segments().last()?.name;
+            let const_id = db.impl_data(impl_id).items.iter().find_map(|item| match item {
+                AssocItemId::ConstId(c) if db.const_data(*c).name.as_ref() == Some(name) => {
+                    Some(*c)
+                }
+                _ => None,
+            })?;
+            if generics(db.upcast(), impl_id.into()).len() != 0 {
+                return None;
+            }
+            db.const_eval(const_id, Substitution::empty(Interner)).ok()
+        }
         _ => None,
     }
 }
@@ -165,6 +293,94 @@ pub fn try_const_usize(c: &Const) -> Option<u128> {
     }
 }
 
+/// How many basic blocks the MIR interpreter may execute to evaluate a constant of a type, which
+/// should be cheap as it happens whenever the type is displayed.
+const UNEVALUATED_CONST_EXECUTION_LIMIT: usize = 1000;
+
+/// Evaluates a constant of a type that lowering kept unevaluated, like `SIZE` in `[u8; SIZE]` or
+/// `N * 2` in `[T; N * 2]`. Each constant item involved is interpreted for at most
+/// [`UNEVALUATED_CONST_EXECUTION_LIMIT`] basic blocks. Returns `None` if the constant depends on
+/// generic parameters or can't be evaluated, or if the MIR config doesn't allow evaluating it.
+pub(crate) fn eval_unevaluated_const_query(db: &dyn HirDatabase, c: Const) -> Option<Const> {
+    let config = db.mir_config();
+    if !config.enable || config.local_crates_only {
+        return None;
+    }
+    let data = c.data(Interner);
+    let ConstValue::Concrete(cc) = &data.value else {
+        return None;
+    };
+    match &cc.interned {
+        ConstScalar::Bytes(..) => Some(c.clone()),
+        ConstScalar::Unknown => None,
+        ConstScalar::UnevaluatedConst(const_id, subst) => {
+            let body = db.optimized_mir_body((*const_id).into()).ok()?;
+            interpret_mir_with_limit(db, &body, subst.clone(), UNEVALUATED_CONST_EXECUTION_LIMIT)
+                .ok()
+        }
+        ConstScalar::BinaryOp(x) => {
+            let (lhs, op, rhs) = &**x;
+            let lhs = db.eval_unevaluated_const(lhs.clone())?;
+            let rhs = db.eval_unevaluated_const(rhs.clone())?;
+            fold_binary_op(&lhs, *op, &rhs, data.ty.clone())
+        }
+    }
+}
+
+pub(crate) fn eval_unevaluated_const_recover(
+    _: &dyn HirDatabase,
+    _: &[String],
+    _: &Const,
+) -> Option<Const> {
+    None
+}
+
+/// Computes `lhs op rhs` for two known integers of type `ty`, or returns `None` if it overflows or
+/// divides by zero, which is an error in a constant.
+pub(crate) fn fold_binary_op(lhs: &Const, op: ArithOp, rhs: &Const, ty: Ty) -> Option<Const> {
+    let bytes = |c: &Const| match &c.data(Interner).value {
+        ConstValue::Concrete(c) => match &c.interned {
+            ConstScalar::Bytes(b, _) => Some(b.clone()),
+            _ => None,
+        },
+        _ => None,
+    };
+    let (lhs, rhs) = (bytes(lhs)?, bytes(rhs)?);
+    let is_signed = match ty.kind(Interner) {
+        TyKind::Scalar(Scalar::Int(_)) => true,
+        TyKind::Scalar(Scalar::Uint(_)) => false,
+        _ => return None,
+    };
+    let read = |b: &[u8]| -> Option<i128> {
+        if is_signed {
+            Some(i128::from_le_bytes(pad16(b, true)))
+        } else {
+            u128::from_le_bytes(pad16(b, false)).try_into().ok()
+        }
+    };
+    let (a, b) = (read(&lhs)?, read(&rhs)?);
+    let bits = lhs.len() as u32 * 8;
+    let shift = || u32::try_from(b).ok().filter(|&x| x < bits);
+    let result = match op {
+        ArithOp::Add => a.checked_add(b)?,
+        ArithOp::Sub => a.checked_sub(b)?,
+        ArithOp::Mul => a.checked_mul(b)?,
+        ArithOp::Div => a.checked_div(b)?,
+        ArithOp::Rem => a.checked_rem(b)?,
+        ArithOp::Shl => a << shift()?,
+        ArithOp::Shr => a >> shift()?,
+        ArithOp::BitXor => a ^ b,
+        ArithOp::BitOr => a | b,
+        ArithOp::BitAnd => a & b,
+    };
+    let result_bytes = result.to_le_bytes()[0..lhs.len()].to_vec();
+    // Shifting left drops the bits shifted out, everything else must fit in the type.
+    if op != ArithOp::Shl && read(&result_bytes) != Some(result) {
+        return None;
+    }
+    Some(intern_const_scalar(ConstScalar::Bytes(result_bytes, MemoryMap::default()), ty))
+}
+
 pub(crate) fn const_eval_recover(
     _: &dyn HirDatabase,
     _: &[String],
@@ -187,8 +403,13 @@ pub(crate) fn const_eval_query(
     const_id: ConstId,
     subst: Substitution,
 ) -> Result<Const, ConstEvalError> {
+    if subst.len(Interner) != generics(db.upcast(), const_id.into()).len() {
+        return Err(ConstEvalError::MirEvalError(MirEvalError::TypeError(
+            "wrong number of generic arguments for const",
+        )));
+    }
     let def = const_id.into();
-    let body = db.mir_body(def)?;
+    let body = db.optimized_mir_body(def)?;
     let c = interpret_mir(db, &body, subst, false)?;
     Ok(c)
 }
@@ -211,12 +432,29 @@ pub(crate) fn const_eval_discriminant_variant(
         };
         return Ok(value);
     }
-    let mir_body = db.mir_body(def)?;
+    let mir_body = db.optimized_mir_body(def)?;
     let c = interpret_mir(db, &mir_body, Substitution::empty(Interner), false)?;
     let c = try_const_usize(&c).unwrap() as i128;
     Ok(c)
 }
 
+/// Evaluates `body`, a speculative body of `owner` lowered by [`Body::speculative_expr`], in the
+/// scope `scope_id` of `owner`, giving up after executing `execution_limit` basic blocks.
+pub fn eval_speculative_body(
+    db: &dyn HirDatabase,
+    owner: DefWithBodyId,
+    scope_id: ScopeId,
+    body: &Body,
+    execution_limit: usize,
+) -> Result<Const, ConstEvalError> {
+    let resolver = resolver_for_speculative_body(db.upcast(), owner, scope_id, body);
+    let infer = infer_speculative_body(db, owner, body, resolver.clone());
+    let mir_body = lower_speculative_body_to_mir(db, owner, body, &infer, resolver)?;
+    let mir_body = run_enabled_passes(db, Arc::new(mir_body));
+    let c = interpret_mir_with_limit(db, &mir_body, Substitution::empty(Interner), execution_limit)?;
+    Ok(c)
+}
+
 // FIXME: Ideally constants in const eval should have separate body (issue #7434), and this function should
 // get an `InferenceResult` instead of an `InferenceContext`. And we should remove `ctx.clone().resolve_all()` here
 // and make this function private. See the fixme comment on `InferenceContext::resolve_all`.
@@ -235,7 +473,10 @@ pub(crate) fn eval_to_const(
         }
     }
     let infer = ctx.clone().resolve_all();
-    if let Ok(mir_body) = lower_to_mir(ctx.db, ctx.owner, &ctx.body, &infer, expr) {
+    let panic_strategy = PanicStrategy::of_crate(db, ctx.resolver.krate());
+    if let Ok(mir_body) =
+        lower_to_mir(ctx.db, ctx.owner, &ctx.body, &infer, expr, panic_strategy, CoverageMode::Off)
+    {
         if let Ok(result) = interpret_mir(db, &mir_body, Substitution::empty(Interner), true) {
             return result;
         }
@@ -1,24 +1,31 @@
-use base_db::fixture::WithFixture;
+use base_db::{fixture::WithFixture, FileId};
 use chalk_ir::Substitution;
-use hir_def::db::DefDatabase;
+use hir_def::{body::Body, db::DefDatabase, ConstId, ModuleDefId};
 
 use crate::{
-    consteval::try_const_usize, db::HirDatabase, mir::pad16, test_db::TestDB, Const, ConstScalar,
-    Interner,
+    consteval::try_const_usize,
+    db::HirDatabase,
+    display::{render_const_limited, ConstRenderLimits},
+    mir::pad16,
+    test_db::TestDB,
+    Const, ConstScalar, Interner, TyBuilder,
 };
 
 use super::{
-    super::mir::{MirEvalError, MirLowerError},
-    ConstEvalError,
+    super::mir::{
+        interpret_raw_mir, MirConfig, MirEvalError, MirLowerError, MirSpan, PanicLocation,
+    },
+    eval_speculative_body, ConstEvalError, UndefinedBehaviorKind,
 };
 
 mod intrinsics;
 
 fn simplify(e: ConstEvalError) -> ConstEvalError {
     match e {
-        ConstEvalError::MirEvalError(MirEvalError::InFunction(_, e)) => {
+        ConstEvalError::MirEvalError(MirEvalError::InFunction(_, e, _)) => {
             simplify(ConstEvalError::MirEvalError(*e))
         }
+        ConstEvalError::MirEvalError(MirEvalError::ConstEvalError(e)) => simplify(*e),
         _ => e,
     }
 }
@@ -28,6 +35,18 @@ fn check_fail(ra_fixture: &str, error: ConstEvalError) {
     assert_eq!(eval_goal(ra_fixture).map_err(simplify), Err(error));
 }
 
+/// Checks that evaluation panics with `message`, and returns the location of the panic.
+#[track_caller]
+fn check_panic(ra_fixture: &str, message: &str) -> Option<PanicLocation> {
+    match eval_goal(ra_fixture).map_err(simplify) {
+        Err(ConstEvalError::MirEvalError(MirEvalError::Panic { message: actual, location })) => {
+            assert_eq!(actual, message);
+            location
+        }
+        r => panic!("Expected a panic but found {r:?}"),
+    }
+}
+
 #[track_caller]
 fn check_number(ra_fixture: &str, answer: i128) {
     let r = eval_goal(ra_fixture).unwrap();
@@ -48,9 +67,30 @@ fn check_number(ra_fixture: &str, answer: i128) {
 }
 
 fn eval_goal(ra_fixture: &str) -> Result<Const, ConstEvalError> {
-    let (db, file_id) = TestDB::with_single_file(ra_fixture);
+    eval_goal_with_config(ra_fixture, MirConfig::default())
+}
+
+fn eval_goal_with_config(ra_fixture: &str, config: MirConfig) -> Result<Const, ConstEvalError> {
+    let (mut db, file_id) = TestDB::with_single_file(ra_fixture);
+    db.set_mir_config(config);
+    let const_id = goal_const(&db, file_id);
+    let result = db.const_eval(const_id, Substitution::empty(Interner));
+    // The optimizations must not change the result of evaluation.
+    let raw_result = db
+        .mir_body(const_id.into())
+        .map_err(ConstEvalError::from)
+        .and_then(|body| Ok(interpret_raw_mir(&db, &body, Substitution::empty(Interner))?));
+    assert_eq!(
+        result.clone().map_err(simplify),
+        raw_result.map_err(simplify),
+        "optimized and raw MIR evaluate differently"
+    );
+    result
+}
+
+fn goal_const(db: &TestDB, file_id: FileId) -> ConstId {
     let module_id = db.module_for_file(file_id);
-    let def_map = module_id.def_map(&db);
+    let def_map = module_id.def_map(db);
     let scope = &def_map[module_id.local_id].scope;
     let const_id = scope
         .declarations()
@@ -65,7 +105,18 @@ fn eval_goal(ra_fixture: &str) -> Result<Const, ConstEvalError> {
             _ => None,
         })
         .unwrap();
-    db.const_eval(const_id, Substitution::empty(Interner))
+    const_id
+}
+
+/// Checks the rendering of the value of `GOAL` within `limits`, and the full rendering if
+/// something is elided.
+#[track_caller]
+fn check_render(ra_fixture: &str, limits: ConstRenderLimits, short: &str, full: Option<&str>) {
+    let (db, file_id) = TestDB::with_single_file(ra_fixture);
+    let c = db.const_eval(goal_const(&db, file_id), Substitution::empty(Interner)).unwrap();
+    let rendered = render_const_limited(&db, &c, limits);
+    assert_eq!(rendered.short, short);
+    assert_eq!(rendered.full.as_deref(), full);
 }
 
 #[test]
@@ -85,9 +136,36 @@ fn bit_op() {
     check_number(r#"const GOAL: i8 = 1 << 8"#, 0);
 }
 
+#[test]
+fn int_to_int_casts() {
+    check_number(r#"const GOAL: i32 = -1i8 as i32"#, -1);
+    check_number(r#"const GOAL: i32 = -128i8 as i32"#, -128);
+    check_number(r#"const GOAL: i32 = 255u8 as i32"#, 255);
+    check_number(r#"const GOAL: u32 = -1i8 as u32"#, u32::MAX as i128);
+    check_number(r#"const GOAL: i64 = -5i32 as i64"#, -5);
+    check_number(r#"const GOAL: u64 = 200u8 as u64"#, 200);
+    check_number(r#"const GOAL: u8 = 300i32 as u8"#, 44);
+    check_number(r#"const GOAL: i8 = 200u8 as i8"#, -56);
+    check_number(r#"const GOAL: i8 = -129i16 as i8"#, 127);
+    check_number(r#"const GOAL: u16 = -1i64 as u16"#, 65535);
+    check_number(
+        r#"
+    const fn widen(x: i8) -> i64 {
+        x as i64
+    }
+    const GOAL: i64 = widen(-3) + widen(100);
+        "#,
+        97,
+    );
+}
+
 #[test]
 fn casts() {
     check_number(r#"const GOAL: usize = 12 as *const i32 as usize"#, 12);
+    check_number(r#"const GOAL: u128 = 5u8 as u128"#, 5);
+    check_number(r#"const GOAL: i128 = -5i8 as i128"#, -5);
+    check_number(r#"const GOAL: u128 = -1i64 as u128"#, -1);
+    check_number(r#"const GOAL: u8 = 0x1_0000_0000_0000_0000_0000_0005u128 as u8"#, 5);
     check_number(
         r#"
     //- minicore: coerce_unsized, index, slice
@@ -112,6 +190,20 @@ fn casts() {
         "#,
         5,
     );
+    check_number(
+        r#"
+    struct S { a: i32, b: i32 }
+    fn second(s: &mut S) -> &mut i32 {
+        &mut s.b
+    }
+    const GOAL: i32 = {
+        let mut s = S { a: 1, b: 2 };
+        let p = second(&mut s) as *const i32;
+        unsafe { *p }
+    };
+        "#,
+        2,
+    );
     check_number(
         r#"
     //- minicore: coerce_unsized, index, slice
@@ -126,6 +218,230 @@ fn casts() {
         "#,
         4,
     );
+    check_number(
+        r#"
+    //- minicore: coerce_unsized, index, slice
+    fn to_array<T>(x: &[T]) -> &[T; 3] {
+        unsafe { &*(x as *const [T] as *const [T; 3]) }
+    }
+    const GOAL: i32 = {
+        let a = [20, 3, 15];
+        let x: &[i32] = &a;
+        let y = *to_array(x);
+        y[0] + y[1] + y[2]
+    };
+        "#,
+        38,
+    );
+    check_number(
+        r#"
+    //- minicore: coerce_unsized, index, slice
+    const GOAL: u8 = {
+        let a = [10, 20, 3, 15];
+        let s: &[u8] = &a;
+        let p = s as *const [u8] as *const [u8; 2];
+        let y = unsafe { *p };
+        y[0] + y[1]
+    };
+        "#,
+        30,
+    );
+    check_fail(
+        r#"
+    //- minicore: coerce_unsized, index, slice
+    fn to_array<T>(x: &[T]) -> &[T; 3] {
+        unsafe { &*(x as *const [T] as *const [T; 3]) }
+    }
+    const GOAL: i32 = {
+        let a = [10, 20];
+        let x: &[i32] = &a;
+        let y = *to_array(x);
+        y[0]
+    };
+        "#,
+        ConstEvalError::MirEvalError(MirEvalError::UndefinedBehavior(
+            "slice pointer cast to a longer array",
+        )),
+    );
+}
+
+#[track_caller]
+fn check_undefined_behavior(ra_fixture: &str, kind: UndefinedBehaviorKind) {
+    match eval_goal(ra_fixture).map_err(simplify) {
+        Err(ConstEvalError::UndefinedBehavior { kind: k, span: MirSpan::ExprId(_) }) => {
+            assert_eq!(k, kind)
+        }
+        r => panic!("expected {kind:?}, found {r:?}"),
+    }
+}
+
+#[test]
+fn mutation_through_shared_references() {
+    check_undefined_behavior(
+        r#"
+    extern "rust-intrinsic" {
+        pub fn transmute<T, U>(e: T) -> U;
+    }
+    const GOAL: i32 = {
+        let x = 1;
+        let r = &x;
+        let m: &mut i32 = unsafe { transmute(r) };
+        *m = 2;
+        x
+    };
+        "#,
+        UndefinedBehaviorKind::WriteThroughSharedRef,
+    );
+    check_undefined_behavior(
+        r#"
+    const GOAL: i32 = {
+        let x = 1;
+        let p = &x as *const i32 as *mut i32;
+        unsafe { *p = 2 };
+        x
+    };
+        "#,
+        UndefinedBehaviorKind::WriteThroughSharedRef,
+    );
+    check_undefined_behavior(
+        r#"
+    extern "rust-intrinsic" {
+        pub fn transmute<T, U>(e: T) -> U;
+    }
+    const X: &i32 = &5;
+    const GOAL: i32 = {
+        let m: &mut i32 = unsafe { transmute(X) };
+        *m = 6;
+        *X
+    };
+        "#,
+        UndefinedBehaviorKind::WriteToReadOnly,
+    );
+    check_number(
+        r#"
+    const GOAL: i32 = {
+        let mut x = 1;
+        let m = &mut x;
+        let s = &*m;
+        let y = *s;
+        *m = y + 1;
+        x
+    };
+        "#,
+        2,
+    );
+    check_number(
+        r#"
+    //- minicore: cell
+    use core::cell::Cell;
+    const GOAL: i32 = {
+        let c = Cell::new(1);
+        c.set(2);
+        let r = &c;
+        r.set(r.get() + 1);
+        c.get()
+    };
+        "#,
+        3,
+    );
+}
+
+#[test]
+fn interior_mutability() {
+    check_number(
+        r#"
+    //- minicore: cell
+    use core::cell::Cell;
+    fn increment(c: &Cell<u32>) {
+        c.set(c.get() + 1);
+    }
+    const GOAL: u32 = {
+        let c = Cell::new(5);
+        let r = &c;
+        increment(r);
+        increment(&c);
+        r.get()
+    };
+        "#,
+        7,
+    );
+    check_number(
+        r#"
+    //- minicore: refcell
+    use core::cell::RefCell;
+    const GOAL: i32 = {
+        let c = RefCell::new(1);
+        {
+            let mut m = c.borrow_mut();
+            *m = *m + 10;
+        }
+        let a = c.borrow();
+        let b = c.borrow();
+        *a + *b
+    };
+        "#,
+        22,
+    );
+    check_panic(
+        r#"
+    //- minicore: refcell
+    use core::cell::RefCell;
+    const GOAL: i32 = {
+        let c = RefCell::new(1);
+        let a = c.borrow_mut();
+        let b = c.borrow_mut();
+        *a + *b
+    };
+        "#,
+        "already borrowed",
+    );
+    check_panic(
+        r#"
+    //- minicore: refcell
+    use core::cell::RefCell;
+    const GOAL: i32 = {
+        let c = RefCell::new(1);
+        let a = c.borrow();
+        let b = c.borrow_mut();
+        *a + *b
+    };
+        "#,
+        "already borrowed",
+    );
+}
+
+#[test]
+fn unsupported_cast() {
+    check_fail(
+        r#"
+    struct Wrapper(u8);
+    const GOAL: u32 = {
+        let x = (Wrapper(1), 2u8);
+        x as u32
+    };
+        "#,
+        ConstEvalError::MirLowerError(MirLowerError::UnsupportedCast {
+            from: "(Wrapper, u8)".to_owned(),
+            to: "u32".to_owned(),
+        }),
+    );
+}
+
+#[test]
+fn array_to_array_cast() {
+    let fixture = r#"
+    const GOAL: [i8; 4] = {
+        let x = [1u8, 2, 3, 4];
+        x as [i8; 4]
+    };
+        "#;
+    let error =
+        MirLowerError::UnsupportedCast { from: "[u8; 4]".to_owned(), to: "[i8; 4]".to_owned() };
+    assert_eq!(
+        error.to_string(),
+        "casting `[u8; 4]` to `[i8; 4]` with `as` is invalid or not supported"
+    );
+    check_fail(fixture, ConstEvalError::MirLowerError(error));
 }
 
 #[test]
@@ -216,6 +532,79 @@ fn reference_autoderef() {
     );
 }
 
+#[test]
+fn reference_comparisons() {
+    check_number(
+        r#"
+    //- minicore: ord
+    const GOAL: bool = &3 < &5;
+        "#,
+        1,
+    );
+    check_number(
+        r#"
+    //- minicore: ord
+    const GOAL: bool = &&7 >= &&8;
+        "#,
+        0,
+    );
+    check_number(
+        r#"
+    //- minicore: ord
+    const GOAL: bool = &-1 < &1;
+        "#,
+        1,
+    );
+    check_number(
+        r#"
+    //- minicore: ord
+    const GOAL: u8 = {
+        let a = "ab" == "ab";
+        let b = "ab" == "abc";
+        let c = "ab" < "abc";
+        let d = "b" > "abc";
+        let e = "abc" <= "ab";
+        (a as u8) + 2 * (b as u8) + 4 * (c as u8) + 8 * (d as u8) + 16 * (e as u8)
+    };
+        "#,
+        13,
+    );
+    check_number(
+        r#"
+    //- minicore: coerce_unsized, slice, ord
+    const GOAL: u8 = {
+        let x: &[i32] = &[1, 2];
+        let y: &[i32] = &[1, 3];
+        let long: &[i32] = &[1, 2, 3, 4, 5, 6, 7, 8];
+        let long_negative: &[i32] = &[1, 2, 3, 4, 5, 6, 7, -8];
+        let a = x != y;
+        let b = long == long;
+        let c = long_negative < long;
+        let d = x < long;
+        (a as u8) + 2 * (b as u8) + 4 * (c as u8) + 8 * (d as u8)
+    };
+        "#,
+        15,
+    );
+    check_number(
+        r#"
+    //- minicore: coerce_unsized, slice, ord
+    const GOAL: u8 = {
+        let big: &[u128] = &[1 << 127];
+        let small: &[u128] = &[1];
+        let negative: &[f64] = &[-1.5, 2.0];
+        let positive: &[f64] = &[1.5, 2.0];
+        let a = small < big;
+        let b = negative < positive;
+        let c: &[f64] = &[-0.0];
+        let d: &[f64] = &[0.0];
+        (a as u8) + 2 * (b as u8) + 4 * ((c == d) as u8)
+    };
+        "#,
+        7,
+    );
+}
+
 #[test]
 fn overloaded_deref() {
     check_number(
@@ -325,6 +714,104 @@ fn function_call() {
     );
 }
 
+#[test]
+fn function_call_with_wrong_argument_count() {
+    check_fail(
+        r#"
+    const fn add(x: usize, y: usize) -> usize {
+        x + y
+    }
+    const GOAL: usize = add(1);
+    "#,
+        ConstEvalError::MirLowerError(MirLowerError::ArgumentCountMismatch {
+            expected: 2,
+            found: 1,
+        }),
+    );
+    check_fail(
+        r#"
+    struct S;
+    impl S {
+        const fn f(&self, x: usize) -> usize {
+            x
+        }
+    }
+    const GOAL: usize = S.f(1, 2);
+    "#,
+        ConstEvalError::MirLowerError(MirLowerError::ArgumentCountMismatch {
+            expected: 2,
+            found: 3,
+        }),
+    );
+    check_fail(
+        r#"
+    const fn f(x: usize) -> usize {
+        x
+    }
+    const GOAL: usize = {
+        let g: fn(usize) -> usize = f;
+        g()
+    };
+    "#,
+        ConstEvalError::MirLowerError(MirLowerError::ArgumentCountMismatch {
+            expected: 1,
+            found: 0,
+        }),
+    );
+}
+
+#[test]
+fn rvalue_receivers() {
+    check_number(
+        r#"
+    struct Counter {
+        n: i32,
+    }
+    impl Counter {
+        fn new(n: i32) -> Counter {
+            Counter { n }
+        }
+        fn bump(&mut self) -> &mut Counter {
+            self.n = self.n + 1;
+            self
+        }
+        fn get(&self) -> i32 {
+            self.n
+        }
+    }
+    const GOAL: i32 = Counter::new(1).bump().bump().get() + Counter::new(10).get();
+        "#,
+        13,
+    );
+    check_number(
+        r#"
+    //- minicore: drop, cell
+    use core::cell::Cell;
+    struct Guard<'a>(&'a Cell<i32>, i32);
+    impl Drop for Guard<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() * 10 + self.1);
+        }
+    }
+    impl Guard<'_> {
+        fn bump(&mut self) -> i32 {
+            self.1 = self.1 + 1;
+            self.1
+        }
+    }
+    const GOAL: i32 = {
+        let c = Cell::new(0);
+        let x = Guard(&c, 1).bump();
+        c.set(c.get() * 10 + x);
+        Guard(&c, 5).bump();
+        c.set(c.get() * 10);
+        c.get()
+    };
+        "#,
+        2260,
+    );
+}
+
 #[test]
 fn trait_basic() {
     check_number(
@@ -372,38 +859,81 @@ fn trait_method() {
 }
 
 #[test]
-fn generic_fn() {
+fn trait_default_method() {
     check_number(
         r#"
     trait Foo {
-        fn f(&self) -> u8;
+        fn f(&self) -> u32;
+        fn g(&self) -> u32 {
+            self.f() * 2
+        }
+        fn h() -> u32 {
+            5
+        }
     }
 
-    impl Foo for () {
-        fn f(&self) -> u8 {
-            0
+    impl Foo for u32 {
+        fn f(&self) -> u32 {
+            *self + 33
         }
     }
 
-    struct Succ<S>(S);
-
-    impl<T: Foo> Foo for Succ<T> {
-        fn f(&self) -> u8 {
-            self.0.f() + 1
+    struct S;
+    impl Foo for S {
+        fn f(&self) -> u32 {
+            1
+        }
+        fn g(&self) -> u32 {
+            100
         }
     }
 
-    const GOAL: u8 = Succ(Succ(())).f();
-    "#,
-        2,
-    );
-    check_number(
-        r#"
-    trait Foo {
-        fn f(&self) -> u8;
+    fn generic<T: Foo>(x: &T) -> u32 {
+        x.g() + T::h()
     }
 
-    impl Foo for u8 {
+    const GOAL: u32 = {
+        let x = 3;
+        x.g() + S.g() + generic(&x) + generic(&S) + <u32 as Foo>::h()
+    };
+    "#,
+        72 + 100 + 77 + 105 + 5,
+    );
+}
+
+#[test]
+fn generic_fn() {
+    check_number(
+        r#"
+    trait Foo {
+        fn f(&self) -> u8;
+    }
+
+    impl Foo for () {
+        fn f(&self) -> u8 {
+            0
+        }
+    }
+
+    struct Succ<S>(S);
+
+    impl<T: Foo> Foo for Succ<T> {
+        fn f(&self) -> u8 {
+            self.0.f() + 1
+        }
+    }
+
+    const GOAL: u8 = Succ(Succ(())).f();
+    "#,
+        2,
+    );
+    check_number(
+        r#"
+    trait Foo {
+        fn f(&self) -> u8;
+    }
+
+    impl Foo for u8 {
         fn f(&self) -> u8 {
             *self + 33
         }
@@ -532,6 +1062,72 @@ fn ifs() {
     );
 }
 
+#[test]
+fn diverging_branches() {
+    check_number(
+        r#"
+    const fn f(c: bool) -> i32 {
+        let x = if c { 7 } else { return 100; };
+        x + 1
+    }
+
+    const GOAL: i32 = f(true) + f(false);
+        "#,
+        108,
+    );
+    check_number(
+        r#"
+    const fn f(n: i32) -> i32 {
+        let y = match n { 0 => return 10, 1 => loop {}, _ => n * 2 };
+        y + 1
+    }
+
+    const GOAL: i32 = f(0) + f(3);
+        "#,
+        17,
+    );
+    check_number(
+        r#"
+    const GOAL: i32 = {
+        let mut i = 0;
+        let mut sum = 0;
+        loop {
+            i = i + 1;
+            let x = match i { 1 => continue, 5 => break, _ => i * 10 };
+            sum = sum + x;
+        }
+        sum
+    };
+        "#,
+        90,
+    );
+    check_number(
+        r#"
+    //- minicore: option, result
+    mod m {
+        enum Void {}
+        pub struct Opaque(Void);
+    }
+    enum Infallible {}
+    fn helper<T, E>(x: T) -> Result<T, E> {
+        Ok(x)
+    }
+    fn wrap<T>(x: T) -> (T, Option<m::Opaque>) {
+        (x, None)
+    }
+    const GOAL: i32 = {
+        let x: Result<i32, Infallible> = helper(5);
+        let y = match x {
+            Ok(v) => v,
+        };
+        let (z, _) = wrap(2);
+        y * z
+    };
+        "#,
+        10,
+    );
+}
+
 #[test]
 fn loops() {
     check_number(
@@ -592,6 +1188,249 @@ fn loops() {
     );
 }
 
+#[test]
+fn loop_break_values() {
+    check_number(
+        r#"
+    const GOAL: i32 = loop { break 5; };
+        "#,
+        5,
+    );
+    // The loop has the type of its break value, which picks the impl.
+    check_number(
+        r#"
+    trait Width {
+        fn width(&self) -> u8;
+    }
+    impl Width for u16 {
+        fn width(&self) -> u8 { 16 }
+    }
+    impl Width for i32 {
+        fn width(&self) -> u8 { 32 }
+    }
+    const GOAL: u8 = {
+        let x = loop { break 5u16; };
+        x.width() + x as u8
+    };
+        "#,
+        21,
+    );
+    check_number(
+        r#"
+    fn compute(x: i32) -> i32 {
+        x * 3
+    }
+    fn first_match(limit: i32, target: i32) -> i32 {
+        let mut i = 0;
+        let x: i32 = loop {
+            if i == target {
+                break compute(i);
+            }
+            if i >= limit {
+                break -1;
+            }
+            i = i + 1;
+        };
+        x
+    }
+    const GOAL: i32 = first_match(10, 4) * 100 + first_match(3, 5);
+        "#,
+        1199,
+    );
+    check_number(
+        r#"
+    const GOAL: i32 = {
+        let mut n = 0;
+        let found: i32 = 'outer: loop {
+            let mut j = 0;
+            loop {
+                if n * j == 12 {
+                    break 'outer n * 10 + j;
+                }
+                if j == 5 {
+                    break;
+                }
+                j = j + 1;
+            }
+            n = n + 1;
+        };
+        found
+    };
+        "#,
+        34,
+    );
+    check_number(
+        r#"
+    fn compute(x: i32) -> i32 {
+        x
+    }
+    fn goal(c: bool, d: bool) -> i32 {
+        let x: i32 = 'outer: loop {
+            loop {
+                if c {
+                    break 'outer compute(1);
+                }
+                if d {
+                    break 'outer 2;
+                }
+                break;
+            }
+            break 4;
+        };
+        x
+    }
+    const GOAL: i32 = goal(true, true) * 100 + goal(false, true) * 10 + goal(false, false);
+        "#,
+        124,
+    );
+}
+
+#[test]
+fn nested_loops_and_labeled_blocks() {
+    // Each `break` and `continue` targets a different level of a labeled block in a loop in a
+    // labeled loop.
+    check_number(
+        r#"
+    fn f(n: i32) -> i32 {
+        let mut acc = 0;
+        let mut i = 0;
+        'outer: loop {
+            i = i + 1;
+            let mut j = 0;
+            'inner: loop {
+                j = j + 1;
+                let v = 'block: {
+                    if j == 1 {
+                        break 'block 100;
+                    }
+                    if j == 2 {
+                        continue 'inner;
+                    }
+                    if i == 2 {
+                        continue 'outer;
+                    }
+                    if i == n {
+                        break 'outer;
+                    }
+                    if j == 4 {
+                        break 'inner;
+                    }
+                    j
+                };
+                acc = acc + v;
+            }
+            acc = acc + 1000;
+        }
+        acc
+    }
+    const GOAL: i32 = f(3);
+        "#,
+        1303,
+    );
+}
+
+#[test]
+fn while_let() {
+    check_number(
+        r#"
+    //- minicore: option
+    struct Stack {
+        items: [i32; 4],
+        len: usize,
+    }
+    impl Stack {
+        fn pop(&mut self) -> Option<&i32> {
+            if self.len == 0 {
+                return None;
+            }
+            self.len = self.len - 1;
+            Some(&self.items[self.len])
+        }
+    }
+    const GOAL: i32 = {
+        let mut stack = Stack { items: [1, 2, 3, 4], len: 4 };
+        let mut acc = 0;
+        while let Some(item) = stack.pop() {
+            acc = acc * 10 + *item;
+        }
+        acc
+    };
+        "#,
+        4321,
+    );
+    check_number(
+        r#"
+    //- minicore: drop, cell, option
+    use core::cell::Cell;
+    struct Guard<'a>(&'a Cell<i32>, i32);
+    impl Drop for Guard<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() * 10 + self.1);
+        }
+    }
+    fn next<'a>(c: &'a Cell<i32>, i: &mut i32) -> Option<Guard<'a>> {
+        *i = *i + 1;
+        if *i > 3 {
+            None
+        } else {
+            Some(Guard(c, *i))
+        }
+    }
+    const GOAL: i32 = {
+        let c = Cell::new(0);
+        let mut i = 0;
+        while let Some(_g) = next(&c, &mut i) {
+            if i == 2 {
+                continue;
+            }
+            c.set(c.get() * 10);
+        }
+        c.get()
+    };
+        "#,
+        1203,
+    );
+}
+
+#[test]
+fn labeled_block_break_without_value() {
+    check_number(
+        r#"
+    const GOAL: u8 = {
+        let mut x = 1;
+        let unit: () = 'a: {
+            if x == 1 {
+                break 'a;
+            }
+            x = 2;
+        };
+        let () = unit;
+        'b: {
+            x = x + 3;
+            break 'b;
+            x = 10;
+        }
+        x
+    };
+        "#,
+        4,
+    );
+    let unit = eval_goal(
+        r#"
+    const GOAL: () = 'a: { break 'a; };
+        "#,
+    )
+    .unwrap();
+    assert_eq!(unit.data(Interner).ty, TyBuilder::unit());
+    match &unit.data(Interner).value {
+        chalk_ir::ConstValue::Concrete(c) => match &c.interned {
+            ConstScalar::Bytes(b, _) => assert!(b.is_empty()),
+            x => panic!("Expected unit but found {:?}", x),
+        },
+        _ => panic!("result of const eval wasn't a concrete const"),
+    }
+}
+
 #[test]
 fn for_loops() {
     check_number(
@@ -629,6 +1468,116 @@ fn for_loops() {
     );
 }
 
+#[test]
+fn for_loops_drop_the_iterator() {
+    check_number(
+        r#"
+    //- minicore: iterator, drop, cell
+    use core::cell::Cell;
+    struct Counter<'a> {
+        log: &'a Cell<i32>,
+        id: i32,
+        n: i32,
+    }
+    impl Drop for Counter<'_> {
+        fn drop(&mut self) {
+            self.log.set(self.log.get() * 10 + self.id);
+        }
+    }
+    impl Iterator for Counter<'_> {
+        type Item = i32;
+        fn next(&mut self) -> Option<i32> {
+            if self.n == 5 {
+                None
+            } else {
+                self.n = self.n + 1;
+                Some(self.n)
+            }
+        }
+    }
+    fn sum_until(log: &Cell<i32>, id: i32, stop: i32) -> i32 {
+        let mut sum = 0;
+        for x in (Counter { log, id, n: 0 }) {
+            if x == stop {
+                return sum;
+            }
+            sum = sum + x;
+        }
+        sum
+    }
+    fn first_over(log: &Cell<i32>, id: i32, limit: i32) -> i32 {
+        'outer: loop {
+            for x in (Counter { log, id, n: 0 }) {
+                if x > limit {
+                    break 'outer x;
+                }
+            }
+            break 0;
+        }
+    }
+    const GOAL: i32 = {
+        let log = Cell::new(0);
+        let a = sum_until(&log, 1, 3);
+        let b = sum_until(&log, 2, 10);
+        let mut c = 0;
+        for x in (Counter { log: &log, id: 3, n: 2 }) {
+            if x == 4 {
+                break;
+            }
+            c = c + x;
+        }
+        let d = first_over(&log, 4, 1);
+        log.get() * 100 + a + b + c + d
+    };
+        "#,
+        123423,
+    );
+    check_number(
+        r#"
+    //- minicore: iterator, drop, cell
+    use core::cell::Cell;
+    struct Counter<'a> {
+        log: &'a Cell<i32>,
+        n: i32,
+    }
+    impl Drop for Counter<'_> {
+        fn drop(&mut self) {
+            self.log.set(self.log.get() + 1);
+        }
+    }
+    impl Iterator for Counter<'_> {
+        type Item = i32;
+        fn next(&mut self) -> Option<i32> {
+            if self.n == 5 {
+                None
+            } else {
+                self.n = self.n + 1;
+                Some(self.n)
+            }
+        }
+    }
+    const GOAL: i32 = {
+        let log = Cell::new(0);
+        let mut s = 0;
+        {
+            let mut it = Counter { log: &log, n: 0 };
+            for x in &mut it {
+                if x == 2 {
+                    break;
+                }
+                s = s + x;
+            }
+            for x in &mut it {
+                s = s * 10 + x;
+            }
+        }
+        log.get() * 10000 + s
+    };
+        "#,
+        11345,
+    );
+}
+
 #[test]
 fn ranges() {
     check_number(
@@ -728,19 +1677,137 @@ fn unions() {
 }
 
 #[test]
-fn tuples() {
+fn union_literals() {
     check_number(
         r#"
-    const GOAL: u8 = {
-        let a = (10, 20, 3, 15);
-        a.1
-    };
+        union U {
+            f1: i64,
+            f2: (i32, i32),
+        }
+
+        const C: U = U { f2: (3, 4) };
+        const GOAL: i32 = unsafe { C.f2.1 };
         "#,
-        20,
+        4,
     );
-    check_number(
+    check_fail(
         r#"
-    const GOAL: u8 = {
+        union U {
+            f1: i64,
+            f2: i32,
+        }
+
+        const GOAL: i64 = unsafe { U {}.f1 };
+        "#,
+        ConstEvalError::MirLowerError(MirLowerError::UnionLiteralFieldCount {
+            union: "U".to_owned(),
+            fields: 0,
+        }),
+    );
+    check_fail(
+        r#"
+        union U {
+            f1: i64,
+            f2: i32,
+        }
+
+        const GOAL: i64 = unsafe { U { f1: 1, f2: 2 }.f1 };
+        "#,
+        ConstEvalError::MirLowerError(MirLowerError::UnionLiteralFieldCount {
+            union: "U".to_owned(),
+            fields: 2,
+        }),
+    );
+    check_fail(
+        r#"
+        union U {
+            f1: i64,
+            f2: i32,
+        }
+
+        const BASE: U = U { f2: 2 };
+        const GOAL: i64 = unsafe { U { f1: 1, ..BASE }.f1 };
+        "#,
+        ConstEvalError::MirLowerError(MirLowerError::UnionLiteralWithSpread("U".to_owned())),
+    );
+    let e = eval_goal(
+        r#"
+        union U {
+            f1: i64,
+            f2: i32,
+        }
+
+        const GOAL: i64 = unsafe { U { f3: 2 }.f1 };
+        "#,
+    );
+    assert!(matches!(
+        e,
+        Err(ConstEvalError::MirLowerError(MirLowerError::UnresolvedField(MirSpan::ExprId(_))))
+    ));
+}
+
+#[test]
+fn enum_variant_literal_with_spread() {
+    // Unlike for structs, the base could be another variant (rustc rejects this with E0436).
+    check_fail(
+        r#"
+        enum E {
+            A { x: i32, y: i32 },
+            B { x: i32 },
+        }
+
+        const BASE: E = E::B { x: 1 };
+        const GOAL: i32 = match (E::A { x: 5, ..BASE }) {
+            E::A { x, y } => x + y,
+            E::B { x } => x,
+        };
+        "#,
+        ConstEvalError::MirLowerError(MirLowerError::EnumVariantLiteralWithSpread(
+            "E::A".to_owned(),
+        )),
+    );
+}
+
+#[test]
+fn zero_sized_fields() {
+    check_number(
+        r#"
+    //- minicore: phantom_data
+    use core::marker::PhantomData;
+    struct Marker;
+    struct S<T> {
+        p: PhantomData<T>,
+        x: i32,
+        m: Marker,
+        u: (),
+    }
+    fn make<T>(x: i32) -> S<T> {
+        S { p: PhantomData, x, m: Marker, u: () }
+    }
+    const GOAL: i32 = {
+        let s: S<u64> = make(5);
+        let S { x, .. } = s;
+        x + make::<u8>(2).x
+    };
+        "#,
+        7,
+    );
+}
+
+#[test]
+fn tuples() {
+    check_number(
+        r#"
+    const GOAL: u8 = {
+        let a = (10, 20, 3, 15);
+        a.1
+    };
+        "#,
+        20,
+    );
+    check_number(
+        r#"
+    const GOAL: u8 = {
         let mut a = (10, 20, 3, 15);
         a.1 = 2;
         a.0 + a.1 + a.2 + a.3
@@ -834,6 +1901,41 @@ fn pattern_matching_literal() {
     );
 }
 
+#[test]
+fn pattern_matching_integer_switch() {
+    check_number(
+        r#"
+    const fn f(x: i8) -> i32 {
+        match x {
+            0 => 1,
+            -1 | 5 => 2,
+            5 => 1000,
+            -128 => 4,
+            x if x > 100 => 8,
+            7 => 16,
+            _ => 32,
+        }
+    }
+    const GOAL: i32 = f(0) + f(-1) + f(5) + f(-128) + f(101) + f(7) + f(3);
+        "#,
+        65,
+    );
+    check_number(
+        r#"
+    const fn f(x: char) -> u8 {
+        match x {
+            'a' => 1,
+            'b' | 'c' => 2,
+            'é' => 4,
+            _ => 8,
+        }
+    }
+    const GOAL: u8 = f('a') + f('c') + f('é') + f('z');
+        "#,
+        15,
+    );
+}
+
 #[test]
 fn pattern_matching_ergonomics() {
     check_number(
@@ -1020,6 +2122,48 @@ fn options() {
     );
 }
 
+#[test]
+fn let_chains() {
+    check_number(
+        r#"
+    //- minicore: option
+    fn f(a: i32, opt: Option<i32>) -> i32 {
+        if a > 0 && let Some(x) = opt && x < 10 {
+            x
+        } else {
+            100
+        }
+    }
+    fn g(a: Option<i32>, b: Option<i32>) -> i32 {
+        if let Some(x) = a && x > 1 && let Some(y) = b {
+            x * y
+        } else {
+            0
+        }
+    }
+    const GOAL: i32 = f(1, Some(3)) + f(0, Some(4)) + f(1, None) + f(1, Some(20))
+        + g(Some(2), Some(5)) + g(Some(1), Some(5)) + g(Some(2), None) + g(None, Some(5));
+        "#,
+        313,
+    );
+    check_number(
+        r#"
+    //- minicore: option
+    const GOAL: i32 = {
+        let zero = 0;
+        let a = false && 1 / zero == 0;
+        let b = true || 1 / zero == 0;
+        let mut n = 0;
+        while n < 10 && let Some(m) = Some(n + 1) {
+            n = m;
+        }
+        (a as i32) + 2 * (b as i32) + n
+    };
+        "#,
+        12,
+    );
+}
+
 #[test]
 fn from_trait() {
     check_number(
@@ -1143,6 +2287,48 @@ fn or_pattern() {
     );
 }
 
+#[test]
+fn matches_macro() {
+    check_number(
+        r#"
+    //- minicore: matches
+    enum Mode { A, B, C }
+    const VALUE: Mode = Mode::B;
+    const VALUE_B: u8 = 3;
+    const OK: bool = matches!(VALUE, Mode::A | Mode::B) && VALUE_B != 0;
+    const NOT_OK: bool = matches!(VALUE, Mode::A | Mode::C) && VALUE_B != 0;
+    const GOAL: u8 = OK as u8 * 10 + NOT_OK as u8;
+        "#,
+        10,
+    );
+    check_number(
+        r#"
+    //- minicore: matches
+    enum Shape { Circle(i32), Rect { w: i32, h: i32 }, Empty }
+    const fn is_big(s: Shape) -> bool {
+        matches!(s, Shape::Circle(r) if r > 10) || matches!(s, Shape::Rect { w, h } if w * h > 100)
+    }
+    const GOAL: u8 = is_big(Shape::Circle(20)) as u8
+        + 2 * is_big(Shape::Circle(5)) as u8
+        + 4 * is_big(Shape::Rect { w: 20, h: 20 }) as u8
+        + 8 * is_big(Shape::Rect { w: 2, h: 2 }) as u8
+        + 16 * is_big(Shape::Empty) as u8;
+        "#,
+        5,
+    );
+    check_number(
+        r#"
+    //- minicore: matches, option
+    const A: Option<i32> = Some(3);
+    const B: i32 = 0;
+    const GOAL: bool = (matches!(A, Some(1) | Some(3)) || B / B == 0)
+        && !(matches!(A, None) && B / B == 0)
+        && matches!(B, 0 | 1,);
+        "#,
+        1,
+    );
+}
+
 #[test]
 fn function_pointer() {
     check_number(
@@ -1187,6 +2373,164 @@ fn function_pointer() {
     );
 }
 
+#[test]
+fn closure_as_function_pointer() {
+    check_number(
+        r#"
+    const GOAL: u8 = {
+        let f: fn() -> u8 = || 5;
+        f()
+    };
+        "#,
+        5,
+    );
+    check_number(
+        r#"
+    fn apply(f: fn(u8, u8) -> u8, x: u8) -> u8 {
+        f(x, x + 1)
+    }
+    const GOAL: u8 = {
+        let c = |a: u8, (b, c): (u8, u8)| a * b + c;
+        let f: fn(u8, (u8, u8)) -> u8 = c;
+        apply(|x, y| x + y, f(3, (2, 1)))
+    };
+        "#,
+        15,
+    );
+}
+
+#[test]
+fn closure_captures() {
+    check_number(
+        r#"
+    //- minicore: fn
+    const GOAL: u8 = {
+        let x = 2;
+        let p = (3, 4);
+        let c = || x + p.0 * p.1;
+        c() + c()
+    };
+        "#,
+        28,
+    );
+    check_number(
+        r#"
+    //- minicore: fn
+    const GOAL: u8 = {
+        let mut n = 0;
+        let mut inc = || {
+            n = n + 1;
+        };
+        inc();
+        inc();
+        inc();
+        n
+    };
+        "#,
+        3,
+    );
+    check_number(
+        r#"
+    //- minicore: fn
+    const GOAL: u8 = {
+        let mut x = 5;
+        let c = move || x * 2;
+        x = 1;
+        c() + x
+    };
+        "#,
+        11,
+    );
+    check_number(
+        r#"
+    //- minicore: fn
+    const GOAL: u8 = {
+        let x = 4;
+        let c = || {
+            let d = || x * 2;
+            d() + 1
+        };
+        c()
+    };
+        "#,
+        9,
+    );
+}
+
+#[test]
+fn closure_consuming_captures() {
+    check_number(
+        r#"
+    //- minicore: fn
+    struct S(u8);
+    fn consume(s: S) -> u8 {
+        s.0
+    }
+    const GOAL: u8 = {
+        let s = S(7);
+        let c = || consume(s);
+        c()
+    };
+        "#,
+        7,
+    );
+    check_number(
+        r#"
+    //- minicore: fn
+    struct S(u8);
+    fn consume(s: S) -> u8 {
+        s.0
+    }
+    fn call_once<F: FnOnce() -> u8>(f: F) -> u8 {
+        f()
+    }
+    fn call_twice<F: FnMut() -> u8>(mut f: F) -> u8 {
+        f() + f()
+    }
+    const GOAL: u8 = {
+        let s = S(3);
+        let mut k = 1;
+        call_once(|| consume(s)) * 10 + call_twice(|| {
+            k = k * 2;
+            k
+        })
+    };
+        "#,
+        36,
+    );
+}
+
+#[test]
+fn return_in_closure() {
+    check_number(
+        r#"
+    fn foo() -> u8 {
+        let f: fn(u8) -> u8 = |x| {
+            if x > 2 {
+                return 10;
+            }
+            x
+        };
+        f(5) + f(1)
+    }
+    const GOAL: u8 = foo();
+        "#,
+        11,
+    );
+    check_number(
+        r#"
+    const GOAL: u8 = {
+        let f: fn() -> u8 = || {
+            let r: fn() -> u8 = || return 3;
+            r() + 1
+        };
+        f() + 1
+    };
+        "#,
+        5,
+    );
+}
+
 #[test]
 fn enum_variant_as_function() {
     check_number(
@@ -1304,6 +2648,25 @@ fn function_traits() {
         "#,
         5,
     );
+    check_number(
+        r#"
+    //- minicore: fn
+    fn five() -> i32 {
+        5
+    }
+    fn sub(a: i32, b: i32) -> i32 {
+        a - b
+    }
+    fn call<F: Fn() -> i32>(f: F) -> i32 {
+        f() * 2
+    }
+    fn call2<F: FnMut(i32, i32) -> i32>(mut f: F) -> i32 {
+        f(10, 3)
+    }
+    const GOAL: i32 = call(five) + call2(sub);
+        "#,
+        17,
+    );
 }
 
 #[test]
@@ -1358,74 +2721,1189 @@ fn dyn_trait() {
 }
 
 #[test]
-fn array_and_index() {
+fn trait_object_arguments() {
+    // The arguments are unsized at the call, so the callee finds the vtable of their type.
     check_number(
         r#"
-    //- minicore: coerce_unsized, index, slice
-    const GOAL: u8 = {
-        let a = [10, 20, 3, 15];
-        let x: &[u8] = &a;
-        x[1]
-    };
+    //- minicore: coerce_unsized
+    trait Foo {
+        fn foo(&self) -> i32;
+    }
+    impl Foo for i32 {
+        fn foo(&self) -> i32 { *self * 2 }
+    }
+    struct S;
+    impl Foo for S {
+        fn foo(&self) -> i32 { 100 }
+    }
+    fn takes(a: &dyn Foo, b: &dyn Foo) -> i32 {
+        a.foo() + b.foo()
+    }
+    const GOAL: i32 = takes(&5, &S) * 10 + takes(&S, &S);
         "#,
-        20,
+        1300,
     );
+}
+
+#[test]
+fn boxes() {
     check_number(
         r#"
-    //- minicore: coerce_unsized, index, slice
-    const GOAL: usize = [1, 2, 3][2];"#,
-        3,
+    //- minicore: box
+    fn unboxed() -> i32 {
+        *Box::new(5)
+    }
+    const GOAL: i32 = unboxed();
+        "#,
+        5,
+    );
+    check_number(
+        r#"
+    //- minicore: box
+    struct Pair(i32, i64);
+    const GOAL: i64 = {
+        let mut b = Box::new(Box::new(Pair(2, 40)));
+        (**b).0 += 1;
+        let Pair(x, y) = **b;
+        x as i64 + y
+    };
+        "#,
+        43,
+    );
+}
+
+#[test]
+fn boxed_trait_objects() {
+    check_number(
+        r#"
+    //- minicore: box, coerce_unsized
+    trait Animal {
+        fn legs(&self) -> i32;
+        fn weight(&self) -> i32 { 1 }
+    }
+    struct Dog(i32);
+    impl Animal for Dog {
+        fn legs(&self) -> i32 { 4 }
+        fn weight(&self) -> i32 { self.0 }
+    }
+    struct Bird;
+    impl Animal for Bird {
+        fn legs(&self) -> i32 { 2 }
+    }
+    fn make(big: bool) -> Box<dyn Animal> {
+        if big { Box::new(Dog(30)) } else { Box::new(Bird) }
+    }
+    fn describe(a: &dyn Animal) -> i32 {
+        a.legs() * 100 + a.weight()
+    }
+    struct Zoo {
+        first: Box<dyn Animal>,
+        second: Box<dyn Animal>,
+    }
+    const GOAL: i32 = {
+        let zoo = Zoo { first: make(true), second: make(false) };
+        let b = make(false);
+        describe(&*zoo.first) * 10000 + zoo.second.legs() * 10 + b.weight()
+    };
+        "#,
+        4300021,
+    );
+}
+
+#[test]
+fn array_and_index() {
+    check_number(
+        r#"
+    //- minicore: coerce_unsized, index, slice
+    const GOAL: u8 = {
+        let a = [10, 20, 3, 15];
+        let x: &[u8] = &a;
+        x[1]
+    };
+        "#,
+        20,
+    );
+    check_number(
+        r#"
+    //- minicore: coerce_unsized, index, slice
+    const GOAL: usize = [1, 2, 3][2];"#,
+        3,
+    );
+    check_number(
+        r#"
+    //- minicore: coerce_unsized, index, slice
+    const GOAL: usize = { let a = [1, 2, 3]; let x: &[i32] = &a; x.len() };"#,
+        3,
+    );
+    check_number(
+        r#"
+    //- minicore: coerce_unsized, index, slice
+    const GOAL: usize = {
+        let a = [1, 2, 3];
+        let x: &[i32] = &a;
+        let y = &*x;
+        y.len()
+    };"#,
+        3,
+    );
+    check_number(
+        r#"
+    //- minicore: coerce_unsized, index, slice
+    const GOAL: usize = [1, 2, 3, 4, 5].len();"#,
+        5,
+    );
+}
+
+#[test]
+fn compound_assignment() {
+    check_number(
+        r#"
+    const GOAL: i32 = {
+        let mut x = 5;
+        x += 3;
+        x -= 1;
+        x *= 4;
+        x /= 2;
+        x %= 5;
+        x <<= 3;
+        x |= 1;
+        x
+    };"#,
+        33,
+    );
+    check_number(
+        r#"
+    //- minicore: index, slice
+    const GOAL: u8 = {
+        let mut a = [1, 2, 3];
+        let mut i = 0;
+        a[{ i += 1; i }] += 10;
+        a[1] + i as u8
+    };"#,
+        13,
+    );
+    check_number(
+        r#"
+    //- minicore: add
+    use core::ops::AddAssign;
+    struct Counter(i32, i32);
+    impl AddAssign<i32> for Counter {
+        fn add_assign(&mut self, rhs: i32) {
+            self.0 += rhs;
+            self.1 += 1;
+        }
+    }
+    const GOAL: i32 = {
+        let mut c = Counter(0, 0);
+        let p = &mut c;
+        *{ p.1 += 100; p } += 5;
+        c += 7;
+        // Without the `Add` impls of minicore, `+` would be ambiguous.
+        c.0 * 1000 - c.1
+    };"#,
+        11898,
+    );
+}
+
+#[test]
+fn array_repeat() {
+    check_number(
+        r#"
+    //- minicore: index, slice
+    const X: [u8; 4] = [1; 4];
+    const GOAL: u8 = X[0] + X[3];"#,
+        2,
+    );
+    check_number(
+        r#"
+    //- minicore: coerce_unsized, index, slice
+    const GOAL: usize = { let a = [(1, 2u8); 3]; let x: &[(i32, u8)] = &a; x.len() };"#,
+        3,
+    );
+    check_number(
+        r#"
+    //- minicore: index, slice
+    fn filled<const N: usize>(x: u16) -> [u16; N] {
+        [x; N]
+    }
+    const GOAL: u16 = { let a = filled::<5>(7); a[0] + a[4] };"#,
+        14,
+    );
+}
+
+#[test]
+fn index_through_references() {
+    check_number(
+        r#"
+    //- minicore: coerce_unsized, index, slice
+    fn get(a: &[i32; 8], i: usize) -> i32 {
+        a[i]
+    }
+    const GOAL: i32 = get(&[1, 2, 3, 4, 5, 6, 7, 8], 6);
+        "#,
+        7,
+    );
+    check_number(
+        r#"
+    //- minicore: coerce_unsized, index, slice
+    fn get(a: &[i32], i: usize) -> i32 {
+        a[i]
+    }
+    const GOAL: i32 = get(&[1, 2, 3], 1) * 10 + get(&[4, 5], 0);
+        "#,
+        24,
+    );
+    check_number(
+        r#"
+    //- minicore: coerce_unsized, index, slice
+    fn set(a: &mut [i32; 4], i: usize, value: i32) {
+        a[i] = value;
+    }
+    fn set_slice(a: &mut [i32], i: usize, value: i32) {
+        a[i] = value;
+    }
+    const GOAL: i32 = {
+        let mut a = [1, 2, 3, 4];
+        set(&mut a, 2, 30);
+        set_slice(&mut a, 3, 40);
+        a[0] + a[1] + a[2] + a[3]
+    };
+        "#,
+        73,
+    );
+    check_number(
+        r#"
+    //- minicore: coerce_unsized, index, slice
+    fn at(m: &[[i32; 3]; 2], i: usize, j: usize) -> i32 {
+        m[i][j]
+    }
+    fn put(m: &mut [[i32; 3]; 2], i: usize, j: usize, value: i32) {
+        m[i][j] = value;
+    }
+    const GOAL: i32 = {
+        let mut m = [[1, 2, 3], [4, 5, 6]];
+        put(&mut m, 0, 1, 20);
+        at(&m, 1, 2) * 100 + at(&m, 0, 1)
+    };
+        "#,
+        620,
+    );
+}
+
+#[test]
+fn index_out_of_bounds() {
+    check_panic(
+        r#"
+    //- minicore: coerce_unsized, index, slice
+    fn get(a: &[i32], i: usize) -> i32 {
+        a[i]
+    }
+    const GOAL: i32 = get(&[1, 2, 3], 3);
+        "#,
+        "index out of bounds",
+    );
+    check_panic(
+        r#"
+    //- minicore: coerce_unsized, index, slice
+    const GOAL: i32 = {
+        let a = [1, 2, 3];
+        let i = 5;
+        a[i]
+    };
+        "#,
+        "index out of bounds",
+    );
+}
+
+#[test]
+fn slice_patterns() {
+    check_number(
+        r#"
+    //- minicore: coerce_unsized, index, slice
+    const GOAL: i32 = {
+        let [a, .., b] = [1, 2, 3, 4];
+        let [c, rest @ ..] = [5, 6, 7];
+        let [d, e] = rest;
+        a * 10000 + b * 1000 + c * 100 + d * 10 + e
+    };
+        "#,
+        14567,
+    );
+    check_number(
+        r#"
+    //- minicore: coerce_unsized, index, slice
+    fn f(x: &[i32]) -> i32 {
+        match x {
+            [] => 1,
+            [a] => *a * 10,
+            [a, b] => *a * 100 + *b * 10,
+            [_, rest @ .., last] => rest.len() as i32 * 1000 + *last,
+        }
+    }
+    const GOAL: i32 = f(&[]) + f(&[2]) + f(&[3, 4]) + f(&[5, 6, 7, 8, 9]);
+        "#,
+        3000 + 9 + 340 + 20 + 1,
+    );
+    check_number(
+        r#"
+    //- minicore: coerce_unsized, index, slice
+    fn sum(x: &[i32]) -> i32 {
+        match x {
+            [first, rest @ ..] => *first + sum(rest),
+            [] => 0,
+        }
+    }
+    fn tail_len(x: &[i32]) -> usize {
+        let [_, rest @ ..] = x else { return 100 };
+        rest.len()
+    }
+    const GOAL: i32 = sum(&[1, 2, 3, 4]) * 10 + tail_len(&[1, 2, 3]) as i32;
+        "#,
+        102,
+    );
+}
+
+#[test]
+fn offset_of() {
+    check_number(
+        r#"
+    //- minicore: offset_of
+    use core::mem::offset_of;
+    #[repr(C)]
+    struct Foo {
+        a: u8,
+        b: u32,
+        c: u16,
+        d: u64,
+    }
+    const GOAL: usize =
+        offset_of!(Foo, a) + 10 * offset_of!(Foo, b) + 100 * offset_of!(Foo, c) + 1000 * offset_of!(Foo, d);
+        "#,
+        16840,
+    );
+    check_number(
+        r#"
+    //- minicore: offset_of
+    use core::mem::offset_of;
+    #[repr(C)]
+    struct Inner {
+        x: u8,
+        y: (u8, u16),
+    }
+    #[repr(C)]
+    struct Outer {
+        a: u32,
+        inner: Inner,
+    }
+    const GOAL: usize = offset_of!(Outer, inner.y.1);
+        "#,
+        8,
+    );
+    check_number(
+        r#"
+    //- minicore: offset_of
+    use core::mem::offset_of;
+    #[repr(C)]
+    struct Pair<T, U> {
+        first: T,
+        second: U,
+    }
+    const GOAL: usize = offset_of!(Pair<u8, u64>, second) + offset_of!(Pair<u16, u8>, second);
+        "#,
+        10,
+    );
+    check_fail(
+        r#"
+    //- minicore: offset_of, option
+    use core::mem::offset_of;
+    const GOAL: usize = offset_of!(Option<u8>, Some.0);
+        "#,
+        ConstEvalError::MirLowerError(MirLowerError::NotSupported(
+            "offset_of on enum variants".to_string(),
+        )),
+    );
+}
+
+#[test]
+fn layout_errors() {
+    let check = |ra_fixture: &str, message: &str| {
+        let error = match eval_goal(ra_fixture).map_err(simplify) {
+            Err(ConstEvalError::MirLowerError(e))
+            | Err(ConstEvalError::MirEvalError(MirEvalError::MirLowerError(_, e))) => e,
+            x => panic!("expected a lowering error, found {x:?}"),
+        };
+        assert!(matches!(error, MirLowerError::LayoutError { .. }), "{error:?}");
+        assert_eq!(error.to_string(), message);
+    };
+    check(
+        r#"
+    //- minicore: offset_of
+    use core::mem::offset_of;
+    struct Recursive { x: u8, next: Recursive }
+    const GOAL: usize = offset_of!(Recursive, x);
+        "#,
+        "cannot compute layout of `Recursive` (recursive without indirection) required here",
+    );
+    check(
+        r#"
+    //- minicore: offset_of
+    use core::mem::offset_of;
+    struct Pair<T, U> { first: T, second: U }
+    fn second_offset<T>() -> usize { offset_of!(Pair<T, u8>, second) }
+    const GOAL: usize = second_offset::<u32>();
+        "#,
+        "cannot compute layout of `Pair<T, u8>` (generic type not fully substituted) required here",
+    );
+    check(
+        r#"
+    //- minicore: slice, coerce_unsized
+    const GOAL: usize = {
+        let s: &[u8] = &[1, 2];
+        let t = &{ *s };
+        0
+    };
+        "#,
+        "cannot compute layout of `[u8]` (unsized type) required here",
+    );
+    check(
+        r#"
+    //- /main.rs target_data_layout:invalid
+    const GOAL: u8 = 2;
+        "#,
+        "cannot compute layout of `u8` (target data layout unavailable) required here",
+    );
+}
+
+#[test]
+fn byte_string() {
+    check_number(
+        r#"
+    //- minicore: coerce_unsized, index, slice
+    const GOAL: u8 = {
+        let a = b"hello";
+        let x: &[u8] = a;
+        x[0]
+    };
+        "#,
+        104,
+    );
+    check_number(
+        r#"
+    //- minicore: coerce_unsized, index, slice
+    const GOAL: usize = {
+        let a = b"ab";
+        let x: &[u8] = b"abc";
+        a.len() + x.len()
+    };
+        "#,
+        5,
+    );
+    check_number(
+        r#"
+    //- minicore: coerce_unsized, index, slice
+    const fn f(x: &[u8]) -> usize {
+        match x {
+            b"ab" => x.len(),
+            b"abc" => 10 * x.len(),
+            _ => 0,
+        }
+    }
+    const GOAL: usize = f(b"ab") + f(b"abc") + f(b"a");
+        "#,
+        32,
+    );
+}
+
+#[test]
+fn render_limits() {
+    let limits = ConstRenderLimits {
+        max_array_elements: 3,
+        max_depth: 2,
+        max_string_len: 5,
+        max_len: 40,
+        keep_expressions: false,
+    };
+    let elements = ["7"; 1000].join(", ");
+    check_render(
+        &format!("const GOAL: [u16; 1000] = [{elements}];"),
+        limits,
+        "[7, 7, 7, …; 1000]",
+        Some(&format!("[{elements}]")),
+    );
+    check_render(
+        r#"
+    struct A { b: B }
+    struct B { c: C }
+    struct C { d: D }
+    struct D { e: E }
+    struct E(i32);
+    const GOAL: A = A { b: B { c: C { d: D { e: E(5) } } } };
+        "#,
+        limits,
+        "A { b: B { c: C { d: … } } }",
+        Some("A { b: B { c: C { d: D { e: E(5) } } } }"),
+    );
+    check_render(
+        r#"const GOAL: &str = "hello world";"#,
+        limits,
+        r#""hello…""#,
+        Some(r#""hello world""#),
+    );
+    check_render(
+        r#"const GOAL: &[u8; 11] = b"hello world";"#,
+        limits,
+        r#"b"hello…""#,
+        Some(r#"b"hello world""#),
+    );
+    check_render(
+        r#"const GOAL: (i32, i32, i32, i32, i32) = (1000000, 2000000, 3000000, 4000000, 5000000);"#,
+        limits,
+        "(1000000, 2000000, 3000000, 4000000, 500…",
+        Some("(1000000, 2000000, 3000000, 4000000, 5000000)"),
+    );
+    check_render(r#"const GOAL: [u8; 3] = [1, 2, 3];"#, limits, "[1, 2, 3]", None);
+}
+
+#[test]
+fn consts() {
+    check_number(
+        r#"
+    const F1: i32 = 1;
+    const F3: i32 = 3 * F2;
+    const F2: i32 = 2 * F1;
+    const GOAL: i32 = F3;
+    "#,
+        6,
+    );
+}
+
+#[test]
+fn statics() {
+    check_number(
+        r#"
+    //- minicore: cell
+    use core::cell::Cell;
+    fn f() -> i32 {
+        static S: Cell<i32> = Cell::new(10);
+        S.set(S.get() + 1);
+        S.get()
+    }
+    const GOAL: i32 = f() + f() + f();
+    "#,
+        36,
+    );
+    check_number(
+        r#"
+    static mut S: i32 = 10;
+    fn bump(x: &mut i32) {
+        *x += 1;
+    }
+    fn f() -> i32 {
+        unsafe {
+            bump(&mut S);
+            let same = &S as *const i32 == &S as *const i32;
+            S * 10 + same as i32
+        }
+    }
+    const GOAL: i32 = f() + f();
+    "#,
+        232,
+    );
+}
+
+#[test]
+fn thread_locals() {
+    check_number(
+        r#"
+    //- minicore: cell, fn
+    use core::cell::Cell;
+    struct LocalKey<T: 'static> {
+        inner: unsafe fn() -> &'static T,
+    }
+    impl<T: 'static> LocalKey<T> {
+        fn with<F: FnOnce(&T) -> R, R>(&'static self, f: F) -> R {
+            let value = unsafe { (self.inner)() };
+            f(value)
+        }
+    }
+    macro_rules! thread_local {
+        ($name:ident: $t:ty = $init:expr) => {
+            static $name: LocalKey<$t> = {
+                #[inline]
+                unsafe fn __getit() -> &'static $t {
+                    #[thread_local]
+                    static VAL: $t = $init;
+                    &VAL
+                }
+                LocalKey { inner: __getit }
+            };
+        };
+    }
+    thread_local!(COUNTER: Cell<u32> = Cell::new(0));
+    fn inc(c: &Cell<u32>) {
+        c.set(c.get() + 1);
+    }
+    fn get(c: &Cell<u32>) -> u32 {
+        c.get()
+    }
+    const GOAL: u32 = {
+        COUNTER.with(inc);
+        COUNTER.with(inc);
+        COUNTER.with(get)
+    };
+    "#,
+        2,
+    );
+    // The macros of std for targets with `#[thread_local]`, expanding to a `const` `LocalKey`
+    // whose accessor lazily initializes a `#[thread_local]` static.
+    check_number(
+        r#"
+    //- minicore: cell, option, fn, panic
+    pub mod thread {
+        pub struct LocalKey<T: 'static> {
+            inner: unsafe fn(Option<&mut Option<T>>) -> Option<&'static T>,
+        }
+        impl<T: 'static> LocalKey<T> {
+            pub const unsafe fn new(
+                inner: unsafe fn(Option<&mut Option<T>>) -> Option<&'static T>,
+            ) -> LocalKey<T> {
+                LocalKey { inner }
+            }
+            pub fn with<F, R>(&'static self, f: F) -> R
+            where
+                F: FnOnce(&T) -> R,
+            {
+                let thread_local = unsafe { (self.inner)(None) };
+                match thread_local {
+                    Some(value) => f(value),
+                    None => panic!("cannot access a Thread Local Storage value during or after destruction"),
+                }
+            }
+        }
+        pub mod local_impl {
+            use core::cell::UnsafeCell;
+            pub struct Key<T> {
+                inner: UnsafeCell<Option<T>>,
+            }
+            impl<T> Key<T> {
+                pub const fn new() -> Key<T> {
+                    Key { inner: UnsafeCell::new(None) }
+                }
+                pub unsafe fn get<F: FnOnce() -> T>(&self, init: F) -> Option<&'static T> {
+                    let ptr = self.inner.get();
+                    if let Some(value) = &*ptr {
+                        return Some(value);
+                    }
+                    *ptr = Some(init());
+                    match &*ptr {
+                        Some(value) => Some(value),
+                        None => None,
+                    }
+                }
+            }
+        }
+    }
+    #[macro_export]
+    macro_rules! thread_local {
+        () => {};
+        ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty = $init:expr; $($rest:tt)*) => (
+            $crate::__thread_local_inner!($(#[$attr])* $vis $name, $t, $init);
+            $crate::thread_local!($($rest)*);
+        );
+        ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty = $init:expr) => (
+            $crate::__thread_local_inner!($(#[$attr])* $vis $name, $t, $init);
+        );
+    }
+    #[macro_export]
+    macro_rules! __thread_local_inner {
+        (@key $t:ty, $init:expr) => {
+            {
+                #[inline]
+                fn __init() -> $t { $init }
+                #[inline]
+                unsafe fn __getit(
+                    init: core::option::Option<&mut core::option::Option<$t>>,
+                ) -> core::option::Option<&'static $t> {
+                    #[thread_local]
+                    static __KEY: $crate::thread::local_impl::Key<$t> =
+                        $crate::thread::local_impl::Key::<$t>::new();
+                    // The initial values passed by `LocalKey::set` are left out, as minicore's
+                    // `Option` has no `take`.
+                    let _ = init;
+                    unsafe { __KEY.get(__init) }
+                }
+                unsafe {
+                    $crate::thread::LocalKey::new(__getit)
+                }
+            }
+        };
+        ($(#[$attr:meta])* $vis:vis $name:ident, $t:ty, $($init:tt)*) => {
+            $(#[$attr])* $vis const $name: $crate::thread::LocalKey<$t> =
+                $crate::__thread_local_inner!(@key $t, $($init)*);
+        };
+    }
+    use core::cell::Cell;
+    thread_local! {
+        static COUNTER: Cell<u32> = Cell::new(0);
+        static OTHER: Cell<u32> = Cell::new(10);
+    }
+    const GOAL: u32 = {
+        COUNTER.with(|c| c.set(c.get() + 1));
+        OTHER.with(|c| c.set(c.get() + 1));
+        COUNTER.with(|c| c.set(c.get() + 1));
+        COUNTER.with(|c| c.get()) * 100 + OTHER.with(|c| c.get())
+    };
+    "#,
+        211,
+    );
+    let r = eval_goal(
+        r#"
+    //- minicore: cell
+    use core::cell::Cell;
+    extern "C" {
+        fn platform_seed() -> u32;
+    }
+    fn f() -> u32 {
+        #[thread_local]
+        static VAL: Cell<u32> = Cell::new(unsafe { platform_seed() });
+        VAL.get()
+    }
+    const GOAL: u32 = f();
+    "#,
+    )
+    .map_err(simplify);
+    assert!(matches!(
+        r,
+        Err(ConstEvalError::MirEvalError(MirEvalError::ThreadLocalAccess(name, _))) if name == "VAL"
+    ));
+}
+
+#[test]
+fn drops() {
+    check_number(
+        r#"
+    //- minicore: drop, cell
+    use core::cell::Cell;
+    struct Guard<'a>(&'a Cell<i32>, i32);
+    impl Drop for Guard<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() * 10 + self.1);
+        }
+    }
+    const GOAL: i32 = {
+        let c = Cell::new(0);
+        {
+            let _a = Guard(&c, 1);
+            let b = Guard(&c, 2);
+            {
+                let _moved = b;
+                let _c = Guard(&c, 3);
+            }
+            let _d = Guard(&c, 4);
+        }
+        c.get()
+    };
+    "#,
+        3241,
+    );
+    check_number(
+        r#"
+    //- minicore: drop, cell
+    use core::cell::Cell;
+    struct Guard<'a>(&'a Cell<i32>, i32);
+    impl Drop for Guard<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() * 10 + self.1);
+        }
+    }
+    fn f(c: &Cell<i32>) -> i32 {
+        let _a = Guard(c, 1);
+        let mut i = 0;
+        loop {
+            let _b = Guard(c, 2);
+            i = i + 1;
+            if i == 1 {
+                continue;
+            }
+            let _c = Guard(c, 3);
+            if i == 2 {
+                break;
+            }
+        }
+        let _d = Guard(c, 4);
+        return 5;
+    }
+    const GOAL: i32 = {
+        let c = Cell::new(0);
+        f(&c);
+        c.get()
+    };
+    "#,
+        23241,
+    );
+    check_number(
+        r#"
+    //- minicore: drop, cell
+    use core::cell::Cell;
+    struct Guard<'a>(&'a Cell<i32>, i32);
+    impl Drop for Guard<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() * 10 + self.1);
+        }
+    }
+    struct Pair<'a>(Guard<'a>, u8, Guard<'a>);
+    enum E<'a> {
+        A(u8),
+        B(Guard<'a>),
+    }
+    struct Outer<'a>(Guard<'a>);
+    impl Drop for Outer<'_> {
+        fn drop(&mut self) {
+            self.0 .0.set(self.0 .0.get() * 10 + 9);
+        }
+    }
+    const GOAL: i32 = {
+        let c = Cell::new(0);
+        {
+            let _p = Pair(Guard(&c, 1), 0, Guard(&c, 2));
+        }
+        {
+            let _t = (Guard(&c, 3), [Guard(&c, 4), Guard(&c, 5)]);
+            let _a = E::A(6);
+            let _b = E::B(Guard(&c, 6));
+        }
+        {
+            let _o = Outer(Guard(&c, 7));
+        }
+        c.get()
+    };
+    "#,
+        1263_4597,
+    );
+    check_number(
+        r#"
+    //- minicore: drop, cell, manually_drop
+    use core::cell::Cell;
+    use core::mem::{forget, ManuallyDrop};
+    struct Guard<'a>(&'a Cell<i32>, i32);
+    impl Drop for Guard<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() * 10 + self.1);
+        }
+    }
+    const GOAL: i32 = {
+        let c = Cell::new(0);
+        {
+            let _m = ManuallyDrop::new(Guard(&c, 1));
+            let _p = (ManuallyDrop::new(Guard(&c, 2)), Guard(&c, 3));
+            forget(Guard(&c, 4));
+        }
+        c.get()
+    };
+    "#,
+        3,
+    );
+}
+
+#[test]
+fn record_update_drops() {
+    check_number(
+        r#"
+    //- minicore: drop, cell
+    use core::cell::Cell;
+    struct Guard<'a>(&'a Cell<i32>, i32);
+    impl Drop for Guard<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() * 10 + self.1);
+        }
+    }
+    struct Pair<'a> { a: Guard<'a>, b: Guard<'a>, n: i32 }
+    fn make(c: &Cell<i32>) -> Pair<'_> {
+        c.set(c.get() * 10 + 9);
+        Pair { a: Guard(c, 1), b: Guard(c, 2), n: 4 }
+    }
+    const GOAL: i32 = {
+        let c = Cell::new(0);
+        {
+            let p = Pair {
+                a: {
+                    c.set(c.get() * 10 + 7);
+                    Guard(&c, 3)
+                },
+                ..make(&c)
+            };
+            c.set(c.get() * 10 + p.n);
+            let _b = p.b;
+            let _a = p.a;
+        }
+        c.get()
+    };
+    "#,
+        791432,
+    );
+}
+
+#[test]
+fn drops_on_panic() {
+    let fixture = r#"
+    struct Guard;
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            let zero = 0;
+            let _ = 1 / zero;
+        }
+    }
+    fn boom(x: i32) -> i32 {
+        10 / x
+    }
+    const GOAL: i32 = {
+        let _g = Guard;
+        boom(0)
+    };
+    "#;
+    // The panic unwinds into the drop of `_g`, which panics again and aborts.
+    check_fail(
+        &format!("//- minicore: drop\n{fixture}"),
+        ConstEvalError::MirEvalError(MirEvalError::ProcessAborted),
+    );
+    // With `panic=abort`, the drop never runs.
+    check_fail(
+        &format!("//- minicore: drop\n//- /main.rs cfg:panic=abort\n{fixture}"),
+        ConstEvalError::MirEvalError(MirEvalError::Panic {
+            message: "Overflow in Div".to_string(),
+            location: None,
+        }),
+    );
+    // A drop that doesn't panic resumes unwinding with the original panic.
+    check_fail(
+        r#"
+    //- minicore: drop
+    struct Guard;
+    impl Drop for Guard {
+        fn drop(&mut self) {}
+    }
+    fn boom(x: i32) -> i32 {
+        let _g = Guard;
+        10 / x
+    }
+    fn f() -> i32 {
+        let _g = Guard;
+        boom(0)
+    }
+    const GOAL: i32 = f();
+    "#,
+        ConstEvalError::MirEvalError(MirEvalError::Panic {
+            message: "Overflow in Div".to_string(),
+            location: None,
+        }),
+    );
+}
+
+#[test]
+fn panic_messages() {
+    check_panic(
+        r#"
+    //- minicore: panic
+    fn get(i: usize) -> usize {
+        if i > 3 {
+            panic!("bad index {i}");
+        }
+        i
+    }
+    const GOAL: usize = get(5);
+    "#,
+        "bad index 5",
+    );
+    check_panic(
+        r#"
+    //- minicore: panic, option
+    const GOAL: i32 = {
+        let x: Option<i32> = None;
+        x.unwrap()
+    };
+    "#,
+        "called `Option::unwrap()` on a `None` value",
+    );
+    check_panic(
+        r#"
+    //- minicore: panic
+    const GOAL: u8 = {
+        let (a, b, c) = (-3i64, 'x', "abc");
+        panic!("{a} {:?} {} {c:?} {}", b, b, true)
+    };
+    "#,
+        "-3 'x' x \"abc\" true",
+    );
+    // Only the formatting traits of `core` are rendered, not other traits with the same name.
+    check_panic(
+        r#"
+    //- minicore: panic
+    use core::fmt::{Arguments, ArgumentV1, Formatter, Result};
+    trait Display {
+        fn fmt(&self, f: &mut Formatter<'_>) -> Result;
+    }
+    impl Display for i32 {
+        fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+            loop {}
+        }
+    }
+    const GOAL: u8 = {
+        let x = 5;
+        let args = [ArgumentV1::new(&x, core::fmt::Display::fmt), ArgumentV1::new(&x, Display::fmt)];
+        core::panicking::panic_fmt(Arguments::new_v1(&["", " "], &args))
+    };
+    "#,
+        "5 <value of type i32>",
+    );
+}
+
+#[test]
+fn panic_locations() {
+    // `Option::unwrap` is `#[track_caller]`, so the panic is reported at its call, at the name of
+    // the method like in rustc.
+    let location = check_panic(
+        r#"
+//- minicore: panic, option
+fn first(x: Option<i32>) -> i32 {
+    x.unwrap()
+}
+const GOAL: i32 = first(None);
+"#,
+        "called `Option::unwrap()` on a `None` value",
     );
-    check_number(
+    assert_eq!(location.map(|it| (it.line, it.column)), Some((2, 7)));
+    // The location is passed on through the chain of `#[track_caller]` functions.
+    let location = check_panic(
         r#"
-    //- minicore: coerce_unsized, index, slice
-    const GOAL: usize = { let a = [1, 2, 3]; let x: &[i32] = &a; x.len() };"#,
-        3,
+//- minicore: panic, option
+#[track_caller]
+fn first(x: Option<i32>) -> i32 {
+    x.unwrap()
+}
+const GOAL: i32 = {
+    let x = None;
+    1 + first(x)
+};
+"#,
+        "called `Option::unwrap()` on a `None` value",
     );
-    check_number(
+    assert_eq!(location.map(|it| (it.line, it.column)), Some((7, 9)));
+    let location = check_panic(
         r#"
-    //- minicore: coerce_unsized, index, slice
-    const GOAL: usize = {
-        let a = [1, 2, 3];
-        let x: &[i32] = &a;
-        let y = &*x;
-        y.len()
-    };"#,
-        3,
+//- minicore: panic
+fn check(x: i32) {
+    if x > 2 {
+        panic!("too big");
+    }
+}
+const GOAL: () = check(3);
+"#,
+        "too big",
     );
+    assert_eq!(location.map(|it| (it.line, it.column)), Some((3, 9)));
     check_number(
         r#"
-    //- minicore: coerce_unsized, index, slice
-    const GOAL: usize = [1, 2, 3, 4, 5].len();"#,
-        5,
+#[lang = "panic_location"]
+pub struct Location<'a> {
+    file: &'a str,
+    line: u32,
+    col: u32,
+}
+impl Location<'_> {
+    #[track_caller]
+    pub const fn caller() -> &'static Location<'static> {
+        unsafe { caller_location() }
+    }
+}
+extern "rust-intrinsic" {
+    fn caller_location() -> &'static Location<'static>;
+}
+#[track_caller]
+fn here() -> u32 {
+    let location = Location::caller();
+    location.line * 100 + location.col
+}
+#[track_caller]
+fn nested() -> u32 {
+    here()
+}
+fn not_tracked() -> u32 {
+    here()
+}
+const GOAL: u64 = ((here() as u64 * 1_000_000) + nested() as u64) * 1_000_000 + not_tracked() as u64;
+"#,
+        // `line * 100 + column` of the calls of `here()`, of `nested()` and of `here()` in
+        // `not_tracked`.
+        2821_002850_002605,
     );
 }
 
 #[test]
-fn byte_string() {
-    check_number(
+fn assert_eq_message() {
+    check_panic(
         r#"
-    //- minicore: coerce_unsized, index, slice
-    const GOAL: u8 = {
-        let a = b"hello";
-        let x: &[u8] = a;
-        x[0]
+    //- minicore: assert
+    const GOAL: i32 = {
+        let x = 2;
+        assert_eq!(x + 1, 4);
+        x
     };
-        "#,
-        104,
+    "#,
+        "assertion failed: `(left == right)`\n  left: `3`,\n right: `4`",
     );
 }
 
 #[test]
-fn consts() {
-    check_number(
+fn panic_message_with_debug_values() {
+    check_panic(
         r#"
-    const F1: i32 = 1;
-    const F3: i32 = 3 * F2;
-    const F2: i32 = 2 * F1;
-    const GOAL: i32 = F3;
+    //- minicore: panic, derive, option
+    use core::fmt::Debug;
+    #[derive(Debug)]
+    struct Point {
+        x: i32,
+        y: (u8, bool),
+    }
+    #[derive(Debug)]
+    struct Meters(u32);
+    #[derive(Debug)]
+    enum Shape {
+        Empty,
+        Dot(Point),
+    }
+    struct Opaque;
+    impl Debug for Opaque {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            loop {}
+        }
+    }
+    const GOAL: i32 = {
+        let p = Point { x: -1, y: (2, true) };
+        let s = Shape::Dot(Point { x: 5, y: (0, false) });
+        panic!("{p:?} {:?} {:?} {s:?} {:?} {:?}", Meters(3), Shape::Empty, [1, 2], Opaque)
+    };
     "#,
-        6,
+        "Point { x: -1, y: (2, true) } Meters(3) Empty Dot(Point { x: 5, y: (0, false) }) \
+         [1, 2] <value of type Opaque>",
+    );
+}
+
+#[test]
+fn nested_items_in_blocks() {
+    check_number(
+        r#"
+    const GOAL: i32 = {
+        let a = 2;
+        fn double(x: i32) -> i32 {
+            x * 2
+        }
+        struct Wrapper(i32);
+        const OFFSET: i32 = 1;
+        let w = Wrapper(double(a));
+        w.0 + OFFSET
+    };
+        "#,
+        5,
     );
 }
 
@@ -1489,6 +3967,14 @@ fn const_transfer_memory() {
     "#,
         7,
     );
+    check_number(
+        r#"
+    const A: &[u8; 4] = &[1, 0, 0, 0];
+    const B: &u32 = &1;
+    const GOAL: bool = A as *const [u8; 4] as usize != B as *const u32 as usize;
+    "#,
+        1,
+    );
 }
 
 #[test]
@@ -1534,8 +4020,7 @@ fn const_generic_subst_assoc_const_impl() {
 
 #[test]
 fn const_trait_assoc() {
-    // FIXME: this should evaluate to 0
-    check_fail(
+    check_number(
         r#"
     struct U0;
     trait ToConst {
@@ -1546,7 +4031,132 @@ fn const_trait_assoc() {
     }
     const GOAL: usize = U0::VAL;
     "#,
-        ConstEvalError::MirLowerError(MirLowerError::IncompleteExpr),
+        0,
+    );
+}
+
+#[test]
+fn const_generic_over_the_owner_params() {
+    check_number(
+        r#"
+    trait Tr {
+        const N: usize;
+        const M: usize = Self::N + 1;
+        fn m() -> usize { Self::M * 10 }
+    }
+    struct A;
+    impl Tr for A { const N: usize = 3; }
+    struct S<T>(T);
+    impl<T: Tr> S<T> {
+        const DOUBLE: usize = T::M * 2;
+        const QUAD: usize = Self::DOUBLE * 2;
+        fn quad() -> usize { Self::QUAD + <T as Tr>::M + T::m() }
+    }
+    fn g<T: Tr>() -> usize { S::<T>::QUAD }
+    const GOAL: usize = S::<A>::quad() + g::<A>();
+        "#,
+        16 + 4 + 40 + 16,
+    );
+}
+
+#[test]
+fn const_trait_assoc_generic() {
+    check_number(
+        r#"
+    trait Sized2 {
+        const SIZE: usize;
+    }
+    impl Sized2 for u8 {
+        const SIZE: usize = 1;
+    }
+    impl Sized2 for u32 {
+        const SIZE: usize = 4;
+    }
+    fn size_of<T: Sized2>() -> usize {
+        T::SIZE
+    }
+    fn size_of_self<T: Sized2>() -> usize {
+        <T as Sized2>::SIZE
+    }
+    const GOAL: usize = size_of::<u8>() + 10 * size_of::<u32>() + 100 * size_of_self::<u32>();
+    "#,
+        441,
+    );
+    check_number(
+        r#"
+    //- minicore: coerce_unsized, index, slice
+    struct S;
+    impl S {
+        const N: usize = 3;
+        const ARR: [u8; Self::N] = [1, 2, 3];
+    }
+    const GOAL: u8 = S::ARR[0] + S::ARR[2];
+    "#,
+        4,
+    );
+}
+
+#[test]
+fn block_local_consts() {
+    check_number(
+        r#"
+    //- minicore: coerce_unsized, index, slice
+    fn f(x: u32) -> u32 {
+        const LOCAL: usize = 2 + 1;
+        let arr: [u32; LOCAL] = [5, 4, 3];
+        match x {
+            LOCAL_PAT => arr[0] + arr[LOCAL - 1],
+            _ => {
+                const LOCAL_PAT: u32 = 2;
+                0
+            }
+        }
+    }
+    const LOCAL_PAT: u32 = 7;
+    const GOAL: u32 = f(7) + f(2);
+    "#,
+        8,
+    );
+    check_number(
+        r#"
+    fn f() -> usize {
+        const OUTER: usize = 4;
+        {
+            const INNER: usize = OUTER * 10;
+            INNER + OUTER
+        }
+    }
+    const GOAL: usize = f();
+    "#,
+        44,
+    );
+    check_number(
+        r#"
+    static S: i32 = {
+        const IN_STATIC: i32 = 5;
+        IN_STATIC * 2
+    };
+    const GOAL: i32 = {
+        const IN_CONST: i32 = 3;
+        IN_CONST + S
+    };
+    "#,
+        13,
+    );
+    check_number(
+        r#"
+    //- minicore: coerce_unsized, index, slice
+    fn f<T>(x: T) -> T {
+        const N: usize = 2;
+        let arr: [u8; N] = [1, 2];
+        match arr.len() {
+            N => x,
+            _ => loop {},
+        }
+    }
+    const GOAL: u8 = f(6);
+    "#,
+        6,
     );
 }
 
@@ -1588,6 +4198,126 @@ fn exec_limits() {
     );
 }
 
+#[test]
+fn configured_exec_limits() {
+    let fixture = r#"
+    const fn f(n: i32) -> i32 {
+        let buffer = (0u64, 0u64, 0u64, 0u64, 0u64, 0u64, 0u64, 0u64);
+        let sum = 0;
+        let i = 0;
+        while i < n {
+            i = i + 1;
+            sum = sum + i;
+        }
+        sum + buffer.0 as i32
+    }
+    const GOAL: i32 = f(100);
+    "#;
+    eval_goal_with_config(fixture, MirConfig::default()).unwrap();
+    let config = MirConfig { eval_step_limit: 50, ..MirConfig::default() };
+    assert_eq!(
+        eval_goal_with_config(fixture, config).map_err(simplify),
+        Err(ConstEvalError::MirEvalError(MirEvalError::ExecutionLimitExceeded))
+    );
+    let config = MirConfig { memory_limit: Some(48), ..MirConfig::default() };
+    assert_eq!(
+        eval_goal_with_config(fixture, config).map_err(simplify),
+        Err(ConstEvalError::MirEvalError(MirEvalError::MemoryLimitExceeded))
+    );
+    let config = MirConfig { memory_limit: Some(1 << 20), ..MirConfig::default() };
+    assert_eq!(
+        eval_goal_with_config("const GOAL: [u8; 1 << 40] = [0u8; 1 << 40];", config)
+            .map_err(simplify),
+        Err(ConstEvalError::MirEvalError(MirEvalError::MemoryLimitExceeded))
+    );
+}
+
+#[test]
+fn memoized_recursive_calls() {
+    let fixture = r#"
+    const fn fib(n: u64) -> u64 {
+        if n < 2 { n } else { fib(n - 1) + fib(n - 2) }
+    }
+    const fn even(n: u32) -> (bool, u32) {
+        if n == 0 { (true, 0) } else { let (e, c) = odd(n - 1); (!e, c + even(n - 1).1 + 1) }
+    }
+    const fn odd(n: u32) -> (bool, u32) {
+        if n == 0 { (false, 0) } else { let (o, c) = even(n - 1); (!o, c + odd(n - 1).1 + 1) }
+    }
+    const GOAL: u64 = fib(40) + even(30).1 as u64;
+    "#;
+    let config = MirConfig { eval_step_limit: 10_000, ..MirConfig::default() };
+    assert_eq!(
+        eval_goal_with_config(fixture, config).map_err(simplify),
+        eval_goal_with_config(
+            r#"
+    const GOAL: u64 = 102334155 + 1073741823;
+    "#,
+            MirConfig::default()
+        )
+        .map_err(simplify)
+    );
+    let config =
+        MirConfig { eval_step_limit: 10_000, memoize_calls: false, ..MirConfig::default() };
+    assert_eq!(
+        eval_goal_with_config(fixture, config).map_err(simplify),
+        Err(ConstEvalError::MirEvalError(MirEvalError::ExecutionLimitExceeded))
+    );
+}
+
+#[test]
+fn calls_writing_through_exposed_addresses_are_not_memoized() {
+    check_number(
+        r#"
+    fn write(addr: usize) {
+        unsafe { *(addr as *mut i32) = 5 }
+    }
+    const GOAL: i32 = {
+        let mut x = 0;
+        let addr = &mut x as *mut i32 as usize;
+        write(addr);
+        x = 0;
+        write(addr);
+        x
+    };
+    "#,
+        5,
+    );
+}
+
+#[test]
+fn calls_with_side_effects_are_not_memoized() {
+    check_number(
+        r#"
+    static mut COUNTER: i32 = 0;
+    fn next(step: i32) -> i32 {
+        unsafe {
+            COUNTER = COUNTER + step;
+            COUNTER
+        }
+    }
+    fn twice(step: i32) -> i32 {
+        next(step) * 10 + next(step)
+    }
+    const GOAL: i32 = twice(1) * 100 + twice(1);
+    "#,
+        1234,
+    );
+    check_number(
+        r#"
+    fn addr(x: i32) -> usize {
+        &x as *const i32 as usize
+    }
+    fn nested(x: i32) -> usize {
+        let padding = (0u64, 0u64);
+        addr(x) + padding.0 as usize
+    }
+    const GOAL: u8 = (nested(1) != addr(1)) as u8;
+    "#,
+        1,
+    );
+}
+
 #[test]
 fn type_error() {
     let e = eval_goal(
@@ -1601,3 +4331,167 @@ fn type_error() {
     );
     assert!(matches!(e, Err(ConstEvalError::MirLowerError(MirLowerError::TypeMismatch(_)))));
 }
+
+#[test]
+fn aggregate_constants() {
+    check_number(
+        r#"
+    struct Point { x: i32, y: i32 }
+    const ORIGIN: Point = Point { x: 0, y: 0 };
+    const GOAL: i32 = ORIGIN.x + 1;
+        "#,
+        1,
+    );
+    check_number(
+        r#"
+    enum Mode { Slow, Fast(u8) }
+    const MODE: Mode = Mode::Fast(3);
+    const GOAL: u8 = match MODE { Mode::Slow => 0, Mode::Fast(x) => x };
+        "#,
+        3,
+    );
+    check_number(
+        r#"
+    //- minicore: index, slice
+    struct Point { x: i32, y: i32 }
+    const POINTS: [Point; 2] = [Point { x: 0, y: 1 }, Point { x: 5, y: 2 }];
+    const GOAL: i32 = POINTS[1].x + POINTS[0].y;
+        "#,
+        6,
+    );
+}
+
+#[test]
+fn iterator_adapters() {
+    check_number(
+        r#"
+    //- minicore: fn, option, range
+    use core::ops::Range;
+    trait Iter {
+        type Item;
+        fn next(&mut self) -> Option<Self::Item>;
+        fn map<B, F: FnMut(Self::Item) -> B>(self, f: F) -> Map<Self, F> where Self: Sized {
+            Map { iter: self, f }
+        }
+        fn sum<S: Sum<Self::Item>>(self) -> S where Self: Sized {
+            S::sum(self)
+        }
+        fn count_all(mut self) -> i32 where Self: Sized {
+            let mut c = 0;
+            while let Some(_) = self.next() {
+                c = c + 1;
+            }
+            c
+        }
+    }
+    trait Sum<A> {
+        fn sum<I: Iter<Item = A>>(iter: I) -> Self;
+    }
+    impl Sum<i32> for i32 {
+        fn sum<I: Iter<Item = i32>>(mut iter: I) -> i32 {
+            let mut s = 0;
+            while let Some(x) = iter.next() {
+                s = s + x;
+            }
+            s
+        }
+    }
+    struct Map<I, F> { iter: I, f: F }
+    impl<B, I: Iter, F: FnMut(I::Item) -> B> Iter for Map<I, F> {
+        type Item = B;
+        fn next(&mut self) -> Option<B> {
+            match self.iter.next() {
+                Some(x) => Some((self.f)(x)),
+                None => None,
+            }
+        }
+    }
+    impl Iter for Range<i32> {
+        type Item = i32;
+        fn next(&mut self) -> Option<i32> {
+            if self.start < self.end {
+                let x = self.start;
+                self.start = x + 1;
+                Some(x)
+            } else {
+                None
+            }
+        }
+    }
+    const GOAL: i32 = {
+        let doubled = (0..3).map(|x| x * 2).sum::<i32>();
+        let plain = (0..4).sum::<i32>();
+        let count = (5..8).map(|x| x + 1).count_all();
+        doubled * 100 + plain * 10 + count
+    };
+        "#,
+        663,
+    );
+}
+
+#[test]
+fn speculative_bodies() {
+    let (db, files) = TestDB::with_many_files(
+        r#"
+//- /main.rs
+mod types;
+mod user;
+mod intrinsics {
+    extern "rust-intrinsic" {
+        pub fn size_of<T>() -> usize;
+    }
+}
+pub mod mem {
+    pub fn size_of<T>() -> usize {
+        unsafe { crate::intrinsics::size_of::<T>() }
+    }
+}
+fn main() {}
+//- /types.rs
+pub struct LocalTypeInScope(u8, u16, u32);
+//- /user.rs
+use crate::{mem, types::LocalTypeInScope};
+fn user() {
+    let n = 1;
+}
+"#,
+    );
+    // Evaluates `text` in the outermost scope of the body of the only function of `file_id`.
+    let eval = |file_id, text: &str| {
+        let module_id = db.module_for_file(file_id);
+        let def_map = module_id.def_map(&db);
+        let owner = def_map[module_id.local_id]
+            .scope
+            .declarations()
+            .find_map(|it| match it {
+                ModuleDefId::FunctionId(it) => Some(it.into()),
+                _ => None,
+            })
+            .unwrap();
+        let scope_id = db.expr_scopes(owner).scope_for(db.body(owner).body_expr).unwrap();
+        let body = Body::speculative_expr(&db, owner, Body::parse_speculative_expr(text).unwrap());
+        eval_speculative_body(&db, owner, scope_id, &body, 1000).map(|c| try_const_usize(&c))
+    };
+    let user = files[2];
+    assert_eq!(eval(user, "mem::size_of::<LocalTypeInScope>() * 2"), Ok(Some(16)));
+    // The body only sees the imports of the module of its owner.
+    assert!(eval(files[0], "mem::size_of::<LocalTypeInScope>() * 2").is_err());
+    // Blocks without items are lowered without being in any file, and their bindings are not the
+    // ones of the owner.
+    assert_eq!(eval(user, "{ let n = mem::size_of::<LocalTypeInScope>(); n * 3 }"), Ok(Some(24)));
+}
+
+#[test]
+fn inline_asm() {
+    check_fail(
+        r#"
+    #[rustc_builtin_macro]
+    macro_rules! asm { () => {} }
+    const GOAL: u8 = {
+        unsafe { asm!("nop") };
+        5
+    };
+    "#,
+        ConstEvalError::MirLowerError(MirLowerError::InlineAsm),
+    );
+}
@@ -92,6 +92,58 @@ fn offset() {
         "#,
         31,
     );
+    check_number(
+        r#"
+        extern "rust-intrinsic" {
+            pub fn offset<T>(dst: *const T, offset: isize) -> *const T;
+        }
+
+        const GOAL: i32 = unsafe {
+            let x = [1i32, 2, 3, 4];
+            let p = &x as *const [i32; 4] as *const i32;
+            let end = offset(p, 4);
+            *offset(end, -1) * 10 + *offset(p, 1)
+        };
+        "#,
+        42,
+    );
+}
+
+#[test]
+fn offset_out_of_bounds() {
+    check_fail(
+        r#"
+        extern "rust-intrinsic" {
+            pub fn offset<T>(dst: *const T, offset: isize) -> *const T;
+        }
+
+        const GOAL: i32 = unsafe {
+            let x = [1i32, 2, 3, 4];
+            let p = &x as *const [i32; 4] as *const i32;
+            *offset(p, 5)
+        };
+        "#,
+        ConstEvalError::MirEvalError(MirEvalError::UndefinedBehavior(
+            "out of bounds pointer arithmetic",
+        )),
+    );
+    check_fail(
+        r#"
+        extern "rust-intrinsic" {
+            pub fn offset<T>(dst: *const T, offset: isize) -> *const T;
+        }
+
+        const fn dangling() -> *const i32 {
+            let x = [1i32, 2, 3, 4];
+            &x as *const [i32; 4] as *const i32
+        }
+
+        const GOAL: i32 = unsafe { *offset(dangling(), 1) };
+        "#,
+        ConstEvalError::MirEvalError(MirEvalError::UndefinedBehavior(
+            "out of bounds pointer arithmetic",
+        )),
+    );
 }
 
 #[test]
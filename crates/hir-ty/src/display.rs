@@ -5,7 +5,7 @@
 use std::fmt::{self, Debug};
 
 use base_db::CrateId;
-use chalk_ir::{BoundVar, TyKind};
+use chalk_ir::{BoundVar, TyKind, UintTy};
 use hir_def::{
     adt::VariantData,
     body,
@@ -14,17 +14,22 @@ use hir_def::{
     generics::{TypeOrConstParamData, TypeParamProvenance},
     item_scope::ItemInNs,
     lang_item::{LangItem, LangItemTarget},
+    layout::{Layout, RustcEnumVariantIdx, TagEncoding, Variants},
     path::{Path, PathKind},
     type_ref::{TraitBoundModifier, TypeBound, TypeRef},
     visibility::Visibility,
-    HasModule, ItemContainerId, LocalFieldId, Lookup, ModuleDefId, ModuleId, TraitId,
+    EnumId, EnumVariantId, HasModule, ItemContainerId, LocalEnumVariantId, LocalFieldId, Lookup,
+    ModuleDefId, ModuleId, TraitId,
 };
 use hir_expand::{hygiene::Hygiene, name::Name};
 use intern::{Internable, Interned};
 use itertools::Itertools;
+use la_arena::ArenaMap;
+use rustc_index::vec::Idx;
 use smallvec::SmallVec;
 
 use crate::{
+    consteval::try_const_usize,
     db::HirDatabase,
     from_assoc_type_id, from_foreign_def_id, from_placeholder_idx,
     layout::layout_of_ty,
@@ -65,6 +70,10 @@ pub struct HirFormatter<'a> {
     pub(crate) max_size: Option<usize>,
     omit_verbose_types: bool,
     display_target: DisplayTarget,
+    const_limits: ConstRenderLimits,
+    /// Whether a part of a constant value was elided because of `const_limits`, or a constant
+    /// written as an expression was rendered as its value.
+    elided_const: bool,
 }
 
 impl HirFormatter<'_> {
@@ -148,6 +157,8 @@ pub trait HirDisplay {
             max_size: None,
             omit_verbose_types: false,
             display_target: DisplayTarget::SourceCode { module_id },
+            const_limits: ConstRenderLimits::default(),
+            elided_const: false,
         }) {
             Ok(()) => {}
             Err(HirDisplayError::FmtError) => panic!("Writing to String can't fail!"),
@@ -287,10 +298,93 @@ impl<T: HirDisplay> HirDisplayWrapper<'_, T> {
             max_size: self.max_size,
             omit_verbose_types: self.omit_verbose_types,
             display_target: self.display_target,
+            const_limits: ConstRenderLimits::default(),
+            elided_const: false,
         })
     }
 }
 
+/// Limits on how much of a constant value is rendered, after which the rest is elided with `…`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConstRenderLimits {
+    /// The number of elements of an array that are rendered.
+    pub max_array_elements: usize,
+    /// How deep the fields of structs, enums and tuples and the elements of arrays are rendered.
+    pub max_depth: usize,
+    /// The number of characters of a string or bytes of a byte string that are rendered.
+    pub max_string_len: usize,
+    /// The number of characters of the whole value.
+    pub max_len: usize,
+    /// Whether constants in types that are written as expressions, like `BLOCK * 2` in
+    /// `[u8; BLOCK * 2]`, are rendered as these expressions instead of their values.
+    pub keep_expressions: bool,
+}
+
+impl ConstRenderLimits {
+    pub const UNLIMITED: ConstRenderLimits = ConstRenderLimits {
+        max_array_elements: usize::MAX,
+        max_depth: usize::MAX,
+        max_string_len: usize::MAX,
+        max_len: usize::MAX,
+        keep_expressions: true,
+    };
+}
+
+impl Default for ConstRenderLimits {
+    fn default() -> Self {
+        ConstRenderLimits {
+            max_array_elements: 10,
+            keep_expressions: false,
+            ..ConstRenderLimits::UNLIMITED
+        }
+    }
+}
+
+/// The result of [`render_const_limited`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedConst {
+    /// The value, with what exceeds the limits elided.
+    pub short: String,
+    /// The value without limits, if something was elided from `short`.
+    pub full: Option<String>,
+}
+
+/// Renders `value` like [`HirDisplay::display`], with the constant values in it rendered within
+/// `limits`.
+pub fn render_const_limited(
+    db: &dyn HirDatabase,
+    value: &impl HirDisplay,
+    limits: ConstRenderLimits,
+) -> RenderedConst {
+    let render = |limits| {
+        let mut result = String::new();
+        let mut f = HirFormatter {
+            db,
+            fmt: &mut result,
+            buf: String::with_capacity(20),
+            curr_size: 0,
+            max_size: None,
+            omit_verbose_types: false,
+            display_target: DisplayTarget::Diagnostics,
+            const_limits: limits,
+            elided_const: false,
+        };
+        // Writing to a `String` can't fail, and `Diagnostics` never fails with a
+        // `DisplaySourceCodeError`.
+        let _ = value.hir_fmt(&mut f);
+        let elided = f.elided_const;
+        (result, elided)
+    };
+    let (mut short, mut elided) = render(limits);
+    if let Some((end, _)) = short.char_indices().nth(limits.max_len) {
+        short.truncate(end);
+        short.push_str(TYPE_HINT_TRUNCATION);
+        elided = true;
+    }
+    let full = elided.then(|| render(ConstRenderLimits::UNLIMITED).0);
+    RenderedConst { short, full }
+}
+
 impl<'a, T> fmt::Display for HirDisplayWrapper<'a, T>
 where
     T: HirDisplay,
@@ -376,13 +470,53 @@ impl HirDisplay for Const {
                 write!(f, "{}", param_data.name().unwrap())
             }
             ConstValue::Concrete(c) => match &c.interned {
-                ConstScalar::Bytes(b, m) => render_const_scalar(f, &b, m, &data.ty),
+                ConstScalar::Bytes(b, m) => render_const_scalar(f, &b, m, &data.ty, 0),
                 ConstScalar::Unknown => f.write_char('_'),
+                ConstScalar::UnevaluatedConst(..) | ConstScalar::BinaryOp(_) => {
+                    // Show what constants like `SIZE * 2` evaluate to, but keep the expression
+                    // itself in code we insert in files and in tests.
+                    if matches!(f.display_target, DisplayTarget::Diagnostics)
+                        && !f.const_limits.keep_expressions
+                    {
+                        if let Some(value) = f.db.eval_unevaluated_const(self.clone()) {
+                            f.elided_const = true;
+                            return value.hir_fmt(f);
+                        }
+                    }
+                    render_unevaluated_const(f, &c.interned)
+                }
             },
         }
     }
 }
 
+fn render_unevaluated_const(
+    f: &mut HirFormatter<'_>,
+    c: &ConstScalar,
+) -> Result<(), HirDisplayError> {
+    match c {
+        ConstScalar::UnevaluatedConst(c, _) => match &f.db.const_data(*c).name {
+            Some(name) => write!(f, "{name}"),
+            None => f.write_char('_'),
+        },
+        ConstScalar::BinaryOp(x) => {
+            let (lhs, op, rhs) = &**x;
+            let operand = |f: &mut HirFormatter<'_>, x: &Const| match &x.data(Interner).value {
+                ConstValue::Concrete(c) if matches!(c.interned, ConstScalar::BinaryOp(_)) => {
+                    write!(f, "(")?;
+                    x.hir_fmt(f)?;
+                    write!(f, ")")
+                }
+                _ => x.hir_fmt(f),
+            };
+            operand(f, lhs)?;
+            write!(f, " {op} ")?;
+            operand(f, rhs)
+        }
+        ConstScalar::Bytes(..) | ConstScalar::Unknown => f.write_char('_'),
+    }
+}
+
 pub struct HexifiedConst(pub Const);
 
 impl HirDisplay for HexifiedConst {
@@ -394,7 +528,7 @@ impl HirDisplay for HexifiedConst {
                     if let ConstScalar::Bytes(b, m) = &c.interned {
                         let value = u128::from_le_bytes(pad16(b, false));
                         if value >= 10 {
-                            render_const_scalar(f, &b, m, &data.ty)?;
+                            render_const_scalar(f, &b, m, &data.ty, 0)?;
                             return write!(f, " ({:#X})", value);
                         }
                     }
@@ -410,7 +544,12 @@ fn render_const_scalar(
     b: &[u8],
     memory_map: &MemoryMap,
     ty: &Ty,
+    depth: usize,
 ) -> Result<(), HirDisplayError> {
+    if depth > f.const_limits.max_depth {
+        f.elided_const = true;
+        return f.write_str(TYPE_HINT_TRUNCATION);
+    }
     match ty.kind(Interner) {
         chalk_ir::TyKind::Scalar(s) => match s {
             Scalar::Bool => write!(f, "{}", if b[0] == 0 { false } else { true }),
@@ -443,9 +582,38 @@ fn render_const_scalar(
         chalk_ir::TyKind::Ref(_, _, t) => match t.kind(Interner) {
             chalk_ir::TyKind::Str => {
                 let addr = usize::from_le_bytes(b[0..b.len() / 2].try_into().unwrap());
-                let bytes = memory_map.0.get(&addr).map(|x| &**x).unwrap_or(&[]);
+                let bytes = memory_map.memory.get(&addr).map(|x| &x[..]).unwrap_or(&[]);
                 let s = std::str::from_utf8(bytes).unwrap_or("<utf8-error>");
-                write!(f, "{s:?}")
+                let max_len = f.const_limits.max_string_len;
+                match s.char_indices().nth(max_len) {
+                    Some((end, _)) => {
+                        f.elided_const = true;
+                        let s = format!("{:?}", &s[..end]);
+                        write!(f, "{}{TYPE_HINT_TRUNCATION}\"", &s[..s.len() - 1])
+                    }
+                    None => write!(f, "{s:?}"),
+                }
+            }
+            chalk_ir::TyKind::Array(e, _) | chalk_ir::TyKind::Slice(e)
+                if matches!(e.kind(Interner), TyKind::Scalar(Scalar::Uint(UintTy::U8))) =>
+            {
+                // Byte strings, which are thin pointers to arrays, or fat pointers to slices when
+                // they are typed as `&[u8]`.
+                let ptr = if matches!(t.kind(Interner), TyKind::Slice(_)) {
+                    &b[..b.len() / 2]
+                } else {
+                    b
+                };
+                let addr = u128::from_le_bytes(pad16(ptr, false)) as usize;
+                let bytes = memory_map.memory.get(&addr).map(|x| &x[..]).unwrap_or(&[]);
+                let max_len = f.const_limits.max_string_len;
+                let shown = &bytes[..bytes.len().min(max_len)];
+                write!(f, "b\"{}", shown.escape_ascii())?;
+                if shown.len() < bytes.len() {
+                    f.elided_const = true;
+                    f.write_str(TYPE_HINT_TRUNCATION)?;
+                }
+                f.write_char('"')
             }
             _ => f.write_str("<ref-not-supported>"),
         },
@@ -473,69 +641,186 @@ fn render_const_scalar(
                     continue;
                 };
                 let size = layout.size.bytes_usize();
-                render_const_scalar(f, &b[offset..offset + size], memory_map, &ty)?;
+                render_const_scalar(f, &b[offset..offset + size], memory_map, &ty, depth + 1)?;
             }
             f.write_str(")")
         }
-        chalk_ir::TyKind::Adt(adt, subst) => match adt.0 {
-            hir_def::AdtId::StructId(s) => {
-                let data = f.db.struct_data(s);
-                let Ok(layout) = f.db.layout_of_adt(adt.0, subst.clone()) else {
+        chalk_ir::TyKind::Adt(adt, subst) => {
+            let Ok(layout) = f.db.layout_of_adt(adt.0, subst.clone()) else {
+                return f.write_str("<layout-error>");
+            };
+            match adt.0 {
+                hir_def::AdtId::StructId(s) => {
+                    let data = f.db.struct_data(s);
+                    write!(f, "{}", data.name)?;
+                    let field_types = f.db.field_types(s.into());
+                    render_variant_after_name(
+                        &data.variant_data,
+                        f,
+                        &field_types,
+                        adt.0.module(f.db.upcast()).krate(),
+                        subst,
+                        VariantValue { b, memory_map, layout: &layout, depth },
+                    )
+                }
+                hir_def::AdtId::UnionId(u) => write!(f, "{}", f.db.union_data(u).name),
+                hir_def::AdtId::EnumId(e) => {
+                    let krate = adt.0.module(f.db.upcast()).krate();
+                    let Some((var_id, var_layout)) =
+                        detect_variant_from_bytes(&layout, f.db, krate, b, e)
+                    else {
+                        return f.write_str("<failed-to-detect-variant>");
+                    };
+                    let enum_data = f.db.enum_data(e);
+                    let data = &enum_data.variants[var_id];
+                    write!(f, "{}::{}", enum_data.name, data.name)?;
+                    let field_types =
+                        f.db.field_types(EnumVariantId { parent: e, local_id: var_id }.into());
+                    render_variant_after_name(
+                        &data.variant_data,
+                        f,
+                        &field_types,
+                        krate,
+                        subst,
+                        VariantValue { b, memory_map, layout: var_layout, depth },
+                    )
+                }
+            }
+        }
+        chalk_ir::TyKind::Array(element_ty, len) => {
+            let krate = *f.db.crate_graph().crates_in_topological_order().last().unwrap();
+            let (Some(len), Ok(layout)) =
+                (try_const_usize(len), layout_of_ty(f.db, element_ty, krate))
+            else {
+                return f.write_str("<layout-error>");
+            };
+            let size = layout.size.bytes_usize();
+            f.write_str("[")?;
+            let max_elements = f.const_limits.max_array_elements;
+            for i in 0..(len as usize).min(max_elements) {
+                if i != 0 {
+                    f.write_str(", ")?;
+                }
+                let Some(element) = b.get(i * size..(i + 1) * size) else {
                     return f.write_str("<layout-error>");
                 };
-                match data.variant_data.as_ref() {
-                    VariantData::Record(fields) | VariantData::Tuple(fields) => {
-                        let field_types = f.db.field_types(s.into());
-                        let krate = adt.0.module(f.db.upcast()).krate();
-                        let render_field = |f: &mut HirFormatter<'_>, id: LocalFieldId| {
-                            let offset = layout
-                                .fields
-                                .offset(u32::from(id.into_raw()) as usize)
-                                .bytes_usize();
-                            let ty = field_types[id].clone().substitute(Interner, subst);
-                            let Ok(layout) = layout_of_ty(f.db, &ty, krate) else {
-                                return f.write_str("<layout-error>");
-                            };
-                            let size = layout.size.bytes_usize();
-                            render_const_scalar(f, &b[offset..offset + size], memory_map, &ty)
-                        };
-                        let mut it = fields.iter();
-                        if matches!(data.variant_data.as_ref(), VariantData::Record(_)) {
-                            write!(f, "{} {{", data.name)?;
-                            if let Some((id, data)) = it.next() {
-                                write!(f, " {}: ", data.name)?;
-                                render_field(f, id)?;
-                            }
-                            for (id, data) in it {
-                                write!(f, ", {}: ", data.name)?;
-                                render_field(f, id)?;
-                            }
-                            write!(f, " }}")?;
-                        } else {
-                            let mut it = it.map(|x| x.0);
-                            write!(f, "{}(", data.name)?;
-                            if let Some(id) = it.next() {
-                                render_field(f, id)?;
-                            }
-                            for id in it {
-                                write!(f, ", ")?;
-                                render_field(f, id)?;
-                            }
-                            write!(f, ")")?;
-                        }
-                        return Ok(());
-                    }
-                    VariantData::Unit => write!(f, "{}", data.name),
-                }
+                render_const_scalar(f, element, memory_map, element_ty, depth + 1)?;
             }
-            hir_def::AdtId::UnionId(u) => write!(f, "{}", f.db.union_data(u).name),
-            hir_def::AdtId::EnumId(_) => f.write_str("<enum-not-supported>"),
-        },
+            if len as usize > max_elements {
+                f.elided_const = true;
+                write!(f, ", {TYPE_HINT_TRUNCATION}; {len}")?;
+            }
+            f.write_str("]")
+        }
         chalk_ir::TyKind::FnDef(..) => ty.hir_fmt(f),
         _ => f.write_str("<not-supported>"),
     }
 }
 
+/// The value of a struct or enum variant being rendered.
+struct VariantValue<'a> {
+    /// The bytes of the value, laid out as `layout`.
+    b: &'a [u8],
+    memory_map: &'a MemoryMap,
+    layout: &'a Layout,
+    /// How deep the value is nested in the rendered constant.
+    depth: usize,
+}
+
+/// Renders the fields of a struct or enum variant value, i.e. the part of its literal after the
+/// name.
+fn render_variant_after_name(
+    data: &VariantData,
+    f: &mut HirFormatter<'_>,
+    field_types: &ArenaMap<LocalFieldId, Binders<Ty>>,
+    krate: CrateId,
+    subst: &Substitution,
+    value: VariantValue<'_>,
+) -> Result<(), HirDisplayError> {
+    let VariantValue { b, memory_map, layout, depth } = value;
+    match data {
+        VariantData::Record(fields) | VariantData::Tuple(fields) => {
+            let render_field = |f: &mut HirFormatter<'_>, id: LocalFieldId| {
+                let offset = layout.fields.offset(u32::from(id.into_raw()) as usize).bytes_usize();
+                let ty = field_types[id].clone().substitute(Interner, subst);
+                let Ok(layout) = layout_of_ty(f.db, &ty, krate) else {
+                    return f.write_str("<layout-error>");
+                };
+                let size = layout.size.bytes_usize();
+                render_const_scalar(f, &b[offset..offset + size], memory_map, &ty, depth + 1)
+            };
+            let mut it = fields.iter();
+            if matches!(data, VariantData::Record(_)) {
+                write!(f, " {{")?;
+                if let Some((id, data)) = it.next() {
+                    write!(f, " {}: ", data.name)?;
+                    render_field(f, id)?;
+                }
+                for (id, data) in it {
+                    write!(f, ", {}: ", data.name)?;
+                    render_field(f, id)?;
+                }
+                write!(f, " }}")?;
+            } else {
+                let mut it = it.map(|x| x.0);
+                write!(f, "(")?;
+                if let Some(id) = it.next() {
+                    render_field(f, id)?;
+                }
+                for id in it {
+                    write!(f, ", ")?;
+                    render_field(f, id)?;
+                }
+                write!(f, ")")?;
+            }
+            Ok(())
+        }
+        VariantData::Unit => Ok(()),
+    }
+}
+
+/// Finds which variant of the enum `e` is stored in `b`, by decoding its tag according to
+/// `layout`, and returns it with the layout of its fields.
+fn detect_variant_from_bytes<'a>(
+    layout: &'a Layout,
+    db: &dyn HirDatabase,
+    krate: CrateId,
+    b: &[u8],
+    e: EnumId,
+) -> Option<(LocalEnumVariantId, &'a Layout)> {
+    let (tag, tag_encoding, variants) = match &layout.variants {
+        Variants::Single { index } => return Some((index.0, layout)),
+        Variants::Multiple { tag, tag_encoding, variants, .. } => (tag, tag_encoding, variants),
+    };
+    let target_data_layout = db.target_data_layout(krate)?;
+    let size = tag.size(&*target_data_layout).bytes_usize();
+    // The tag is the only field of enums with several variants.
+    let offset = layout.fields.offset(0).bytes_usize();
+    let tag = u128::from_le_bytes(pad16(b.get(offset..offset + size)?, false));
+    let mask = if size >= 16 { u128::MAX } else { (1 << (8 * size)) - 1 };
+    let index = match tag_encoding {
+        TagEncoding::Direct => {
+            let (local_id, _) = db.enum_data(e).variants.iter().find(|&(local_id, _)| {
+                let discriminant =
+                    db.const_eval_discriminant(EnumVariantId { parent: e, local_id });
+                discriminant.map_or(false, |d| d as u128 & mask == tag)
+            })?;
+            RustcEnumVariantIdx(local_id)
+        }
+        TagEncoding::Niche { untagged_variant, niche_variants, niche_start } => {
+            let relative = tag.wrapping_sub(*niche_start) & mask;
+            let start = niche_variants.start().index() as u128;
+            let end = niche_variants.end().index() as u128;
+            if relative <= end - start {
+                RustcEnumVariantIdx::new((start + relative) as usize)
+            } else {
+                *untagged_variant
+            }
+        }
+    };
+    Some((index.0, &variants[index]))
+}
+
 impl HirDisplay for BoundVar {
     fn hir_fmt(&self, f: &mut HirFormatter<'_>) -> Result<(), HirDisplayError> {
         write!(f, "?{}.{}", self.debruijn.depth(), self.index)
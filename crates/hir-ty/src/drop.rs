@@ -0,0 +1,108 @@
+//! Utilities for computing whether a type needs to be dropped.
+
+use std::sync::Arc;
+
+use base_db::CrateId;
+use hir_def::{lang_item::LangItem, AdtId, EnumVariantId, VariantId};
+
+use crate::{
+    consteval::try_const_usize, db::HirDatabase, infer::closure_captures,
+    method_resolution::implements_trait, Canonical, CanonicalVarKinds, Interner, TraitEnvironment,
+    Ty, TyKind,
+};
+
+/// Whether a value of type `ty` needs to be dropped when it goes out of scope, i.e. whether the
+/// type or one of the types it contains has a `Drop` impl. Types which we can't see through, like
+/// generic parameters, conservatively need drop unless they are `Copy`.
+pub(crate) fn needs_drop_query(db: &dyn HirDatabase, ty: Ty, env: Arc<TraitEnvironment>) -> bool {
+    let needs_drop = |ty: &Ty| db.needs_drop(ty.clone(), env.clone());
+    match ty.kind(Interner) {
+        TyKind::Adt(chalk_ir::AdtId(adt), subst) => {
+            if is_manually_drop(db, *adt, env.krate) {
+                return false;
+            }
+            if implements_lang_trait(db, &ty, &env, LangItem::Drop) {
+                return true;
+            }
+            let variants: Vec<VariantId> = match *adt {
+                AdtId::StructId(s) => vec![s.into()],
+                // Fields of unions are never dropped.
+                AdtId::UnionId(_) => return false,
+                AdtId::EnumId(e) => db
+                    .enum_data(e)
+                    .variants
+                    .iter()
+                    .map(|(local_id, _)| EnumVariantId { parent: e, local_id }.into())
+                    .collect(),
+            };
+            variants.into_iter().any(|v| {
+                db.field_types(v)
+                    .iter()
+                    .any(|(_, field_ty)| needs_drop(&field_ty.clone().substitute(Interner, subst)))
+            })
+        }
+        TyKind::Tuple(_, subst) => {
+            subst.iter(Interner).any(|x| needs_drop(x.assert_ty_ref(Interner)))
+        }
+        TyKind::Array(inner, len) => try_const_usize(len) != Some(0) && needs_drop(inner),
+        TyKind::Slice(inner) => needs_drop(inner),
+        TyKind::Closure(c, _) => {
+            closure_captures(db, *c).0.iter().any(|capture| needs_drop(&capture.ty))
+        }
+        TyKind::Dyn(_) | TyKind::Generator(..) | TyKind::GeneratorWitness(..) => true,
+        TyKind::Placeholder(_)
+        | TyKind::BoundVar(_)
+        | TyKind::Alias(_)
+        | TyKind::AssociatedType(..)
+        | TyKind::OpaqueType(..) => !implements_lang_trait(db, &ty, &env, LangItem::Copy),
+        TyKind::Scalar(_)
+        | TyKind::Str
+        | TyKind::Ref(..)
+        | TyKind::Raw(..)
+        | TyKind::FnDef(..)
+        | TyKind::Function(_)
+        | TyKind::Never
+        | TyKind::Foreign(_)
+        | TyKind::InferenceVar(..)
+        | TyKind::Error => false,
+    }
+}
+
+pub(crate) fn needs_drop_recover(
+    _db: &dyn HirDatabase,
+    _cycle: &[String],
+    _ty: &Ty,
+    _env: &Arc<TraitEnvironment>,
+) -> bool {
+    // The query doesn't look behind references and pointers, and owning pointers like `Box`
+    // implement `Drop`, which is checked before the fields. So the cycle comes from a type that
+    // contains itself directly, which is an error (it has an infinite size), and we just say it
+    // doesn't need drop.
+    false
+}
+
+/// Whether `adt` is `ManuallyDrop`, whose content is never dropped.
+pub(crate) fn is_manually_drop(db: &dyn HirDatabase, adt: AdtId, krate: CrateId) -> bool {
+    let manually_drop = db.lang_item(krate, LangItem::ManuallyDrop).and_then(|x| x.as_struct());
+    matches!(adt, AdtId::StructId(s) if Some(s) == manually_drop)
+}
+
+fn implements_lang_trait(
+    db: &dyn HirDatabase,
+    ty: &Ty,
+    env: &Arc<TraitEnvironment>,
+    lang: LangItem,
+) -> bool {
+    let Some(trait_) = db.lang_item(env.krate, lang).and_then(|x| x.as_trait()) else {
+        return false;
+    };
+    implements_trait(
+        &Canonical { value: ty.clone(), binders: CanonicalVarKinds::empty(Interner) },
+        db,
+        env.clone(),
+        trait_,
+    )
+}
+
+#[cfg(test)]
+mod tests;
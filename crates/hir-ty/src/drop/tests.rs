@@ -0,0 +1,128 @@
+use base_db::fixture::WithFixture;
+use hir_def::db::DefDatabase;
+
+use crate::{db::HirDatabase, test_db::TestDB};
+
+/// Whether the type of the parameter `x` of the function `goal` needs drop.
+fn needs_drop(ra_fixture: &str) -> bool {
+    let ra_fixture = format!(
+        r#"
+//- minicore: drop, copy, manually_drop
+struct Vec<T>(*mut T, usize, usize);
+impl<T> Drop for Vec<T> {{
+    fn drop(&mut self) {{}}
+}}
+struct String {{
+    vec: Vec<u8>,
+}}
+{ra_fixture}"#
+    );
+    let (db, file_id) = TestDB::with_single_file(&ra_fixture);
+    let module_id = db.module_for_file(file_id);
+    let def_map = module_id.def_map(&db);
+    let scope = &def_map[module_id.local_id].scope;
+    let func_id = scope
+        .declarations()
+        .find_map(|x| match x {
+            hir_def::ModuleDefId::FunctionId(x) => {
+                (db.function_data(x).name.to_smol_str() == "goal").then_some(x)
+            }
+            _ => None,
+        })
+        .unwrap();
+    let body = db.body(func_id.into());
+    let binding = body.bindings.iter().find(|x| x.1.name.to_smol_str() == "x").unwrap().0;
+    let ty = db.infer(func_id.into()).type_of_binding[binding].clone();
+    db.needs_drop(ty, db.trait_environment_for_body(func_id.into()))
+}
+
+#[test]
+fn primitives() {
+    assert!(!needs_drop("fn goal(x: i32) {}"));
+    assert!(!needs_drop("fn goal(x: &String) {}"));
+    assert!(!needs_drop("fn goal(x: *const String) {}"));
+    assert!(!needs_drop("fn goal(x: fn(String)) {}"));
+}
+
+#[test]
+fn drop_impl() {
+    assert!(needs_drop("fn goal(x: Vec<i32>) {}"));
+    assert!(needs_drop("fn goal(x: String) {}"));
+}
+
+#[test]
+fn compound_types() {
+    assert!(needs_drop("fn goal(x: (i32, String)) {}"));
+    assert!(!needs_drop("fn goal(x: (i32, u8)) {}"));
+    assert!(needs_drop("fn goal(x: [String; 2]) {}"));
+    assert!(!needs_drop("fn goal(x: [String; 0]) {}"));
+    assert!(!needs_drop("fn goal(x: [i32; 2]) {}"));
+    assert!(needs_drop(
+        r#"
+enum E { A(i32), B(String) }
+fn goal(x: E) {}
+"#
+    ));
+    assert!(!needs_drop(
+        r#"
+enum E { A(i32), B }
+fn goal(x: E) {}
+"#
+    ));
+    assert!(!needs_drop(
+        r#"
+union U { a: i32, b: String }
+fn goal(x: U) {}
+"#
+    ));
+}
+
+#[test]
+fn recursive_types() {
+    assert!(!needs_drop(
+        r#"
+struct List<'a> { next: Option<&'a List<'a>> }
+enum Option<T> { None, Some(T) }
+fn goal(x: List<'static>) {}
+"#
+    ));
+    assert!(needs_drop(
+        r#"
+struct Tree { children: Vec<Tree> }
+fn goal(x: Tree) {}
+"#
+    ));
+    // An infinitely sized type, whose query is a cycle.
+    assert!(!needs_drop(
+        r#"
+struct Infinite { inner: Infinite }
+fn goal(x: Infinite) {}
+"#
+    ));
+}
+
+#[test]
+fn manually_drop() {
+    assert!(!needs_drop("fn goal(x: core::mem::ManuallyDrop<String>) {}"));
+    assert!(!needs_drop("fn goal(x: [core::mem::ManuallyDrop<String>; 2]) {}"));
+    assert!(needs_drop("fn goal(x: (core::mem::ManuallyDrop<String>, String)) {}"));
+}
+
+#[test]
+fn generics() {
+    assert!(needs_drop("fn goal<T>(x: T) {}"));
+    assert!(!needs_drop("fn goal<T: Copy>(x: T) {}"));
+    assert!(needs_drop("fn goal<T: Copy>(x: (T, String)) {}"));
+    assert!(!needs_drop(
+        r#"
+struct Wrapper<T>(T);
+fn goal(x: Wrapper<i32>) {}
+"#
+    ));
+    assert!(needs_drop(
+        r#"
+struct Wrapper<T>(T);
+fn goal(x: Wrapper<String>) {}
+"#
+    ));
+}
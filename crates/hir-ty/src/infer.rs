@@ -38,9 +38,9 @@ use stdx::{always, never};
 
 use crate::{
     db::HirDatabase, fold_tys, fold_tys_and_consts, infer::coerce::CoerceMany,
-    lower::ImplTraitLoweringMode, static_lifetime, to_assoc_type_id, AliasEq, AliasTy, Const,
-    DomainGoal, GenericArg, Goal, ImplTraitId, InEnvironment, Interner, ProjectionTy, RpitId,
-    Substitution, TraitRef, Ty, TyBuilder, TyExt, TyKind,
+    lower::ImplTraitLoweringMode, static_lifetime, to_assoc_type_id, traits::FnTrait, AliasEq,
+    AliasTy, ClosureId, Const, DomainGoal, GenericArg, Goal, ImplTraitId, InEnvironment, Interner,
+    ProjectionTy, RpitId, Substitution, TraitEnvironment, TraitRef, Ty, TyBuilder, TyExt, TyKind,
 };
 
 // This lint has a false positive here. See the link below for details.
@@ -51,6 +51,9 @@ pub use coerce::could_coerce;
 #[allow(unreachable_pub)]
 pub use unify::could_unify;
 
+pub(crate) use closure::closure_captures;
+pub use closure::{CaptureKind, CapturedItem};
+
 pub(crate) mod unify;
 mod path;
 mod expr;
@@ -102,18 +105,36 @@ pub(crate) fn infer_query(db: &dyn HirDatabase, def: DefWithBodyId) -> Arc<Infer
 
     ctx.infer_mut_body();
 
-    Arc::new(ctx.resolve_all())
+    let mut result = ctx.resolve_all();
+    result.closure_info = closure::infer_closure_captures(db, def, &body, &result);
+    Arc::new(result)
+}
+
+/// Infers the types of `body`, a speculative body of `owner` which isn't stored in the database,
+/// with the resolver of the scope it is in. Its value may have any type. It can't contain closures,
+/// as their types are looked up in the inference result of `owner`.
+pub(crate) fn infer_speculative_body(
+    db: &dyn HirDatabase,
+    owner: DefWithBodyId,
+    body: &Body,
+    resolver: Resolver,
+) -> InferenceResult {
+    let _p = profile::span("infer_speculative_body");
+    let mut ctx = InferenceContext::new(db, owner, body, resolver);
+    ctx.return_ty = ctx.table.new_type_var();
+    ctx.infer_body();
+    ctx.infer_mut_body();
+    ctx.resolve_all()
 }
 
-/// Fully normalize all the types found within `ty` in context of `owner` body definition.
+/// Fully normalize all the types found within `ty` in the trait environment `trait_env`.
 ///
 /// This is appropriate to use only after type-check: it assumes
 /// that normalization will succeed, for example.
-pub(crate) fn normalize(db: &dyn HirDatabase, owner: DefWithBodyId, ty: Ty) -> Ty {
+pub(crate) fn normalize(db: &dyn HirDatabase, trait_env: Arc<TraitEnvironment>, ty: Ty) -> Ty {
     if !ty.data(Interner).flags.intersects(TypeFlags::HAS_PROJECTION) {
         return ty;
     }
-    let trait_env = db.trait_environment_for_body(owner);
     let mut table = unify::InferenceTable::new(db, trait_env);
 
     let ty_with_vars = table.normalize_associated_types_in(ty);
@@ -372,6 +393,8 @@ pub struct InferenceResult {
     pub pat_adjustments: FxHashMap<PatId, Vec<Ty>>,
     pub pat_binding_modes: FxHashMap<PatId, BindingMode>,
     pub expr_adjustments: FxHashMap<ExprId, Vec<Adjustment>>,
+    /// For each closure of the body, records what it captures and the `Fn*` trait it implements.
+    closure_info: FxHashMap<ClosureId, (Vec<CapturedItem>, FnTrait)>,
 }
 
 impl InferenceResult {
@@ -393,6 +416,9 @@ impl InferenceResult {
     pub fn assoc_resolutions_for_pat(&self, id: PatId) -> Option<(AssocItemId, Substitution)> {
         self.assoc_resolutions.get(&id.into()).cloned()
     }
+    pub fn closure_info(&self, closure: ClosureId) -> (&[CapturedItem], FnTrait) {
+        self.closure_info.get(&closure).map_or((&[], FnTrait::Fn), |(c, f)| (c, *f))
+    }
     pub fn type_mismatch_for_expr(&self, expr: ExprId) -> Option<&TypeMismatch> {
         self.type_mismatches.get(&expr.into())
     }
@@ -744,12 +770,15 @@ impl<'a> InferenceContext<'a> {
         self.result.standard_types.unknown.clone()
     }
 
-    /// Replaces ConstScalar::Unknown by a new type var, so we can maybe still infer it.
+    /// Replaces ConstScalar::Unknown and unevaluated constants by a new type var, so we can maybe
+    /// still infer it.
     fn insert_const_vars_shallow(&mut self, c: Const) -> Const {
         let data = c.data(Interner);
         match &data.value {
-            ConstValue::Concrete(cc) => match cc.interned {
-                crate::ConstScalar::Unknown => self.table.new_const_var(data.ty.clone()),
+            ConstValue::Concrete(cc) => match &cc.interned {
+                crate::ConstScalar::Unknown
+                | crate::ConstScalar::UnevaluatedConst(..)
+                | crate::ConstScalar::BinaryOp(..) => self.table.new_const_var(data.ty.clone()),
                 _ => c,
             },
             _ => c,
@@ -1,15 +1,29 @@
 //! Inference of closure parameter types based on the closure's expected type.
 
-use chalk_ir::{cast::Cast, AliasEq, AliasTy, FnSubst, WhereClause};
-use hir_def::{expr::ExprId, HasModule};
+use std::sync::Arc;
+
+use chalk_ir::{cast::Cast, AliasEq, AliasTy, FnSubst, Mutability, WhereClause};
+use hir_def::{
+    body::{scope::ExprScopes, Body},
+    expr::{
+        BinaryOp, BindingAnnotation, BindingId, CaptureBy, Expr, ExprId, Pat, PatId, Statement,
+        UnaryOp,
+    },
+    lang_item::LangItem,
+    resolver::{resolver_for_expr, ValueNs},
+    DefWithBodyId, HasModule, TraitId,
+};
+use rustc_hash::{FxHashMap, FxHashSet};
 use smallvec::SmallVec;
 
 use crate::{
-    to_chalk_trait_id, utils, ChalkTraitId, DynTy, FnPointer, FnSig, Interner, Substitution, Ty,
-    TyExt, TyKind,
+    db::HirDatabase, method_resolution::implements_trait, static_lifetime, to_chalk_trait_id,
+    traits::FnTrait, utils, Adjust, AutoBorrow, Canonical, CanonicalVarKinds, ChalkTraitId,
+    ClosureId, DynTy, FnPointer, FnSig, Interner, OverloadedDeref, Substitution, TraitEnvironment,
+    Ty, TyExt, TyKind,
 };
 
-use super::{Expectation, InferenceContext};
+use super::{Expectation, InferenceContext, InferenceResult};
 
 impl InferenceContext<'_> {
     // This function handles both closures and generators.
@@ -86,3 +100,386 @@ impl InferenceContext<'_> {
         None
     }
 }
+
+/// Returns what the closure captures, and the `Fn*` trait it implements.
+pub(crate) fn closure_captures(
+    db: &dyn HirDatabase,
+    closure: ClosureId,
+) -> (Vec<CapturedItem>, FnTrait) {
+    let (owner, _) = db.lookup_intern_closure(closure.into());
+    let infer = db.infer(owner);
+    let (captures, fn_trait) = infer.closure_info(closure);
+    (captures.to_vec(), fn_trait)
+}
+
+/// How a closure captures a binding of its enclosing body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureKind {
+    /// The closure holds a reference to the binding.
+    ByRef(Mutability),
+    /// The value of the binding is moved, or copied, into the closure.
+    ByValue,
+}
+
+impl CaptureKind {
+    fn rank(self) -> u8 {
+        match self {
+            CaptureKind::ByRef(Mutability::Not) => 0,
+            CaptureKind::ByRef(Mutability::Mut) => 1,
+            CaptureKind::ByValue => 2,
+        }
+    }
+}
+
+/// A binding of the enclosing body which is captured by a closure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedItem {
+    pub binding: BindingId,
+    pub kind: CaptureKind,
+    /// The type of the field of the closure which holds the capture, i.e. a reference to the type
+    /// of the binding if it is captured by reference.
+    pub ty: Ty,
+}
+
+/// How an expression which denotes a place is used by its parent expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum PlaceUse {
+    /// The place is borrowed, or a `Copy` value is read from it.
+    Ref,
+    /// The place is mutated through a reference stored in it, which only needs the reference to
+    /// be borrowed uniquely.
+    MutThroughRef,
+    /// The place is assigned or mutably borrowed.
+    Mut,
+    /// The value is moved out of the place.
+    Move,
+}
+
+/// Computes what each closure of `owner` captures, and which `Fn*` trait it implements.
+///
+/// Captures are whole bindings: a closure which uses a field of a binding captures the binding.
+/// A `move` closure captures by value, otherwise a binding is captured by reference unless the
+/// closure moves it. A closure which moves a captured value out only implements `FnOnce`, and one
+/// which mutates a captured place implements `FnMut`.
+pub(super) fn infer_closure_captures(
+    db: &dyn HirDatabase,
+    owner: DefWithBodyId,
+    body: &Body,
+    infer: &InferenceResult,
+) -> FxHashMap<ClosureId, (Vec<CapturedItem>, FnTrait)> {
+    let krate = owner.module(db.upcast()).krate();
+    let mut collector = CaptureCollector {
+        db,
+        owner,
+        body,
+        infer,
+        scopes: db.expr_scopes(owner),
+        trait_env: db.trait_environment_for_body(owner),
+        copy_trait: db.lang_item(krate, LangItem::Copy).and_then(|x| x.as_trait()),
+        result: FxHashMap::default(),
+    };
+    for (expr, _) in body.exprs.iter() {
+        collector.closure_info(expr);
+    }
+    collector.result
+}
+
+struct CaptureCollector<'a> {
+    db: &'a dyn HirDatabase,
+    owner: DefWithBodyId,
+    body: &'a Body,
+    infer: &'a InferenceResult,
+    scopes: Arc<ExprScopes>,
+    trait_env: Arc<TraitEnvironment>,
+    copy_trait: Option<TraitId>,
+    result: FxHashMap<ClosureId, (Vec<CapturedItem>, FnTrait)>,
+}
+
+/// The captures of the closure being analyzed.
+struct ClosureCaptures {
+    /// The bindings which are visible where the closure is defined.
+    outer_bindings: FxHashSet<BindingId>,
+    capture_by: CaptureBy,
+    captures: Vec<(BindingId, CaptureKind)>,
+    fn_trait: FnTrait,
+}
+
+impl CaptureCollector<'_> {
+    /// Analyzes `expr` if it is a closure whose captures aren't known yet.
+    fn closure_info(&mut self, expr: ExprId) -> Option<ClosureId> {
+        let &Expr::Closure { body: closure_body, capture_by, .. } = &self.body[expr] else {
+            return None;
+        };
+        let TyKind::Closure(id, _) = self.infer[expr].kind(Interner) else {
+            return None;
+        };
+        if self.result.contains_key(id) {
+            return Some(*id);
+        }
+        let mut closure = ClosureCaptures {
+            outer_bindings: self
+                .scopes
+                .scope_chain(self.scopes.scope_for(expr))
+                .flat_map(|scope| self.scopes.entries(scope))
+                .map(|entry| entry.binding())
+                .collect(),
+            capture_by,
+            captures: vec![],
+            fn_trait: FnTrait::Fn,
+        };
+        self.walk_expr(&mut closure, closure_body, PlaceUse::Move);
+        let captures = closure
+            .captures
+            .into_iter()
+            .map(|(binding, kind)| {
+                let binding_ty = self.infer[binding].clone();
+                let ty = match kind {
+                    CaptureKind::ByRef(m) => {
+                        TyKind::Ref(m, static_lifetime(), binding_ty).intern(Interner)
+                    }
+                    CaptureKind::ByValue => binding_ty,
+                };
+                CapturedItem { binding, kind, ty }
+            })
+            .collect();
+        self.result.insert(*id, (captures, closure.fn_trait));
+        Some(*id)
+    }
+
+    fn is_copy(&self, ty: &Ty) -> bool {
+        // Without the `Copy` trait we can't tell, so we are conservative and don't move anything.
+        let Some(copy_trait) = self.copy_trait else {
+            return true;
+        };
+        let ty = Canonical { value: ty.clone(), binders: CanonicalVarKinds::empty(Interner) };
+        implements_trait(&ty, self.db, self.trait_env.clone(), copy_trait)
+    }
+
+    /// The use of a value of type `ty`, which is read if it is `Copy` and moved otherwise.
+    fn read(&self, ty: &Ty) -> PlaceUse {
+        if self.is_copy(ty) {
+            PlaceUse::Ref
+        } else {
+            PlaceUse::Move
+        }
+    }
+
+    fn record(&self, closure: &mut ClosureCaptures, binding: BindingId, place_use: PlaceUse) {
+        let (kind, fn_trait) = match (place_use, closure.capture_by) {
+            (PlaceUse::Move, _) => (CaptureKind::ByValue, FnTrait::FnOnce),
+            (PlaceUse::Ref, CaptureBy::Ref) => (CaptureKind::ByRef(Mutability::Not), FnTrait::Fn),
+            (PlaceUse::MutThroughRef, CaptureBy::Ref) => {
+                (CaptureKind::ByRef(Mutability::Not), FnTrait::FnMut)
+            }
+            (PlaceUse::Mut, CaptureBy::Ref) => {
+                (CaptureKind::ByRef(Mutability::Mut), FnTrait::FnMut)
+            }
+            (PlaceUse::Ref, CaptureBy::Value) => (CaptureKind::ByValue, FnTrait::Fn),
+            (PlaceUse::Mut | PlaceUse::MutThroughRef, CaptureBy::Value) => {
+                (CaptureKind::ByValue, FnTrait::FnMut)
+            }
+        };
+        closure.fn_trait = match (closure.fn_trait, fn_trait) {
+            (FnTrait::FnOnce, _) | (_, FnTrait::FnOnce) => FnTrait::FnOnce,
+            (FnTrait::FnMut, _) | (_, FnTrait::FnMut) => FnTrait::FnMut,
+            (FnTrait::Fn, FnTrait::Fn) => FnTrait::Fn,
+        };
+        match closure.captures.iter_mut().find(|(b, _)| *b == binding) {
+            Some((_, existing)) => {
+                if kind.rank() > existing.rank() {
+                    *existing = kind;
+                }
+            }
+            None => closure.captures.push((binding, kind)),
+        }
+    }
+
+    /// Walks `expr`, whose value is used as `place_use` after its adjustments are applied.
+    fn walk_expr(&mut self, closure: &mut ClosureCaptures, expr: ExprId, place_use: PlaceUse) {
+        let mut place_use = place_use;
+        let adjustments = self.infer.expr_adjustments.get(&expr).map_or(&[][..], |x| &**x);
+        for (i, adjustment) in adjustments.iter().enumerate().rev() {
+            place_use = match &adjustment.kind {
+                Adjust::Borrow(AutoBorrow::Ref(m) | AutoBorrow::RawPtr(m)) => match m {
+                    Mutability::Mut => PlaceUse::Mut,
+                    Mutability::Not => PlaceUse::Ref,
+                },
+                Adjust::Deref(None) => match place_use {
+                    PlaceUse::Mut | PlaceUse::MutThroughRef => PlaceUse::MutThroughRef,
+                    PlaceUse::Ref | PlaceUse::Move => PlaceUse::Ref,
+                },
+                Adjust::Deref(Some(OverloadedDeref(Some(Mutability::Mut)))) => PlaceUse::Mut,
+                Adjust::Deref(Some(_)) => PlaceUse::Ref,
+                Adjust::NeverToAny => place_use,
+                Adjust::Pointer(_) => {
+                    let source = match i {
+                        0 => &self.infer[expr],
+                        _ => &adjustments[i - 1].target,
+                    };
+                    self.read(source)
+                }
+            };
+        }
+        match &self.body[expr] {
+            Expr::Path(p) => {
+                let resolver = resolver_for_expr(self.db.upcast(), self.owner, expr);
+                if let Some(ValueNs::LocalBinding(binding)) =
+                    resolver.resolve_path_in_value_ns_fully(self.db.upcast(), p)
+                {
+                    if closure.outer_bindings.contains(&binding) {
+                        let place_use = match place_use {
+                            PlaceUse::Move => self.read(&self.infer[binding]),
+                            x => x,
+                        };
+                        self.record(closure, binding, place_use);
+                    }
+                }
+            }
+            &Expr::Field { expr: base, .. } => {
+                let base_use = match place_use {
+                    PlaceUse::Move => self.read(&self.infer[expr]),
+                    x => x,
+                };
+                self.walk_expr(closure, base, base_use);
+            }
+            &Expr::Index { base, index } => {
+                let base_use = match place_use {
+                    PlaceUse::Move => self.read(&self.infer[expr]),
+                    x => x,
+                };
+                self.walk_expr(closure, base, base_use);
+                self.walk_expr(closure, index, PlaceUse::Move);
+            }
+            &Expr::UnaryOp { expr: inner, op: UnaryOp::Deref } => {
+                let inner_use = match (self.infer[inner].kind(Interner), place_use) {
+                    (
+                        TyKind::Ref(..) | TyKind::Raw(..),
+                        PlaceUse::Mut | PlaceUse::MutThroughRef,
+                    ) => PlaceUse::MutThroughRef,
+                    (_, PlaceUse::Mut | PlaceUse::MutThroughRef) => PlaceUse::Mut,
+                    (_, PlaceUse::Ref | PlaceUse::Move) => PlaceUse::Ref,
+                };
+                self.walk_expr(closure, inner, inner_use);
+            }
+            &Expr::Ref { expr: inner, mutability, .. } => {
+                let inner_use = match mutability {
+                    hir_def::type_ref::Mutability::Mut => PlaceUse::Mut,
+                    hir_def::type_ref::Mutability::Shared => PlaceUse::Ref,
+                };
+                self.walk_expr(closure, inner, inner_use);
+            }
+            &Expr::BinaryOp { lhs, rhs, op: Some(BinaryOp::Assignment { .. }) } => {
+                self.walk_expr(closure, lhs, PlaceUse::Mut);
+                self.walk_expr(closure, rhs, PlaceUse::Move);
+            }
+            &Expr::BinaryOp { lhs, rhs, op: Some(BinaryOp::CmpOp(_)) } => {
+                // Comparisons take their operands by reference.
+                self.walk_expr(closure, lhs, PlaceUse::Ref);
+                self.walk_expr(closure, rhs, PlaceUse::Ref);
+            }
+            &Expr::Call { callee, ref args, .. } => {
+                let callee_use = match self.infer[callee].kind(Interner) {
+                    &TyKind::Closure(id, _) => {
+                        let (closure_owner, closure_expr) =
+                            self.db.lookup_intern_closure(id.into());
+                        if closure_owner == self.owner {
+                            self.closure_info(closure_expr);
+                        }
+                        let fn_trait =
+                            self.result.get(&id).map_or(FnTrait::Fn, |(_, fn_trait)| *fn_trait);
+                        match fn_trait {
+                            FnTrait::Fn => PlaceUse::Ref,
+                            FnTrait::FnMut => PlaceUse::Mut,
+                            FnTrait::FnOnce => PlaceUse::Move,
+                        }
+                    }
+                    _ => PlaceUse::Move,
+                };
+                self.walk_expr(closure, callee, callee_use);
+                for &arg in args.iter() {
+                    self.walk_expr(closure, arg, PlaceUse::Move);
+                }
+            }
+            Expr::Closure { .. } => {
+                let Some(id) = self.closure_info(expr) else {
+                    return;
+                };
+                let inner_captures = self.result[&id].0.clone();
+                for capture in inner_captures {
+                    if closure.outer_bindings.contains(&capture.binding) {
+                        let place_use = match capture.kind {
+                            CaptureKind::ByRef(Mutability::Not) => PlaceUse::Ref,
+                            CaptureKind::ByRef(Mutability::Mut) => PlaceUse::Mut,
+                            CaptureKind::ByValue => self.read(&self.infer[capture.binding]),
+                        };
+                        self.record(closure, capture.binding, place_use);
+                    }
+                }
+            }
+            Expr::Block { statements, tail, .. } | Expr::Unsafe { statements, tail, .. } => {
+                for statement in statements.iter() {
+                    match statement {
+                        Statement::Let { pat, initializer, else_branch, .. } => {
+                            if let Some(initializer) = initializer {
+                                let place_use = self.pat_use(*pat);
+                                self.walk_expr(closure, *initializer, place_use);
+                            }
+                            if let Some(else_branch) = else_branch {
+                                self.walk_expr(closure, *else_branch, PlaceUse::Move);
+                            }
+                        }
+                        Statement::Expr { expr, .. } => {
+                            self.walk_expr(closure, *expr, PlaceUse::Move);
+                        }
+                    }
+                }
+                if let Some(tail) = tail {
+                    self.walk_expr(closure, *tail, PlaceUse::Move);
+                }
+            }
+            Expr::Match { expr: scrutinee, arms } => {
+                let place_use =
+                    arms.iter().map(|arm| self.pat_use(arm.pat)).max().unwrap_or(PlaceUse::Ref);
+                self.walk_expr(closure, *scrutinee, place_use);
+                for arm in arms.iter() {
+                    if let Some(guard) = arm.guard {
+                        self.walk_expr(closure, guard, PlaceUse::Move);
+                    }
+                    self.walk_expr(closure, arm.expr, PlaceUse::Move);
+                }
+            }
+            &Expr::Let { pat, expr: scrutinee } => {
+                let place_use = self.pat_use(pat);
+                self.walk_expr(closure, scrutinee, place_use);
+            }
+            e => e.walk_child_exprs(|child| self.walk_expr(closure, child, PlaceUse::Move)),
+        }
+    }
+
+    /// How a place matched against `pat` is used by the bindings of the pattern.
+    fn pat_use(&self, pat: PatId) -> PlaceUse {
+        let mut place_use = PlaceUse::Ref;
+        let mut stack = vec![pat];
+        while let Some(pat) = stack.pop() {
+            match &self.body[pat] {
+                // The bindings below a reference, either explicit or added by match ergonomics,
+                // only use the referenced place, not the matched one.
+                _ if self.infer.pat_adjustments.contains_key(&pat) => continue,
+                Pat::Ref { .. } => continue,
+                &Pat::Bind { id, .. } => {
+                    let binding_use = match self.body.bindings[id].mode {
+                        BindingAnnotation::Ref => PlaceUse::Ref,
+                        BindingAnnotation::RefMut => PlaceUse::Mut,
+                        BindingAnnotation::Unannotated | BindingAnnotation::Mutable => {
+                            self.read(&self.infer[id])
+                        }
+                    };
+                    place_use = place_use.max(binding_use);
+                }
+                _ => (),
+            }
+            self.body[pat].walk_child_pats(|child| stack.push(child));
+        }
+        place_use
+    }
+}
@@ -239,7 +239,7 @@ impl<'a> InferenceContext<'a> {
                 self.diverges = Diverges::Maybe;
                 TyBuilder::unit()
             }
-            Expr::Closure { body, args, ret_type, arg_types, closure_kind } => {
+            Expr::Closure { body, args, ret_type, arg_types, closure_kind, capture_by: _ } => {
                 assert_eq!(args.len(), arg_types.len());
 
                 let mut sig_tys = Vec::with_capacity(arg_types.len() + 1);
@@ -619,7 +619,11 @@ impl<'a> InferenceContext<'a> {
                 // FIXME: propagate the "castable to" expectation
                 let inner_ty = self.infer_expr_no_expect(*expr);
                 match (inner_ty.kind(Interner), cast_ty.kind(Interner)) {
-                    (TyKind::Ref(_, _, inner), TyKind::Raw(_, cast)) => {
+                    // Like rustc, cast a reference to a raw pointer by coercing it if possible, so
+                    // that the operand gets the adjustments of the coercion.
+                    (TyKind::Ref(_, _, inner), TyKind::Raw(_, cast))
+                        if self.coerce(Some(*expr), &inner_ty, &cast_ty).is_err() =>
+                    {
                         // FIXME: record invalid cast diagnostic in case of mismatch
                         self.unify(inner, cast);
                     }
@@ -890,6 +894,10 @@ impl<'a> InferenceContext<'a> {
                 // expression reaching this branch is an error.
                 self.err_ty()
             }
+            Expr::OffsetOf { .. } => TyBuilder::usize(),
+            // FIXME: `asm!` has the type `()` unless it has `options(noreturn)`, but its expansion
+            // doesn't keep the options, so we diverge like the expansion does.
+            Expr::InlineAsm { e } => self.infer_expr_no_expect(*e),
         };
         // use a new type variable if we got unknown here
         let ty = self.insert_type_vars_shallow(ty);
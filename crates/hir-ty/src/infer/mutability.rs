@@ -8,7 +8,10 @@ use hir_def::{
 };
 use hir_expand::name;
 
-use crate::{lower::lower_to_chalk_mutability, Adjust, Adjustment, AutoBorrow, OverloadedDeref};
+use crate::{
+    lower::lower_to_chalk_mutability, Adjust, Adjustment, AutoBorrow, Interner, OverloadedDeref,
+    TyKind,
+};
 
 use super::InferenceContext;
 
@@ -112,10 +115,15 @@ impl<'a> InferenceContext<'a> {
                                     .and_then(|it| it.last_mut());
                                 if let Some(Adjustment {
                                     kind: Adjust::Borrow(AutoBorrow::Ref(mutability)),
-                                    ..
+                                    target,
                                 }) = base_adjustments
                                 {
                                     *mutability = Mutability::Mut;
+                                    if let TyKind::Ref(_, lt, inner) = target.kind(Interner) {
+                                        *target =
+                                            TyKind::Ref(Mutability::Mut, lt.clone(), inner.clone())
+                                                .intern(Interner);
+                                    }
                                 }
                             }
                         }
@@ -170,12 +178,14 @@ impl<'a> InferenceContext<'a> {
             | Expr::Array(Array::ElementList { elements: exprs, is_assignee_expr: _ }) => {
                 self.infer_mut_not_expr_iter(exprs.iter().copied());
             }
+            Expr::InlineAsm { e } => self.infer_mut_expr(*e, Mutability::Not),
             // These don't need any action, as they don't have sub expressions
             Expr::Range { lhs: None, rhs: None, range_type: _ }
             | Expr::Literal(_)
             | Expr::Path(_)
             | Expr::Continue { .. }
-            | Expr::Underscore => (),
+            | Expr::Underscore
+            | Expr::OffsetOf { .. } => (),
         }
     }
 
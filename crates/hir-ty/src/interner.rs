@@ -40,8 +40,17 @@ impl_internable!(
     InternedWrapper<Vec<chalk_ir::ProgramClause<Interner>>>,
     InternedWrapper<Vec<chalk_ir::QuantifiedWhereClause<Interner>>>,
     InternedWrapper<Vec<chalk_ir::Variance>>,
+    InternedWrapper<Vec<u8>>,
 );
 
+/// The bytes of an allocation in the [`MemoryMap`](crate::MemoryMap) of a constant. Constants
+/// with identical allocations, like repeated string literals, share their storage.
+pub type InternedBytes = Interned<InternedWrapper<Vec<u8>>>;
+
+pub(crate) fn intern_bytes(bytes: Vec<u8>) -> InternedBytes {
+    Interned::new(InternedWrapper(bytes))
+}
+
 impl chalk_ir::interner::Interner for Interner {
     type InternedType = Interned<InternedWrapper<chalk_ir::TyData<Interner>>>;
     type InternedLifetime = Interned<InternedWrapper<chalk_ir::LifetimeData<Self>>>;
@@ -265,7 +274,11 @@ impl chalk_ir::interner::Interner for Interner {
         c1: &Self::InternedConcreteConst,
         c2: &Self::InternedConcreteConst,
     ) -> bool {
-        (c1 == &ConstScalar::Unknown) || (c2 == &ConstScalar::Unknown) || (c1 == c2)
+        (c1 == &ConstScalar::Unknown)
+            || (c2 == &ConstScalar::Unknown)
+            || c1.is_unevaluated()
+            || c2.is_unevaluated()
+            || (c1 == c2)
     }
 
     fn intern_generic_arg(
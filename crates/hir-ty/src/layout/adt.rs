@@ -123,5 +123,5 @@ pub fn layout_of_adt_recover(
     _: &AdtId,
     _: &Substitution,
 ) -> Result<Layout, LayoutError> {
-    user_error!("infinite sized recursive type");
+    Err(LayoutError::RecursiveTypeWithoutIndirection)
 }
@@ -11,6 +11,7 @@ mod autoderef;
 mod builder;
 mod chalk_db;
 mod chalk_ext;
+mod drop;
 pub mod consteval;
 pub mod mir;
 mod infer;
@@ -34,7 +35,11 @@ mod tests;
 #[cfg(test)]
 mod test_db;
 
-use std::{collections::HashMap, hash::Hash, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    sync::Arc,
+};
 
 use chalk_ir::{
     fold::{Shift, TypeFoldable},
@@ -43,7 +48,11 @@ use chalk_ir::{
     NoSolution, TyData,
 };
 use either::Either;
-use hir_def::{expr::ExprId, type_ref::Rawness, TypeOrConstParamId};
+use hir_def::{
+    expr::{ArithOp, ExprId},
+    type_ref::Rawness,
+    ConstId, TypeOrConstParamId,
+};
 use hir_expand::name;
 use la_arena::{Arena, Idx};
 use mir::MirEvalError;
@@ -62,7 +71,7 @@ pub use infer::{
     could_coerce, could_unify, Adjust, Adjustment, AutoBorrow, BindingMode, InferenceDiagnostic,
     InferenceResult, OverloadedDeref, PointerCast,
 };
-pub use interner::Interner;
+pub use interner::{InternedBytes, Interner};
 pub use lower::{
     associated_type_shorthand_candidates, CallableDefId, ImplTraitLoweringMode, TyDefId,
     TyLoweringContext, ValueTyDefId,
@@ -148,13 +157,19 @@ pub type WhereClause = chalk_ir::WhereClause<Interner>;
 
 /// A constant can have reference to other things. Memory map job is holding
 /// the neccessary bits of memory of the const eval session to keep the constant
-/// meaningful.
+/// meaningful. It is ordered by address, so that loading a constant allocates its
+/// memory in the same order on every run.
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
-pub struct MemoryMap(pub HashMap<usize, Vec<u8>>);
+pub struct MemoryMap {
+    pub memory: BTreeMap<usize, InternedBytes>,
+    /// The types of the function pointers in the constant, by the ids which the evaluator that
+    /// created it stored in their place.
+    pub fn_pointers: BTreeMap<usize, Ty>,
+}
 
 impl MemoryMap {
     fn insert(&mut self, addr: usize, x: Vec<u8>) {
-        self.0.insert(addr, x);
+        self.memory.insert(addr, interner::intern_bytes(x));
     }
 
     /// This functions convert each address by a function `f` which gets the byte intervals and assign an address
@@ -164,7 +179,7 @@ impl MemoryMap {
         &self,
         mut f: impl FnMut(&[u8]) -> Result<usize, MirEvalError>,
     ) -> Result<HashMap<usize, usize>, MirEvalError> {
-        self.0.iter().map(|x| Ok((*x.0, f(x.1)?))).collect()
+        self.memory.iter().map(|x| Ok((*x.0, f(x.1)?))).collect()
     }
 }
 
@@ -178,6 +193,21 @@ pub enum ConstScalar {
     // https://github.com/rust-lang/rust-analyzer/pull/8813#issuecomment-840679177
     // https://rust-lang.zulipchat.com/#narrow/stream/144729-wg-traits/topic/Handling.20non.20evaluatable.20constants'.20equality/near/238386348
     Unknown,
+    /// An associated constant whose substitution mentions generic parameters of the body using it,
+    /// so it can only be evaluated by the MIR interpreter, after substituting them. Constant items
+    /// named in types, like `SIZE` in `[u8; SIZE]`, are kept unevaluated as well.
+    UnevaluatedConst(ConstId, Substitution),
+    /// An arithmetic expression in a type, like `N * 2` in `[T; N * 2]`, that couldn't be folded
+    /// when the type was lowered.
+    BinaryOp(Box<(Const, ArithOp, Const)>),
+}
+
+impl ConstScalar {
+    /// Whether this is a constant of a type that isn't evaluated yet. These are equal to any other
+    /// constant, like unknown ones.
+    pub fn is_unevaluated(&self) -> bool {
+        matches!(self, ConstScalar::UnevaluatedConst(..) | ConstScalar::BinaryOp(..))
+    }
 }
 
 impl Hash for ConstScalar {
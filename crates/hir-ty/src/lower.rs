@@ -25,8 +25,8 @@ use hir_def::{
         TypeOrConstParamData, TypeParamProvenance, WherePredicate, WherePredicateTypeTarget,
     },
     lang_item::{lang_attr, LangItem},
-    path::{GenericArg, GenericArgs, ModPath, Path, PathKind, PathSegment, PathSegments},
-    resolver::{HasResolver, Resolver, TypeNs},
+    path::{GenericArg, GenericArgs, Path, PathKind, PathSegment, PathSegments},
+    resolver::{HasResolver, Resolver, TypeNs, ValueNs},
     type_ref::{ConstRefOrPath, TraitBoundModifier, TraitRef as HirTraitRef, TypeBound, TypeRef},
     AdtId, AssocItemId, ConstId, ConstParamId, DefWithBodyId, EnumId, EnumVariantId, FunctionId,
     GenericDefId, HasModule, ImplId, ItemContainerId, LocalFieldId, Lookup, ModuleDefId, StaticId,
@@ -42,17 +42,21 @@ use syntax::ast;
 
 use crate::{
     all_super_traits,
-    consteval::{intern_const_ref, path_to_const, unknown_const, unknown_const_as_generic},
+    consteval::{
+        fold_binary_op, intern_const_ref, intern_const_scalar, path_to_const, unknown_const,
+        unknown_const_as_generic,
+    },
     db::HirDatabase,
     make_binders,
     mapping::{from_chalk_trait_id, ToChalk},
     static_lifetime, to_assoc_type_id, to_chalk_trait_id, to_placeholder_idx,
     utils::Generics,
     utils::{all_super_trait_refs, associated_type_by_name_including_super_traits, generics},
-    AliasEq, AliasTy, Binders, BoundVar, CallableSig, Const, DebruijnIndex, DynTy, FnPointer,
-    FnSig, FnSubst, GenericArgData, ImplTraitId, Interner, ParamKind, PolyFnSig, ProjectionTy,
-    QuantifiedWhereClause, QuantifiedWhereClauses, ReturnTypeImplTrait, ReturnTypeImplTraits,
-    Substitution, TraitEnvironment, TraitRef, TraitRefExt, Ty, TyBuilder, TyKind, WhereClause,
+    AliasEq, AliasTy, Binders, BoundVar, CallableSig, Const, ConstScalar, ConstValue,
+    DebruijnIndex, DynTy, FnPointer, FnSig, FnSubst, GenericArgData, ImplTraitId, Interner,
+    ParamKind, PolyFnSig, ProjectionTy, QuantifiedWhereClause, QuantifiedWhereClauses,
+    ReturnTypeImplTrait, ReturnTypeImplTraits, Substitution, TraitEnvironment, TraitRef,
+    TraitRefExt, Ty, TyBuilder, TyKind, WhereClause,
 };
 
 #[derive(Debug)]
@@ -1979,8 +1983,8 @@ pub(crate) fn generic_arg_to_chalk<'a, T>(
             if let TypeRef::Path(p) = t {
                 let p = p.mod_path()?;
                 if p.kind == PathKind::Plain {
-                    if let [n] = p.segments() {
-                        let c = ConstRefOrPath::Path(n.clone());
+                    if let [_] = p.segments() {
+                        let c = ConstRefOrPath::Path(p.clone());
                         return Some(
                             GenericArgData::Const(for_const(this, &c, c_ty)).intern(Interner),
                         );
@@ -1999,22 +2003,55 @@ pub(crate) fn const_or_path_to_chalk(
     expected_ty: Ty,
     value: &ConstRefOrPath,
     mode: ParamLoweringMode,
-    args: impl FnOnce() -> Generics,
+    args: impl Fn() -> Generics,
     debruijn: DebruijnIndex,
 ) -> Const {
     match value {
         ConstRefOrPath::Scalar(s) => intern_const_ref(db, s, expected_ty, resolver.krate()),
-        ConstRefOrPath::Path(n) => {
-            let path = ModPath::from_segments(PathKind::Plain, Some(n.clone()));
-            path_to_const(
+        ConstRefOrPath::Path(path) => {
+            let path = Path::from_known_path_with_no_generic(path.clone());
+            path_to_const(db, resolver, &path, mode, &args, debruijn)
+                .or_else(|| match resolver.resolve_path_in_value_ns_fully(db.upcast(), &path)? {
+                    // Constant items are evaluated only when needed, like when the type is
+                    // displayed, as evaluating them while lowering types can easily cycle.
+                    ValueNs::ConstId(c) if generics(db.upcast(), c.into()).len() == 0 => {
+                        let value = ConstScalar::UnevaluatedConst(c, Substitution::empty(Interner));
+                        Some(intern_const_scalar(value, expected_ty.clone()))
+                    }
+                    _ => None,
+                })
+                .unwrap_or_else(|| unknown_const(expected_ty))
+        }
+        ConstRefOrPath::BinaryOp(lhs, op, rhs) => {
+            let args: &dyn Fn() -> Generics = &args;
+            let lhs = const_or_path_to_chalk(
+                db,
+                resolver,
+                expected_ty.clone(),
+                lhs,
+                mode,
+                args,
+                debruijn,
+            );
+            let rhs = const_or_path_to_chalk(
                 db,
                 resolver,
-                &Path::from_known_path_with_no_generic(path),
+                expected_ty.clone(),
+                rhs,
                 mode,
                 args,
                 debruijn,
-            )
-            .unwrap_or_else(|| unknown_const(expected_ty))
+            );
+            let is_unknown = |c: &Const| match &c.data(Interner).value {
+                ConstValue::Concrete(c) => c.interned == ConstScalar::Unknown,
+                _ => false,
+            };
+            if is_unknown(&lhs) || is_unknown(&rhs) {
+                return unknown_const(expected_ty);
+            }
+            fold_binary_op(&lhs, *op, &rhs, expected_ty.clone()).unwrap_or_else(|| {
+                intern_const_scalar(ConstScalar::BinaryOp(Box::new((lhs, *op, rhs))), expected_ty)
+            })
         }
     }
 }
@@ -727,9 +727,12 @@ pub fn is_dyn_method(
     None
 }
 
-/// Looks up the impl method that actually runs for the trait method `func`.
+/// Looks up the method of the impl which implements the trait method `func` for `fn_subst`, which
+/// is the method that actually runs.
 ///
-/// Returns `func` if it's not a method defined in a trait or the lookup failed.
+/// Returns `func` with `fn_subst` if it's not a method defined in a trait, if the lookup failed,
+/// or if the impl doesn't override a provided method: `fn_subst` already substitutes `Self` and
+/// the parameters of the trait in its default body.
 pub fn lookup_impl_method(
     db: &dyn HirDatabase,
     env: Arc<TraitEnvironment>,
@@ -749,7 +752,13 @@ pub fn lookup_impl_method(
     let name = &db.function_data(func).name;
     lookup_impl_assoc_item_for_trait_ref(trait_ref, db, env, name)
         .and_then(|assoc| {
-            if let (AssocItemId::FunctionId(id), subst) = assoc {
+            if let (AssocItemId::FunctionId(id), impl_subst) = assoc {
+                // The generic parameters of the method itself come first, and are the same for the
+                // trait method and the impl method.
+                let subst = Substitution::from_iter(
+                    Interner,
+                    fn_subst.iter(Interner).take(fn_params).chain(impl_subst.iter(Interner)),
+                );
                 Some((id, subst))
             } else {
                 None
@@ -808,11 +817,20 @@ fn find_matching_impl(
                 return None;
             }
 
-            let wcs = crate::chalk_db::convert_where_clauses(db, impl_.into(), &impl_substs)
-                .into_iter()
-                .map(|b| b.cast(Interner));
-            let goal = crate::Goal::all(Interner, wcs);
-            table.try_obligation(goal).map(|_| (impl_data, table.resolve_completely(impl_substs)))
+            let wcs: Vec<crate::Goal> =
+                crate::chalk_db::convert_where_clauses(db, impl_.into(), &impl_substs)
+                    .into_iter()
+                    .map(|b| b.cast(Interner))
+                    .collect();
+            let goal = crate::Goal::all(Interner, wcs.iter().cloned());
+            table.try_obligation(goal)?;
+            // Parameters of the impl which don't appear in the trait ref, like `B` in
+            // `impl<B, F: FnMut() -> B> Trait for S<F>`, are determined by the where clauses.
+            for wc in wcs {
+                table.register_obligation(wc);
+            }
+            table.resolve_obligations_as_possible();
+            Some((impl_data, table.resolve_completely(impl_substs)))
         });
         if r.is_some() {
             break r;
@@ -3,25 +3,49 @@
 use std::{fmt::Display, iter};
 
 use crate::{
-    infer::PointerCast, Const, ConstScalar, InferenceResult, Interner, MemoryMap, Substitution, Ty,
+    db::HirDatabase, infer::PointerCast, static_lifetime, Const, ConstScalar, InferenceResult,
+    Interner, MemoryMap, Substitution, Ty, TyKind,
 };
-use chalk_ir::Mutability;
+use base_db::CrateId;
+use chalk_ir::{Mutability, TypeFlags};
 use hir_def::{
     expr::{BindingId, Expr, ExprId, Ordering, PatId},
-    DefWithBodyId, FieldId, UnionId, VariantId,
+    DefWithBodyId, EnumVariantId, FieldId, StaticId, UnionId, VariantId,
 };
 use la_arena::{Arena, ArenaMap, Idx, RawIdx};
+use rustc_hash::{FxHashMap, FxHashSet};
 
 mod eval;
 mod lower;
 mod borrowck;
 mod pretty;
+mod canonical;
+mod transform;
+mod panic_sites;
+mod callees;
+mod type_check;
+mod value_usage;
 
 pub use borrowck::{borrowck_query, BorrowckResult, MutabilityReason};
-pub use eval::{interpret_mir, pad16, Evaluator, MirEvalError};
-pub use lower::{lower_to_mir, mir_body_query, mir_body_recover, MirLowerError};
+pub use callees::{mir_callees_query, CalleeSet};
+pub(crate) use eval::interpret_mir_with_limit;
+#[cfg(test)]
+pub(crate) use eval::interpret_raw_mir;
+pub use eval::{interpret_mir, pad16, Evaluator, MirEvalError, PanicLocation};
+pub use lower::{
+    lower_speculative_body_to_mir, lower_to_mir, mir_body_for_closure_query, mir_body_query,
+    mir_body_recover, MirLowerError, NonStructuralMatch,
+};
+pub use panic_sites::{panic_sites, PanicSite, PanicSiteKind};
 use smallvec::{smallvec, SmallVec};
 use stdx::impl_from;
+pub(crate) use transform::run_enabled_passes;
+pub use transform::{
+    optimize, optimized_mir_body_for_closure_query, optimized_mir_body_query,
+    optimized_mir_body_recover, MirPass, PassStats,
+};
+pub use type_check::{MirInvalidProjection, MirTypeMismatch, ProjectionError};
+pub use value_usage::ValueUsage;
 
 use super::consteval::{intern_const_scalar, try_const_usize};
 
@@ -32,9 +56,73 @@ fn return_slot() -> LocalId {
     LocalId::from_raw(RawIdx::from(0))
 }
 
-#[derive(Debug, PartialEq, Eq)]
+/// Controls which crates the MIR based features (e.g. the mutability diagnostics and the evaluation
+/// of constants on hover) run on, and how much the interpreter may execute.
+///
+/// HIR doesn't know which crates are local, so `local_crates_only` is applied by the IDE layer.
+/// The constants in displayed types are left unevaluated when it is set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MirConfig {
+    /// Whether the MIR based features are enabled at all.
+    pub enable: bool,
+    /// Only run the MIR based features on the crates of the workspace, not on the dependencies.
+    pub local_crates_only: bool,
+    /// The number of basic blocks an evaluation executes before giving up.
+    pub eval_step_limit: usize,
+    /// The number of bytes of stack and heap memory an evaluation may use, unlimited if `None`.
+    pub memory_limit: Option<usize>,
+    /// Whether an evaluation reuses the results of calls which only depend on their arguments,
+    /// instead of executing the same call again.
+    pub memoize_calls: bool,
+    /// The optimization passes which don't run on the MIR handed to the evaluator.
+    pub disabled_passes: Vec<MirPass>,
+}
+
+impl Default for MirConfig {
+    fn default() -> Self {
+        MirConfig {
+            enable: true,
+            local_crates_only: false,
+            eval_step_limit: 100_000,
+            memory_limit: None,
+            memoize_calls: true,
+            disabled_passes: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Local {
     pub ty: Ty,
+    pub kind: LocalKind,
+}
+
+/// Why a local exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocalKind {
+    /// The local holding the value which the body returns, which is always `_0`.
+    ReturnSlot,
+    /// A parameter which is not a binding on its own, like the environment of a closure or a
+    /// parameter with a pattern. Parameters which are just a binding are `UserBinding`s.
+    Arg,
+    /// The local of a binding of the source code.
+    UserBinding(BindingId),
+    /// A local which is introduced by lowering.
+    Temp(TempReason),
+}
+
+/// Why lowering introduced a temporary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TempReason {
+    /// Holds the value of an expression, a constant or an argument, for a use which needs a place.
+    Operand,
+    /// Holds a reference which is dereferenced right away, like the result of an overloaded
+    /// `Deref` or `Index`, or the reference to a static.
+    Deref,
+    /// Holds a value which is switched on, like the discriminant of an enum.
+    Discriminant,
+    /// Holds the iterator, a mutable reference to it or the next item in a desugared `for` loop.
+    Iterator,
 }
 
 /// An operand in MIR represents a "value" in Rust, the definition of which is undecided and part of
@@ -73,6 +161,9 @@ pub enum Operand {
     Move(Place),
     /// Constants are already semantically values, and remain unchanged.
     Constant(Const),
+    /// A reference to the given static. Unlike rustc, which uses a constant with a pointer to the
+    /// static's allocation, we keep the static id so the evaluator can lazily initialize it.
+    Static(StaticId),
 }
 
 impl Operand {
@@ -87,6 +178,14 @@ impl Operand {
     fn const_zst(ty: Ty) -> Operand {
         Self::from_bytes(vec![], ty)
     }
+
+    /// The place this operand reads from, if it isn't a constant.
+    pub fn place(&self) -> Option<&Place> {
+        match self {
+            Operand::Copy(p) | Operand::Move(p) => Some(p),
+            Operand::Constant(_) | Operand::Static(_) => None,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -97,8 +196,12 @@ pub enum ProjectionElem<V, T> {
     Index(V),
     ConstantIndex { offset: u64, min_length: u64, from_end: bool },
     Subslice { from: u64, to: u64, from_end: bool },
-    //Downcast(Option<Symbol>, VariantIdx),
+    // Views the enum as its given variant, so that the following `Field` projections are at the
+    // layout of the variant. Doesn't change the address or the type of the place.
+    Downcast(EnumVariantId),
     OpaqueCast(T),
+    // The field of a closure which holds its `n`th capture.
+    ClosureField(usize),
 }
 
 type PlaceElem = ProjectionElem<LocalId, Ty>;
@@ -123,7 +226,9 @@ pub enum AggregateKind {
     Tuple(Ty),
     Adt(VariantId, Substitution),
     Union(UnionId, FieldId),
-    //Closure(LocalDefId, SubstsRef),
+    /// The type is of the closure. Only closures without captures are supported at the moment,
+    /// so the operands are always empty.
+    Closure(Ty),
     //Generator(LocalDefId, SubstsRef, Movability),
 }
 
@@ -154,10 +259,10 @@ impl SwitchTargets {
     /// The iterator may be empty, in which case the `SwitchInt` instruction is equivalent to
     /// `goto otherwise;`.
     pub fn new(
-        targets: impl Iterator<Item = (u128, BasicBlockId)>,
+        targets: impl IntoIterator<Item = (u128, BasicBlockId)>,
         otherwise: BasicBlockId,
     ) -> Self {
-        let (values, mut targets): (SmallVec<_>, SmallVec<_>) = targets.unzip();
+        let (values, mut targets): (SmallVec<_>, SmallVec<_>) = targets.into_iter().unzip();
         targets.push(otherwise);
         Self { values, targets }
     }
@@ -188,6 +293,12 @@ impl SwitchTargets {
         &self.targets
     }
 
+    /// Returns a mutable slice with all possible jump targets (including the fallback target),
+    /// which can be used to renumber the basic blocks.
+    pub fn all_targets_mut(&mut self) -> &mut [BasicBlockId] {
+        &mut self.targets
+    }
+
     /// Finds the `BasicBlock` to which this `SwitchInt` will branch given the
     /// specific value. This cannot fail, as it'll return the `otherwise`
     /// branch if there's not a specific match for the value.
@@ -333,6 +444,8 @@ pub enum Terminator {
         // This `Span` is the span of the function, without the dot and receiver
         // (e.g. `foo(a, b)` in `x.foo(a, b)`
         //fn_span: Span,
+        /// The expression this call comes from, like a call expression or an overloaded operator.
+        span: MirSpan,
     },
 
     /// Evaluates the operand, which must have type `bool`. If it is not equal to `expected`,
@@ -418,6 +531,32 @@ pub enum Terminator {
     },
 }
 
+impl Terminator {
+    /// The blocks control flow can continue to after this terminator, including the cleanup
+    /// blocks taken on unwinding.
+    pub fn successors(&self) -> impl Iterator<Item = BasicBlockId> + '_ {
+        let (targets, first, second): (&[BasicBlockId], _, _) = match self {
+            Terminator::Goto { target } => (&[], Some(*target), None),
+            Terminator::SwitchInt { targets, .. } => (targets.all_targets(), None, None),
+            Terminator::Resume
+            | Terminator::Abort
+            | Terminator::Return
+            | Terminator::Unreachable
+            | Terminator::GeneratorDrop => (&[], None, None),
+            Terminator::Drop { target, unwind, .. }
+            | Terminator::DropAndReplace { target, unwind, .. } => (&[], Some(*target), *unwind),
+            Terminator::Call { target, cleanup, .. } => (&[], *target, *cleanup),
+            Terminator::Assert { target, cleanup, .. } => (&[], Some(*target), *cleanup),
+            Terminator::Yield { resume, drop, .. } => (&[], Some(*resume), *drop),
+            Terminator::FalseEdge { real_target, imaginary_target } => {
+                (&[], Some(*real_target), Some(*imaginary_target))
+            }
+            Terminator::FalseUnwind { real_target, unwind } => (&[], Some(*real_target), *unwind),
+        };
+        targets.iter().copied().chain(first).chain(second)
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum BorrowKind {
     /// Data must be immutable and is aliasable.
@@ -659,7 +798,9 @@ pub enum Rvalue {
     /// Corresponds to source code like `[x; 32]`.
     ///
     /// [#74836]: https://github.com/rust-lang/rust/issues/74836
-    //Repeat(Operand, ty::Const),
+    ///
+    /// The length is the one of the array type, which may be a const generic parameter.
+    Repeat(Operand, Const),
 
     /// Creates a reference of the indicated kind to the place.
     ///
@@ -720,7 +861,7 @@ pub enum Rvalue {
     ///   types and return a value of that type.
     /// * The remaining operations accept signed integers, unsigned integers, or floats with
     ///   matching types and return a value of that type.
-    //BinaryOp(BinOp, Box<(Operand, Operand)>),
+    BinaryOp(BinOp, Operand, Operand),
 
     /// Same as `BinaryOp`, but yields `(T, bool)` with a `bool` indicating an error condition.
     ///
@@ -777,6 +918,12 @@ pub enum Rvalue {
     /// affects alias analysis.
     ShallowInitBox(Operand, Ty),
 
+    /// Allocates the memory for a value of the given type on the heap, and creates a
+    /// shallow-initialized `Box` pointing to it. This combines the call to the allocator and the
+    /// `ShallowInitBox` which rustc emits for `box` expressions, since the size of the value is
+    /// not known before monomorphization.
+    ShallowInitBoxWithAlloc(Ty),
+
     /// A CopyForDeref is equivalent to a read from a place at the
     /// codegen level, but is treated specially by drop elaboration. When such a read happens, it
     /// is guaranteed (via nature of the mir_opt `Derefer` in rustc_mir_transform/src/deref_separator)
@@ -802,6 +949,9 @@ pub enum StatementKind {
     //Retag(RetagKind, Box<Place>),
     //AscribeUserType(Place, UserTypeProjection, Variance),
     //Intrinsic(Box<NonDivergingIntrinsic>),
+    /// Increments the coverage counter with this index. Only emitted when lowering with coverage
+    /// instrumentation, see [`lower_to_mir`].
+    Coverage(usize),
     Nop,
 }
 impl StatementKind {
@@ -816,7 +966,7 @@ pub struct Statement {
     pub span: MirSpan,
 }
 
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct BasicBlock {
     /// List of statements in this block.
     pub statements: Vec<Statement>,
@@ -838,7 +988,7 @@ pub struct BasicBlock {
     pub is_cleanup: bool,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MirBody {
     pub basic_blocks: Arena<BasicBlock>,
     pub locals: Arena<Local>,
@@ -847,13 +997,429 @@ pub struct MirBody {
     pub arg_count: usize,
     pub binding_locals: ArenaMap<BindingId, LocalId>,
     pub param_locals: Vec<LocalId>,
+    pub source_map: MirSourceMap,
+}
+
+/// How a body is entered: the locals which a caller writes the arguments to, in order, and the
+/// local which it reads the result from after the body returns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryArguments {
+    /// The return slot, which is always local 0.
+    pub return_local: LocalId,
+    pub return_ty: Ty,
+    /// The locals of the arguments with their types, which are locals 1 to `arg_count`. The
+    /// environment of a closure comes first, before the parameters.
+    pub args: Vec<(LocalId, Ty)>,
+}
+
+/// What a panic does, i.e. the `-C panic` strategy of the crate a body belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PanicStrategy {
+    /// Panics unwind the stack, running the cleanup blocks of the frames they pass through.
+    #[default]
+    Unwind,
+    /// Panics abort the process, so no cleanup blocks are generated at all.
+    Abort,
+}
+
+impl PanicStrategy {
+    /// The strategy of `krate`, read from its `panic` cfg like `cfg(panic = "abort")`.
+    pub fn of_crate(db: &dyn HirDatabase, krate: CrateId) -> Self {
+        let crate_graph = db.crate_graph();
+        if crate_graph[krate].cfg_options.get_cfg_values("panic").any(|x| x == "abort") {
+            PanicStrategy::Abort
+        } else {
+            PanicStrategy::Unwind
+        }
+    }
+}
+
+/// Whether a body is instrumented to count how often its branches are taken, see [`lower_to_mir`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CoverageMode {
+    #[default]
+    Off,
+    /// Every block which a `SwitchInt` jumps to starts with a counter, like rustc's
+    /// `-C instrument-coverage`.
+    Branches,
+}
+
+/// The location of a statement in a body, or of the terminator of a block if `statement_index` is
+/// the number of statements in that block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub block: BasicBlockId,
+    pub statement_index: usize,
+}
+
+/// Links the statements and blocks of a body back to the expressions and patterns they were
+/// lowered from. Each statement and block belongs to the innermost expression or pattern whose
+/// lowering created it, so the ones of the subexpressions are not included in those of their
+/// parents. Optimization passes move statements around, so optimized bodies have an empty map.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MirSourceMap {
+    statements: FxHashMap<MirSpan, Vec<Location>>,
+    blocks: FxHashMap<MirSpan, Vec<BasicBlockId>>,
+    destinations: FxHashMap<ExprId, Place>,
+    place_usages: FxHashMap<ExprId, ValueUsage>,
+}
+
+impl MirSourceMap {
+    /// The statements lowered for `span`, in the order in which they were created. In canonical
+    /// bodies they are in the order of their locations instead.
+    pub fn statements(&self, span: impl Into<MirSpan>) -> &[Location] {
+        self.statements.get(&span.into()).map_or(&[], |it| it.as_slice())
+    }
+
+    /// The blocks created while lowering `span`, in the order in which they were created. In
+    /// canonical bodies they are in the order of the blocks instead.
+    pub fn blocks(&self, span: impl Into<MirSpan>) -> &[BasicBlockId] {
+        self.blocks.get(&span.into()).map_or(&[], |it| it.as_slice())
+    }
+
+    /// The place which the value of `expr` was lowered to, if it was lowered to a place at all
+    /// and not e.g. only borrowed or used as an operand.
+    pub fn destination(&self, expr: ExprId) -> Option<&Place> {
+        self.destinations.get(&expr)
+    }
+
+    fn set_destination(&mut self, expr: ExprId, place: &Place) {
+        // An expression lowered more than once keeps its first destination.
+        self.destinations.entry(expr).or_insert_with(|| place.clone());
+    }
+
+    /// Records how the place of a place expression like `xs[i]` is used, when the expression is
+    /// lowered as a place and not written to a destination.
+    fn set_place_usage(&mut self, expr: ExprId, usage: ValueUsage) {
+        self.place_usages.entry(expr).or_insert(usage);
+    }
+
+    fn push_statement(&mut self, span: MirSpan, location: Location) {
+        self.statements.entry(span).or_default().push(location);
+    }
+
+    fn push_block(&mut self, span: MirSpan, block: BasicBlockId) {
+        self.blocks.entry(span).or_default().push(block);
+    }
+
+    /// Updates the blocks of the map after the blocks of the body were reallocated. Blocks
+    /// missing from `block_map` were removed, so their statements are dropped from the map.
+    fn renumber_blocks(&mut self, block_map: &ArenaMap<BasicBlockId, BasicBlockId>) {
+        for locations in self.statements.values_mut() {
+            locations.retain_mut(|l| match block_map.get(l.block) {
+                Some(&b) => {
+                    l.block = b;
+                    true
+                }
+                None => false,
+            });
+        }
+        for blocks in self.blocks.values_mut() {
+            *blocks = blocks.iter().filter_map(|&b| block_map.get(b).copied()).collect();
+        }
+        self.statements.retain(|_, it| !it.is_empty());
+        self.blocks.retain(|_, it| !it.is_empty());
+    }
+
+    /// Sorts the statements and blocks of each span, so that the map doesn't depend on the order
+    /// in which lowering created them.
+    fn sort(&mut self) {
+        for locations in self.statements.values_mut() {
+            locations.sort_by_key(|l| (l.block.into_raw(), l.statement_index));
+        }
+        for blocks in self.blocks.values_mut() {
+            blocks.sort_by_key(|b| b.into_raw());
+        }
+    }
+}
+
+/// The number of blocks and statements of a body, which profiling spans report and [`optimize`]
+/// records for each pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MirBodyStats {
+    pub blocks: usize,
+    pub statements: usize,
+}
+
+impl Display for MirBodyStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} blocks, {} statements", self.blocks, self.statements)
+    }
+}
+
+impl MirBody {
+    pub fn stats(&self) -> MirBodyStats {
+        MirBodyStats {
+            blocks: self.basic_blocks.len(),
+            statements: self.basic_blocks.iter().map(|(_, b)| b.statements.len()).sum(),
+        }
+    }
+
+    /// Removes the blocks for which `keep` returns `false`, and renumbers the remaining ones in
+    /// their current order, updating the targets of all terminators. The start block and the
+    /// blocks that a kept block jumps to are kept even if `keep` returns `false` for them, so that
+    /// no terminator is left without its target.
+    pub fn retain_blocks(&mut self, keep: impl Fn(BasicBlockId) -> bool) {
+        let mut kept: ArenaMap<BasicBlockId, ()> = ArenaMap::default();
+        let mut stack: Vec<BasicBlockId> = self
+            .basic_blocks
+            .iter()
+            .map(|(b, _)| b)
+            .filter(|&b| b == self.start_block || keep(b))
+            .collect();
+        while let Some(b) = stack.pop() {
+            if kept.insert(b, ()).is_none() {
+                stack.extend(canonical::successors(&self.basic_blocks[b]));
+            }
+        }
+        let block_order: Vec<BasicBlockId> =
+            self.basic_blocks.iter().map(|(b, _)| b).filter(|&b| kept.get(b).is_some()).collect();
+        if block_order.len() == self.basic_blocks.len() {
+            return;
+        }
+        let local_order: Vec<LocalId> = self.locals.iter().map(|(l, _)| l).collect();
+        *self = self.renumber(&block_order, &local_order);
+    }
+
+    /// The terminators of the blocks which have one, for passes which redirect jumps in place.
+    pub fn terminators_mut(
+        &mut self,
+    ) -> impl Iterator<Item = (BasicBlockId, &mut Terminator)> + '_ {
+        self.basic_blocks.iter_mut().filter_map(|(b, block)| Some((b, block.terminator.as_mut()?)))
+    }
+
+    pub fn entry_arguments(&self) -> EntryArguments {
+        EntryArguments {
+            return_local: return_slot(),
+            return_ty: self.locals[return_slot()].ty.clone(),
+            args: self.param_locals.iter().map(|&l| (l, self.locals[l].ty.clone())).collect(),
+        }
+    }
+
+    /// The local holding the value of `binding`, if it belongs to this body.
+    pub fn local_for_binding(&self, binding: BindingId) -> Option<LocalId> {
+        self.binding_locals.get(binding).copied()
+    }
+
+    /// Whether this body is simple enough to be evaluated eagerly, without the limits of a full
+    /// evaluation: it has no calls, drops or loops, and the types of its locals don't depend on
+    /// generic parameters. An evaluation of such a body executes each block at most once.
+    pub fn is_trivially_const(&self) -> bool {
+        let placeholder_flags = TypeFlags::HAS_TY_PLACEHOLDER | TypeFlags::HAS_CT_PLACEHOLDER;
+        if self.locals.iter().any(|(_, l)| l.ty.data(Interner).flags.intersects(placeholder_flags))
+        {
+            return false;
+        }
+        let order = self.reverse_postorder();
+        let mut position = ArenaMap::default();
+        for (i, &block) in order.iter().enumerate() {
+            position.insert(block, i);
+        }
+        order.iter().all(|&block| {
+            let data = &self.basic_blocks[block];
+            let is_simple = matches!(
+                data.terminator,
+                Some(
+                    Terminator::Goto { .. }
+                        | Terminator::SwitchInt { .. }
+                        | Terminator::Assert { .. }
+                        | Terminator::Return
+                        | Terminator::Unreachable
+                        | Terminator::FalseEdge { .. }
+                )
+            );
+            // Blocks reached through a jump back to an earlier block in reverse postorder are in a
+            // loop.
+            is_simple && canonical::successors(data).all(|s| position[s] > position[block])
+        })
+    }
+
+    /// The set of locals which are mentioned by any statement or terminator of this body, either
+    /// as the base of a place or as an index. Storage statements don't count as uses, so a local
+    /// which is absent from the set can be removed together with its storage statements.
+    pub fn used_locals(&self) -> FxHashSet<LocalId> {
+        let mut result = FxHashSet::default();
+        self.for_each_place(|_, p| {
+            result.insert(p.local);
+            for proj in &p.projection {
+                if let ProjectionElem::Index(l) = proj {
+                    result.insert(*l);
+                }
+            }
+        });
+        result
+    }
+
+    /// Like `walk_places`, but only reads the places.
+    fn for_each_place(&self, mut f: impl FnMut(Location, &Place)) {
+        fn for_operand(op: &Operand, f: &mut impl FnMut(&Place)) {
+            if let Some(p) = op.place() {
+                f(p);
+            }
+        }
+        for (block_id, block) in self.basic_blocks.iter() {
+            for (statement_index, statement) in block.statements.iter().enumerate() {
+                let location = Location { block: block_id, statement_index };
+                let mut f = |p: &Place| f(location, p);
+                match &statement.kind {
+                    StatementKind::Assign(p, r) => {
+                        f(p);
+                        match r {
+                            Rvalue::ShallowInitBox(o, _)
+                            | Rvalue::UnaryOp(_, o)
+                            | Rvalue::Cast(_, o, _)
+                            | Rvalue::Repeat(o, _)
+                            | Rvalue::Use(o) => for_operand(o, &mut f),
+                            Rvalue::CopyForDeref(p)
+                            | Rvalue::Discriminant(p)
+                            | Rvalue::Len(p)
+                            | Rvalue::Ref(_, p) => f(p),
+                            Rvalue::BinaryOp(_, o1, o2) | Rvalue::CheckedBinaryOp(_, o1, o2) => {
+                                for_operand(o1, &mut f);
+                                for_operand(o2, &mut f);
+                            }
+                            Rvalue::Aggregate(_, ops) => {
+                                ops.iter().for_each(|op| for_operand(op, &mut f));
+                            }
+                            Rvalue::ShallowInitBoxWithAlloc(_) => (),
+                        }
+                    }
+                    StatementKind::Deinit(p) => f(p),
+                    StatementKind::StorageLive(_)
+                    | StatementKind::StorageDead(_)
+                    | StatementKind::Coverage(_)
+                    | StatementKind::Nop => (),
+                }
+            }
+            let location = Location { block: block_id, statement_index: block.statements.len() };
+            let mut f = |p: &Place| f(location, p);
+            match &block.terminator {
+                Some(Terminator::SwitchInt { discr: o, .. })
+                | Some(Terminator::Assert { cond: o, .. }) => for_operand(o, &mut f),
+                Some(Terminator::Drop { place, .. }) => f(place),
+                Some(Terminator::DropAndReplace { place, value, .. }) => {
+                    f(place);
+                    for_operand(value, &mut f);
+                }
+                Some(Terminator::Call { func, args, destination, .. }) => {
+                    for_operand(func, &mut f);
+                    args.iter().for_each(|x| for_operand(x, &mut f));
+                    f(destination);
+                }
+                Some(Terminator::Yield { value, resume_arg, .. }) => {
+                    for_operand(value, &mut f);
+                    f(resume_arg);
+                }
+                Some(
+                    Terminator::FalseEdge { .. }
+                    | Terminator::FalseUnwind { .. }
+                    | Terminator::Goto { .. }
+                    | Terminator::Resume
+                    | Terminator::GeneratorDrop
+                    | Terminator::Abort
+                    | Terminator::Return
+                    | Terminator::Unreachable,
+                )
+                | None => (),
+            }
+        }
+    }
+
+    /// Calls `f` on every place mentioned in this body, including places used as operands of
+    /// statements and terminators, with the location of the statement or terminator.
+    fn walk_places(&mut self, mut f: impl FnMut(Location, &mut Place)) {
+        fn for_operand(op: &mut Operand, f: &mut impl FnMut(&mut Place)) {
+            match op {
+                Operand::Copy(p) | Operand::Move(p) => f(p),
+                Operand::Constant(_) | Operand::Static(_) => (),
+            }
+        }
+        for (block_id, block) in self.basic_blocks.iter_mut() {
+            for (statement_index, statement) in block.statements.iter_mut().enumerate() {
+                let location = Location { block: block_id, statement_index };
+                let mut f = |p: &mut Place| f(location, p);
+                match &mut statement.kind {
+                    StatementKind::Assign(p, r) => {
+                        f(p);
+                        match r {
+                            Rvalue::ShallowInitBox(o, _)
+                            | Rvalue::UnaryOp(_, o)
+                            | Rvalue::Cast(_, o, _)
+                            | Rvalue::Repeat(o, _)
+                            | Rvalue::Use(o) => for_operand(o, &mut f),
+                            Rvalue::CopyForDeref(p)
+                            | Rvalue::Discriminant(p)
+                            | Rvalue::Len(p)
+                            | Rvalue::Ref(_, p) => f(p),
+                            Rvalue::BinaryOp(_, o1, o2) | Rvalue::CheckedBinaryOp(_, o1, o2) => {
+                                for_operand(o1, &mut f);
+                                for_operand(o2, &mut f);
+                            }
+                            Rvalue::Aggregate(_, ops) => {
+                                for op in ops {
+                                    for_operand(op, &mut f);
+                                }
+                            }
+                            Rvalue::ShallowInitBoxWithAlloc(_) => (),
+                        }
+                    }
+                    StatementKind::Deinit(p) => f(p),
+                    StatementKind::StorageLive(_)
+                    | StatementKind::StorageDead(_)
+                    | StatementKind::Coverage(_)
+                    | StatementKind::Nop => (),
+                }
+            }
+            let location = Location { block: block_id, statement_index: block.statements.len() };
+            let mut f = |p: &mut Place| f(location, p);
+            if let Some(terminator) = &mut block.terminator {
+                match terminator {
+                    Terminator::SwitchInt { discr, .. } => for_operand(discr, &mut f),
+                    Terminator::FalseEdge { .. }
+                    | Terminator::FalseUnwind { .. }
+                    | Terminator::Goto { .. }
+                    | Terminator::Resume
+                    | Terminator::GeneratorDrop
+                    | Terminator::Abort
+                    | Terminator::Return
+                    | Terminator::Unreachable => (),
+                    Terminator::Drop { place, .. } => {
+                        f(place);
+                    }
+                    Terminator::DropAndReplace { place, value, .. } => {
+                        f(place);
+                        for_operand(value, &mut f);
+                    }
+                    Terminator::Call { func, args, destination, .. } => {
+                        for_operand(func, &mut f);
+                        args.iter_mut().for_each(|x| for_operand(x, &mut f));
+                        f(destination);
+                    }
+                    Terminator::Assert { cond, .. } => {
+                        for_operand(cond, &mut f);
+                    }
+                    Terminator::Yield { value, resume_arg, .. } => {
+                        for_operand(value, &mut f);
+                        f(resume_arg);
+                    }
+                }
+            }
+        }
+    }
 }
 
 fn const_as_usize(c: &Const) -> usize {
     try_const_usize(c).unwrap() as usize
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+/// The type of `Operand::Static(s)`, a reference to the static which is mutable for `static mut`.
+fn static_ref_ty(db: &dyn HirDatabase, s: StaticId) -> Ty {
+    let ty = db.value_ty(s.into()).substitute(Interner, &Substitution::empty(Interner));
+    let mutability = if db.static_data(s).mutable { Mutability::Mut } else { Mutability::Not };
+    TyKind::Ref(mutability, static_lifetime(), ty).intern(Interner)
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 pub enum MirSpan {
     ExprId(ExprId),
     PatId(PatId),
@@ -861,3 +1427,6 @@ pub enum MirSpan {
 }
 
 impl_from!(ExprId, PatId for MirSpan);
+
+#[cfg(test)]
+mod tests;
@@ -3,7 +3,7 @@
 // Currently it is an ad-hoc implementation, only useful for mutability analysis. Feel free to remove all of these
 // if needed for implementing a proper borrow checker.
 
-use std::sync::Arc;
+use std::{iter, sync::Arc};
 
 use hir_def::DefWithBodyId;
 use la_arena::ArenaMap;
@@ -12,8 +12,8 @@ use stdx::never;
 use crate::db::HirDatabase;
 
 use super::{
-    BasicBlockId, BorrowKind, LocalId, MirBody, MirLowerError, MirSpan, Place, ProjectionElem,
-    Rvalue, StatementKind, Terminator,
+    BasicBlockId, BorrowKind, LocalId, Location, MirBody, MirLowerError, MirSpan, Place,
+    ProjectionElem, Rvalue, StatementKind, Terminator,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -39,6 +39,36 @@ pub fn borrowck_query(
     Ok(Arc::new(r))
 }
 
+impl MirBody {
+    /// All the places where `local` is written: assignments to it or to a part of it (including
+    /// its initialization), call results stored in it and `&mut` borrows of it. Writes through a
+    /// dereference of `local` don't count, as they mutate the pointee instead.
+    pub fn assignments_of(&self, local: LocalId) -> Vec<(Location, MirSpan)> {
+        let mut result = vec![];
+        for (block_id, block) in self.basic_blocks.iter() {
+            for (statement_index, statement) in block.statements.iter().enumerate() {
+                let StatementKind::Assign(place, value) = &statement.kind else { continue };
+                let is_written = place.local == local && is_place_direct(place);
+                let is_borrowed = matches!(
+                    value,
+                    Rvalue::Ref(BorrowKind::Mut { .. }, p) if p.local == local && is_place_direct(p)
+                );
+                if is_written || is_borrowed {
+                    result.push((Location { block: block_id, statement_index }, statement.span));
+                }
+            }
+            if let Some(Terminator::Call { destination, span, .. }) = &block.terminator {
+                if destination.local == local && is_place_direct(destination) {
+                    let location =
+                        Location { block: block_id, statement_index: block.statements.len() };
+                    result.push((location, *span));
+                }
+            }
+        }
+        result
+    }
+}
+
 fn is_place_direct(lvalue: &Place) -> bool {
     !lvalue.projection.iter().any(|x| *x == ProjectionElem::Deref)
 }
@@ -61,6 +91,8 @@ fn place_case(lvalue: &Place) -> ProjectionCase {
             | ProjectionElem::Subslice { .. }
             | ProjectionElem::Field(_)
             | ProjectionElem::TupleField(_)
+            | ProjectionElem::ClosureField(_)
+            | ProjectionElem::Downcast(_)
             | ProjectionElem::Index(_) => {
                 is_part_of = true;
             }
@@ -100,7 +132,10 @@ fn ever_initialized_map(body: &MirBody) -> ArenaMap<BasicBlockId, ArenaMap<Local
                         is_ever_initialized = false;
                     }
                 }
-                StatementKind::Deinit(_) | StatementKind::Nop | StatementKind::StorageLive(_) => (),
+                StatementKind::Deinit(_)
+                | StatementKind::Nop
+                | StatementKind::StorageLive(_)
+                | StatementKind::Coverage(_) => (),
             }
         }
         let Some(terminator) = &block.terminator else {
@@ -120,8 +155,10 @@ fn ever_initialized_map(body: &MirBody) -> ArenaMap<BasicBlockId, ArenaMap<Local
                 }
                 target.into_iter().chain(cleanup.into_iter()).copied().collect()
             }
-            Terminator::Drop { .. }
-            | Terminator::DropAndReplace { .. }
+            Terminator::Drop { target, unwind, .. } => {
+                iter::once(target).chain(unwind.iter()).copied().collect()
+            }
+            Terminator::DropAndReplace { .. }
             | Terminator::Assert { .. }
             | Terminator::Yield { .. }
             | Terminator::GeneratorDrop
@@ -187,7 +224,10 @@ fn mutability_of_locals(body: &MirBody) -> ArenaMap<LocalId, MutabilityReason> {
                 StatementKind::StorageDead(p) => {
                     ever_init_map.insert(*p, false);
                 }
-                StatementKind::Deinit(_) | StatementKind::StorageLive(_) | StatementKind::Nop => (),
+                StatementKind::Deinit(_)
+                | StatementKind::StorageLive(_)
+                | StatementKind::Coverage(_)
+                | StatementKind::Nop => (),
             }
         }
         let Some(terminator) = &block.terminator else {
@@ -0,0 +1,193 @@
+//! The functions a body may call, which are the edges of the call graph.
+
+use std::sync::Arc;
+
+use hir_def::{
+    adt::StructKind, lang_item::LangItem, AdtId, DefWithBodyId, EnumVariantId, FunctionId,
+    HasModule, VariantId,
+};
+use hir_expand::name;
+use rustc_hash::FxHashSet;
+
+use crate::{
+    db::HirDatabase,
+    infer::closure_captures,
+    mapping::from_chalk,
+    method_resolution::{is_dyn_method, lookup_impl_method},
+    CallableDefId, Interner, Substitution, TraitEnvironment, Ty, TyKind,
+};
+
+use super::{AggregateKind, MirBody, Operand, Rvalue, StatementKind, Terminator};
+
+/// The statically known callees of a body, without their generic arguments.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CalleeSet {
+    /// The called functions and tuple-like constructors, and the `drop` functions of the `Drop`
+    /// impls of the dropped values and of their fields. Trait methods are resolved to the method of the impl when the
+    /// generic arguments allow it, and stay the method of the trait otherwise. They are ordered by
+    /// the first block calling them, so that the order doesn't depend on hashing.
+    pub callees: Vec<CallableDefId>,
+    /// Whether the body calls through a function pointer or a trait object, which may call
+    /// anything. This is also set for bodies that can't be lowered to MIR, but not for functions
+    /// without a body.
+    pub has_indirect_calls: bool,
+    /// The elements of `callees`, to skip the duplicates.
+    seen: FxHashSet<CallableDefId>,
+}
+
+impl CalleeSet {
+    fn insert(&mut self, callee: CallableDefId) {
+        if self.seen.insert(callee) {
+            self.callees.push(callee);
+        }
+    }
+}
+
+pub fn mir_callees_query(db: &dyn HirDatabase, def: DefWithBodyId) -> Arc<CalleeSet> {
+    let _p = profile::span("mir_callees_query");
+    let mut result = CalleeSet::default();
+    if let DefWithBodyId::FunctionId(f) = def {
+        // Extern functions and trait methods without a default have no body to find calls in.
+        if !db.function_data(f).has_body() {
+            return Arc::new(result);
+        }
+    }
+    match db.mir_body(def) {
+        Ok(body) => {
+            let env = db.trait_environment_for_body(def);
+            collect_callees(db, &env, &body, &mut result);
+        }
+        Err(_) => result.has_indirect_calls = true,
+    }
+    Arc::new(result)
+}
+
+/// Adds the callees of `body` to `result`, and those of the closures it creates, whose bodies run
+/// as part of it.
+fn collect_callees(
+    db: &dyn HirDatabase,
+    env: &Arc<TraitEnvironment>,
+    body: &MirBody,
+    result: &mut CalleeSet,
+) {
+    let drop_fn = db
+        .lang_item(body.owner.module(db.upcast()).krate(), LangItem::Drop)
+        .and_then(|it| it.as_trait())
+        .and_then(|it| db.trait_data(it).method_by_name(&name![drop]));
+    for (_, block) in body.basic_blocks.iter() {
+        for statement in &block.statements {
+            let StatementKind::Assign(_, Rvalue::Aggregate(kind, _)) = &statement.kind else {
+                continue;
+            };
+            match kind {
+                &AggregateKind::Adt(variant, _) => {
+                    let is_tuple_like =
+                        matches!(variant.variant_data(db.upcast()).kind(), StructKind::Tuple);
+                    let callee = match variant {
+                        VariantId::StructId(it) if is_tuple_like => CallableDefId::StructId(it),
+                        VariantId::EnumVariantId(it) if is_tuple_like => {
+                            CallableDefId::EnumVariantId(it)
+                        }
+                        _ => continue,
+                    };
+                    result.insert(callee);
+                }
+                AggregateKind::Closure(ty) => {
+                    let TyKind::Closure(closure, _) = ty.kind(Interner) else { continue };
+                    match db.mir_body_for_closure(*closure) {
+                        Ok(closure_body) => collect_callees(db, env, &closure_body, result),
+                        Err(_) => result.has_indirect_calls = true,
+                    }
+                }
+                _ => (),
+            }
+        }
+        match &block.terminator {
+            Some(Terminator::Call { func, .. }) => {
+                let Operand::Constant(c) = func else {
+                    result.has_indirect_calls = true;
+                    continue;
+                };
+                let TyKind::FnDef(def, subst) = c.data(Interner).ty.kind(Interner) else {
+                    result.has_indirect_calls = true;
+                    continue;
+                };
+                let callee = match from_chalk(db, *def) {
+                    CallableDefId::FunctionId(f) => {
+                        if is_dyn_method(db, env.clone(), f, subst.clone()).is_some() {
+                            result.has_indirect_calls = true;
+                            continue;
+                        }
+                        CallableDefId::FunctionId(
+                            lookup_impl_method(db, env.clone(), f, subst.clone()).0,
+                        )
+                    }
+                    it => it,
+                };
+                result.insert(callee);
+            }
+            Some(Terminator::Drop { place, .. } | Terminator::DropAndReplace { place, .. }) => {
+                let (Some(drop_fn), Some(ty)) = (drop_fn, place.ty(db, body)) else { continue };
+                collect_drop_glue(db, env, drop_fn, ty, &mut FxHashSet::default(), result);
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Adds the `drop` functions that dropping a value of type `ty` calls, which are those of the
+/// `Drop` impls of `ty` and of its fields, in the order the evaluator's drop glue runs them. The
+/// fields of all the variants of enums are included.
+fn collect_drop_glue(
+    db: &dyn HirDatabase,
+    env: &Arc<TraitEnvironment>,
+    drop_fn: FunctionId,
+    ty: Ty,
+    visited: &mut FxHashSet<Ty>,
+    result: &mut CalleeSet,
+) {
+    // This also skips the content of `ManuallyDrop`, which doesn't need drop.
+    if !db.needs_drop(ty.clone(), env.clone()) || !visited.insert(ty.clone()) {
+        return;
+    }
+    let subst = Substitution::from1(Interner, ty.clone());
+    let (imp, _) = lookup_impl_method(db, env.clone(), drop_fn, subst);
+    // Types without a `Drop` impl resolve to the method of the trait.
+    if imp != drop_fn {
+        result.insert(CallableDefId::FunctionId(imp));
+    }
+    let fields: Vec<Ty> = match ty.kind(Interner) {
+        TyKind::Adt(adt, subst) => {
+            let variants: Vec<VariantId> = match adt.0 {
+                AdtId::StructId(s) => vec![s.into()],
+                AdtId::UnionId(_) => return,
+                AdtId::EnumId(e) => db
+                    .enum_data(e)
+                    .variants
+                    .iter()
+                    .map(|(local_id, _)| EnumVariantId { parent: e, local_id }.into())
+                    .collect(),
+            };
+            variants
+                .into_iter()
+                .flat_map(|v| {
+                    db.field_types(v)
+                        .iter()
+                        .map(|(_, field_ty)| field_ty.clone().substitute(Interner, subst))
+                        .collect::<Vec<_>>()
+                })
+                .collect()
+        }
+        TyKind::Tuple(_, subst) => {
+            subst.iter(Interner).map(|x| x.assert_ty_ref(Interner).clone()).collect()
+        }
+        TyKind::Array(inner, _) | TyKind::Slice(inner) => vec![inner.clone()],
+        TyKind::Closure(c, _) => {
+            closure_captures(db, *c).0.iter().map(|capture| capture.ty.clone()).collect()
+        }
+        _ => return,
+    };
+    for field_ty in fields {
+        collect_drop_glue(db, env, drop_fn, field_ty, visited, result);
+    }
+}
@@ -0,0 +1,201 @@
+//! Canonical form of MIR bodies, used for comparing bodies independently of the numbering of their
+//! blocks and locals.
+
+use la_arena::{Arena, ArenaMap};
+
+use super::{
+    return_slot, BasicBlock, BasicBlockId, LocalId, MirBody, Place, ProjectionElem, StatementKind,
+    Terminator,
+};
+
+impl MirBody {
+    /// Returns a copy of this body in which basic blocks are ordered by reverse postorder from
+    /// the start block, and locals (except the return slot and parameters, which keep their
+    /// positions) are numbered by their first use in that order. Unreachable blocks and unused
+    /// locals are kept at the end, in their original relative order. The lists of the source map
+    /// are sorted, so they don't keep the order in which lowering created their entries.
+    pub fn canonicalize(&self) -> MirBody {
+        let mut block_order = self.reverse_postorder();
+        let mut visited: ArenaMap<BasicBlockId, ()> =
+            block_order.iter().map(|&b| (b, ())).collect();
+        for (b, _) in self.basic_blocks.iter() {
+            if visited.insert(b, ()).is_none() {
+                block_order.push(b);
+            }
+        }
+        let ordered_blocks =
+            self.renumber(&block_order, &self.locals.iter().map(|x| x.0).collect::<Vec<_>>());
+        let local_order = ordered_blocks.locals_by_first_use();
+        let mut result = ordered_blocks.renumber(
+            &ordered_blocks.basic_blocks.iter().map(|x| x.0).collect::<Vec<_>>(),
+            &local_order,
+        );
+        result.source_map.sort();
+        result
+    }
+
+    /// Checks if two bodies are equal up to numbering of their blocks and locals. This is useful
+    /// for tests that should not break when lowering changes the order in which it allocates
+    /// blocks or temporaries.
+    pub fn canonically_eq(&self, other: &MirBody) -> bool {
+        self.canonicalize() == other.canonicalize()
+    }
+
+    pub(super) fn reverse_postorder(&self) -> Vec<BasicBlockId> {
+        let mut visited: ArenaMap<BasicBlockId, ()> = ArenaMap::default();
+        let mut postorder = vec![];
+        // Iterative dfs, with a stack of (block, index of the next successor to visit)
+        let mut stack = vec![(self.start_block, 0)];
+        visited.insert(self.start_block, ());
+        while let Some((block, next)) = stack.last_mut() {
+            match successors(&self.basic_blocks[*block]).nth(*next) {
+                Some(target) => {
+                    *next += 1;
+                    if visited.insert(target, ()).is_none() {
+                        stack.push((target, 0));
+                    }
+                }
+                None => {
+                    postorder.push(*block);
+                    stack.pop();
+                }
+            }
+        }
+        postorder.reverse();
+        postorder
+    }
+
+    fn locals_by_first_use(&self) -> Vec<LocalId> {
+        let mut seen: ArenaMap<LocalId, ()> = ArenaMap::default();
+        let mut order = vec![];
+        let mut visit = |l: LocalId, order: &mut Vec<LocalId>| {
+            if seen.insert(l, ()).is_none() {
+                order.push(l);
+            }
+        };
+        // The return slot and parameters are part of the signature, and never move.
+        visit(return_slot(), &mut order);
+        for &l in &self.param_locals {
+            visit(l, &mut order);
+        }
+        // Storage statements are visited after all places. It doesn't matter much, since storage
+        // statements are only emitted for bindings, and those are always assigned somewhere.
+        self.for_each_place(|_, p| {
+            visit(p.local, &mut order);
+            for proj in &p.projection {
+                if let ProjectionElem::Index(l) = proj {
+                    visit(*l, &mut order);
+                }
+            }
+        });
+        for (_, block) in self.basic_blocks.iter() {
+            for statement in &block.statements {
+                if let StatementKind::StorageLive(l) | StatementKind::StorageDead(l) =
+                    statement.kind
+                {
+                    visit(l, &mut order);
+                }
+            }
+        }
+        for (l, _) in self.locals.iter() {
+            visit(l, &mut order);
+        }
+        order
+    }
+
+    /// Creates a copy of this body, with blocks and locals reallocated in the given orders.
+    pub(super) fn renumber(
+        &self,
+        block_order: &[BasicBlockId],
+        local_order: &[LocalId],
+    ) -> MirBody {
+        let mut locals = Arena::new();
+        let mut local_map: ArenaMap<LocalId, LocalId> = ArenaMap::default();
+        for &l in local_order {
+            local_map.insert(l, locals.alloc(self.locals[l].clone()));
+        }
+        let mut basic_blocks = Arena::new();
+        let mut block_map: ArenaMap<BasicBlockId, BasicBlockId> = ArenaMap::default();
+        for &b in block_order {
+            block_map.insert(b, basic_blocks.alloc(self.basic_blocks[b].clone()));
+        }
+        let mut result = MirBody {
+            basic_blocks,
+            locals,
+            start_block: block_map[self.start_block],
+            owner: self.owner,
+            arg_count: self.arg_count,
+            binding_locals: self.binding_locals.iter().map(|(b, l)| (b, local_map[*l])).collect(),
+            param_locals: self.param_locals.iter().map(|l| local_map[*l]).collect(),
+            source_map: self.source_map.clone(),
+        };
+        result.source_map.renumber_blocks(&block_map);
+        let renumber_place = |p: &mut Place| {
+            p.local = local_map[p.local];
+            for proj in &mut p.projection {
+                if let ProjectionElem::Index(l) = proj {
+                    *l = local_map[*l];
+                }
+            }
+        };
+        result.source_map.destinations.values_mut().for_each(renumber_place);
+        result.walk_places(|_, p| renumber_place(p));
+        for (_, block) in result.basic_blocks.iter_mut() {
+            for statement in &mut block.statements {
+                if let StatementKind::StorageLive(l) | StatementKind::StorageDead(l) =
+                    &mut statement.kind
+                {
+                    *l = local_map[*l];
+                }
+            }
+            if let Some(terminator) = &mut block.terminator {
+                for_each_successor_mut(terminator, |b| *b = block_map[*b]);
+            }
+        }
+        result
+    }
+}
+
+pub(super) fn successors(block: &BasicBlock) -> impl Iterator<Item = BasicBlockId> + '_ {
+    block.terminator.iter().flat_map(Terminator::successors)
+}
+
+pub(super) fn for_each_successor_mut(
+    terminator: &mut Terminator,
+    mut f: impl FnMut(&mut BasicBlockId),
+) {
+    match terminator {
+        Terminator::Goto { target } => f(target),
+        Terminator::SwitchInt { targets, .. } => targets.all_targets_mut().iter_mut().for_each(f),
+        Terminator::Resume
+        | Terminator::Abort
+        | Terminator::Return
+        | Terminator::Unreachable
+        | Terminator::GeneratorDrop => (),
+        Terminator::Drop { target, unwind, .. }
+        | Terminator::DropAndReplace { target, unwind, .. } => {
+            f(target);
+            unwind.iter_mut().for_each(f);
+        }
+        Terminator::Call { target, cleanup, .. } => {
+            target.iter_mut().for_each(&mut f);
+            cleanup.iter_mut().for_each(f);
+        }
+        Terminator::Assert { target, cleanup, .. } => {
+            f(target);
+            cleanup.iter_mut().for_each(f);
+        }
+        Terminator::Yield { resume, drop, .. } => {
+            f(resume);
+            drop.iter_mut().for_each(f);
+        }
+        Terminator::FalseEdge { real_target, imaginary_target } => {
+            f(real_target);
+            f(imaginary_target);
+        }
+        Terminator::FalseUnwind { real_target, unwind } => {
+            f(real_target);
+            unwind.iter_mut().for_each(f);
+        }
+    }
+}
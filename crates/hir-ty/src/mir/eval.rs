@@ -1,39 +1,56 @@
 //! This module provides a MIR interpreter, which is used in const eval.
 
-use std::{borrow::Cow, collections::HashMap, iter, ops::Range, sync::Arc};
+use std::{
+    borrow::Cow,
+    cell::Cell,
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    iter, mem,
+    ops::Range,
+    sync::Arc,
+};
 
-use base_db::CrateId;
+use base_db::{CrateId, FileId};
 use chalk_ir::{
     fold::{FallibleTypeFolder, TypeFoldable, TypeSuperFoldable},
-    DebruijnIndex, TyKind,
+    DebruijnIndex, Mutability, TyKind,
 };
 use hir_def::{
-    builtin_type::BuiltinType,
+    builtin_type::{BuiltinFloat, BuiltinType},
     lang_item::{lang_attr, LangItem},
     layout::{Layout, LayoutError, RustcEnumVariantIdx, TagEncoding, Variants},
-    AdtId, DefWithBodyId, EnumVariantId, FunctionId, HasModule, ItemContainerId, Lookup, VariantId,
+    AdtId, DefWithBodyId, EnumVariantId, FunctionId, HasModule, ItemContainerId, StaticId,
+    TypeOrConstParamId, VariantId,
 };
-use intern::Interned;
+use hir_expand::{name, InFile};
 use la_arena::ArenaMap;
+use syntax::{ast, AstNode, SyntaxNodePtr};
 
 use crate::{
-    consteval::{intern_const_scalar, ConstEvalError},
+    consteval::{intern_const_scalar, try_const_usize, ConstEvalError, UndefinedBehaviorKind},
     db::HirDatabase,
+    drop::is_manually_drop,
     from_placeholder_idx,
-    infer::{normalize, PointerCast},
+    infer::{closure_captures, normalize, PointerCast},
+    lang_items::is_box,
     layout::layout_of_ty,
     mapping::from_chalk,
-    method_resolution::{is_dyn_method, lookup_impl_method},
+    method_resolution::{is_dyn_method, lookup_impl_const, lookup_impl_method},
+    static_lifetime,
     traits::FnTrait,
-    CallableDefId, Const, ConstScalar, FnDefId, GenericArgData, Interner, MemoryMap, Substitution,
-    TraitEnvironment, Ty, TyBuilder, TyExt,
+    utils::{generics, is_intrinsic, Generics},
+    AliasTy, CallableDefId, ClosureId, Const, ConstScalar, FnDefId, GenericArgData, Interner,
+    MemoryMap, ProjectionTy, Substitution, TraitEnvironment, Ty, TyBuilder, TyExt,
 };
 
 use super::{
-    const_as_usize, return_slot, AggregateKind, BinOp, CastKind, LocalId, MirBody, MirLowerError,
-    Operand, Place, ProjectionElem, Rvalue, StatementKind, Terminator, UnOp,
+    const_as_usize, return_slot, static_ref_ty, AggregateKind, BinOp, BorrowKind, CastKind,
+    LocalId, MirBody, MirLowerError, MirSpan, Operand, Place, ProjectionElem, Rvalue,
+    StatementKind, Terminator, UnOp,
 };
 
+mod format;
+
 macro_rules! from_bytes {
     ($ty:tt, $value:expr) => {
         ($ty::from_le_bytes(match ($value).try_into() {
@@ -70,6 +87,9 @@ impl VTableMap {
     }
 }
 
+/// A memoized call: the called function, its generic arguments and the bytes of its arguments.
+type CallMemoKey = (FunctionId, Substitution, Vec<Vec<u8>>);
+
 pub struct Evaluator<'a> {
     db: &'a dyn HirDatabase,
     trait_env: Arc<TraitEnvironment>,
@@ -79,6 +99,22 @@ pub struct Evaluator<'a> {
     /// store the type as an interned id in place of function and vtable pointers, and we recover back the type at the
     /// time of use.
     vtable_map: VTableMap,
+    /// Statics are initialized lazily, on their first use, and live until the end of the evaluation. We
+    /// only have one thread, so thread locals are modeled exactly like ordinary statics.
+    static_locations: HashMap<StaticId, Address>,
+    /// The memory holding the pointer to each static which `Operand::Static` evaluates to, so that
+    /// reading a static doesn't allocate again.
+    static_pointers: HashMap<StaticId, Address>,
+    /// The start address and the size of every allocation (stack locals and heap allocations),
+    /// used to check that pointer arithmetic stays in bounds of the allocation of the pointer.
+    allocations: BTreeMap<usize, usize>,
+    /// The start address of the allocations which can't be written, like the memory of constants.
+    read_only_allocations: HashSet<usize>,
+    /// The addresses holding a pointer derived from a shared reference to a type without interior
+    /// mutability. We don't have an aliasing model, but copying such a pointer (also by transmuting
+    /// or casting it) keeps it in this set, so that writing through it is detected. Pointers which
+    /// lose their provenance, like the arguments of calls, are assumed to be writable.
+    shared_pointers: BTreeSet<usize>,
     crate_id: CrateId,
     // FIXME: This is a workaround, see the comment on `interpret_mir`
     assert_placeholder_ty_is_unused: bool,
@@ -86,6 +122,20 @@ pub struct Evaluator<'a> {
     execution_limit: usize,
     /// An additional limit on stack depth, to prevent stack overflow
     stack_depth_limit: usize,
+    /// The number of bytes the stack and the heap may grow to, if limited.
+    memory_limit: Option<usize>,
+    /// Execute the raw MIR of the called functions instead of the optimized one. Only used in
+    /// tests, to check that the optimizations don't change the result of evaluation.
+    raw_mir: bool,
+    /// Whether the results of calls which only depend on their arguments are kept in `call_memo`,
+    /// so that identical calls, like the ones of naive recursive functions, run only once.
+    memoize_calls: bool,
+    /// The results of the memoized calls.
+    call_memo: HashMap<CallMemoKey, Vec<u8>>,
+    /// Whether the memoizable call being executed, including the functions it called, only
+    /// depended on its arguments so far. Cleared by accessing a static with mutable state and by
+    /// exposing the address of a pointer, which depends on where the call is executed.
+    frame_is_pure: bool,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -124,7 +174,9 @@ impl Interval {
     fn write_from_interval(&self, memory: &mut Evaluator<'_>, interval: Interval) -> Result<()> {
         // FIXME: this could be more efficent
         let bytes = &interval.get(memory)?.to_vec();
-        memory.write_memory(self.addr, bytes)
+        memory.write_memory(self.addr, bytes)?;
+        memory.copy_provenance(interval, self.addr);
+        Ok(())
     }
 
     fn slice(self, range: Range<usize>) -> Interval {
@@ -153,11 +205,21 @@ enum IntervalOrOwned {
     Borrowed(Interval),
 }
 impl IntervalOrOwned {
-    pub(crate) fn to_vec(self, memory: &Evaluator<'_>) -> Result<Vec<u8>> {
-        Ok(match self {
-            IntervalOrOwned::Owned(o) => o,
-            IntervalOrOwned::Borrowed(b) => b.get(memory)?.to_vec(),
-        })
+    /// Writes the value at `addr`. Pointers copied from a borrowed interval keep their provenance.
+    fn write_to(self, memory: &mut Evaluator<'_>, addr: Address) -> Result<()> {
+        match self {
+            IntervalOrOwned::Owned(o) => memory.write_memory(addr, &o),
+            IntervalOrOwned::Borrowed(b) => {
+                Interval::new(addr, b.size).write_from_interval(memory, b)
+            }
+        }
+    }
+
+    fn get<'a>(&'a self, memory: &'a Evaluator<'a>) -> Result<&'a [u8]> {
+        match self {
+            IntervalOrOwned::Owned(o) => Ok(o),
+            IntervalOrOwned::Borrowed(b) => b.get(memory),
+        }
     }
 }
 
@@ -198,6 +260,15 @@ impl Address {
     }
 }
 
+/// A place in the source, like the `core::panic::Location` that rustc passes to `#[track_caller]`
+/// functions. The file is left to be named by the IDE, which knows the paths of files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PanicLocation {
+    pub file_id: FileId,
+    pub line: u32,
+    pub column: u32,
+}
+
 #[derive(Clone, PartialEq, Eq)]
 pub enum MirEvalError {
     ConstEvalError(Box<ConstEvalError>),
@@ -207,16 +278,63 @@ pub enum MirEvalError {
     /// Means that code had undefined behavior. We don't try to actively detect UB, but if it was detected
     /// then use this type of error.
     UndefinedBehavior(&'static str),
-    Panic(String),
+    /// A panic, with the location of the call which raised it if it is known.
+    Panic {
+        message: String,
+        location: Option<PanicLocation>,
+    },
     MirLowerError(FunctionId, MirLowerError),
+    MirLowerErrorForClosure(ClosureId, MirLowerError),
     TypeIsUnsized(Ty, &'static str),
     NotSupported(String),
     InvalidConst(Const),
-    InFunction(FunctionId, Box<MirEvalError>),
+    /// An error in a called function, with the body and the span of the call if it is known.
+    InFunction(FunctionId, Box<MirEvalError>, Option<(DefWithBodyId, MirSpan)>),
     ExecutionLimitExceeded,
+    /// The evaluation used more stack and heap memory than the `memory_limit` of the `MirConfig`.
+    MemoryLimitExceeded,
+    /// The `MirConfig` disables the MIR based features for the crate of the evaluated body.
+    Disabled,
     StackOverflow,
     TargetDataLayoutNotAvailable,
     InvalidVTableId(usize),
+    /// The initializer of a `#[thread_local]` static failed to lower or evaluate.
+    ThreadLocalAccess(String, Box<MirEvalError>),
+    /// A read of a static declared in an `extern` block, which only has a value at runtime.
+    ExternStatic(StaticId),
+    /// An `Abort` terminator was executed, e.g. because a drop panicked during unwinding.
+    ProcessAborted,
+}
+
+impl MirEvalError {
+    /// Whether this error is a panic, which unwinds through the cleanup blocks of its callers.
+    fn is_panic(&self) -> bool {
+        match self {
+            MirEvalError::Panic { .. } => true,
+            MirEvalError::InFunction(_, e, _) => e.is_panic(),
+            _ => false,
+        }
+    }
+
+    /// The message and the location of the panic, if this error is a panic of the evaluated code.
+    pub fn panic_message(&self) -> Option<(&str, Option<&PanicLocation>)> {
+        match self {
+            MirEvalError::Panic { message, location } => Some((message, location.as_ref())),
+            MirEvalError::InFunction(_, e, _) => e.panic_message(),
+            _ => None,
+        }
+    }
+
+    /// Records that the error happened in a call at `span` of `owner`, if it is the error of a
+    /// called function whose call site is not known yet.
+    fn with_call_site(self, owner: DefWithBodyId, span: MirSpan) -> Self {
+        match self {
+            MirEvalError::InFunction(func, e, None) => {
+                MirEvalError::InFunction(func, e, Some((owner, span)))
+            }
+            e => e,
+        }
+    }
 }
 
 impl std::fmt::Debug for MirEvalError {
@@ -230,24 +348,39 @@ impl std::fmt::Debug for MirEvalError {
             Self::UndefinedBehavior(arg0) => {
                 f.debug_tuple("UndefinedBehavior").field(arg0).finish()
             }
-            Self::Panic(msg) => write!(f, "Panic with message:\n{msg:?}"),
+            Self::Panic { message, location: Some(location) } => {
+                write!(f, "Panic at {location:?} with message:\n{message:?}")
+            }
+            Self::Panic { message, location: None } => {
+                write!(f, "Panic with message:\n{message:?}")
+            }
             Self::TargetDataLayoutNotAvailable => write!(f, "TargetDataLayoutNotAvailable"),
             Self::TypeIsUnsized(ty, it) => write!(f, "{ty:?} is unsized. {it} should be sized."),
             Self::ExecutionLimitExceeded => write!(f, "execution limit exceeded"),
+            Self::MemoryLimitExceeded => write!(f, "memory limit exceeded"),
+            Self::Disabled => write!(f, "MIR evaluation is disabled for this crate"),
             Self::StackOverflow => write!(f, "stack overflow"),
+            Self::ProcessAborted => write!(f, "process aborted"),
             Self::MirLowerError(arg0, arg1) => {
                 f.debug_tuple("MirLowerError").field(arg0).field(arg1).finish()
             }
+            Self::MirLowerErrorForClosure(arg0, arg1) => {
+                f.debug_tuple("MirLowerErrorForClosure").field(arg0).field(arg1).finish()
+            }
             Self::InvalidVTableId(arg0) => f.debug_tuple("InvalidVTableId").field(arg0).finish(),
+            Self::ThreadLocalAccess(name, e) => {
+                write!(f, "thread-local access cannot be evaluated for `{name}`: {e:?}")
+            }
+            Self::ExternStatic(arg0) => f.debug_tuple("ExternStatic").field(arg0).finish(),
             Self::NotSupported(arg0) => f.debug_tuple("NotSupported").field(arg0).finish(),
             Self::InvalidConst(arg0) => {
                 let data = &arg0.data(Interner);
                 f.debug_struct("InvalidConst").field("ty", &data.ty).field("value", &arg0).finish()
             }
-            Self::InFunction(func, e) => {
+            Self::InFunction(func, e, _) => {
                 let mut e = &**e;
                 let mut stack = vec![*func];
-                while let Self::InFunction(f, next_e) = e {
+                while let Self::InFunction(f, next_e, _) = e {
                     e = &next_e;
                     stack.push(*f);
                 }
@@ -277,6 +410,96 @@ struct Locals<'a> {
     ptr: &'a ArenaMap<LocalId, Address>,
     body: &'a MirBody,
     subst: &'a Substitution,
+    /// The frame which called this one, if any.
+    caller: Option<&'a Locals<'a>>,
+    /// Whether the function of this frame is `#[track_caller]`, and so reports its panics at the
+    /// location of its call.
+    track_caller: bool,
+    /// The span of the call which this frame is executing.
+    call_span: Cell<MirSpan>,
+}
+
+impl<'a> Locals<'a> {
+    /// The locals of a frame which isn't called by another one, like the body of a constant.
+    fn root(
+        ptr: &'a ArenaMap<LocalId, Address>,
+        body: &'a MirBody,
+        subst: &'a Substitution,
+    ) -> Self {
+        Locals {
+            ptr,
+            body,
+            subst,
+            caller: None,
+            track_caller: false,
+            call_span: Cell::new(MirSpan::Unknown),
+        }
+    }
+}
+
+/// Locals of a frame that hold a value, so that `Drop` terminators run their drop glue. Moving out
+/// of a local clears its flag, so that the value is not dropped twice.
+#[derive(Default)]
+struct DropFlags {
+    need_drop: HashSet<LocalId>,
+    /// Parts of the locals in `need_drop` which were moved out or dropped already, like the fields
+    /// taken from the base of a functional record update.
+    moved_out: Vec<Place>,
+}
+
+impl DropFlags {
+    fn add_place(&mut self, p: &Place) {
+        if p.projection.is_empty() {
+            self.need_drop.insert(p.local);
+            self.moved_out.retain(|x| x.local != p.local);
+        } else {
+            self.moved_out.retain(|x| x != p);
+        }
+    }
+
+    /// Clears the flag of `p`, and returns if it needed to be dropped.
+    fn remove_place(&mut self, p: &Place) -> bool {
+        if p.projection.is_empty() {
+            let partially_moved = self.moved_out.iter().any(|x| x.local == p.local);
+            self.moved_out.retain(|x| x.local != p.local);
+            // FIXME: Drop glue doesn't drop the fields of a value yet, so there is nothing left to
+            // drop in a partially moved value, which can't have a `Drop` impl itself.
+            return self.need_drop.remove(&p.local) && !partially_moved;
+        }
+        if !self.need_drop.contains(&p.local)
+            || self
+                .moved_out
+                .iter()
+                .any(|x| x.local == p.local && p.projection.starts_with(&x.projection))
+        {
+            return false;
+        }
+        self.moved_out.push(p.clone());
+        true
+    }
+
+    fn remove_moved(&mut self, op: &Operand) {
+        if let Operand::Move(p) = op {
+            self.remove_place(p);
+        }
+    }
+
+    fn remove_moved_in_rvalue(&mut self, r: &Rvalue) {
+        match r {
+            Rvalue::Use(o) | Rvalue::UnaryOp(_, o) | Rvalue::Cast(_, o, _) => self.remove_moved(o),
+            Rvalue::ShallowInitBox(o, _) | Rvalue::Repeat(o, _) => self.remove_moved(o),
+            Rvalue::BinaryOp(_, o1, o2) | Rvalue::CheckedBinaryOp(_, o1, o2) => {
+                self.remove_moved(o1);
+                self.remove_moved(o2);
+            }
+            Rvalue::Aggregate(_, ops) => ops.iter().for_each(|o| self.remove_moved(o)),
+            Rvalue::CopyForDeref(_)
+            | Rvalue::Discriminant(_)
+            | Rvalue::Len(_)
+            | Rvalue::Ref(..)
+            | Rvalue::ShallowInitBoxWithAlloc(_) => {}
+        }
+    }
 }
 
 pub fn interpret_mir(
@@ -289,15 +512,46 @@ pub fn interpret_mir(
     // a zero size, hoping that they are all outside of our current body. Even without a fix for #7434, we can
     // (and probably should) do better here, for example by excluding bindings outside of the target expression.
     assert_placeholder_ty_is_unused: bool,
+) -> Result<Const> {
+    let evaluator = Evaluator::new(db, body, assert_placeholder_ty_is_unused);
+    interpret_mir_with(evaluator, body, subst)
+}
+
+/// Like [`interpret_mir`], but gives up after executing `execution_limit` basic blocks, for
+/// evaluations that must be cheap.
+pub(crate) fn interpret_mir_with_limit(
+    db: &dyn HirDatabase,
+    body: &MirBody,
+    subst: Substitution,
+    execution_limit: usize,
+) -> Result<Const> {
+    let mut evaluator = Evaluator::new(db, body, false);
+    evaluator.execution_limit = execution_limit;
+    interpret_mir_with(evaluator, body, subst)
+}
+
+/// Like [`interpret_mir`], but executes the raw MIR of the called functions instead of the
+/// optimized one.
+#[cfg(test)]
+pub(crate) fn interpret_raw_mir(
+    db: &dyn HirDatabase,
+    body: &MirBody,
+    subst: Substitution,
+) -> Result<Const> {
+    let mut evaluator = Evaluator::new(db, body, false);
+    evaluator.raw_mir = true;
+    interpret_mir_with(evaluator, body, subst)
+}
+
+fn interpret_mir_with(
+    mut evaluator: Evaluator<'_>,
+    body: &MirBody,
+    subst: Substitution,
 ) -> Result<Const> {
     let ty = body.locals[return_slot()].ty.clone();
-    let mut evaluator = Evaluator::new(db, body, assert_placeholder_ty_is_unused);
-    let bytes = evaluator.interpret_mir(&body, None.into_iter(), subst.clone())?;
-    let memory_map = evaluator.create_memory_map(
-        &bytes,
-        &ty,
-        &Locals { ptr: &ArenaMap::new(), body: &body, subst: &subst },
-    )?;
+    let bytes = evaluator.interpret_mir(body, None.into_iter(), &subst, None)?;
+    let memory_map =
+        evaluator.create_memory_map(&bytes, &ty, &Locals::root(&ArenaMap::new(), &body, &subst))?;
     return Ok(intern_const_scalar(ConstScalar::Bytes(bytes, memory_map), ty));
 }
 
@@ -309,16 +563,46 @@ impl Evaluator<'_> {
     ) -> Evaluator<'a> {
         let crate_id = body.owner.module(db.upcast()).krate();
         let trait_env = db.trait_environment_for_body(body.owner);
+        let config = db.mir_config();
         Evaluator {
             stack: vec![0],
             heap: vec![0],
             vtable_map: VTableMap::default(),
+            static_locations: HashMap::default(),
+            static_pointers: HashMap::default(),
+            allocations: BTreeMap::default(),
+            read_only_allocations: HashSet::default(),
+            shared_pointers: BTreeSet::default(),
             db,
             trait_env,
             crate_id,
             assert_placeholder_ty_is_unused,
             stack_depth_limit: 100,
-            execution_limit: 100_000,
+            execution_limit: config.eval_step_limit,
+            memory_limit: config.memory_limit,
+            raw_mir: false,
+            memoize_calls: config.memoize_calls,
+            call_memo: HashMap::default(),
+            frame_is_pure: true,
+        }
+    }
+
+    fn mir_body(&self, def: DefWithBodyId) -> std::result::Result<Arc<MirBody>, MirLowerError> {
+        if self.raw_mir {
+            self.db.mir_body(def)
+        } else {
+            self.db.optimized_mir_body(def)
+        }
+    }
+
+    fn mir_body_for_closure(
+        &self,
+        closure: ClosureId,
+    ) -> std::result::Result<Arc<MirBody>, MirLowerError> {
+        if self.raw_mir {
+            self.db.mir_body_for_closure(closure)
+        } else {
+            self.db.optimized_mir_body_for_closure(closure)
         }
     }
 
@@ -349,24 +633,24 @@ impl Evaluator<'_> {
         &'a self,
         p: &Place,
         locals: &'a Locals<'a>,
-    ) -> Result<(Address, Ty, Option<Interval>)> {
+    ) -> Result<(Address, Ty, Option<IntervalOrOwned>)> {
         let mut addr = locals.ptr[p.local];
         let mut ty: Ty =
             self.ty_filler(&locals.body.locals[p.local].ty, locals.subst, locals.body.owner)?;
         let mut metadata = None; // locals are always sized
         for proj in &p.projection {
+            let base_ty = ty;
+            // Derefs of types other than pointers and `Box` are overloaded, which MIR disallows.
+            ty = proj.projected_ty(self.db, base_ty.clone(), self.crate_id).map_err(|_| {
+                MirEvalError::TypeError("projection which doesn't apply to the type of the place")
+            })?;
             match proj {
                 ProjectionElem::Deref => {
-                    ty = match &ty.data(Interner).kind {
-                        TyKind::Raw(_, inner) | TyKind::Ref(_, _, inner) => inner.clone(),
-                        _ => {
-                            return Err(MirEvalError::TypeError(
-                                "Overloaded deref in MIR is disallowed",
-                            ))
-                        }
-                    };
                     metadata = if self.size_of(&ty, locals)?.is_none() {
-                        Some(Interval { addr: addr.offset(self.ptr_size()), size: self.ptr_size() })
+                        Some(IntervalOrOwned::Borrowed(Interval {
+                            addr: addr.offset(self.ptr_size()),
+                            size: self.ptr_size(),
+                        }))
                     } else {
                         None
                     };
@@ -377,86 +661,170 @@ impl Evaluator<'_> {
                     let offset =
                         from_bytes!(usize, self.read_memory(locals.ptr[*op], self.ptr_size())?);
                     metadata = None; // Result of index is always sized
-                    match &ty.data(Interner).kind {
-                        TyKind::Ref(_, _, inner) => match &inner.data(Interner).kind {
-                            TyKind::Slice(inner) => {
-                                ty = inner.clone();
-                                let ty_size = self.size_of_sized(
-                                    &ty,
-                                    locals,
-                                    "slice inner type should be sized",
-                                )?;
-                                let value = self.read_memory(addr, self.ptr_size() * 2)?;
-                                addr = Address::from_bytes(&value[0..8])?.offset(ty_size * offset);
-                            }
-                            x => not_supported!("MIR index for ref type {x:?}"),
-                        },
-                        TyKind::Array(inner, _) | TyKind::Slice(inner) => {
-                            ty = inner.clone();
-                            let ty_size = self.size_of_sized(
-                                &ty,
-                                locals,
-                                "array inner type should be sized",
-                            )?;
-                            addr = addr.offset(ty_size * offset);
+                    let ty_size =
+                        self.size_of_sized(&ty, locals, "array inner type should be sized")?;
+                    addr = addr.offset(ty_size * offset);
+                }
+                &ProjectionElem::TupleField(f) => {
+                    let layout = self.layout(&base_ty)?;
+                    let offset = layout.fields.offset(f).bytes_usize();
+                    addr = addr.offset(offset);
+                    metadata = None; // tuple field is always sized
+                }
+                &ProjectionElem::ClosureField(f) => {
+                    let layout = self.layout(&base_ty)?;
+                    let offset = layout.fields.offset(f).bytes_usize();
+                    addr = addr.offset(offset);
+                    metadata = None; // captures are always sized
+                }
+                ProjectionElem::Field(f) => {
+                    let TyKind::Adt(adt, subst) = base_ty.kind(Interner) else {
+                        return Err(MirEvalError::TypeError("Only adt has fields"));
+                    };
+                    let layout = self.layout_adt(adt.0, subst.clone())?;
+                    let variant_layout = match &layout.variants {
+                        Variants::Single { .. } => &layout,
+                        Variants::Multiple { variants, .. } => {
+                            &variants[match f.parent {
+                                hir_def::VariantId::EnumVariantId(x) => {
+                                    RustcEnumVariantIdx(x.local_id)
+                                }
+                                _ => {
+                                    return Err(MirEvalError::TypeError(
+                                        "Multivariant layout only happens for enums",
+                                    ))
+                                }
+                            }]
                         }
-                        x => not_supported!("MIR index for type {x:?}"),
-                    }
+                    };
+                    let offset = variant_layout
+                        .fields
+                        .offset(u32::from(f.local_id.into_raw()) as usize)
+                        .bytes_usize();
+                    addr = addr.offset(offset);
+                    // FIXME: support structs with unsized fields
+                    metadata = None;
                 }
-                &ProjectionElem::TupleField(f) => match &ty.data(Interner).kind {
-                    TyKind::Tuple(_, subst) => {
-                        let layout = self.layout(&ty)?;
-                        ty = subst
-                            .as_slice(Interner)
-                            .get(f)
-                            .ok_or(MirEvalError::TypeError("not enough tuple fields"))?
-                            .assert_ty_ref(Interner)
-                            .clone();
-                        let offset = layout.fields.offset(f).bytes_usize();
-                        addr = addr.offset(offset);
-                        metadata = None; // tuple field is always sized
+                // The variant layout is selected by the `Field` projection which follows.
+                ProjectionElem::Downcast(_) => (),
+                &ProjectionElem::ConstantIndex { offset, from_end, .. } => {
+                    let len = self.place_len(&base_ty, metadata.as_ref())?;
+                    let offset = offset as usize;
+                    let index = if from_end { len.checked_sub(offset) } else { Some(offset) };
+                    let Some(index) = index.filter(|&index| index < len) else {
+                        return Err(MirEvalError::UndefinedBehavior("constant index out of bounds"));
+                    };
+                    metadata = None; // Result of index is always sized
+                    let ty_size =
+                        self.size_of_sized(&ty, locals, "array inner type should be sized")?;
+                    addr = addr.offset(ty_size * index);
+                }
+                &ProjectionElem::Subslice { from, to, from_end } => {
+                    let len = self.place_len(&base_ty, metadata.as_ref())?;
+                    let (from, to) = (from as usize, to as usize);
+                    let end = if from_end { len.checked_sub(to) } else { Some(to) };
+                    let Some(sub_len) =
+                        end.filter(|&end| end <= len).and_then(|end| end.checked_sub(from))
+                    else {
+                        return Err(MirEvalError::UndefinedBehavior("subslice out of bounds"));
+                    };
+                    let (TyKind::Array(inner, _) | TyKind::Slice(inner)) = base_ty.kind(Interner)
+                    else {
+                        return Err(MirEvalError::TypeError("subslice of a non array type"));
+                    };
+                    let inner_size =
+                        self.size_of_sized(inner, locals, "array inner type should be sized")?;
+                    addr = addr.offset(inner_size * from);
+                    // A subslice of an array is an array, and the one of a slice is a slice with
+                    // a shorter length.
+                    if let TyKind::Slice(_) = ty.kind(Interner) {
+                        let len = sub_len.to_le_bytes()[0..self.ptr_size()].to_vec();
+                        metadata = Some(IntervalOrOwned::Owned(len));
                     }
-                    _ => return Err(MirEvalError::TypeError("Only tuple has tuple fields")),
+                }
+                ProjectionElem::OpaqueCast(_) => not_supported!("opaque cast"),
+            }
+        }
+        Ok((addr, ty, metadata))
+    }
+
+    /// The number of elements of a place of array or slice type `ty`, whose `metadata` holds the
+    /// length if it is a slice.
+    fn place_len(&self, ty: &Ty, metadata: Option<&IntervalOrOwned>) -> Result<usize> {
+        match ty.kind(Interner) {
+            TyKind::Array(_, len) => match try_const_usize(len) {
+                Some(len) => Ok(len as usize),
+                None => not_supported!("array with unknown length"),
+            },
+            TyKind::Slice(_) => match metadata {
+                Some(metadata) => {
+                    let len = from_bytes!(usize, metadata.get(self)?);
+                    Ok(len)
+                }
+                None => Err(MirEvalError::TypeError("slice place without a length")),
+            },
+            _ => Err(MirEvalError::TypeError("length of a type which is not an array or slice")),
+        }
+    }
+
+    /// The discriminant of the value of type `ty` stored in `bytes`, or `0` if `ty` is not an enum.
+    fn compute_discriminant(&self, ty: Ty, bytes: &[u8]) -> Result<i128> {
+        let layout = self.layout(&ty)?;
+        let enum_id = 'b: {
+            match ty.kind(Interner) {
+                TyKind::Adt(e, _) => match e.0 {
+                    AdtId::EnumId(e) => break 'b e,
+                    _ => (),
                 },
-                ProjectionElem::Field(f) => match &ty.data(Interner).kind {
-                    TyKind::Adt(adt, subst) => {
-                        let layout = self.layout_adt(adt.0, subst.clone())?;
-                        let variant_layout = match &layout.variants {
-                            Variants::Single { .. } => &layout,
-                            Variants::Multiple { variants, .. } => {
-                                &variants[match f.parent {
-                                    hir_def::VariantId::EnumVariantId(x) => {
-                                        RustcEnumVariantIdx(x.local_id)
-                                    }
-                                    _ => {
-                                        return Err(MirEvalError::TypeError(
-                                            "Multivariant layout only happens for enums",
-                                        ))
-                                    }
-                                }]
+                _ => (),
+            }
+            return Ok(0);
+        };
+        match layout.variants {
+            Variants::Single { index } => {
+                let r = self.db.const_eval_discriminant(EnumVariantId {
+                    parent: enum_id,
+                    local_id: index.0,
+                })?;
+                Ok(r)
+            }
+            Variants::Multiple { tag, tag_encoding, .. } => {
+                let Some(target_data_layout) = self.db.target_data_layout(self.crate_id) else {
+                    not_supported!("missing target data layout");
+                };
+                let size = tag.size(&*target_data_layout).bytes_usize();
+                let offset = layout.fields.offset(0).bytes_usize(); // The only field on enum variants is the tag field
+                match tag_encoding {
+                    TagEncoding::Direct => {
+                        let tag = &bytes[offset..offset + size];
+                        Ok(i128::from_le_bytes(pad16(tag, false)))
+                    }
+                    TagEncoding::Niche { untagged_variant, niche_start, .. } => {
+                        let tag = &bytes[offset..offset + size];
+                        let candidate_discriminant = i128::from_le_bytes(pad16(tag, false))
+                            .wrapping_sub(niche_start as i128);
+                        let enum_data = self.db.enum_data(enum_id);
+                        let result = 'b: {
+                            for (local_id, _) in enum_data.variants.iter() {
+                                if candidate_discriminant
+                                    == self.db.const_eval_discriminant(EnumVariantId {
+                                        parent: enum_id,
+                                        local_id,
+                                    })?
+                                {
+                                    break 'b candidate_discriminant;
+                                }
                             }
+                            self.db.const_eval_discriminant(EnumVariantId {
+                                parent: enum_id,
+                                local_id: untagged_variant.0,
+                            })?
                         };
-                        ty = self.db.field_types(f.parent)[f.local_id]
-                            .clone()
-                            .substitute(Interner, subst);
-                        let offset = variant_layout
-                            .fields
-                            .offset(u32::from(f.local_id.into_raw()) as usize)
-                            .bytes_usize();
-                        addr = addr.offset(offset);
-                        // FIXME: support structs with unsized fields
-                        metadata = None;
+                        Ok(result)
                     }
-                    _ => return Err(MirEvalError::TypeError("Only adt has fields")),
-                },
-                ProjectionElem::ConstantIndex { .. } => {
-                    not_supported!("constant index")
                 }
-                ProjectionElem::Subslice { .. } => not_supported!("subslice"),
-                ProjectionElem::OpaqueCast(_) => not_supported!("opaque cast"),
             }
         }
-        Ok((addr, ty, metadata))
     }
 
     fn layout(&self, ty: &Ty) -> Result<Layout> {
@@ -474,10 +842,23 @@ impl Evaluator<'_> {
         Ok(self.place_addr_and_ty_and_metadata(p, locals)?.1)
     }
 
+    /// The type behind a pointer the interpreter can dereference itself: raw pointers, references
+    /// and `Box`, whose only field is its pointer so that it is laid out like one.
+    fn builtin_pointee(&self, ty: &Ty) -> Option<Ty> {
+        match ty.kind(Interner) {
+            TyKind::Raw(_, inner) | TyKind::Ref(_, _, inner) => Some(inner.clone()),
+            TyKind::Adt(adt, subst) if is_box(adt.0, self.db) => {
+                subst.as_slice(Interner).first()?.ty(Interner).cloned()
+            }
+            _ => None,
+        }
+    }
+
     fn operand_ty(&self, o: &Operand, locals: &Locals<'_>) -> Result<Ty> {
         Ok(match o {
             Operand::Copy(p) | Operand::Move(p) => self.place_ty(p, locals)?,
             Operand::Constant(c) => c.data(Interner).ty.clone(),
+            &Operand::Static(s) => static_ref_ty(self.db, s),
         })
     }
 
@@ -488,11 +869,33 @@ impl Evaluator<'_> {
         })
     }
 
+    /// Executes `body` in a new frame, called from the frame `caller` if it is known. The `bool` is
+    /// whether the called function is `#[track_caller]`.
     fn interpret_mir(
         &mut self,
         body: &MirBody,
         args: impl Iterator<Item = Vec<u8>>,
-        subst: Substitution,
+        subst: &Substitution,
+        caller: Option<(&Locals<'_>, bool)>,
+    ) -> Result<Vec<u8>> {
+        let frame_start = Stack(self.stack.len()).to_usize();
+        let result = self.interpret_mir_inner(body, args, subst, caller);
+        // The locals of the frame are gone, however it exited, so pointers to them can't be offset
+        // anymore. Deeper frames have already unregistered theirs.
+        let frame_allocations: Vec<_> =
+            self.allocations.range(frame_start..).map(|(&start, _)| start).collect();
+        for start in frame_allocations {
+            self.allocations.remove(&start);
+        }
+        result
+    }
+
+    fn interpret_mir_inner(
+        &mut self,
+        body: &MirBody,
+        args: impl Iterator<Item = Vec<u8>>,
+        subst: &Substitution,
+        caller: Option<(&Locals<'_>, bool)>,
     ) -> Result<Vec<u8>> {
         if let Some(x) = self.stack_depth_limit.checked_sub(1) {
             self.stack_depth_limit = x;
@@ -500,7 +903,19 @@ impl Evaluator<'_> {
             return Err(MirEvalError::StackOverflow);
         }
         let mut current_block_idx = body.start_block;
-        let mut locals = Locals { ptr: &ArenaMap::new(), body: &body, subst: &subst };
+        let (caller, track_caller) = match caller {
+            Some((caller, track_caller)) => (Some(caller), track_caller),
+            None => (None, false),
+        };
+        let mut locals = Locals {
+            ptr: &ArenaMap::new(),
+            body,
+            subst,
+            caller,
+            track_caller,
+            call_span: Cell::new(MirSpan::Unknown),
+        };
+        let mut local_allocations = vec![];
         let (locals_ptr, stack_size) = {
             let mut stack_ptr = self.stack.len();
             let addr = body
@@ -511,6 +926,7 @@ impl Evaluator<'_> {
                         self.size_of_sized(&x.ty, &locals, "no unsized local in extending stack")?;
                     let my_ptr = stack_ptr;
                     stack_ptr += size;
+                    local_allocations.push((Stack(my_ptr), size));
                     Ok((id, Stack(my_ptr)))
                 })
                 .collect::<Result<ArenaMap<LocalId, _>>>()?;
@@ -518,18 +934,25 @@ impl Evaluator<'_> {
             (addr, stack_size)
         };
         locals.ptr = &locals_ptr;
+        self.check_memory_limit(stack_size)?;
         self.stack.extend(iter::repeat(0).take(stack_size));
-        let mut remain_args = body.arg_count;
-        for ((_, addr), value) in locals_ptr.iter().skip(1).zip(args) {
-            self.write_memory(*addr, &value)?;
-            if remain_args == 0 {
+        for (addr, size) in local_allocations {
+            self.register_allocation(addr, size);
+        }
+        let entry = body.entry_arguments();
+        let mut params = entry.args.iter();
+        for value in args {
+            let Some((local, _)) = params.next() else {
                 return Err(MirEvalError::TypeError("more arguments provided"));
-            }
-            remain_args -= 1;
+            };
+            self.write_memory(locals_ptr[*local], &value)?;
         }
-        if remain_args > 0 {
+        if params.next().is_some() {
             return Err(MirEvalError::TypeError("not enough arguments provided"));
         }
+        let mut drop_flags = DropFlags::default();
+        // The panic we are unwinding from while executing cleanup blocks, resumed by `Resume`.
+        let mut unwinding = None;
         loop {
             let current_block = &body.basic_blocks[current_block_idx];
             if let Some(x) = self.execution_limit.checked_sub(1) {
@@ -541,12 +964,19 @@ impl Evaluator<'_> {
                 match &statement.kind {
                     StatementKind::Assign(l, r) => {
                         let addr = self.place_addr(l, &locals)?;
-                        let result = self.eval_rvalue(r, &locals)?.to_vec(&self)?;
-                        self.write_memory(addr, &result)?;
+                        self.check_place_is_writable(l, addr, statement.span, &locals)?;
+                        let is_shared_pointer = self.is_shared_pointer(r, &locals)?;
+                        self.eval_rvalue(r, &locals)?.write_to(self, addr)?;
+                        if is_shared_pointer {
+                            self.shared_pointers.insert(addr.to_usize());
+                        }
+                        drop_flags.remove_moved_in_rvalue(r);
+                        drop_flags.add_place(l);
                     }
                     StatementKind::Deinit(_) => not_supported!("de-init statement"),
                     StatementKind::StorageLive(_)
                     | StatementKind::StorageDead(_)
+                    | StatementKind::Coverage(_)
                     | StatementKind::Nop => (),
                 }
             }
@@ -559,30 +989,72 @@ impl Evaluator<'_> {
                 }
                 Terminator::Call {
                     func,
-                    args,
-                    destination,
+                    args: arg_operands,
+                    destination: destination_place,
                     target,
-                    cleanup: _,
+                    cleanup,
                     from_hir_call: _,
+                    span,
                 } => {
-                    let destination = self.place_interval(destination, &locals)?;
+                    let destination = self.place_interval(destination_place, &locals)?;
                     let fn_ty = self.operand_ty(func, &locals)?;
-                    let args = args
+                    locals.call_span.set(*span);
+                    let args = arg_operands
                         .iter()
                         .map(|x| self.operand_ty_and_eval(x, &locals))
                         .collect::<Result<Vec<_>>>()?;
-                    match &fn_ty.data(Interner).kind {
+                    arg_operands.iter().for_each(|x| drop_flags.remove_moved(x));
+                    let stack_depth_limit = self.stack_depth_limit;
+                    let result = match &fn_ty.data(Interner).kind {
                         TyKind::Function(_) => {
                             let bytes = self.eval_operand(func, &locals)?;
-                            self.exec_fn_pointer(bytes, destination, &args, &locals)?;
+                            self.exec_fn_pointer(bytes, destination, &args, &locals)
                         }
                         TyKind::FnDef(def, generic_args) => {
-                            self.exec_fn_def(*def, generic_args, destination, &args, &locals)?;
+                            self.exec_fn_def(*def, generic_args, destination, &args, &locals)
                         }
                         x => not_supported!("unknown function type {x:?}"),
+                    };
+                    let result = result.map_err(|e| e.with_call_site(body.owner, *span));
+                    match (result, cleanup) {
+                        (Ok(()), _) => {
+                            drop_flags.add_place(destination_place);
+                            current_block_idx =
+                                target.expect("broken mir, function without target");
+                        }
+                        (Err(e), Some(cleanup)) if e.is_panic() => {
+                            // The frames of the callee are gone, but they didn't get the chance to
+                            // give back their stack depth.
+                            self.stack_depth_limit = stack_depth_limit;
+                            unwinding = Some(e);
+                            current_block_idx = *cleanup;
+                        }
+                        (Err(e), _) => return Err(e),
                     }
-                    current_block_idx = target.expect("broken mir, function without target");
                 }
+                Terminator::Drop { place, target, unwind } => {
+                    if drop_flags.remove_place(place) {
+                        let (addr, ty, _) = self.place_addr_and_ty_and_metadata(place, &locals)?;
+                        let stack_depth_limit = self.stack_depth_limit;
+                        match (self.run_drop_glue(addr, &ty, &locals), unwind) {
+                            (Ok(()), _) => (),
+                            (Err(e), Some(unwind)) if e.is_panic() => {
+                                self.stack_depth_limit = stack_depth_limit;
+                                unwinding = Some(e);
+                                current_block_idx = *unwind;
+                                continue;
+                            }
+                            (Err(e), _) => return Err(e),
+                        }
+                    }
+                    current_block_idx = *target;
+                }
+                Terminator::Resume => {
+                    return Err(unwinding
+                        .take()
+                        .unwrap_or(MirEvalError::TypeError("resume without a panic to unwind")));
+                }
+                Terminator::Abort => return Err(MirEvalError::ProcessAborted),
                 Terminator::SwitchInt { discr, targets } => {
                     let val = u128::from_le_bytes(pad16(
                         self.eval_operand(discr, &locals)?.get(&self)?,
@@ -591,18 +1063,37 @@ impl Evaluator<'_> {
                     current_block_idx = targets.target_for_value(val);
                 }
                 Terminator::Return => {
-                    let ty = body.locals[return_slot()].ty.clone();
                     self.stack_depth_limit += 1;
                     return Ok(self
                         .read_memory(
-                            locals.ptr[return_slot()],
-                            self.size_of_sized(&ty, &locals, "return type")?,
+                            locals.ptr[entry.return_local],
+                            self.size_of_sized(&entry.return_ty, &locals, "return type")?,
                         )?
                         .to_owned());
                 }
                 Terminator::Unreachable => {
                     return Err(MirEvalError::UndefinedBehavior("unreachable executed"));
                 }
+                Terminator::Assert { cond, expected, target, cleanup } => {
+                    let cond = self.eval_operand(cond, &locals)?.get(self)?[0] != 0;
+                    if cond == *expected {
+                        current_block_idx = *target;
+                    } else {
+                        // The assertions don't store their message, and bounds checks are the only
+                        // ones lowering emits.
+                        let e = MirEvalError::Panic {
+                            message: "index out of bounds".to_string(),
+                            location: None,
+                        };
+                        match cleanup {
+                            Some(cleanup) => {
+                                unwinding = Some(e);
+                                current_block_idx = *cleanup;
+                            }
+                            None => return Err(e),
+                        }
+                    }
+                }
                 _ => not_supported!("unknown terminator"),
             }
         }
@@ -624,7 +1115,11 @@ impl Evaluator<'_> {
                 }
                 Owned(r)
             }
-            Rvalue::Len(_) => not_supported!("rvalue len"),
+            Rvalue::Len(p) => {
+                let (_, ty, metadata) = self.place_addr_and_ty_and_metadata(p, locals)?;
+                let len = self.place_len(&ty, metadata.as_ref())?;
+                Owned(len.to_le_bytes()[0..self.ptr_size()].to_vec())
+            }
             Rvalue::UnaryOp(op, val) => {
                 let mut c = self.eval_operand(val, locals)?.get(&self)?;
                 let mut ty = self.operand_ty(val, locals)?;
@@ -653,22 +1148,60 @@ impl Evaluator<'_> {
                 }
                 Owned(c)
             }
-            Rvalue::CheckedBinaryOp(op, lhs, rhs) => {
+            Rvalue::BinaryOp(BinOp::Offset, lhs, rhs) => {
+                let ptr_ty = self.operand_ty(lhs, locals)?;
+                let TyKind::Raw(_, pointee) = ptr_ty.kind(Interner) else {
+                    return Err(MirEvalError::TypeError("offset of a non raw pointer"));
+                };
+                let size = self.size_of_sized(pointee, locals, "offset pointee type")?;
+                let count_ty = self.operand_ty(rhs, locals)?;
+                let is_signed = matches!(count_ty.as_builtin(), Some(BuiltinType::Int(_)));
+                let ptr = from_bytes!(usize, self.eval_operand(lhs, locals)?.get(self)?);
+                let count = i128::from_le_bytes(pad16(
+                    self.eval_operand(rhs, locals)?.get(self)?,
+                    is_signed,
+                ));
+                let result = self.offset_ptr(ptr, count * size as i128)?;
+                Owned(result.to_le_bytes().to_vec())
+            }
+            Rvalue::BinaryOp(op, lhs, rhs) => {
                 let lc = self.eval_operand(lhs, locals)?;
                 let rc = self.eval_operand(rhs, locals)?;
                 let mut lc = lc.get(&self)?;
                 let mut rc = rc.get(&self)?;
                 let mut ty = self.operand_ty(lhs, locals)?;
+                // Comparisons of references compare the pointees, like the `PartialEq` and
+                // `PartialOrd` impls of references do.
                 while let TyKind::Ref(_, _, z) = ty.kind(Interner) {
                     ty = z.clone();
-                    let size = if ty.kind(Interner) == &TyKind::Str {
-                        let ns = from_bytes!(usize, &lc[self.ptr_size()..self.ptr_size() * 2]);
-                        lc = &lc[..self.ptr_size()];
-                        rc = &rc[..self.ptr_size()];
-                        ns
-                    } else {
-                        self.size_of_sized(&ty, locals, "operand of binary op")?
+                    let element = match ty.kind(Interner) {
+                        TyKind::Str => Some((1, BuiltinType::Str)),
+                        TyKind::Slice(t) => {
+                            let Some(builtin) = t.as_builtin() else {
+                                not_supported!("comparison of slices with non scalar elements");
+                            };
+                            Some((self.size_of_sized(t, locals, "slice inner type")?, builtin))
+                        }
+                        _ => None,
                     };
+                    if let Some((element_size, element)) = element {
+                        // `None` if some elements are unordered, like a float NaN, which makes the
+                        // slices unequal.
+                        let ordering = self.compare_slices(lc, rc, element_size, element)?;
+                        let r = match op {
+                            BinOp::Eq => ordering.map_or(false, Ordering::is_eq),
+                            BinOp::Ne => ordering.map_or(true, Ordering::is_ne),
+                            BinOp::Lt => ordering.map_or(false, Ordering::is_lt),
+                            BinOp::Le => ordering.map_or(false, Ordering::is_le),
+                            BinOp::Gt => ordering.map_or(false, Ordering::is_gt),
+                            BinOp::Ge => ordering.map_or(false, Ordering::is_ge),
+                            _ => {
+                                return Err(MirEvalError::TypeError("invalid binary op on slices"))
+                            }
+                        };
+                        return Ok(Owned(vec![r as u8]));
+                    }
+                    let size = self.size_of_sized(&ty, locals, "operand of binary op")?;
                     lc = self.read_memory(Address::from_bytes(lc)?, size)?;
                     rc = self.read_memory(Address::from_bytes(rc)?, size)?;
                 }
@@ -700,12 +1233,18 @@ impl Evaluator<'_> {
                         let r = match op {
                             BinOp::Add => l128.overflowing_add(r128).0,
                             BinOp::Mul => l128.overflowing_mul(r128).0,
-                            BinOp::Div => l128.checked_div(r128).ok_or_else(|| {
-                                MirEvalError::Panic(format!("Overflow in {op:?}"))
-                            })?,
-                            BinOp::Rem => l128.checked_rem(r128).ok_or_else(|| {
-                                MirEvalError::Panic(format!("Overflow in {op:?}"))
-                            })?,
+                            BinOp::Div => {
+                                l128.checked_div(r128).ok_or_else(|| MirEvalError::Panic {
+                                    message: format!("Overflow in {op:?}"),
+                                    location: None,
+                                })?
+                            }
+                            BinOp::Rem => {
+                                l128.checked_rem(r128).ok_or_else(|| MirEvalError::Panic {
+                                    message: format!("Overflow in {op:?}"),
+                                    location: None,
+                                })?
+                            }
                             BinOp::Sub => l128.overflowing_sub(r128).0,
                             BinOp::BitAnd => l128 & r128,
                             BinOp::BitOr => l128 | r128,
@@ -715,16 +1254,25 @@ impl Evaluator<'_> {
                         let r = r.to_le_bytes();
                         for &k in &r[lc.len()..] {
                             if k != 0 && (k != 255 || !is_signed) {
-                                return Err(MirEvalError::Panic(format!("Overflow in {op:?}")));
+                                return Err(MirEvalError::Panic {
+                                    message: format!("Overflow in {op:?}"),
+                                    location: None,
+                                });
                             }
                         }
                         Owned(r[0..lc.len()].into())
                     }
                     BinOp::Shl | BinOp::Shr => {
                         let shift_amout = if r128 < 0 {
-                            return Err(MirEvalError::Panic(format!("Overflow in {op:?}")));
+                            return Err(MirEvalError::Panic {
+                                message: format!("Overflow in {op:?}"),
+                                location: None,
+                            });
                         } else if r128 > 128 {
-                            return Err(MirEvalError::Panic(format!("Overflow in {op:?}")));
+                            return Err(MirEvalError::Panic {
+                                message: format!("Overflow in {op:?}"),
+                                location: None,
+                            });
                         } else {
                             r128 as u8
                         };
@@ -735,70 +1283,51 @@ impl Evaluator<'_> {
                         };
                         Owned(r.to_le_bytes()[0..lc.len()].into())
                     }
-                    BinOp::Offset => not_supported!("offset binop"),
+                    BinOp::Offset => {
+                        return Err(MirEvalError::TypeError("offset of a non raw pointer"))
+                    }
                 }
             }
+            Rvalue::CheckedBinaryOp(op, lhs, rhs) => {
+                let ty = self.operand_ty(lhs, locals)?;
+                let is_signed = match ty.kind(Interner) {
+                    TyKind::Scalar(chalk_ir::Scalar::Int(_)) => true,
+                    TyKind::Scalar(chalk_ir::Scalar::Uint(_)) => false,
+                    _ => not_supported!("checked binary op on non integer type"),
+                };
+                let rhs_ty = self.operand_ty(rhs, locals)?;
+                let is_rhs_signed = matches!(rhs_ty.as_builtin(), Some(BuiltinType::Int(_)));
+                let lc = self.eval_operand(lhs, locals)?.get(self)?.to_vec();
+                let rc = self.eval_operand(rhs, locals)?.get(self)?.to_vec();
+                let (value, overflowed) = checked_int_op(op, &lc, &rc, is_signed, is_rhs_signed)?;
+                let pair =
+                    TyKind::Tuple(2, Substitution::from_iter(Interner, [ty, TyBuilder::bool()]))
+                        .intern(Interner);
+                let layout = self.layout(&pair)?;
+                let mut r = vec![0; layout.size.bytes_usize()];
+                let offset = layout.fields.offset(0).bytes_usize();
+                r[offset..offset + lc.len()].copy_from_slice(&value[..lc.len()]);
+                r[layout.fields.offset(1).bytes_usize()] = overflowed as u8;
+                Owned(r)
+            }
             Rvalue::Discriminant(p) => {
                 let ty = self.place_ty(p, locals)?;
                 let bytes = self.eval_place(p, locals)?.get(&self)?;
-                let layout = self.layout(&ty)?;
-                let enum_id = 'b: {
-                    match ty.kind(Interner) {
-                        TyKind::Adt(e, _) => match e.0 {
-                            AdtId::EnumId(e) => break 'b e,
-                            _ => (),
-                        },
-                        _ => (),
-                    }
-                    return Ok(Owned(0u128.to_le_bytes().to_vec()));
-                };
-                match layout.variants {
-                    Variants::Single { index } => {
-                        let r = self.db.const_eval_discriminant(EnumVariantId {
-                            parent: enum_id,
-                            local_id: index.0,
-                        })?;
-                        Owned(r.to_le_bytes().to_vec())
-                    }
-                    Variants::Multiple { tag, tag_encoding, .. } => {
-                        let Some(target_data_layout) = self.db.target_data_layout(self.crate_id) else {
-                            not_supported!("missing target data layout");
-                        };
-                        let size = tag.size(&*target_data_layout).bytes_usize();
-                        let offset = layout.fields.offset(0).bytes_usize(); // The only field on enum variants is the tag field
-                        match tag_encoding {
-                            TagEncoding::Direct => {
-                                let tag = &bytes[offset..offset + size];
-                                Owned(pad16(tag, false).to_vec())
-                            }
-                            TagEncoding::Niche { untagged_variant, niche_start, .. } => {
-                                let tag = &bytes[offset..offset + size];
-                                let candidate_discriminant = i128::from_le_bytes(pad16(tag, false))
-                                    .wrapping_sub(niche_start as i128);
-                                let enum_data = self.db.enum_data(enum_id);
-                                let result = 'b: {
-                                    for (local_id, _) in enum_data.variants.iter() {
-                                        if candidate_discriminant
-                                            == self.db.const_eval_discriminant(EnumVariantId {
-                                                parent: enum_id,
-                                                local_id,
-                                            })?
-                                        {
-                                            break 'b candidate_discriminant;
-                                        }
-                                    }
-                                    self.db.const_eval_discriminant(EnumVariantId {
-                                        parent: enum_id,
-                                        local_id: untagged_variant.0,
-                                    })?
-                                };
-                                Owned(result.to_le_bytes().to_vec())
-                            }
-                        }
-                    }
-                }
+                Owned(self.compute_discriminant(ty, bytes)?.to_le_bytes().to_vec())
             }
             Rvalue::ShallowInitBox(_, _) => not_supported!("shallow init box"),
+            Rvalue::Repeat(x, len) => {
+                let len = self.eval_const(len, locals)?.get(self)?;
+                let len = from_bytes!(usize, len);
+                let value = self.eval_operand(x, locals)?.get(self)?;
+                let size = value.len().checked_mul(len).ok_or(MirEvalError::MemoryLimitExceeded)?;
+                self.check_memory_limit(size)?;
+                Owned(value.repeat(len))
+            }
+            Rvalue::ShallowInitBoxWithAlloc(ty) => {
+                let size = self.size_of_sized(ty, locals, "boxed value")?;
+                Owned(self.heap_allocate(size)?.to_bytes())
+            }
             Rvalue::CopyForDeref(_) => not_supported!("copy for deref"),
             Rvalue::Aggregate(kind, values) => {
                 let values = values
@@ -815,7 +1344,18 @@ impl Evaluator<'_> {
                         Owned(r)
                     }
                     AggregateKind::Tuple(ty) => {
-                        let layout = self.layout(&ty)?;
+                        let layout =
+                            self.layout(&self.ty_filler(ty, locals.subst, locals.body.owner)?)?;
+                        Owned(self.make_by_layout(
+                            layout.size.bytes_usize(),
+                            &layout,
+                            None,
+                            values.iter().copied(),
+                        )?)
+                    }
+                    AggregateKind::Closure(ty) => {
+                        let layout =
+                            self.layout(&self.ty_filler(ty, locals.subst, locals.body.owner)?)?;
                         Owned(self.make_by_layout(
                             layout.size.bytes_usize(),
                             &layout,
@@ -851,6 +1391,8 @@ impl Evaluator<'_> {
                 CastKind::Pointer(cast) => match cast {
                     PointerCast::ReifyFnPointer => {
                         let current_ty = self.operand_ty(operand, locals)?;
+                        let current_ty =
+                            self.ty_filler(&current_ty, locals.subst, locals.body.owner)?;
                         if let TyKind::FnDef(_, _) = &current_ty.data(Interner).kind {
                             let id = self.vtable_map.id(current_ty);
                             let ptr_size = self.ptr_size();
@@ -859,47 +1401,44 @@ impl Evaluator<'_> {
                             not_supported!("ReifyFnPointer cast of a non FnDef type");
                         }
                     }
+                    PointerCast::ClosureFnPointer(_) => {
+                        let current_ty = self.operand_ty(operand, locals)?;
+                        if let TyKind::Closure(_, _) = &current_ty.data(Interner).kind {
+                            let id = self.vtable_map.id(current_ty);
+                            let ptr_size = self.ptr_size();
+                            Owned(id.to_le_bytes()[0..ptr_size].to_vec())
+                        } else {
+                            not_supported!("ClosureFnPointer cast of a non closure type");
+                        }
+                    }
                     PointerCast::Unsize => {
                         let current_ty = self.operand_ty(operand, locals)?;
-                        match &target_ty.data(Interner).kind {
-                            TyKind::Raw(_, ty) | TyKind::Ref(_, _, ty) => {
-                                match &ty.data(Interner).kind {
-                                    TyKind::Slice(_) => match &current_ty.data(Interner).kind {
-                                        TyKind::Raw(_, ty) | TyKind::Ref(_, _, ty) => {
-                                            match &ty.data(Interner).kind {
-                                                TyKind::Array(_, size) => {
-                                                    let addr = self
-                                                        .eval_operand(operand, locals)?
-                                                        .get(&self)?;
-                                                    let len = const_as_usize(size);
-                                                    let mut r = Vec::with_capacity(16);
-                                                    r.extend(addr.iter().copied());
-                                                    r.extend(len.to_le_bytes().into_iter());
-                                                    Owned(r)
-                                                }
-                                                _ => {
-                                                    not_supported!("slice unsizing from non arrays")
-                                                }
-                                            }
-                                        }
-                                        _ => not_supported!("slice unsizing from non pointers"),
-                                    },
-                                    TyKind::Dyn(_) => match &current_ty.data(Interner).kind {
-                                        TyKind::Raw(_, ty) | TyKind::Ref(_, _, ty) => {
-                                            let vtable = self.vtable_map.id(ty.clone());
-                                            let addr =
-                                                self.eval_operand(operand, locals)?.get(&self)?;
-                                            let mut r = Vec::with_capacity(16);
-                                            r.extend(addr.iter().copied());
-                                            r.extend(vtable.to_le_bytes().into_iter());
-                                            Owned(r)
-                                        }
-                                        _ => not_supported!("dyn unsizing from non pointers"),
-                                    },
-                                    _ => not_supported!("unknown unsized cast"),
+                        let (Some(target_pointee), Some(current_pointee)) =
+                            (self.builtin_pointee(target_ty), self.builtin_pointee(&current_ty))
+                        else {
+                            not_supported!("unsized cast on unknown pointer type");
+                        };
+                        match &target_pointee.data(Interner).kind {
+                            TyKind::Slice(_) => match &current_pointee.data(Interner).kind {
+                                TyKind::Array(_, size) => {
+                                    let addr = self.eval_operand(operand, locals)?.get(&self)?;
+                                    let len = const_as_usize(size);
+                                    let mut r = Vec::with_capacity(16);
+                                    r.extend(addr.iter().copied());
+                                    r.extend(len.to_le_bytes().into_iter());
+                                    Owned(r)
                                 }
+                                _ => not_supported!("slice unsizing from non arrays"),
+                            },
+                            TyKind::Dyn(_) => {
+                                let vtable = self.vtable_map.id(current_pointee);
+                                let addr = self.eval_operand(operand, locals)?.get(&self)?;
+                                let mut r = Vec::with_capacity(16);
+                                r.extend(addr.iter().copied());
+                                r.extend(vtable.to_le_bytes().into_iter());
+                                Owned(r)
                             }
-                            _ => not_supported!("unsized cast on unknown pointer type"),
+                            _ => not_supported!("unknown unsized cast"),
                         }
                     }
                     x => not_supported!("pointer cast {x:?}"),
@@ -908,8 +1447,18 @@ impl Evaluator<'_> {
                 CastKind::IntToInt
                 | CastKind::PointerExposeAddress
                 | CastKind::PointerFromExposedAddress => {
-                    // FIXME: handle signed cast
-                    let current = pad16(self.eval_operand(operand, locals)?.get(&self)?, false);
+                    // Exposed addresses can be turned back into pointers to any memory.
+                    if !matches!(kind, CastKind::IntToInt) {
+                        self.frame_is_pure = false;
+                    }
+                    // Widening sign extends signed sources and zero extends the others, and
+                    // narrowing truncates, which is taking the low bytes of the extended value.
+                    let current_ty = self.operand_ty(operand, locals)?;
+                    let is_signed = matches!(
+                        current_ty.kind(Interner),
+                        TyKind::Scalar(chalk_ir::Scalar::Int(_))
+                    );
+                    let current = pad16(self.eval_operand(operand, locals)?.get(&self)?, is_signed);
                     let dest_size =
                         self.size_of_sized(target_ty, locals, "destination of int to int cast")?;
                     Owned(current[0..dest_size].to_vec())
@@ -919,8 +1468,28 @@ impl Evaluator<'_> {
                 CastKind::IntToFloat => not_supported!("float to int cast"),
                 CastKind::PtrToPtr => {
                     let current = pad16(self.eval_operand(operand, locals)?.get(&self)?, false);
+                    let current_ty = self.operand_ty(operand, locals)?;
+                    let target_ty = self.ty_filler(target_ty, locals.subst, locals.body.owner)?;
+                    if let (Some(TyKind::Slice(_)), Some(TyKind::Array(_, size))) = (
+                        current_ty.as_reference_or_ptr().map(|x| x.0.kind(Interner)),
+                        target_ty.as_reference_or_ptr().map(|x| x.0.kind(Interner)),
+                    ) {
+                        // A slice to array cast only keeps the data pointer, so we check the length
+                        // here, as nothing else could detect the out of bound accesses later. Using
+                        // an array longer than the slice is undefined behavior, while longer slices
+                        // are fine, the array just covers a prefix of them.
+                        let ptr_size = self.ptr_size();
+                        let len = from_bytes!(usize, &current[ptr_size..ptr_size * 2]);
+                        if let Some(array_len) = try_const_usize(size) {
+                            if (len as u128) < array_len {
+                                return Err(MirEvalError::UndefinedBehavior(
+                                    "slice pointer cast to a longer array",
+                                ));
+                            }
+                        }
+                    }
                     let dest_size =
-                        self.size_of_sized(target_ty, locals, "destination of ptr to ptr cast")?;
+                        self.size_of_sized(&target_ty, locals, "destination of ptr to ptr cast")?;
                     Owned(current[0..dest_size].to_vec())
                 }
                 CastKind::FnPtrToPtr => not_supported!("fn ptr to ptr cast"),
@@ -1003,56 +1572,149 @@ impl Evaluator<'_> {
         Ok(result)
     }
 
+    /// The error for reading the value of the const parameter `param`, which is not known as the
+    /// body is interpreted without substituting it.
+    fn const_param_in_runtime_context(&self, param: Option<TypeOrConstParamId>) -> MirEvalError {
+        let name = param
+            .and_then(|it| {
+                let params = self.db.generic_params(it.parent);
+                Some(params.type_or_consts[it.local_id].name()?.to_string())
+            })
+            .unwrap_or_else(|| "_".to_string());
+        let e = MirLowerError::ConstParamInRuntimeContext(name);
+        MirEvalError::ConstEvalError(Box::new(ConstEvalError::MirLowerError(e)))
+    }
+
     fn eval_operand(&mut self, x: &Operand, locals: &Locals<'_>) -> Result<Interval> {
         Ok(match x {
             Operand::Copy(p) | Operand::Move(p) => self.eval_place(p, locals)?,
-            Operand::Constant(konst) => {
-                let data = &konst.data(Interner);
-                match &data.value {
-                    chalk_ir::ConstValue::BoundVar(b) => {
-                        let c = locals
-                            .subst
-                            .as_slice(Interner)
-                            .get(b.index)
-                            .ok_or(MirEvalError::TypeError("missing generic arg"))?
-                            .assert_const_ref(Interner);
-                        self.eval_operand(&Operand::Constant(c.clone()), locals)?
+            Operand::Constant(konst) => self.eval_const(konst, locals)?,
+            Operand::Static(st) => {
+                let ty = self
+                    .db
+                    .value_ty((*st).into())
+                    .substitute(Interner, &Substitution::empty(Interner));
+                if self.db.static_data(*st).mutable || self.has_interior_mutability(&ty) {
+                    self.frame_is_pure = false;
+                }
+                if let Some(&ptr) = self.static_pointers.get(st) {
+                    return Ok(Interval::new(ptr, self.ptr_size()));
+                }
+                let addr = self.eval_static(*st, locals)?;
+                let ptr = self.heap_allocate(self.ptr_size())?;
+                self.write_memory(ptr, &addr.to_bytes())?;
+                self.static_pointers.insert(*st, ptr);
+                Interval::new(ptr, self.ptr_size())
+            }
+        })
+    }
+
+    fn eval_const(&mut self, konst: &Const, locals: &Locals<'_>) -> Result<Interval> {
+        let data = &konst.data(Interner);
+        Ok(match &data.value {
+            chalk_ir::ConstValue::BoundVar(b) => {
+                let Some(c) = locals.subst.as_slice(Interner).get(b.index) else {
+                    let param = locals.body.owner.as_generic_def_id().and_then(|def| {
+                        generics(self.db.upcast(), def).iter().nth(b.index).map(|x| x.0)
+                    });
+                    return Err(self.const_param_in_runtime_context(param));
+                };
+                let c = c.assert_const_ref(Interner);
+                self.eval_const(c, locals)?
+            }
+            chalk_ir::ConstValue::InferenceVar(_) => {
+                not_supported!("inference var constant")
+            }
+            // Lengths of array types in the body refer to the parameter like this.
+            chalk_ir::ConstValue::Placeholder(idx) => {
+                let param = from_placeholder_idx(self.db, *idx);
+                let arg = locals.body.owner.as_generic_def_id().and_then(|def| {
+                    let i = generics(self.db.upcast(), def).param_idx(param)?;
+                    locals.subst.as_slice(Interner).get(i)?.constant(Interner).cloned()
+                });
+                // The body is generic over the parameter, which is not substituted.
+                let Some(c) = arg else {
+                    return Err(self.const_param_in_runtime_context(Some(param)));
+                };
+                self.eval_const(&c, locals)?
+            }
+            chalk_ir::ConstValue::Concrete(c) => match &c.interned {
+                ConstScalar::Bytes(v, memory_map) => {
+                    let mut v: Cow<'_, [u8]> = Cow::Borrowed(v);
+                    // Every allocation gets its own address, even if its bytes are shared with
+                    // another constant, which may need a different alignment.
+                    let patch_map = memory_map.transform_addresses(|b| {
+                        let addr = self.heap_allocate(b.len())?;
+                        self.write_memory(addr, b)?;
+                        self.read_only_allocations.insert(addr.to_usize());
+                        Ok(addr.to_usize())
+                    })?;
+                    let size = self.size_of(&data.ty, locals)?.unwrap_or(v.len());
+                    if size != v.len() {
+                        // Handle self enum
+                        if size == 16 && v.len() < 16 {
+                            v = Cow::Owned(pad16(&v, false).to_vec());
+                        } else if size < 16 && v.len() == 16 {
+                            v = Cow::Owned(v[0..size].to_vec());
+                        } else {
+                            return Err(MirEvalError::InvalidConst(konst.clone()));
+                        }
                     }
-                    chalk_ir::ConstValue::InferenceVar(_) => {
-                        not_supported!("inference var constant")
+                    let addr = self.heap_allocate(size)?;
+                    self.write_memory(addr, &v)?;
+                    self.patch_addresses(&patch_map, addr, &data.ty, locals)?;
+                    if !memory_map.fn_pointers.is_empty() {
+                        self.patch_fn_pointers(memory_map, addr, &v, &data.ty, locals)?;
                     }
-                    chalk_ir::ConstValue::Placeholder(_) => not_supported!("placeholder constant"),
-                    chalk_ir::ConstValue::Concrete(c) => match &c.interned {
-                        ConstScalar::Bytes(v, memory_map) => {
-                            let mut v: Cow<'_, [u8]> = Cow::Borrowed(v);
-                            let patch_map = memory_map.transform_addresses(|b| {
-                                let addr = self.heap_allocate(b.len());
-                                self.write_memory(addr, b)?;
-                                Ok(addr.to_usize())
-                            })?;
-                            let size = self.size_of(&data.ty, locals)?.unwrap_or(v.len());
-                            if size != v.len() {
-                                // Handle self enum
-                                if size == 16 && v.len() < 16 {
-                                    v = Cow::Owned(pad16(&v, false).to_vec());
-                                } else if size < 16 && v.len() == 16 {
-                                    v = Cow::Owned(v[0..size].to_vec());
-                                } else {
-                                    return Err(MirEvalError::InvalidConst(konst.clone()));
-                                }
-                            }
-                            let addr = self.heap_allocate(size);
-                            self.write_memory(addr, &v)?;
-                            self.patch_addresses(&patch_map, addr, &data.ty, locals)?;
-                            Interval::new(addr, size)
-                        }
-                        ConstScalar::Unknown => not_supported!("evaluating unknown const"),
-                    },
+                    Interval::new(addr, size)
                 }
-            }
+                ConstScalar::UnevaluatedConst(const_id, subst) => {
+                    let subst = self.subst_filler(subst, locals);
+                    let (const_id, subst) =
+                        lookup_impl_const(self.db, self.trait_env.clone(), *const_id, subst);
+                    let c = self.db.const_eval(const_id, subst)?;
+                    self.eval_const(&c, locals)?
+                }
+                ConstScalar::BinaryOp(_) => {
+                    not_supported!("evaluating constant expression of a type")
+                }
+                ConstScalar::Unknown => not_supported!("evaluating unknown const"),
+            },
         })
     }
 
+    fn eval_static(&mut self, st: StaticId, locals: &Locals<'_>) -> Result<Address> {
+        if let Some(o) = self.static_locations.get(&st) {
+            return Ok(*o);
+        };
+        let static_data = self.db.static_data(st);
+        let result = if !static_data.is_extern {
+            self.mir_body(st.into())
+                .map_err(|e| {
+                    MirEvalError::ConstEvalError(Box::new(ConstEvalError::MirLowerError(e)))
+                })
+                .and_then(|body| self.interpret_mir_with_no_arg(&body))
+        } else {
+            Err(MirEvalError::ExternStatic(st))
+        };
+        let result = match result {
+            Ok(x) => x,
+            Err(e) if self.db.attrs(st.into()).by_key("thread_local").exists() => {
+                return Err(MirEvalError::ThreadLocalAccess(
+                    static_data.name.to_string(),
+                    Box::new(e),
+                ));
+            }
+            Err(e) => return Err(e),
+        };
+        let ty = self.db.value_ty(st.into()).substitute(Interner, &Substitution::empty(Interner));
+        let size = self.size_of_sized(&ty, locals, "static")?;
+        let addr = self.heap_allocate(size)?;
+        self.write_memory(addr, &result)?;
+        self.static_locations.insert(st, addr);
+        Ok(addr)
+    }
+
     fn eval_place(&mut self, p: &Place, locals: &Locals<'_>) -> Result<Interval> {
         let addr = self.place_addr(p, locals)?;
         Ok(Interval::new(
@@ -1061,6 +1723,34 @@ impl Evaluator<'_> {
         ))
     }
 
+    /// Compares the elements of the slices behind the fat pointers `lc` and `rc` lexicographically,
+    /// and then their lengths.
+    fn compare_slices(
+        &self,
+        lc: &[u8],
+        rc: &[u8],
+        element_size: usize,
+        element: BuiltinType,
+    ) -> Result<Option<Ordering>> {
+        let ptr_size = self.ptr_size();
+        let l_len = from_bytes!(usize, &lc[ptr_size..ptr_size * 2]);
+        let r_len = from_bytes!(usize, &rc[ptr_size..ptr_size * 2]);
+        let common = l_len.min(r_len);
+        if element_size != 0 && common != 0 {
+            let l =
+                self.read_memory(Address::from_bytes(&lc[..ptr_size])?, common * element_size)?;
+            let r =
+                self.read_memory(Address::from_bytes(&rc[..ptr_size])?, common * element_size)?;
+            for (l, r) in l.chunks(element_size).zip(r.chunks(element_size)) {
+                match compare_scalars(l, r, element)? {
+                    Some(Ordering::Equal) => (),
+                    ordering => return Ok(ordering),
+                }
+            }
+        }
+        Ok(Some(l_len.cmp(&r_len)))
+    }
+
     fn read_memory(&self, addr: Address, size: usize) -> Result<&[u8]> {
         let (mem, pos) = match addr {
             Stack(x) => (&self.stack, x),
@@ -1077,6 +1767,150 @@ impl Evaluator<'_> {
         mem.get_mut(pos..pos + r.len())
             .ok_or(MirEvalError::UndefinedBehavior("out of bound memory write"))?
             .copy_from_slice(r);
+        // The pointers that were stored there, if any, are overwritten.
+        let start = addr.to_usize();
+        let overwritten: Vec<_> =
+            self.shared_pointers.range(start..start + r.len()).copied().collect();
+        for x in overwritten {
+            self.shared_pointers.remove(&x);
+        }
+        Ok(())
+    }
+
+    /// Makes the pointers copied from `from` to `to` keep their provenance.
+    fn copy_provenance(&mut self, from: Interval, to: Address) {
+        let start = from.addr.to_usize();
+        let copied: Vec<_> = self
+            .shared_pointers
+            .range(start..start + from.size)
+            .map(|x| to.offset(x - start).to_usize())
+            .collect();
+        self.shared_pointers.extend(copied);
+    }
+
+    /// Whether the value of `r` is a pointer derived from a shared reference to a type without
+    /// interior mutability, directly or by reborrowing or casting such a pointer.
+    fn is_shared_pointer(&self, r: &Rvalue, locals: &Locals<'_>) -> Result<bool> {
+        Ok(match r {
+            Rvalue::Ref(kind, p) => {
+                (*kind == BorrowKind::Shared
+                    && !self.has_interior_mutability(&self.place_ty(p, locals)?))
+                    || self.is_behind_shared_pointer(p, locals)?
+            }
+            Rvalue::Cast(_, Operand::Copy(p) | Operand::Move(p), _) => {
+                self.shared_pointers.contains(&self.place_addr(p, locals)?.to_usize())
+            }
+            _ => false,
+        })
+    }
+
+    /// Whether the last dereference in `p` is of a pointer derived from a shared reference.
+    fn is_behind_shared_pointer(&self, p: &Place, locals: &Locals<'_>) -> Result<bool> {
+        let Some(deref) = p.projection.iter().rposition(|x| *x == ProjectionElem::Deref) else {
+            return Ok(false);
+        };
+        let pointer = Place { local: p.local, projection: p.projection[..deref].to_vec() };
+        Ok(self.shared_pointers.contains(&self.place_addr(&pointer, locals)?.to_usize()))
+    }
+
+    /// Whether `ty` contains an `UnsafeCell`, which allows mutation through shared references.
+    /// Types which we can't see through conservatively have interior mutability.
+    fn has_interior_mutability(&self, ty: &Ty) -> bool {
+        match ty.kind(Interner) {
+            TyKind::Adt(chalk_ir::AdtId(adt), subst) => {
+                if lang_attr(self.db.upcast(), *adt) == Some(LangItem::UnsafeCell) {
+                    return true;
+                }
+                let variants: Vec<VariantId> = match *adt {
+                    AdtId::StructId(s) => vec![s.into()],
+                    AdtId::UnionId(u) => vec![u.into()],
+                    AdtId::EnumId(e) => self
+                        .db
+                        .enum_data(e)
+                        .variants
+                        .iter()
+                        .map(|(local_id, _)| EnumVariantId { parent: e, local_id }.into())
+                        .collect(),
+                };
+                variants.into_iter().any(|v| {
+                    self.db.field_types(v).iter().any(|(_, field_ty)| {
+                        self.has_interior_mutability(&field_ty.clone().substitute(Interner, subst))
+                    })
+                })
+            }
+            TyKind::Tuple(_, subst) => subst
+                .iter(Interner)
+                .any(|x| self.has_interior_mutability(x.assert_ty_ref(Interner))),
+            TyKind::Array(inner, _) | TyKind::Slice(inner) => self.has_interior_mutability(inner),
+            TyKind::Closure(c, _) => closure_captures(self.db, *c)
+                .0
+                .iter()
+                .any(|capture| self.has_interior_mutability(&capture.ty)),
+            // The pointee of a reference is not part of the value.
+            TyKind::Scalar(_)
+            | TyKind::Str
+            | TyKind::Ref(..)
+            | TyKind::Raw(..)
+            | TyKind::FnDef(..)
+            | TyKind::Function(_)
+            | TyKind::Never => false,
+            _ => true,
+        }
+    }
+
+    /// Whether the values of `ty` hold no pointers, so that they are fully described by their
+    /// bytes. Types which we can't see through conservatively hold pointers.
+    fn is_plain_data(&self, ty: &Ty) -> bool {
+        match ty.kind(Interner) {
+            TyKind::Adt(chalk_ir::AdtId(adt), subst) => {
+                let variants: Vec<VariantId> = match *adt {
+                    AdtId::StructId(s) => vec![s.into()],
+                    AdtId::UnionId(u) => vec![u.into()],
+                    AdtId::EnumId(e) => self
+                        .db
+                        .enum_data(e)
+                        .variants
+                        .iter()
+                        .map(|(local_id, _)| EnumVariantId { parent: e, local_id }.into())
+                        .collect(),
+                };
+                variants.into_iter().all(|v| {
+                    self.db.field_types(v).iter().all(|(_, field_ty)| {
+                        self.is_plain_data(&field_ty.clone().substitute(Interner, subst))
+                    })
+                })
+            }
+            TyKind::Tuple(_, subst) => {
+                subst.iter(Interner).all(|x| self.is_plain_data(x.assert_ty_ref(Interner)))
+            }
+            TyKind::Array(inner, _) => self.is_plain_data(inner),
+            TyKind::Scalar(_) | TyKind::FnDef(..) | TyKind::Never => true,
+            _ => false,
+        }
+    }
+
+    /// Checks that assigning to `p`, which is at `addr`, is not undefined behavior.
+    fn check_place_is_writable(
+        &self,
+        p: &Place,
+        addr: Address,
+        span: MirSpan,
+        locals: &Locals<'_>,
+    ) -> Result<()> {
+        let undefined_behavior = |kind| {
+            MirEvalError::ConstEvalError(Box::new(ConstEvalError::UndefinedBehavior { kind, span }))
+        };
+        let addr = addr.to_usize();
+        let in_read_only_allocation =
+            self.allocations.range(..=addr).next_back().map_or(false, |(start, size)| {
+                addr < start + size && self.read_only_allocations.contains(start)
+            });
+        if in_read_only_allocation {
+            return Err(undefined_behavior(UndefinedBehaviorKind::WriteToReadOnly));
+        }
+        if self.is_behind_shared_pointer(p, locals)? {
+            return Err(undefined_behavior(UndefinedBehaviorKind::WriteThroughSharedRef));
+        }
         Ok(())
     }
 
@@ -1133,7 +1967,8 @@ impl Evaluator<'_> {
         struct Filler<'a> {
             db: &'a dyn HirDatabase,
             subst: &'a Substitution,
-            skip_params: usize,
+            /// The generics `subst` is for, which tell where each parameter is in it.
+            generics: Option<Generics>,
         }
         impl FallibleTypeFolder<Interner> for Filler<'_> {
             type Error = MirEvalError;
@@ -1157,7 +1992,8 @@ impl Evaluator<'_> {
                         match impl_trait_id {
                             crate::ImplTraitId::ReturnTypeImplTrait(func, idx) => {
                                 let infer = self.db.infer(func.into());
-                                let filler = &mut Filler { db: self.db, subst, skip_params: 0 };
+                                let generics = Some(generics(self.db.upcast(), func.into()));
+                                let filler = &mut Filler { db: self.db, subst, generics };
                                 filler.try_fold_ty(infer.type_of_rpit[idx].clone(), outer_binder)
                             }
                             crate::ImplTraitId::AsyncBlockTypeImplTrait(_, _) => {
@@ -1165,6 +2001,18 @@ impl Evaluator<'_> {
                             }
                         }
                     }
+                    // Inference leaves the projections it can't normalize in generic code (like
+                    // `Self::Item` in a default method) in this form. Once the generic parameters
+                    // are filled they can be normalized, but only as an alias.
+                    TyKind::AssociatedType(id, subst) => {
+                        let substitution =
+                            subst.clone().try_fold_with(self.as_dyn(), outer_binder)?;
+                        Ok(TyKind::Alias(AliasTy::Projection(ProjectionTy {
+                            associated_ty_id: *id,
+                            substitution,
+                        }))
+                        .intern(Interner))
+                    }
                     _ => ty.try_super_fold_with(self.as_dyn(), outer_binder),
                 }
             }
@@ -1176,26 +2024,88 @@ impl Evaluator<'_> {
             ) -> std::result::Result<Ty, Self::Error> {
                 let x = from_placeholder_idx(self.db, idx);
                 Ok(self
-                    .subst
-                    .as_slice(Interner)
-                    .get((u32::from(x.local_id.into_raw()) as usize) + self.skip_params)
+                    .generics
+                    .as_ref()
+                    .and_then(|g| g.param_idx(x))
+                    .and_then(|i| self.subst.as_slice(Interner).get(i))
                     .and_then(|x| x.ty(Interner))
                     .ok_or(MirEvalError::TypeError("Generic arg not provided"))?
                     .clone())
             }
+
+            fn try_fold_free_placeholder_const(
+                &mut self,
+                _ty: Ty,
+                idx: chalk_ir::PlaceholderIndex,
+                _outer_binder: DebruijnIndex,
+            ) -> std::result::Result<Const, Self::Error> {
+                let x = from_placeholder_idx(self.db, idx);
+                Ok(self
+                    .generics
+                    .as_ref()
+                    .and_then(|g| g.param_idx(x))
+                    .and_then(|i| self.subst.as_slice(Interner).get(i))
+                    .and_then(|x| x.constant(Interner))
+                    .ok_or(MirEvalError::TypeError("Generic arg not provided"))?
+                    .clone())
+            }
         }
-        let filler = &mut Filler { db: self.db, subst, skip_params: 0 };
-        Ok(normalize(self.db, owner, ty.clone().try_fold_with(filler, DebruijnIndex::INNERMOST)?))
+        let generics = owner.as_generic_def_id().map(|def| generics(self.db.upcast(), def));
+        let filler = &mut Filler { db: self.db, subst, generics };
+        // The filled type has no placeholders left, but may name impls of downstream crates, which
+        // the environment of `owner` can't see if it is in a dependency.
+        Ok(normalize(
+            self.db,
+            self.trait_env.clone(),
+            ty.clone().try_fold_with(filler, DebruijnIndex::INNERMOST)?,
+        ))
     }
 
-    fn heap_allocate(&mut self, s: usize) -> Address {
+    fn heap_allocate(&mut self, s: usize) -> Result<Address> {
+        self.check_memory_limit(s)?;
         let pos = self.heap.len();
         self.heap.extend(iter::repeat(0).take(s));
-        Address::Heap(pos)
+        self.register_allocation(Address::Heap(pos), s);
+        Ok(Address::Heap(pos))
+    }
+
+    /// Checks that the stack and the heap can grow by `additional` bytes in total.
+    fn check_memory_limit(&self, additional: usize) -> Result<()> {
+        match self.memory_limit {
+            Some(limit) if self.stack.len() + self.heap.len() + additional > limit => {
+                Err(MirEvalError::MemoryLimitExceeded)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn register_allocation(&mut self, addr: Address, size: usize) {
+        // Zero sized allocations share their address with the next allocation, and can't be
+        // offset anyway.
+        if size != 0 {
+            self.allocations.insert(addr.to_usize(), size);
+        }
+    }
+
+    /// Offsets `ptr` by `offset` bytes. Like `pointer::offset`, the result should be in bounds of
+    /// (or one byte past the end of) the allocation that `ptr` points into.
+    // FIXME: We don't keep the provenance of pointers, so a pointer to the start of an allocation
+    // is also considered to be one past the end of the previous allocation.
+    fn offset_ptr(&self, ptr: usize, offset: i128) -> Result<usize> {
+        let result = ptr as i128 + offset;
+        let in_bounds = |(&start, &size): (&usize, &usize)| {
+            let range = start as i128..=(start + size) as i128;
+            range.contains(&(ptr as i128)) && range.contains(&result)
+        };
+        // The allocation starting at `ptr` and the one before it are the only ones containing it.
+        if offset != 0 && !self.allocations.range(..=ptr).rev().take(2).any(in_bounds) {
+            return Err(MirEvalError::UndefinedBehavior("out of bounds pointer arithmetic"));
+        }
+        Ok(result as usize)
     }
 
     pub fn interpret_mir_with_no_arg(&mut self, body: &MirBody) -> Result<Vec<u8>> {
-        self.interpret_mir(&body, vec![].into_iter(), Substitution::empty(Interner))
+        self.interpret_mir(body, vec![].into_iter(), &Substitution::empty(Interner), None)
     }
 
     fn detect_lang_function(&self, def: FunctionId) -> Option<LangItem> {
@@ -1208,6 +2118,74 @@ impl Evaluator<'_> {
         None
     }
 
+    fn is_track_caller(&self, def: FunctionId) -> bool {
+        self.db.function_data(def).attrs.by_key("track_caller").exists()
+    }
+
+    /// The location which a `#[track_caller]` function called by `frame`, or `frame` itself if it
+    /// is `#[track_caller]`, reports its panics at. Like in rustc, this is the call being executed
+    /// by the closest frame which is not `#[track_caller]`.
+    fn caller_location(&self, frame: &Locals<'_>) -> Option<PanicLocation> {
+        let mut frame = frame;
+        while frame.track_caller {
+            frame = frame.caller?;
+        }
+        self.span_location(frame.body.owner, frame.call_span.get())
+    }
+
+    /// The location of `span` of `owner` in its file, which for the calls of methods is the one of
+    /// the method name, like in rustc.
+    fn span_location(&self, owner: DefWithBodyId, span: MirSpan) -> Option<PanicLocation> {
+        let (_, source_map) = self.db.body_with_source_map(owner);
+        let ptr = match span {
+            MirSpan::ExprId(e) => source_map.expr_syntax(e).ok()?.map(SyntaxNodePtr::from),
+            MirSpan::PatId(p) => source_map
+                .pat_syntax(p)
+                .ok()?
+                .map(|it| it.either(SyntaxNodePtr::from, SyntaxNodePtr::from)),
+            MirSpan::Unknown => return None,
+        };
+        let node = ptr.value.to_node(&ptr.file_syntax(self.db.upcast()));
+        let node = match ast::MethodCallExpr::cast(node.clone()).and_then(|it| it.name_ref()) {
+            Some(name) => name.syntax().clone(),
+            None => node,
+        };
+        let range = InFile::new(ptr.file_id, &node).original_file_range(self.db.upcast());
+        let text = self.db.file_text(range.file_id);
+        let before = text.get(..usize::from(range.range.start()))?;
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().map_or(0, |it| it.chars().count()) + 1;
+        Some(PanicLocation { file_id: range.file_id, line: line as u32, column: column as u32 })
+    }
+
+    /// Allocates the `core::panic::Location` of `location`, which lives as long as the evaluation.
+    fn allocate_panic_location(
+        &mut self,
+        location: &PanicLocation,
+        locals: &Locals<'_>,
+    ) -> Result<Address> {
+        let Some(location_struct) = self
+            .db
+            .lang_item(self.crate_id, LangItem::PanicLocation)
+            .and_then(|x| x.as_struct())
+        else {
+            not_supported!("core::panic::Location not found");
+        };
+        let ty =
+            TyKind::Adt(chalk_ir::AdtId(location_struct.into()), Substitution::empty(Interner))
+                .intern(Interner);
+        let size = self.size_of_sized(&ty, locals, "panic location")?;
+        let addr = self.heap_allocate(size)?;
+        // FIXME: The paths of files are only known to the IDE, so the file name is left empty.
+        let (file, _) = self.field_by_name(addr, &ty, "file")?;
+        self.write_memory(file, &[addr.to_bytes(), 0usize.to_le_bytes().to_vec()].concat())?;
+        let (line, _) = self.field_by_name(addr, &ty, "line")?;
+        self.write_memory(line, &location.line.to_le_bytes())?;
+        let (column, _) = self.field_by_name(addr, &ty, "col")?;
+        self.write_memory(column, &location.column.to_le_bytes())?;
+        Ok(addr)
+    }
+
     fn detect_fn_trait(&self, def: FunctionId) -> Option<FnTrait> {
         use LangItem::*;
         let ItemContainerId::TraitId(parent) = self.db.lookup_intern_function(def).container else {
@@ -1253,6 +2231,14 @@ impl Evaluator<'_> {
             }
             _ => (),
         }
+        // Function pointers hold ids of the `vtable_map` of this evaluator, which are only
+        // meaningful with the types they stand for.
+        let mut offsets = vec![];
+        self.fn_pointer_offsets(ty, bytes, 0, &mut offsets, locals)?;
+        for offset in offsets {
+            let id = from_bytes!(usize, &bytes[offset..offset + self.ptr_size()]);
+            mm.fn_pointers.insert(id, self.vtable_map.ty(id)?.clone());
+        }
         Ok(mm)
     }
 
@@ -1288,6 +2274,28 @@ impl Evaluator<'_> {
         Ok(())
     }
 
+    /// Replaces the ids of the function pointers in the constant value `bytes`, which were
+    /// assigned by the evaluator that created `memory_map`, with ids of this evaluator.
+    fn patch_fn_pointers(
+        &mut self,
+        memory_map: &MemoryMap,
+        addr: Address,
+        bytes: &[u8],
+        ty: &Ty,
+        locals: &Locals<'_>,
+    ) -> Result<()> {
+        let ptr_size = self.ptr_size();
+        let mut offsets = vec![];
+        self.fn_pointer_offsets(ty, bytes, 0, &mut offsets, locals)?;
+        for offset in offsets {
+            let id = from_bytes!(usize, &bytes[offset..offset + ptr_size]);
+            let fn_ty = memory_map.fn_pointers.get(&id).ok_or(MirEvalError::InvalidVTableId(id))?;
+            let id = self.vtable_map.id(fn_ty.clone());
+            self.write_memory(addr.offset(offset), &id.to_le_bytes()[..ptr_size])?;
+        }
+        Ok(())
+    }
+
     fn exec_intrinsic(
         &mut self,
         as_str: &str,
@@ -1297,6 +2305,13 @@ impl Evaluator<'_> {
         locals: &Locals<'_>,
     ) -> Result<()> {
         match as_str {
+            "caller_location" => {
+                let Some(location) = self.caller_location(locals) else {
+                    not_supported!("caller location of a call without a source");
+                };
+                let addr = self.allocate_panic_location(&location, locals)?;
+                destination.write_from_bytes(self, &addr.to_bytes())
+            }
             "size_of" => {
                 let Some(ty) = generic_args.as_slice(Interner).get(0).and_then(|x| x.ty(Interner)) else {
                     return Err(MirEvalError::TypeError("size_of generic arg is not provided"));
@@ -1336,10 +2351,15 @@ impl Evaluator<'_> {
                 let Some(ty) = generic_args.as_slice(Interner).get(0).and_then(|x| x.ty(Interner)) else {
                     return Err(MirEvalError::TypeError("offset generic arg is not provided"));
                 };
-                let ptr = u128::from_le_bytes(pad16(ptr.get(self)?, false));
-                let offset = u128::from_le_bytes(pad16(offset.get(self)?, false));
-                let size = self.size_of_sized(ty, locals, "offset ptr type")? as u128;
-                let ans = ptr + offset * size;
+                let is_signed = matches!(offset.ty.as_builtin(), Some(BuiltinType::Int(_)));
+                let ptr = from_bytes!(usize, ptr.get(self)?);
+                let offset = i128::from_le_bytes(pad16(offset.get(self)?, is_signed));
+                let size = self.size_of_sized(ty, locals, "offset ptr type")? as i128;
+                let ans = if as_str == "offset" {
+                    self.offset_ptr(ptr, offset * size)?
+                } else {
+                    (ptr as i128).wrapping_add(offset.wrapping_mul(size)) as usize
+                };
                 destination.write_from_bytes(self, &ans.to_le_bytes()[0..destination.size])
             }
             "assert_inhabited" | "assert_zero_valid" | "assert_uninit_valid" => {
@@ -1360,23 +2380,163 @@ impl Evaluator<'_> {
                 let [tuple, const_fn, _] = args else {
                     return Err(MirEvalError::TypeError("const_eval_select args are not provided"));
                 };
-                let mut args = vec![const_fn.clone()];
-                let TyKind::Tuple(_, fields) = tuple.ty.kind(Interner) else {
-                    return Err(MirEvalError::TypeError("const_eval_select arg[0] is not a tuple"));
-                };
-                let layout = self.layout(&tuple.ty)?;
-                for (i, field) in fields.iter(Interner).enumerate() {
-                    let field = field.assert_ty_ref(Interner).clone();
-                    let offset = layout.fields.offset(i).bytes_usize();
-                    let addr = tuple.interval.addr.offset(offset);
-                    args.push(IntervalAndTy::new(addr, field, self, locals)?);
-                }
-                self.exec_fn_trait(&args, destination, locals)
+                self.exec_fn_trait(&[const_fn.clone(), tuple.clone()], destination, locals)
             }
             _ => not_supported!("unknown intrinsic {as_str}"),
         }
     }
 
+    /// Drops the value of type `ty` at `addr`, by calling its `Drop` impl, if any, and then
+    /// dropping its fields.
+    fn run_drop_glue(&mut self, addr: Address, ty: &Ty, locals: &Locals<'_>) -> Result<()> {
+        if !self.db.needs_drop(ty.clone(), self.trait_env.clone()) {
+            return Ok(());
+        }
+        self.run_drop_impl(addr, ty, locals)?;
+        let size = self.size_of_sized(ty, locals, "dropped value")?;
+        let bytes = self.read_memory(addr, size)?.to_vec();
+        // FIXME: drop the values behind boxes and trait objects.
+        for (offset, field_ty) in self.value_fields(ty, &bytes, locals)? {
+            if let TyKind::Adt(adt, _) = field_ty.kind(Interner) {
+                if is_manually_drop(self.db, adt.0, self.crate_id) {
+                    continue;
+                }
+            }
+            self.run_drop_glue(addr.offset(offset), &field_ty, locals)?;
+        }
+        Ok(())
+    }
+
+    /// The offsets and the types of the fields of the value of type `ty` in `bytes`, which are the
+    /// fields of the active variant for enums, and the elements for arrays.
+    fn value_fields(&self, ty: &Ty, bytes: &[u8], locals: &Locals<'_>) -> Result<Vec<(usize, Ty)>> {
+        Ok(match ty.kind(Interner) {
+            TyKind::Adt(adt, subst) => {
+                let layout = self.layout_adt(adt.0, subst.clone())?;
+                let variant: VariantId = match adt.0 {
+                    AdtId::StructId(s) => s.into(),
+                    // It isn't known which field of a union is in use.
+                    AdtId::UnionId(_) => return Ok(vec![]),
+                    AdtId::EnumId(e) => {
+                        let discriminant = self.compute_discriminant(ty.clone(), bytes)?;
+                        let enum_data = self.db.enum_data(e);
+                        let mut variants = enum_data.variants.iter().map(|(id, _)| id);
+                        let Some(local_id) = variants.find(|&local_id| {
+                            let variant = EnumVariantId { parent: e, local_id };
+                            self.db.const_eval_discriminant(variant) == Ok(discriminant)
+                        }) else {
+                            return Err(MirEvalError::TypeError("enum with an invalid discriminant"));
+                        };
+                        EnumVariantId { parent: e, local_id }.into()
+                    }
+                };
+                let variant_layout = match (&layout.variants, variant) {
+                    (Variants::Multiple { variants, .. }, VariantId::EnumVariantId(v)) => {
+                        &variants[RustcEnumVariantIdx(v.local_id)]
+                    }
+                    _ => &layout,
+                };
+                let fields = self.db.field_types(variant);
+                fields
+                    .iter()
+                    .map(|(id, field_ty)| {
+                        let offset =
+                            variant_layout.fields.offset(u32::from(id.into_raw()) as usize);
+                        (offset.bytes_usize(), field_ty.clone().substitute(Interner, subst))
+                    })
+                    .collect()
+            }
+            TyKind::Tuple(_, subst) => {
+                let layout = self.layout(ty)?;
+                subst
+                    .iter(Interner)
+                    .enumerate()
+                    .map(|(i, field_ty)| {
+                        let offset = layout.fields.offset(i).bytes_usize();
+                        (offset, field_ty.assert_ty_ref(Interner).clone())
+                    })
+                    .collect()
+            }
+            TyKind::Closure(c, _) => {
+                let layout = self.layout(ty)?;
+                closure_captures(self.db, *c)
+                    .0
+                    .iter()
+                    .enumerate()
+                    .map(|(i, capture)| (layout.fields.offset(i).bytes_usize(), capture.ty.clone()))
+                    .collect()
+            }
+            TyKind::Array(inner, len) => {
+                let len = try_const_usize(len)
+                    .ok_or(MirEvalError::TypeError("array length is not a concrete value"))?;
+                let size = self.size_of_sized(inner, locals, "array inner type")?;
+                (0..len as usize).map(|i| (i * size, inner.clone())).collect()
+            }
+            _ => vec![],
+        })
+    }
+
+    /// Pushes the offsets of the function pointers in the value of type `ty` in `bytes`, shifted
+    /// by `base`, to `offsets`.
+    fn fn_pointer_offsets(
+        &self,
+        ty: &Ty,
+        bytes: &[u8],
+        base: usize,
+        offsets: &mut Vec<usize>,
+        locals: &Locals<'_>,
+    ) -> Result<()> {
+        fn may_contain_fn_pointer(ty: &Ty) -> bool {
+            match ty.kind(Interner) {
+                TyKind::Array(inner, _) => may_contain_fn_pointer(inner),
+                TyKind::Scalar(_) | TyKind::Str | TyKind::Ref(..) | TyKind::Raw(..) => false,
+                _ => true,
+            }
+        }
+        if !may_contain_fn_pointer(ty) {
+            return Ok(());
+        }
+        if let TyKind::Function(_) = ty.kind(Interner) {
+            offsets.push(base);
+            return Ok(());
+        }
+        for (offset, field_ty) in self.value_fields(ty, bytes, locals)? {
+            let size = self.size_of_sized(&field_ty, locals, "field type")?;
+            let field_bytes = bytes
+                .get(offset..offset + size)
+                .ok_or(MirEvalError::TypeError("field out of the bounds of its value"))?;
+            self.fn_pointer_offsets(&field_ty, field_bytes, base + offset, offsets, locals)?;
+        }
+        Ok(())
+    }
+
+    /// Calls the `Drop` impl of `ty` on the value at `addr`, if `ty` has one.
+    fn run_drop_impl(&mut self, addr: Address, ty: &Ty, locals: &Locals<'_>) -> Result<()> {
+        let Some(drop_fn) = self
+            .db
+            .lang_item(self.crate_id, LangItem::Drop)
+            .and_then(|x| x.as_trait())
+            .and_then(|x| self.db.trait_data(x).method_by_name(&name![drop]))
+        else {
+            return Ok(());
+        };
+        let generic_args = Substitution::from1(Interner, ty.clone());
+        let (imp, _) =
+            lookup_impl_method(self.db, self.trait_env.clone(), drop_fn, generic_args.clone());
+        if imp == drop_fn {
+            // There is no `Drop` impl for this type.
+            return Ok(());
+        }
+        let ptr_size = self.ptr_size();
+        let ptr = self.heap_allocate(ptr_size)?;
+        self.write_memory(ptr, &addr.to_bytes())?;
+        let arg = IntervalAndTy {
+            interval: Interval::new(ptr, ptr_size),
+            ty: TyKind::Ref(Mutability::Mut, static_lifetime(), ty.clone()).intern(Interner),
+        };
+        self.exec_fn_with_args(drop_fn, &[arg], generic_args, locals, Interval::new(ptr, 0))
+    }
+
     fn exec_fn_pointer(
         &mut self,
         bytes: Interval,
@@ -1386,14 +2546,51 @@ impl Evaluator<'_> {
     ) -> Result<()> {
         let id = from_bytes!(usize, bytes.get(self)?);
         let next_ty = self.vtable_map.ty(id)?.clone();
-        if let TyKind::FnDef(def, generic_args) = &next_ty.data(Interner).kind {
-            self.exec_fn_def(*def, generic_args, destination, args, &locals)?;
-        } else {
-            return Err(MirEvalError::TypeError("function pointer to non function"));
+        match &next_ty.data(Interner).kind {
+            TyKind::FnDef(def, generic_args) => {
+                self.exec_fn_def(*def, generic_args, destination, args, &locals)?;
+            }
+            TyKind::Closure(closure, _) => {
+                // Only closures without captures are coerced to function pointers, so the closure
+                // itself is a ZST.
+                let closure_data = Interval::new(bytes.addr, 0);
+                self.exec_closure(*closure, closure_data, destination, args, locals)?;
+            }
+            _ => return Err(MirEvalError::TypeError("function pointer to non function")),
         }
         Ok(())
     }
 
+    /// Executes the body of a closure whose value, which holds its captures, is `closure_data`.
+    fn exec_closure(
+        &mut self,
+        closure: ClosureId,
+        closure_data: Interval,
+        destination: Interval,
+        args: &[IntervalAndTy],
+        locals: &Locals<'_>,
+    ) -> Result<()> {
+        let mir_body = self
+            .mir_body_for_closure(closure)
+            .map_err(|e| MirEvalError::MirLowerErrorForClosure(closure, e))?;
+        // FIXME: The closure body shares the generic parameters of its enclosing body, but we only
+        // know their values when it is called from that same body.
+        let empty_subst = Substitution::empty(Interner);
+        let subst = if mir_body.owner == locals.body.owner { locals.subst } else { &empty_subst };
+        // The closure takes itself as the first parameter, by reference unless it is `FnOnce`.
+        let env = match closure_captures(self.db, closure).1 {
+            FnTrait::FnOnce => closure_data.get(self)?.to_owned(),
+            FnTrait::FnMut | FnTrait::Fn => closure_data.addr.to_bytes(),
+        };
+        let arg_bytes = iter::once(Ok(env))
+            .chain(args.iter().map(|x| Ok(x.get(self)?.to_owned())))
+            .collect::<Result<Vec<_>>>()?;
+        let result =
+            self.interpret_mir(&mir_body, arg_bytes.into_iter(), subst, Some((locals, false)))?;
+        destination.write_from_bytes(self, &result)?;
+        Ok(())
+    }
+
     fn exec_fn_def(
         &mut self,
         def: FnDefId,
@@ -1447,18 +2644,7 @@ impl Evaluator<'_> {
         destination: Interval,
     ) -> Result<()> {
         let function_data = self.db.function_data(def);
-        let is_intrinsic = match &function_data.abi {
-            Some(abi) => *abi == Interned::new_str("rust-intrinsic"),
-            None => match def.lookup(self.db.upcast()).container {
-                hir_def::ItemContainerId::ExternBlockId(block) => {
-                    let id = block.lookup(self.db.upcast()).id;
-                    id.item_tree(self.db.upcast())[id.value].abi.as_deref()
-                        == Some("rust-intrinsic")
-                }
-                _ => false,
-            },
-        };
-        if is_intrinsic {
+        if is_intrinsic(self.db, def) {
             return self.exec_intrinsic(
                 function_data.name.as_text().unwrap_or_default().as_str(),
                 args,
@@ -1470,7 +2656,7 @@ impl Evaluator<'_> {
         let arg_bytes =
             args.iter().map(|x| Ok(x.get(&self)?.to_owned())).collect::<Result<Vec<_>>>()?;
         let result = if let Some(x) = self.detect_lang_function(def) {
-            self.exec_lang_item(x, &arg_bytes)?
+            self.exec_lang_item(x, args, &arg_bytes, locals)?
         } else {
             if let Some(self_ty_idx) =
                 is_dyn_method(self.db, self.trait_env.clone(), def, generic_args.clone())
@@ -1510,22 +2696,76 @@ impl Evaluator<'_> {
                 lookup_impl_method(self.db, self.trait_env.clone(), def, generic_args);
             let generic_args = self.subst_filler(&generic_args, &locals);
             let def = imp.into();
-            let mir_body =
-                self.db.mir_body(def).map_err(|e| MirEvalError::MirLowerError(imp, e))?;
-            self.interpret_mir(&mir_body, arg_bytes.iter().cloned(), generic_args)
-                .map_err(|e| MirEvalError::InFunction(imp, Box::new(e)))?
+            let mir_body = self.mir_body(def).map_err(|e| MirEvalError::MirLowerError(imp, e))?;
+            let track_caller = self.is_track_caller(imp);
+            // The location of the call is an implicit argument of `#[track_caller]` functions.
+            let memo_key = (!track_caller
+                && self.is_memoizable_call(&mir_body, args, &generic_args))
+            .then(|| (imp, generic_args.clone(), arg_bytes.clone()));
+            if let Some(result) = memo_key.as_ref().and_then(|key| self.call_memo.get(key)) {
+                result.clone()
+            } else {
+                let caller_is_pure = mem::replace(&mut self.frame_is_pure, true);
+                let caller = Some((locals, track_caller));
+                let result = self
+                    .interpret_mir(&mir_body, arg_bytes.into_iter(), &generic_args, caller)
+                    .map_err(|e| MirEvalError::InFunction(imp, Box::new(e), None));
+                let is_pure = self.frame_is_pure;
+                self.frame_is_pure = caller_is_pure && is_pure;
+                let result = result?;
+                if let Some(key) = memo_key.filter(|_| is_pure) {
+                    self.call_memo.insert(key, result.clone());
+                }
+                result
+            }
         };
         destination.write_from_bytes(self, &result)?;
         Ok(())
     }
 
+    /// Whether the result of calling `body` with `args` can be memoized, which is the case if the
+    /// arguments and the result are plain data, so that the call can't observe or change memory
+    /// other than its own. Whether the call doesn't access other state is only known after it.
+    fn is_memoizable_call(
+        &self,
+        body: &MirBody,
+        args: &[IntervalAndTy],
+        generic_args: &Substitution,
+    ) -> bool {
+        if !self.memoize_calls || !args.iter().all(|x| self.is_plain_data(&x.ty)) {
+            return false;
+        }
+        let return_ty = &body.locals[return_slot()].ty;
+        let Ok(return_ty) = self.ty_filler(return_ty, generic_args, body.owner) else {
+            return false;
+        };
+        self.is_plain_data(&return_ty)
+    }
+
+    /// Executes `Fn::call`, `FnMut::call_mut` or `FnOnce::call_once`. Like in rustc, the arguments of
+    /// the callee are passed as a single tuple after the callee itself.
     fn exec_fn_trait(
         &mut self,
         args: &[IntervalAndTy],
         destination: Interval,
         locals: &Locals<'_>,
     ) -> Result<()> {
-        let func = args.get(0).ok_or(MirEvalError::TypeError("fn trait with no arg"))?;
+        let [func, tuple] = args else {
+            return Err(MirEvalError::TypeError("fn trait call should have two arguments"));
+        };
+        let TyKind::Tuple(_, fields) = tuple.ty.kind(Interner) else {
+            return Err(MirEvalError::TypeError("arguments of fn trait call are not a tuple"));
+        };
+        let layout = self.layout(&tuple.ty)?;
+        let args = fields
+            .iter(Interner)
+            .enumerate()
+            .map(|(i, field)| {
+                let field = field.assert_ty_ref(Interner).clone();
+                let offset = layout.fields.offset(i).bytes_usize();
+                IntervalAndTy::new(tuple.interval.addr.offset(offset), field, self, locals)
+            })
+            .collect::<Result<Vec<_>>>()?;
         let mut func_ty = func.ty.clone();
         let mut func_data = func.interval;
         while let TyKind::Ref(_, _, z) = func_ty.kind(Interner) {
@@ -1541,27 +2781,45 @@ impl Evaluator<'_> {
         }
         match &func_ty.data(Interner).kind {
             TyKind::FnDef(def, subst) => {
-                self.exec_fn_def(*def, subst, destination, &args[1..], locals)?;
+                self.exec_fn_def(*def, subst, destination, &args, locals)?;
             }
             TyKind::Function(_) => {
-                self.exec_fn_pointer(func_data, destination, &args[1..], locals)?;
+                self.exec_fn_pointer(func_data, destination, &args, locals)?;
+            }
+            TyKind::Closure(closure, _) => {
+                self.exec_closure(*closure, func_data, destination, &args, locals)?;
             }
             x => not_supported!("Call FnTrait methods with type {x:?}"),
         }
         Ok(())
     }
 
-    fn exec_lang_item(&self, x: LangItem, args: &[Vec<u8>]) -> Result<Vec<u8>> {
+    fn exec_lang_item(
+        &self,
+        x: LangItem,
+        args: &[IntervalAndTy],
+        arg_bytes: &[Vec<u8>],
+        locals: &Locals<'_>,
+    ) -> Result<Vec<u8>> {
         use LangItem::*;
-        let mut args = args.iter();
         match x {
-            // FIXME: we want to find the panic message from arguments, but it wouldn't work
-            // currently even if we do that, since macro expansion of panic related macros
-            // is dummy.
-            PanicFmt | BeginPanic => Err(MirEvalError::Panic("<format-args>".to_string())),
+            PanicFmt => {
+                let message = args
+                    .first()
+                    .and_then(|arguments| self.render_fmt_arguments(arguments).ok())
+                    .unwrap_or_else(|| "<format-args>".to_string());
+                Err(MirEvalError::Panic { message, location: self.caller_location(locals) })
+            }
+            BeginPanic => {
+                let message = args
+                    .first()
+                    .map(|payload| self.render_panic_payload(payload))
+                    .unwrap_or_else(|| "<format-args>".to_string());
+                Err(MirEvalError::Panic { message, location: self.caller_location(locals) })
+            }
             SliceLen => {
-                let arg = args
-                    .next()
+                let arg = arg_bytes
+                    .first()
                     .ok_or(MirEvalError::TypeError("argument of <[T]>::len() is not provided"))?;
                 let ptr_size = arg.len() / 2;
                 Ok(arg[ptr_size..].into())
@@ -1571,8 +2829,76 @@ impl Evaluator<'_> {
     }
 }
 
+/// Computes `lhs op rhs` for integers of the size of `lhs`, wrapping around on overflow, and
+/// whether it overflowed, like `Rvalue::CheckedBinaryOp` does. The result is in the low bytes.
+fn checked_int_op(
+    op: &BinOp,
+    lhs: &[u8],
+    rhs: &[u8],
+    is_signed: bool,
+    is_rhs_signed: bool,
+) -> Result<([u8; 16], bool)> {
+    let bits = lhs.len() as u32 * 8;
+    let l = i128::from_le_bytes(pad16(lhs, is_signed));
+    let r = i128::from_le_bytes(pad16(rhs, is_rhs_signed));
+    let (value, overflowed) = match op {
+        BinOp::Shl | BinOp::Shr => {
+            let overflowed = !(0..bits as i128).contains(&r);
+            // The shift amount is masked to the bit width of the type, like `wrapping_shl` does.
+            let amount = r.rem_euclid(bits as i128) as u32;
+            let value = match (op, is_signed) {
+                (BinOp::Shl, _) => l << amount,
+                (_, true) => l >> amount,
+                (_, false) => ((l as u128) >> amount) as i128,
+            };
+            (value, overflowed)
+        }
+        BinOp::Add | BinOp::Sub | BinOp::Mul if is_signed => {
+            let (value, overflowed) = match op {
+                BinOp::Add => l.overflowing_add(r),
+                BinOp::Sub => l.overflowing_sub(r),
+                _ => l.overflowing_mul(r),
+            };
+            let unused = 128 - bits;
+            (value, overflowed || (value << unused) >> unused != value)
+        }
+        BinOp::Add | BinOp::Sub | BinOp::Mul => {
+            let (l, r) = (l as u128, r as u128);
+            let (value, overflowed) = match op {
+                BinOp::Add => l.overflowing_add(r),
+                BinOp::Sub => l.overflowing_sub(r),
+                _ => l.overflowing_mul(r),
+            };
+            (value as i128, overflowed || (bits < 128 && value >> bits != 0))
+        }
+        _ => not_supported!("checked {op:?}"),
+    };
+    Ok((value.to_le_bytes(), overflowed))
+}
+
+/// Compares the scalars of type `ty` in `l` and `r`, like their `PartialOrd` impls.
+fn compare_scalars(l: &[u8], r: &[u8], ty: BuiltinType) -> Result<Option<Ordering>> {
+    fn float_bytes<const N: usize>(x: &[u8]) -> Result<[u8; N]> {
+        x.try_into().map_err(|_| MirEvalError::TypeError("float of an invalid size"))
+    }
+    Ok(match ty {
+        BuiltinType::Int(_) => {
+            Some(i128::from_le_bytes(pad16(l, true)).cmp(&i128::from_le_bytes(pad16(r, true))))
+        }
+        BuiltinType::Float(BuiltinFloat::F32) => {
+            f32::from_le_bytes(float_bytes(l)?).partial_cmp(&f32::from_le_bytes(float_bytes(r)?))
+        }
+        BuiltinType::Float(BuiltinFloat::F64) => {
+            f64::from_le_bytes(float_bytes(l)?).partial_cmp(&f64::from_le_bytes(float_bytes(r)?))
+        }
+        BuiltinType::Uint(_) | BuiltinType::Char | BuiltinType::Bool | BuiltinType::Str => {
+            Some(u128::from_le_bytes(pad16(l, false)).cmp(&u128::from_le_bytes(pad16(r, false))))
+        }
+    })
+}
+
 pub fn pad16(x: &[u8], is_signed: bool) -> [u8; 16] {
-    let is_negative = is_signed && x.last().unwrap_or(&0) > &128;
+    let is_negative = is_signed && x.last().unwrap_or(&0) >= &128;
     let fill_with = if is_negative { 255 } else { 0 };
     x.iter()
         .copied()
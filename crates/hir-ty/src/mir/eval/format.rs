@@ -0,0 +1,380 @@
+//! Rendering of the `fmt::Arguments` of panics into their messages. We don't run the formatting
+//! machinery of `core`, which needs a lot of things we don't support, but render the values of the
+//! primitive types and of the types with a derived `Debug` impl ourselves.
+
+use std::fmt::Write;
+
+use chalk_ir::{FloatTy, Scalar};
+use hir_def::{
+    adt::StructKind, layout::RustcEnumVariantIdx, layout::Variants, resolver::HasResolver, AdtId,
+    EnumVariantId, FunctionId, ItemContainerId, Lookup, VariantId,
+};
+use hir_expand::mod_path::path;
+
+use crate::{
+    consteval::try_const_usize, display::HirDisplay, mapping::from_chalk,
+    method_resolution::TyFingerprint, CallableDefId, Interner, Substitution, Ty, TyExt, TyKind,
+};
+
+use super::{Address, Evaluator, IntervalAndTy, MirEvalError, Result};
+
+/// How deep into nested values we go, to keep the messages short.
+const MAX_DEPTH: usize = 4;
+
+#[derive(Debug, Clone, Copy)]
+enum FormatTrait {
+    Display,
+    /// The `fmt` method of the `Debug` trait, to find the impls of the nested values.
+    Debug(FunctionId),
+}
+
+impl Evaluator<'_> {
+    /// Renders the `fmt::Arguments` passed to `panic_fmt`. The values which we can't render are
+    /// replaced by a `<value of type T>` placeholder.
+    pub(super) fn render_fmt_arguments(&self, arguments: &IntervalAndTy) -> Result<String> {
+        let addr = arguments.interval.addr;
+        let (pieces, pieces_ty) = self.field_by_name(addr, &arguments.ty, "pieces")?;
+        let (args, args_ty) = self.field_by_name(addr, &arguments.ty, "args")?;
+        let (pieces, _) = self.slice_elements(pieces, &pieces_ty)?;
+        let (args, arg_ty) = self.slice_elements(args, &args_ty)?;
+        if pieces.len() < args.len() {
+            // The expansion of `format_args!` doesn't know where the arguments go.
+            return Err(MirEvalError::TypeError("format arguments without pieces"));
+        }
+        let mut result = String::new();
+        for (i, piece) in pieces.into_iter().enumerate() {
+            result += &self.read_str(piece)?;
+            if let Some(&arg) = args.get(i) {
+                let (value, value_ty) = self.field_by_name(arg, &arg_ty, "value")?;
+                let (formatter, _) = self.field_by_name(arg, &arg_ty, "formatter")?;
+                let value = Address::from_bytes(self.read_memory(value, self.ptr_size())?)?;
+                let formatter = self.read_memory(formatter, self.ptr_size())?;
+                match self.formatter_trait(self.vtable_map.ty_of_bytes(formatter)?) {
+                    Some((Some(trait_), ty)) => {
+                        self.render_value(value, &ty, trait_, 0, &mut result)
+                    }
+                    Some((None, ty)) => self.render_placeholder(&ty, &mut result),
+                    None => self.render_placeholder(&value_ty, &mut result),
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Renders the payload of `begin_panic`, which is usually a `&str`.
+    pub(super) fn render_panic_payload(&self, payload: &IntervalAndTy) -> String {
+        let mut result = String::new();
+        self.render_value(payload.interval.addr, &payload.ty, FormatTrait::Display, 0, &mut result);
+        result
+    }
+
+    /// The trait and the self type of a formatting function, e.g. `<i32 as Display>::fmt`. The
+    /// trait is `None` for methods of other traits.
+    fn formatter_trait(&self, formatter: &Ty) -> Option<(Option<FormatTrait>, Ty)> {
+        let TyKind::FnDef(def, subst) = formatter.kind(Interner) else { return None };
+        let CallableDefId::FunctionId(f) = from_chalk(self.db, *def) else { return None };
+        // `format_args!` refers to the methods of the traits, which are resolved to the impls only
+        // when called.
+        let ItemContainerId::TraitId(trait_) = f.lookup(self.db.upcast()).container else {
+            return None;
+        };
+        let self_ty = subst.at(Interner, 0).ty(Interner)?.clone();
+        let def_map = self.db.crate_def_map(self.crate_id);
+        let resolver = def_map.module_id(def_map.root()).resolver(self.db.upcast());
+        let is_trait = |path| resolver.resolve_known_trait(self.db.upcast(), &path) == Some(trait_);
+        let trait_ = if is_trait(path![core::fmt::Display]) {
+            Some(FormatTrait::Display)
+        } else if is_trait(path![core::fmt::Debug]) {
+            Some(FormatTrait::Debug(f))
+        } else {
+            None
+        };
+        Some((trait_, self_ty))
+    }
+
+    fn render_value(
+        &self,
+        addr: Address,
+        ty: &Ty,
+        trait_: FormatTrait,
+        depth: usize,
+        out: &mut String,
+    ) {
+        let len = out.len();
+        if depth > MAX_DEPTH || self.try_render_value(addr, ty, trait_, depth, out).is_err() {
+            out.truncate(len);
+            self.render_placeholder(ty, out);
+        }
+    }
+
+    fn try_render_value(
+        &self,
+        addr: Address,
+        ty: &Ty,
+        trait_: FormatTrait,
+        depth: usize,
+        out: &mut String,
+    ) -> Result<()> {
+        let is_debug = matches!(trait_, FormatTrait::Debug(_));
+        match ty.kind(Interner) {
+            TyKind::Scalar(scalar) => {
+                let size = self.layout(ty)?.size.bytes_usize();
+                let bytes = self.read_memory(addr, size)?;
+                let mut padded = [0; 16];
+                padded[..size].copy_from_slice(bytes);
+                match scalar {
+                    Scalar::Bool => write!(out, "{}", bytes[0] != 0),
+                    Scalar::Char => {
+                        let c = char::from_u32(u32::from_le_bytes(padded[..4].try_into().unwrap()))
+                            .ok_or(MirEvalError::UndefinedBehavior("invalid char"))?;
+                        if is_debug {
+                            write!(out, "{c:?}")
+                        } else {
+                            write!(out, "{c}")
+                        }
+                    }
+                    Scalar::Int(_) => {
+                        let shift = 128 - 8 * size as u32;
+                        write!(out, "{}", (i128::from_le_bytes(padded) << shift) >> shift)
+                    }
+                    Scalar::Uint(_) => write!(out, "{}", u128::from_le_bytes(padded)),
+                    Scalar::Float(FloatTy::F32) => {
+                        let f = f32::from_le_bytes(padded[..4].try_into().unwrap());
+                        if is_debug {
+                            write!(out, "{f:?}")
+                        } else {
+                            write!(out, "{f}")
+                        }
+                    }
+                    Scalar::Float(FloatTy::F64) => {
+                        let f = f64::from_le_bytes(padded[..8].try_into().unwrap());
+                        if is_debug {
+                            write!(out, "{f:?}")
+                        } else {
+                            write!(out, "{f}")
+                        }
+                    }
+                }
+                .unwrap();
+            }
+            TyKind::Ref(_, _, inner) => {
+                let ptr_size = self.ptr_size();
+                match inner.kind(Interner) {
+                    TyKind::Str => {
+                        let s = self.read_str(addr)?;
+                        if is_debug {
+                            write!(out, "{s:?}").unwrap();
+                        } else {
+                            *out += &s;
+                        }
+                    }
+                    TyKind::Dyn(_) => {
+                        let fat = self.read_memory(addr, 2 * ptr_size)?;
+                        let value = Address::from_bytes(&fat[..ptr_size])?;
+                        let ty = self.vtable_map.ty_of_bytes(&fat[ptr_size..])?;
+                        self.try_render_value(value, ty, trait_, depth, out)?;
+                    }
+                    TyKind::Slice(_) if is_debug => {
+                        let (elements, element_ty) = self.slice_elements(addr, ty)?;
+                        self.render_debug_list(elements, &element_ty, trait_, depth, out);
+                    }
+                    TyKind::Slice(_) => {
+                        return Err(MirEvalError::TypeError("no Display for slices"))
+                    }
+                    _ => {
+                        let value = Address::from_bytes(self.read_memory(addr, ptr_size)?)?;
+                        self.try_render_value(value, inner, trait_, depth, out)?;
+                    }
+                }
+            }
+            TyKind::Tuple(_, subst) if is_debug => {
+                let layout = self.layout(ty)?;
+                let fields = subst.iter(Interner).enumerate().map(|(i, field_ty)| {
+                    (
+                        addr.offset(layout.fields.offset(i).bytes_usize()),
+                        field_ty.assert_ty_ref(Interner),
+                    )
+                });
+                *out += "(";
+                for (i, (field, field_ty)) in fields.enumerate() {
+                    if i != 0 {
+                        *out += ", ";
+                    }
+                    self.render_value(field, field_ty, trait_, depth + 1, out);
+                }
+                if subst.len(Interner) == 1 {
+                    *out += ",";
+                }
+                *out += ")";
+            }
+            TyKind::Array(element_ty, len) if is_debug => {
+                let len = try_const_usize(len)
+                    .ok_or(MirEvalError::TypeError("array with unknown length"))?;
+                let size = self.layout(element_ty)?.size.bytes_usize();
+                let elements = (0..len as usize).map(|i| addr.offset(i * size)).collect();
+                self.render_debug_list(elements, element_ty, trait_, depth, out);
+            }
+            &TyKind::Adt(chalk_ir::AdtId(adt), ref subst) => match trait_ {
+                FormatTrait::Debug(debug_fmt) if self.has_derived_impl(debug_fmt, ty) => {
+                    self.render_derived_debug(addr, adt, subst, trait_, depth, out)?;
+                }
+                _ => return Err(MirEvalError::TypeError("can't render the value of this type")),
+            },
+            _ => return Err(MirEvalError::TypeError("can't render the value of this type")),
+        }
+        Ok(())
+    }
+
+    /// Renders like `#[derive(Debug)]` does, e.g. `Foo { a: 1, b: 2 }`.
+    fn render_derived_debug(
+        &self,
+        addr: Address,
+        adt: AdtId,
+        subst: &Substitution,
+        trait_: FormatTrait,
+        depth: usize,
+        out: &mut String,
+    ) -> Result<()> {
+        let layout = self.layout_adt(adt, subst.clone())?;
+        let (variant, name, variant_layout) = match adt {
+            AdtId::StructId(s) => {
+                (VariantId::from(s), self.db.struct_data(s).name.clone(), &layout)
+            }
+            AdtId::EnumId(e) => {
+                let bytes = self.read_memory(addr, layout.size.bytes_usize())?;
+                let ty = TyKind::Adt(chalk_ir::AdtId(adt), subst.clone()).intern(Interner);
+                let discriminant = self.compute_discriminant(ty, bytes)?;
+                let enum_data = self.db.enum_data(e);
+                let (local_id, data) = enum_data
+                    .variants
+                    .iter()
+                    .find(|&(local_id, _)| {
+                        let id = EnumVariantId { parent: e, local_id };
+                        self.db.const_eval_discriminant(id).ok() == Some(discriminant)
+                    })
+                    .ok_or(MirEvalError::TypeError("no variant with this discriminant"))?;
+                let variant_layout = match &layout.variants {
+                    Variants::Single { .. } => &layout,
+                    Variants::Multiple { variants, .. } => &variants[RustcEnumVariantIdx(local_id)],
+                };
+                let variant = EnumVariantId { parent: e, local_id }.into();
+                (variant, data.name.clone(), variant_layout)
+            }
+            AdtId::UnionId(_) => return Err(MirEvalError::TypeError("can't render unions")),
+        };
+        write!(out, "{name}").unwrap();
+        let variant_data = variant.variant_data(self.db.upcast());
+        let field_types = self.db.field_types(variant);
+        let fields = variant_data.fields().iter().map(|(id, data)| {
+            let offset = variant_layout.fields.offset(u32::from(id.into_raw()) as usize);
+            let ty = field_types[id].clone().substitute(Interner, subst);
+            (&data.name, addr.offset(offset.bytes_usize()), ty)
+        });
+        let (start, end) = match variant_data.kind() {
+            StructKind::Record => (" { ", " }"),
+            StructKind::Tuple => ("(", ")"),
+            StructKind::Unit => return Ok(()),
+        };
+        *out += start;
+        for (i, (name, field, field_ty)) in fields.enumerate() {
+            if i != 0 {
+                *out += ", ";
+            }
+            if variant_data.kind() == StructKind::Record {
+                write!(out, "{name}: ").unwrap();
+            }
+            self.render_value(field, &field_ty, trait_, depth + 1, out);
+        }
+        *out += end;
+        Ok(())
+    }
+
+    fn render_debug_list(
+        &self,
+        elements: Vec<Address>,
+        element_ty: &Ty,
+        trait_: FormatTrait,
+        depth: usize,
+        out: &mut String,
+    ) {
+        *out += "[";
+        for (i, element) in elements.into_iter().enumerate() {
+            if i != 0 {
+                *out += ", ";
+            }
+            self.render_value(element, element_ty, trait_, depth + 1, out);
+        }
+        *out += "]";
+    }
+
+    fn render_placeholder(&self, ty: &Ty, out: &mut String) {
+        write!(out, "<value of type {}>", ty.display(self.db)).unwrap();
+    }
+
+    /// Whether the impl of the trait of `trait_fn` for `ty` comes from a builtin derive. Derived
+    /// impls have no body we could execute.
+    fn has_derived_impl(&self, trait_fn: FunctionId, ty: &Ty) -> bool {
+        let ItemContainerId::TraitId(trait_) = trait_fn.lookup(self.db.upcast()).container else {
+            return false;
+        };
+        let Some(self_ty) = TyFingerprint::for_trait_impl(ty) else { return false };
+        let impls = self.db.trait_impls_in_deps(self.trait_env.krate);
+        let result = impls.for_trait_and_self_ty(trait_, self_ty).any(|impl_| {
+            let file_id = impl_.lookup(self.db.upcast()).id.file_id();
+            file_id.is_builtin_derive(self.db.upcast()).is_some()
+        });
+        result
+    }
+
+    /// Finds the field `name` of the struct of type `ty` stored at `addr`.
+    pub(super) fn field_by_name(
+        &self,
+        addr: Address,
+        ty: &Ty,
+        name: &str,
+    ) -> Result<(Address, Ty)> {
+        let TyKind::Adt(chalk_ir::AdtId(AdtId::StructId(s)), subst) = ty.kind(Interner) else {
+            return Err(MirEvalError::TypeError("field of a non struct type"));
+        };
+        let data = self.db.struct_data(*s);
+        let (id, _) = data
+            .variant_data
+            .fields()
+            .iter()
+            .find(|(_, field)| field.name.to_smol_str() == name)
+            .ok_or(MirEvalError::TypeError("struct has no field with this name"))?;
+        let layout = self.layout_adt((*s).into(), subst.clone())?;
+        let offset = layout.fields.offset(u32::from(id.into_raw()) as usize).bytes_usize();
+        let field_ty = self.db.field_types((*s).into())[id].clone().substitute(Interner, subst);
+        Ok((addr.offset(offset), field_ty))
+    }
+
+    /// The addresses of the elements of the slice referenced by the `&[T]` stored at `addr`.
+    fn slice_elements(&self, addr: Address, ty: &Ty) -> Result<(Vec<Address>, Ty)> {
+        let Some(TyKind::Slice(element_ty)) = ty.as_reference().map(|(it, ..)| it.kind(Interner))
+        else {
+            return Err(MirEvalError::TypeError("not a reference to a slice"));
+        };
+        let ptr_size = self.ptr_size();
+        let fat = self.read_memory(addr, 2 * ptr_size)?;
+        let start = Address::from_bytes(&fat[..ptr_size])?;
+        let len = usize::from_le_bytes(pad_usize(&fat[ptr_size..]));
+        let size = self.layout(element_ty)?.size.bytes_usize();
+        Ok(((0..len).map(|i| start.offset(i * size)).collect(), element_ty.clone()))
+    }
+
+    /// Reads the `&str` stored at `addr`.
+    fn read_str(&self, addr: Address) -> Result<String> {
+        let ptr_size = self.ptr_size();
+        let fat = self.read_memory(addr, 2 * ptr_size)?;
+        let start = Address::from_bytes(&fat[..ptr_size])?;
+        let len = usize::from_le_bytes(pad_usize(&fat[ptr_size..]));
+        Ok(String::from_utf8_lossy(self.read_memory(start, len)?).into_owned())
+    }
+}
+
+fn pad_usize(bytes: &[u8]) -> [u8; std::mem::size_of::<usize>()] {
+    let mut result = [0; std::mem::size_of::<usize>()];
+    result[..bytes.len()].copy_from_slice(bytes);
+    result
+}
@@ -1,29 +1,41 @@
 //! This module generates a polymorphic MIR from a hir body
 
-use std::{iter, mem, sync::Arc};
+use std::{fmt, iter, sync::Arc};
 
-use chalk_ir::{BoundVar, ConstData, DebruijnIndex, TyKind};
+use chalk_ir::{BoundVar, CanonicalVarKinds, ConstData, DebruijnIndex, TyKind};
 use hir_def::{
     adt::{StructKind, VariantData},
     body::Body,
     expr::{
-        Array, BindingAnnotation, BindingId, ExprId, LabelId, Literal, MatchArm, Pat, PatId,
-        RecordFieldPat, RecordLitField,
+        ArithOp, Array, BindingAnnotation, BindingId, ExprId, LabelId, Literal, MatchArm, Pat,
+        PatId, RecordFieldPat, RecordLitField,
     },
     lang_item::{LangItem, LangItemTarget},
-    layout::LayoutError,
+    layout::{Layout, LayoutError},
     path::Path,
-    resolver::{resolver_for_expr, ResolveValueResult, ValueNs},
+    resolver::{HasResolver, ResolveValueResult, Resolver, ValueNs},
+    type_ref::TypeRef,
     AdtId, DefWithBodyId, EnumVariantId, HasModule, ItemContainerId, LocalFieldId, TraitId,
 };
 use hir_expand::name::Name;
 use la_arena::ArenaMap;
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 
 use crate::{
-    consteval::ConstEvalError, db::HirDatabase, display::HirDisplay, infer::TypeMismatch,
-    inhabitedness::is_ty_uninhabited_from, layout::layout_of_ty, mapping::ToChalk, static_lifetime,
-    utils::generics, Adjust, Adjustment, AutoBorrow, CallableDefId, TyBuilder, TyExt,
+    collect_placeholders,
+    consteval::ConstEvalError,
+    db::HirDatabase,
+    display::HirDisplay,
+    infer::{closure_captures, CaptureKind, CapturedItem, TypeMismatch},
+    inhabitedness::is_ty_uninhabited_from,
+    lang_items::is_box,
+    layout::layout_of_ty,
+    mapping::ToChalk,
+    method_resolution::{implements_trait, lookup_impl_const},
+    static_lifetime,
+    traits::FnTrait,
+    utils::{generics, is_intrinsic},
+    Adjust, Adjustment, AutoBorrow, CallableDefId, Canonical, ClosureId, TyBuilder, TyExt,
 };
 
 use super::*;
@@ -33,43 +45,120 @@ mod pattern_matching;
 
 use pattern_matching::AdtPatternShape;
 
+/// A loop or a labeled block being lowered, which `break` and `continue` can target.
 #[derive(Debug, Clone)]
 struct LoopBlocks {
     begin: BasicBlockId,
-    /// `None` for loops that are not terminating
+    /// `None` until a `break` or the end of a labeled block needs it, and for loops that are not
+    /// terminating.
     end: Option<BasicBlockId>,
     place: Place,
+    /// Number of drop scopes outside of the loop, which `break` and `continue` should not drop.
+    drop_scope_index: usize,
+    // FIXME: we should resolve labels in HIR lowering and always work with label id here, not
+    // with raw names.
+    label: Option<Name>,
+    /// Whether this is a labeled block, which is only the target of `break`s with its label.
+    is_block: bool,
+}
+
+/// What the `SwitchInt` of the arms lowered by `lower_match_arms_as_switch` switches on.
+enum SwitchScrutinee {
+    /// The value of the scrutinee, which is an integer, `char` or `bool` of `size` bytes.
+    Scalar { size: usize },
+    /// The discriminant of the scrutinee, which is an enum.
+    Discriminant,
+}
+
+#[derive(Debug, Default)]
+struct DropScope {
+    /// Locals of the bindings and temporaries of this scope that need to be dropped when it ends,
+    /// in declaration order.
+    locals: Vec<LocalId>,
+    /// Temporaries and bindings whose storage lives until the end of this scope, after their
+    /// drops, with the spans of their `StorageDead` statements.
+    storage: Vec<(LocalId, MirSpan)>,
 }
 
 struct MirLowerCtx<'a> {
     result: MirBody,
     owner: DefWithBodyId,
-    current_loop_blocks: Option<LoopBlocks>,
-    // FIXME: we should resolve labels in HIR lowering and always work with label id here, not
-    // with raw names.
-    labeled_loop_blocks: FxHashMap<Name, LoopBlocks>,
+    /// The loops and labeled blocks enclosing the expression being lowered, innermost last.
+    loop_blocks: Vec<LoopBlocks>,
     discr_temp: Option<Place>,
     db: &'a dyn HirDatabase,
     body: &'a Body,
     infer: &'a InferenceResult,
+    /// Resolver of the innermost block being lowered, so that items declared in blocks (e.g.
+    /// consts used in patterns) are visible.
+    resolver: Resolver,
+    drop_scopes: Vec<DropScope>,
+    panic_strategy: PanicStrategy,
+    /// The cleanup blocks created so far, by the block they continue unwinding to and the local
+    /// they drop, so that the unwind paths of locals which are alive together share their blocks.
+    cleanup_blocks: FxHashMap<(BasicBlockId, LocalId), BasicBlockId>,
+    /// The `Resume` block which ends every unwind path, and the `Abort` block for panics in drops.
+    resume_and_abort: Option<(BasicBlockId, BasicBlockId)>,
+    /// The innermost expression or pattern being lowered, which the new statements and blocks are
+    /// attributed to in the source map.
+    current_source: MirSpan,
+    /// When lowering the body of a closure, the places in its environment which hold the captured
+    /// bindings of the enclosing body.
+    captures: FxHashMap<BindingId, Place>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum MirLowerError {
     ConstEvalError(Box<ConstEvalError>),
-    LayoutError(LayoutError),
+    /// The layout of a type couldn't be computed, with the type rendered for display and the
+    /// expression or pattern which needs the layout.
+    LayoutError {
+        error: LayoutError,
+        ty: String,
+        span: MirSpan,
+    },
     IncompleteExpr,
     /// Trying to lower a trait function, instead of an implementation
     TraitFunctionDefinition(TraitId, Name),
     UnresolvedName(String),
     RecordLiteralWithoutPath,
     UnresolvedMethod,
-    UnresolvedField,
+    /// A field which doesn't exist, with the span of the expression or pattern which names it.
+    UnresolvedField(MirSpan),
     MissingFunctionDefinition,
     TypeMismatch(TypeMismatch),
+    /// A constant used in a pattern has a type that can't be compared structurally.
+    NonStructuralMatchConst {
+        pat: PatId,
+        ty: Ty,
+        reason: NonStructuralMatch,
+    },
     /// This should be never happen. Type mismatch should catch everything.
     TypeError(&'static str),
     NotSupported(String),
+    /// A cast between two types which we don't know how to lower, with the types rendered for
+    /// display. This is either a cast which isn't implemented yet, or one which is not a valid `as`
+    /// cast at all, like one between two array types: type inference doesn't check casts yet.
+    UnsupportedCast {
+        from: String,
+        to: String,
+    },
+    /// A union literal which doesn't have exactly one field, with the name of the union.
+    UnionLiteralFieldCount {
+        union: String,
+        fields: usize,
+    },
+    /// A union literal with a functional update (`..base`), which isn't allowed on unions.
+    UnionLiteralWithSpread(String),
+    /// An enum variant literal with a functional update (`..base`), which isn't allowed as the base
+    /// may be another variant. Holds the path of the variant.
+    EnumVariantLiteralWithSpread(String),
+    /// A call whose number of arguments (including the receiver of a method call) doesn't match
+    /// the signature of the callee. Type inference reports these, but still lets them through.
+    ArgumentCountMismatch {
+        expected: usize,
+        found: usize,
+    },
     ContinueWithoutLoop,
     BreakWithoutLoop,
     Loop,
@@ -78,6 +167,24 @@ pub enum MirLowerError {
     LangItemNotFound(LangItem),
     MutatingRvalue,
     UnresolvedLabel,
+    /// A `#[naked]` function, whose body is only inline assembly and can't be lowered.
+    NakedFunction,
+    /// Inline assembly, which we can neither lower nor evaluate.
+    InlineAsm,
+    /// A const parameter, with its name, whose value is needed to interpret a body which is
+    /// interpreted without the generic arguments, like a generic function run on its own.
+    ConstParamInRuntimeContext(String),
+}
+
+/// Why the type of a constant used in a pattern can't be compared structurally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NonStructuralMatch {
+    Float,
+    /// The `PartialEq` impl of the type is written by hand, so it may disagree with comparing the
+    /// values field by field.
+    ManualPartialEq,
+    MissingPartialEq,
+    Opaque,
 }
 
 macro_rules! not_supported {
@@ -102,26 +209,60 @@ impl From<ConstEvalError> for MirLowerError {
     }
 }
 
-impl From<LayoutError> for MirLowerError {
-    fn from(value: LayoutError) -> Self {
-        MirLowerError::LayoutError(value)
-    }
-}
-
 impl MirLowerError {
     fn unresolved_path(db: &dyn HirDatabase, p: &Path) -> Self {
         Self::UnresolvedName(p.display(db).to_string())
     }
 }
 
+impl fmt::Display for MirLowerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MirLowerError::LayoutError { error, ty, .. } => {
+                write!(f, "cannot compute layout of `{ty}` ({error}) required here")
+            }
+            MirLowerError::UnsupportedCast { from, to } => {
+                write!(f, "casting `{from}` to `{to}` with `as` is invalid or not supported")
+            }
+            MirLowerError::NakedFunction => write!(f, "naked functions have no MIR body"),
+            MirLowerError::InlineAsm => write!(f, "inline assembly is not supported"),
+            MirLowerError::ConstParamInRuntimeContext(name) => {
+                write!(f, "the value of const parameter `{name}` is not known here")
+            }
+            // FIXME: render the other errors for users as well
+            _ => write!(f, "{self:?}"),
+        }
+    }
+}
+
 type Result<T> = std::result::Result<T, MirLowerError>;
 
 impl MirLowerCtx<'_> {
-    fn temp(&mut self, ty: Ty) -> Result<LocalId> {
-        if matches!(ty.kind(Interner), TyKind::Slice(_) | TyKind::Dyn(_)) {
-            implementation_error!("unsized temporaries");
+    fn temp(&mut self, ty: Ty, reason: TempReason) -> Result<LocalId> {
+        if matches!(ty.kind(Interner), TyKind::Slice(_) | TyKind::Str | TyKind::Dyn(_)) {
+            return Err(self.layout_error(LayoutError::Unsized, &ty));
+        }
+        Ok(self.result.locals.alloc(Local { ty, kind: LocalKind::Temp(reason) }))
+    }
+
+    /// The layout of `ty`, which the expression or pattern being lowered needs.
+    fn layout_of(&self, ty: &Ty) -> Result<Layout> {
+        layout_of_ty(self.db, ty, self.owner.module(self.db.upcast()).krate())
+            .map_err(|e| self.layout_error(e, ty))
+    }
+
+    /// Whether values of `ty` take no space. Types whose layout can't be computed, like generic
+    /// ones, are assumed not to be.
+    fn is_zst(&self, ty: &Ty) -> bool {
+        self.layout_of(ty).map_or(false, |layout| layout.abi.is_sized() && layout.size.bytes() == 0)
+    }
+
+    fn layout_error(&self, error: LayoutError, ty: &Ty) -> MirLowerError {
+        MirLowerError::LayoutError {
+            error,
+            ty: ty.display(self.db).to_string(),
+            span: self.current_source,
         }
-        Ok(self.result.locals.alloc(Local { ty }))
     }
 
     fn lower_expr_to_some_operand(
@@ -135,13 +276,60 @@ impl MirLowerCtx<'_> {
                     let ty = self.expr_ty(expr_id);
                     return Ok(Some((self.lower_literal_to_operand(ty, l)?, current)));
                 }
+                // Unit structs and `()` have no bytes to store, so they are used as constants
+                // instead of being written to a temporary. Enum variants are not considered, as
+                // the layout of an enum may depend on the discriminants being evaluated.
+                Expr::Path(_)
+                    if matches!(
+                        self.infer.variant_resolution_for_expr(expr_id),
+                        Some(VariantId::StructId(_))
+                    ) =>
+                {
+                    let ty = self.expr_ty(expr_id);
+                    if self.is_zst(&ty) {
+                        return Ok(Some((Operand::const_zst(ty), current)));
+                    }
+                }
+                Expr::Tuple { exprs, .. } if exprs.is_empty() => {
+                    return Ok(Some((Operand::const_zst(TyBuilder::unit()), current)));
+                }
                 _ => (),
             }
         }
         let Some((p, current)) = self.lower_expr_as_place(current, expr_id, true)? else {
             return Ok(None);
         };
-        Ok(Some((Operand::Copy(p), current)))
+        self.result.source_map.set_place_usage(expr_id, ValueUsage::Used);
+        let ty = self.expr_ty_after_adjustments(expr_id);
+        Ok(Some((self.push_move_or_copy(p, &ty), current)))
+    }
+
+    /// The place which holds the value of `binding`, which is in the environment of the closure
+    /// if the binding is captured.
+    fn binding_place(&self, binding: BindingId) -> Place {
+        match self.captures.get(&binding) {
+            Some(place) => place.clone(),
+            None => self.result.binding_locals[binding].into(),
+        }
+    }
+
+    /// Creates an operand which reads the value in `place`, which has type `ty`. The value is moved
+    /// out of the place if `ty` is not `Copy`, so every read of a place should go through here.
+    fn push_move_or_copy(&self, place: Place, ty: &Ty) -> Operand {
+        if self.is_copy(ty) {
+            Operand::Copy(place)
+        } else {
+            Operand::Move(place)
+        }
+    }
+
+    fn is_copy(&self, ty: &Ty) -> bool {
+        // Without the `Copy` trait we can't tell, so we are conservative and don't move anything.
+        let Ok(LangItemTarget::Trait(copy_trait)) = self.resolve_lang_item(LangItem::Copy) else {
+            return true;
+        };
+        let ty = Canonical { value: ty.clone(), binders: CanonicalVarKinds::empty(Interner) };
+        implements_trait(&ty, self.db, self.db.trait_environment_for_body(self.owner), copy_trait)
     }
 
     fn lower_expr_to_place_with_adjust(
@@ -151,20 +339,31 @@ impl MirLowerCtx<'_> {
         current: BasicBlockId,
         adjustments: &[Adjustment],
     ) -> Result<Option<BasicBlockId>> {
+        self.result.source_map.set_destination(expr_id, &place);
         match adjustments.split_last() {
             Some((last, rest)) => match &last.kind {
                 Adjust::NeverToAny => {
-                    let temp = self.temp(TyKind::Never.intern(Interner))?;
-                    self.lower_expr_to_place_with_adjust(expr_id, temp.into(), current, rest)
+                    let temp = self.temp(TyKind::Never.intern(Interner), TempReason::Operand)?;
+                    // No value of type `!` exists, so if lowering the expression doesn't already
+                    // end the path (e.g. when reading a `!` typed local), we end it here. The
+                    // destination is never assigned on this path, so it must not reach the code
+                    // which reads it.
+                    if let Some(b) =
+                        self.lower_expr_to_place_with_adjust(expr_id, temp.into(), current, rest)?
+                    {
+                        self.set_terminator(b, Terminator::Unreachable);
+                    }
+                    Ok(None)
                 }
                 Adjust::Deref(_) => {
                     let Some((p, current)) = self.lower_expr_as_place_with_adjust(current, expr_id, true, adjustments)? else {
                             return Ok(None);
                         };
-                    self.push_assignment(current, place, Operand::Copy(p).into(), expr_id.into());
+                    let op = self.push_move_or_copy(p, &last.target);
+                    self.push_assignment(current, place, op.into(), expr_id.into());
                     Ok(Some(current))
                 }
-                Adjust::Borrow(AutoBorrow::Ref(m) | AutoBorrow::RawPtr(m)) => {
+                Adjust::Borrow(AutoBorrow::Ref(m)) => {
                     let Some((p, current)) = self.lower_expr_as_place_with_adjust(current, expr_id, true, rest)? else {
                             return Ok(None);
                         };
@@ -172,6 +371,28 @@ impl MirLowerCtx<'_> {
                     self.push_assignment(current, place, Rvalue::Ref(bk, p), expr_id.into());
                     Ok(Some(current))
                 }
+                Adjust::Borrow(AutoBorrow::RawPtr(m)) => {
+                    let Some((p, current)) = self.lower_expr_as_place_with_adjust(current, expr_id, true, rest)? else {
+                            return Ok(None);
+                        };
+                    // There is no `AddressOf` rvalue, so we borrow the place and cast the
+                    // reference to a raw pointer.
+                    let TyKind::Raw(_, pointee) = last.target.kind(Interner) else {
+                        implementation_error!("raw pointer borrow to a non raw pointer type");
+                    };
+                    let ref_ty =
+                        TyKind::Ref(*m, static_lifetime(), pointee.clone()).intern(Interner);
+                    let tmp: Place = self.temp(ref_ty, TempReason::Operand)?.into();
+                    let bk = BorrowKind::from_chalk(*m);
+                    self.push_assignment(current, tmp.clone(), Rvalue::Ref(bk, p), expr_id.into());
+                    self.push_assignment(
+                        current,
+                        place,
+                        Rvalue::Cast(CastKind::PtrToPtr, Operand::Copy(tmp), last.target.clone()),
+                        expr_id.into(),
+                    );
+                    Ok(Some(current))
+                }
                 Adjust::Pointer(cast) => {
                     let Some((p, current)) = self.lower_expr_as_place_with_adjust(current, expr_id, true, rest)? else {
                             return Ok(None);
@@ -199,13 +420,32 @@ impl MirLowerCtx<'_> {
         place: Place,
         prev_block: BasicBlockId,
     ) -> Result<Option<BasicBlockId>> {
-        if let Some(adjustments) = self.infer.expr_adjustments.get(&expr_id) {
-            return self.lower_expr_to_place_with_adjust(expr_id, place, prev_block, adjustments);
-        }
-        self.lower_expr_to_place_without_adjust(expr_id, place, prev_block)
+        self.with_source(expr_id.into(), |this| {
+            if let Some(adjustments) = this.infer.expr_adjustments.get(&expr_id) {
+                return this.lower_expr_to_place_with_adjust(
+                    expr_id,
+                    place,
+                    prev_block,
+                    adjustments,
+                );
+            }
+            this.lower_expr_to_place_without_adjust(expr_id, place, prev_block)
+        })
     }
 
     fn lower_expr_to_place_without_adjust(
+        &mut self,
+        expr_id: ExprId,
+        place: Place,
+        current: BasicBlockId,
+    ) -> Result<Option<BasicBlockId>> {
+        self.result.source_map.set_destination(expr_id, &place);
+        self.with_source(expr_id.into(), |this| {
+            this.lower_expr_to_place_without_adjust_inner(expr_id, place, current)
+        })
+    }
+
+    fn lower_expr_to_place_without_adjust_inner(
         &mut self,
         expr_id: ExprId,
         place: Place,
@@ -221,42 +461,42 @@ impl MirLowerCtx<'_> {
                     }
                 }
                 Err(MirLowerError::IncompleteExpr)
-            },
+            }
             Expr::Path(p) => {
                 let unresolved_name = || MirLowerError::unresolved_path(self.db, p);
-                let resolver = resolver_for_expr(self.db.upcast(), self.owner, expr_id);
+                let resolver = self.resolver_for_expr(expr_id);
                 let pr = resolver
                     .resolve_path_in_value_ns(self.db.upcast(), p)
                     .ok_or_else(unresolved_name)?;
                 let pr = match pr {
                     ResolveValueResult::ValueNs(v) => v,
                     ResolveValueResult::Partial(..) => {
-                        if let Some((assoc, subst)) = self
-                            .infer
-                            .assoc_resolutions_for_expr(expr_id)
+                        if let Some((assoc, subst)) = self.infer.assoc_resolutions_for_expr(expr_id)
                         {
                             match assoc {
                                 hir_def::AssocItemId::ConstId(c) => {
-                                    self.lower_const(c, current, place, subst, expr_id.into())?;
-                                    return Ok(Some(current))
-                                },
+                                    let ty = self.expr_ty(expr_id);
+                                    self.lower_const(c, current, place, subst, ty, expr_id.into())?;
+                                    return Ok(Some(current));
+                                }
                                 hir_def::AssocItemId::FunctionId(_) => {
                                     // FnDefs are zero sized, no action is needed.
-                                    return Ok(Some(current))
+                                    return Ok(Some(current));
                                 }
                                 hir_def::AssocItemId::TypeAliasId(_) => {
                                     // FIXME: If it is unreachable, use proper error instead of `not_supported`.
                                     not_supported!("associated functions and types")
-                                },
+                                }
                             }
-                        } else if let Some(variant) = self
-                            .infer
-                            .variant_resolution_for_expr(expr_id)
+                        } else if let Some(variant) =
+                            self.infer.variant_resolution_for_expr(expr_id)
                         {
                             match variant {
                                 VariantId::EnumVariantId(e) => ValueNs::EnumVariantId(e),
                                 VariantId::StructId(s) => ValueNs::StructId(s),
-                                VariantId::UnionId(_) => implementation_error!("Union variant as path"),
+                                VariantId::UnionId(_) => {
+                                    implementation_error!("Union variant as path")
+                                }
                             }
                         } else {
                             return Err(unresolved_name());
@@ -265,20 +505,34 @@ impl MirLowerCtx<'_> {
                 };
                 match pr {
                     ValueNs::LocalBinding(pat_id) => {
-                        self.push_assignment(
+                        let op =
+                            self.push_move_or_copy(self.binding_place(pat_id), &self.infer[pat_id]);
+                        self.push_assignment(current, place, op.into(), expr_id.into());
+                        Ok(Some(current))
+                    }
+                    ValueNs::ConstId(const_id) => {
+                        let ty = self.expr_ty(expr_id);
+                        self.lower_const(
+                            const_id,
                             current,
                             place,
-                            Operand::Copy(self.result.binding_locals[pat_id].into()).into(),
+                            Substitution::empty(Interner),
+                            ty,
                             expr_id.into(),
-                        );
+                        )?;
                         Ok(Some(current))
                     }
-                    ValueNs::ConstId(const_id) => {
-                        self.lower_const(const_id, current, place, Substitution::empty(Interner), expr_id.into())?;
+                    ValueNs::StaticId(_) => {
+                        let Some((p, current)) = self.lower_expr_as_place_without_adjust(current, expr_id, true)? else {
+                            return Ok(None);
+                        };
+                        let op = self.push_move_or_copy(p, &self.expr_ty(expr_id));
+                        self.push_assignment(current, place, op.into(), expr_id.into());
                         Ok(Some(current))
                     }
                     ValueNs::EnumVariantId(variant_id) => {
-                        let variant_data = &self.db.enum_data(variant_id.parent).variants[variant_id.local_id];
+                        let variant_data =
+                            &self.db.enum_data(variant_id.parent).variants[variant_id.local_id];
                         if variant_data.variant_data.kind() == StructKind::Unit {
                             let ty = self.infer.type_of_expr[expr_id].clone();
                             current = self.lower_enum_variant(
@@ -329,25 +583,17 @@ impl MirLowerCtx<'_> {
                 }
             }
             Expr::If { condition, then_branch, else_branch } => {
-                let Some((discr, current)) = self.lower_expr_to_some_operand(*condition, current)? else {
-                    return Ok(None);
+                let (start_of_then, start_of_else) = self.lower_condition(*condition, current)?;
+                let end_of_then = match start_of_then {
+                    Some(start) => self.lower_expr_to_place(*then_branch, place.clone(), start)?,
+                    None => None,
                 };
-                let start_of_then = self.new_basic_block();
-                let end_of_then =
-                    self.lower_expr_to_place(*then_branch, place.clone(), start_of_then)?;
-                let start_of_else = self.new_basic_block();
-                let end_of_else = if let Some(else_branch) = else_branch {
-                    self.lower_expr_to_place(*else_branch, place, start_of_else)?
-                } else {
-                    Some(start_of_else)
+                let end_of_else = match (start_of_else, else_branch) {
+                    (Some(start), Some(else_branch)) => {
+                        self.lower_expr_to_place(*else_branch, place, start)?
+                    }
+                    (start, _) => start,
                 };
-                self.set_terminator(
-                    current,
-                    Terminator::SwitchInt {
-                        discr,
-                        targets: SwitchTargets::static_if(1, start_of_then, start_of_else),
-                    },
-                );
                 Ok(self.merge_blocks(end_of_then, end_of_else))
             }
             Expr::Let { pat, expr } => {
@@ -381,81 +627,134 @@ impl MirLowerCtx<'_> {
                 Ok(self.merge_blocks(Some(then_target), else_target))
             }
             Expr::Unsafe { id: _, statements, tail } => {
-                self.lower_block_to_place(statements, current, *tail, place)
+                self.lower_block_to_place(expr_id, statements, current, *tail, place)
             }
             Expr::Block { id: _, statements, tail, label } => {
                 if let Some(label) = label {
-                    self.lower_loop(current, place.clone(), Some(*label), |this, begin| {
-                        if let Some(block) = this.lower_block_to_place(statements, begin, *tail, place)? {
+                    self.lower_loop(current, place.clone(), Some(*label), true, |this, begin| {
+                        if let Some(block) =
+                            this.lower_block_to_place(expr_id, statements, begin, *tail, place)?
+                        {
                             let end = this.current_loop_end()?;
                             this.set_goto(block, end);
                         }
                         Ok(())
                     })
                 } else {
-                    self.lower_block_to_place(statements, current, *tail, place)
+                    self.lower_block_to_place(expr_id, statements, current, *tail, place)
                 }
             }
-            Expr::Loop { body, label } => self.lower_loop(current, place, *label, |this, begin| {
-                if let Some((_, block)) = this.lower_expr_as_place(begin, *body, true)? {
-                    this.set_goto(block, begin);
-                }
-                Ok(())
-            }),
+            Expr::Loop { body, label } => {
+                self.lower_loop(current, place, *label, false, |this, begin| {
+                    if let Some((_, block)) = this.lower_expr_as_place(begin, *body, true)? {
+                        this.set_goto(block, begin);
+                    }
+                    Ok(())
+                })
+            }
             Expr::While { condition, body, label } => {
-                self.lower_loop(current, place, *label, |this, begin| {
-                    let Some((discr, to_switch)) = this.lower_expr_to_some_operand(*condition, begin)? else {
+                self.lower_loop(current, place, *label, false, |this, begin| {
+                    // The bindings of the condition are scoped to one iteration of the body, so
+                    // they die before the condition is evaluated again, or when it fails.
+                    let let_pats = this.condition_let_pats(*condition);
+                    let (after_cond, exit) = this.lower_condition(*condition, begin)?;
+                    if let Some(exit) = exit {
+                        this.push_storage_dead(exit, &let_pats);
+                        let end = this.current_loop_end()?;
+                        this.set_goto(exit, end);
+                    }
+                    let Some(after_cond) = after_cond else {
                         return Ok(());
                     };
-                    let end = this.current_loop_end()?;
-                    let after_cond = this.new_basic_block();
-                    this.set_terminator(
-                        to_switch,
-                        Terminator::SwitchInt {
-                            discr,
-                            targets: SwitchTargets::static_if(1, after_cond, end),
-                        },
-                    );
-                    if let Some((_, block)) = this.lower_expr_as_place(after_cond, *body, true)? {
-                        this.set_goto(block, begin);
+                    // `break` and `continue` in the body end the storage of the bindings too.
+                    let mut storage = vec![];
+                    for &pat in &let_pats {
+                        this.body.walk_bindings_in_pat(pat, |b| {
+                            storage.push((this.result.binding_locals[b], MirSpan::PatId(pat)))
+                        });
+                    }
+                    this.drop_scopes.push(DropScope { locals: vec![], storage });
+                    for &pat in &let_pats {
+                        this.push_drop_scope_bindings(pat);
+                    }
+                    match this.lower_expr_as_place(after_cond, *body, true) {
+                        Ok(Some((_, block))) => {
+                            let block = this.pop_drop_scope(block);
+                            this.set_goto(block, begin);
+                        }
+                        r => {
+                            this.drop_scopes.pop();
+                            r?;
+                        }
                     }
                     Ok(())
                 })
             }
             &Expr::For { iterable, pat, body, label } => {
-                let into_iter_fn = self.resolve_lang_item(LangItem::IntoIterIntoIter)?
-                    .as_function().ok_or(MirLowerError::LangItemNotFound(LangItem::IntoIterIntoIter))?;
-                let iter_next_fn = self.resolve_lang_item(LangItem::IteratorNext)?
-                    .as_function().ok_or(MirLowerError::LangItemNotFound(LangItem::IteratorNext))?;
-                let option_some = self.resolve_lang_item(LangItem::OptionSome)?
-                    .as_enum_variant().ok_or(MirLowerError::LangItemNotFound(LangItem::OptionSome))?;
+                let into_iter_fn = self
+                    .resolve_lang_item(LangItem::IntoIterIntoIter)?
+                    .as_function()
+                    .ok_or(MirLowerError::LangItemNotFound(LangItem::IntoIterIntoIter))?;
+                let iter_next_fn = self
+                    .resolve_lang_item(LangItem::IteratorNext)?
+                    .as_function()
+                    .ok_or(MirLowerError::LangItemNotFound(LangItem::IteratorNext))?;
+                let option_some = self
+                    .resolve_lang_item(LangItem::OptionSome)?
+                    .as_enum_variant()
+                    .ok_or(MirLowerError::LangItemNotFound(LangItem::OptionSome))?;
                 let option = option_some.parent;
                 let into_iter_fn_op = Operand::const_zst(
                     TyKind::FnDef(
                         self.db.intern_callable_def(CallableDefId::FunctionId(into_iter_fn)).into(),
-                        Substitution::from1(Interner, self.expr_ty(iterable))
-                    ).intern(Interner));
-                let iter_next_fn_op = Operand::const_zst(
-                    TyKind::FnDef(
-                        self.db.intern_callable_def(CallableDefId::FunctionId(iter_next_fn)).into(),
-                        Substitution::from1(Interner, self.expr_ty(iterable))
-                    ).intern(Interner));
+                        Substitution::from1(Interner, self.expr_ty(iterable)),
+                    )
+                    .intern(Interner),
+                );
                 let &Some(iterator_ty) = &self.infer.type_of_for_iterator.get(&expr_id) else {
                     return Err(MirLowerError::TypeError("unknown for loop iterator type"));
                 };
-                let ref_mut_iterator_ty = TyKind::Ref(Mutability::Mut, static_lifetime(), iterator_ty.clone()).intern(Interner);
+                let iter_next_fn_op = Operand::const_zst(
+                    TyKind::FnDef(
+                        self.db.intern_callable_def(CallableDefId::FunctionId(iter_next_fn)).into(),
+                        Substitution::from1(Interner, iterator_ty.clone()),
+                    )
+                    .intern(Interner),
+                );
+                let ref_mut_iterator_ty =
+                    TyKind::Ref(Mutability::Mut, static_lifetime(), iterator_ty.clone())
+                        .intern(Interner);
                 let item_ty = &self.infer.type_of_pat[pat];
-                let option_item_ty = TyKind::Adt(chalk_ir::AdtId(option.into()), Substitution::from1(Interner, item_ty.clone())).intern(Interner);
-                let iterator_place: Place = self.temp(iterator_ty.clone())?.into();
-                let option_item_place: Place = self.temp(option_item_ty.clone())?.into();
-                let ref_mut_iterator_place: Place = self.temp(ref_mut_iterator_ty)?.into();
-                let Some(current) = self.lower_call_and_args(into_iter_fn_op, Some(iterable).into_iter(), iterator_place.clone(), current, false)?
+                let option_item_ty = TyKind::Adt(
+                    chalk_ir::AdtId(option.into()),
+                    Substitution::from1(Interner, item_ty.clone()),
+                )
+                .intern(Interner);
+                let iterator_place: Place =
+                    self.temp(iterator_ty.clone(), TempReason::Iterator)?.into();
+                let option_item_place: Place =
+                    self.temp(option_item_ty.clone(), TempReason::Iterator)?.into();
+                let ref_mut_iterator_place: Place =
+                    self.temp(ref_mut_iterator_ty, TempReason::Iterator)?.into();
+                // The iterator lives in a scope around the loop, so that it is dropped however the
+                // loop is left, but not by the `break`s and `continue`s of the loop itself.
+                self.drop_scopes.push(DropScope::default());
+                let Some(current) = self.lower_call_and_args(into_iter_fn_op, Some(iterable).into_iter(), iterator_place.clone(), current, false, expr_id.into())?
                 else {
+                    self.drop_scopes.pop();
                     return Ok(None);
                 };
-                self.push_assignment(current, ref_mut_iterator_place.clone(), Rvalue::Ref(BorrowKind::Mut { allow_two_phase_borrow: false }, iterator_place), expr_id.into());
-                self.lower_loop(current, place, label, |this, begin| {
-                    let Some(current) = this.lower_call(iter_next_fn_op, vec![Operand::Copy(ref_mut_iterator_place)], option_item_place.clone(), begin, false)?
+                self.push_drop_scope_temporary(iterator_place.local);
+                let end = self.lower_loop(current, place, label, false, |this, begin| {
+                    // Like in rustc, each call to `next` gets a fresh `&mut` to the iterator, which
+                    // it consumes.
+                    this.push_assignment(
+                        begin,
+                        ref_mut_iterator_place.clone(),
+                        Rvalue::Ref(BorrowKind::Mut { allow_two_phase_borrow: false }, iterator_place),
+                        expr_id.into(),
+                    );
+                    let Some(current) = this.lower_call(iter_next_fn_op, vec![Operand::Move(ref_mut_iterator_place)], option_item_place.clone(), begin, false, expr_id.into())?
                     else {
                         return Ok(());
                     };
@@ -474,36 +773,78 @@ impl MirLowerCtx<'_> {
                         this.set_goto(block, begin);
                     }
                     Ok(())
-                })
-            },
+                });
+                match end {
+                    Ok(Some(end)) => Ok(Some(self.pop_drop_scope(end))),
+                    r => {
+                        self.drop_scopes.pop();
+                        r
+                    }
+                }
+            }
             Expr::Call { callee, args, .. } => {
-                if let Some((func_id, generic_args)) =
-                    self.infer.method_resolution(expr_id) {
+                if let Some((func_id, generic_args)) = self.infer.method_resolution(expr_id) {
+                    // Calling a value which is not a function item or pointer, e.g. `f: impl Fn()`.
+                    // Type inference resolved it to a method of the `Fn*` traits, so we desugar
+                    // `f(a, b)` to `Fn::call(&f, (a, b))`.
+                    let tuple_ty = generic_args
+                        .as_slice(Interner)
+                        .get(1)
+                        .and_then(|x| x.ty(Interner))
+                        .cloned()
+                        .ok_or(MirLowerError::TypeError("fn trait call without argument tuple"))?;
                     let ty = chalk_ir::TyKind::FnDef(
                         CallableDefId::FunctionId(func_id).to_chalk(self.db),
                         generic_args,
                     )
                     .intern(Interner);
                     let func = Operand::from_bytes(vec![], ty);
-                    return self.lower_call_and_args(
+                    let Some((callee, current)) = self.lower_expr_to_some_operand(*callee, current)? else {
+                        return Ok(None);
+                    };
+                    let Some((tuple, current)) = self.lower_args_as_tuple(args, tuple_ty, current, expr_id.into())? else {
+                        return Ok(None);
+                    };
+                    return self.lower_call(
                         func,
-                        iter::once(*callee).chain(args.iter().copied()),
+                        vec![callee, tuple],
                         place,
                         current,
                         self.is_uninhabited(expr_id),
+                        expr_id.into(),
                     );
                 }
                 let callee_ty = self.expr_ty_after_adjustments(*callee);
+                if let TyKind::Closure(..) = callee_ty.kind(Interner) {
+                    return self
+                        .lower_closure_call(callee_ty, *callee, args, place, current, expr_id);
+                }
                 match &callee_ty.data(Interner).kind {
                     chalk_ir::TyKind::FnDef(..) => {
+                        self.check_argument_count(&callee_ty, args.len())?;
                         let func = Operand::from_bytes(vec![], callee_ty.clone());
-                        self.lower_call_and_args(func, args.iter().copied(), place, current, self.is_uninhabited(expr_id))
+                        self.lower_call_and_args(
+                            func,
+                            args.iter().copied(),
+                            place,
+                            current,
+                            self.is_uninhabited(expr_id),
+                            expr_id.into(),
+                        )
                     }
                     chalk_ir::TyKind::Function(_) => {
+                        self.check_argument_count(&callee_ty, args.len())?;
                         let Some((func, current)) = self.lower_expr_to_some_operand(*callee, current)? else {
                             return Ok(None);
                         };
-                        self.lower_call_and_args(func, args.iter().copied(), place, current, self.is_uninhabited(expr_id))
+                        self.lower_call_and_args(
+                            func,
+                            args.iter().copied(),
+                            place,
+                            current,
+                            self.is_uninhabited(expr_id),
+                            expr_id.into(),
+                        )
                     }
                     TyKind::Error => return Err(MirLowerError::MissingFunctionDefinition),
                     _ => return Err(MirLowerError::TypeError("function call on bad type")),
@@ -517,23 +858,45 @@ impl MirLowerCtx<'_> {
                     generic_args,
                 )
                 .intern(Interner);
+                self.check_argument_count(&ty, args.len() + 1)?;
                 let func = Operand::from_bytes(vec![], ty);
-                self.lower_call_and_args(
+                let Some((receiver, current)) = self.lower_method_receiver(*receiver, current)?
+                else {
+                    return Ok(None);
+                };
+                let Some((args, current)) = self.lower_args(args.iter().copied(), current)? else {
+                    return Ok(None);
+                };
+                self.lower_call(
                     func,
-                    iter::once(*receiver).chain(args.iter().copied()),
+                    iter::once(receiver).chain(args).collect(),
                     place,
                     current,
                     self.is_uninhabited(expr_id),
+                    expr_id.into(),
                 )
             }
             Expr::Match { expr, arms } => {
-                let Some((cond_place, mut current)) = self.lower_expr_as_place(current, *expr, true)?
-                else {
+                let cond = if self.is_place_expr(*expr) {
+                    self.lower_expr_as_place(current, *expr, true)?
+                } else {
+                    self.lower_scrutinee_to_temporary(*expr, current)?
+                };
+                let Some((cond_place, mut current)) = cond else {
                     return Ok(None);
                 };
                 let cond_ty = self.expr_ty_after_adjustments(*expr);
                 let mut end = None;
-                for MatchArm { pat, guard, expr } in arms.iter() {
+                let (c, switched) = self.lower_match_arms_as_switch(
+                    current,
+                    &cond_place,
+                    &cond_ty,
+                    arms,
+                    &place,
+                    &mut end,
+                )?;
+                current = c;
+                for MatchArm { pat, guard, expr } in arms[switched..].iter() {
                     let (then, mut otherwise) = self.pattern_match(
                         current,
                         None,
@@ -546,7 +909,13 @@ impl MirLowerCtx<'_> {
                         let next = self.new_basic_block();
                         let o = otherwise.get_or_insert_with(|| self.new_basic_block());
                         if let Some((discr, c)) = self.lower_expr_to_some_operand(guard, then)? {
-                            self.set_terminator(c, Terminator::SwitchInt { discr, targets: SwitchTargets::static_if(1, next, *o) });
+                            self.set_terminator(
+                                c,
+                                Terminator::SwitchInt {
+                                    discr,
+                                    targets: SwitchTargets::static_if(1, next, *o),
+                                },
+                            );
                         }
                         next
                     } else {
@@ -570,60 +939,62 @@ impl MirLowerCtx<'_> {
                 }
                 Ok(end)
             }
-            Expr::Continue { label } => match label {
-                Some(_) => not_supported!("continue with label"),
-                None => {
-                    let loop_data =
-                        self.current_loop_blocks.as_ref().ok_or(MirLowerError::ContinueWithoutLoop)?;
-                    self.set_goto(current, loop_data.begin);
-                    Ok(None)
+            Expr::Continue { label } => {
+                let index = self.loop_index(label.as_ref(), MirLowerError::ContinueWithoutLoop)?;
+                let LoopBlocks { begin, drop_scope_index, is_block, .. } = self.loop_blocks[index];
+                if is_block {
+                    return Err(MirLowerError::ContinueWithoutLoop);
                 }
-            },
+                let current = self.drop_scopes_from(current, drop_scope_index);
+                self.set_goto(current, begin);
+                Ok(None)
+            }
             Expr::Break { expr, label } => {
+                // The target is resolved before lowering the value, so that every `break` of a
+                // loop writes to the same place and jumps to the same end block, which is
+                // allocated by the first `break` that needs it.
+                let index = self.loop_index(label.as_ref(), MirLowerError::BreakWithoutLoop)?;
+                let end = self.loop_end(index);
+                let LoopBlocks { place: loop_place, drop_scope_index, .. } =
+                    self.loop_blocks[index].clone();
                 if let Some(expr) = expr {
-                    let loop_data = match label {
-                        Some(l) => self.labeled_loop_blocks.get(l).ok_or(MirLowerError::UnresolvedLabel)?,
-                        None => self.current_loop_blocks.as_ref().ok_or(MirLowerError::BreakWithoutLoop)?,
-                    };
-                    let Some(c) = self.lower_expr_to_place(*expr, loop_data.place.clone(), current)? else {
+                    let Some(c) = self.lower_expr_to_place(*expr, loop_place, current)? else {
                         return Ok(None);
                     };
                     current = c;
+                } else {
+                    // A `break` without a value gives `()` to the loop or labeled block it exits.
+                    self.push_assignment(
+                        current,
+                        loop_place,
+                        Operand::const_zst(TyBuilder::unit()).into(),
+                        expr_id.into(),
+                    );
                 }
-                let end = match label {
-                    Some(l) => self.labeled_loop_blocks.get(l).ok_or(MirLowerError::UnresolvedLabel)?.end.expect("We always generate end for labeled loops"),
-                    None => self.current_loop_end()?,
-                };
+                let current = self.drop_scopes_from(current, drop_scope_index);
                 self.set_goto(current, end);
                 Ok(None)
             }
             Expr::Return { expr } => {
+                // Closures are lowered to bodies of their own (see `mir_body_for_closure_query`),
+                // so this is always the return slot of the innermost closure or function.
                 if let Some(expr) = expr {
-                    if let Some(c) = self.lower_expr_to_place(*expr, return_slot().into(), current)? {
+                    if let Some(c) =
+                        self.lower_expr_to_place(*expr, return_slot().into(), current)?
+                    {
                         current = c;
                     } else {
                         return Ok(None);
                     }
                 }
+                let current = self.drop_scopes_from(current, 0);
                 self.set_terminator(current, Terminator::Return);
                 Ok(None)
             }
             Expr::Yield { .. } => not_supported!("yield"),
             Expr::RecordLit { fields, path, spread, ellipsis: _, is_assignee_expr: _ } => {
-                let spread_place = match spread {
-                    &Some(x) => {
-                        let Some((p, c)) = self.lower_expr_as_place(current, x, true)? else {
-                            return Ok(None);
-                        };
-                        current = c;
-                        Some(p)
-                    },
-                    None => None,
-                };
-                let variant_id = self
-                    .infer
-                    .variant_resolution_for_expr(expr_id)
-                    .ok_or_else(|| match path {
+                let variant_id =
+                    self.infer.variant_resolution_for_expr(expr_id).ok_or_else(|| match path {
                         Some(p) => MirLowerError::UnresolvedName(p.display(self.db).to_string()),
                         None => MirLowerError::RecordLiteralWithoutPath,
                     })?;
@@ -632,52 +1003,121 @@ impl MirLowerCtx<'_> {
                     _ => not_supported!("Non ADT record literal"),
                 };
                 let variant_data = variant_id.variant_data(self.db.upcast());
+                let field_types = self.db.field_types(variant_id);
                 match variant_id {
                     VariantId::EnumVariantId(_) | VariantId::StructId(_) => {
+                        if let (VariantId::EnumVariantId(v), Some(_)) = (variant_id, spread) {
+                            let enum_data = self.db.enum_data(v.parent);
+                            return Err(MirLowerError::EnumVariantLiteralWithSpread(format!(
+                                "{}::{}",
+                                enum_data.name, enum_data.variants[v.local_id].name
+                            )));
+                        }
                         let mut operands = vec![None; variant_data.fields().len()];
                         for RecordLitField { name, expr } in fields.iter() {
-                            let field_id =
-                                variant_data.field(name).ok_or(MirLowerError::UnresolvedField)?;
+                            let field_id = variant_data
+                                .field(name)
+                                .ok_or(MirLowerError::UnresolvedField((*expr).into()))?;
                             let Some((op, c)) = self.lower_expr_to_some_operand(*expr, current)? else {
                                 return Ok(None);
                             };
                             current = c;
                             operands[u32::from(field_id.into_raw()) as usize] = Some(op);
                         }
+                        // The base is evaluated after the explicit fields, and only once no matter
+                        // how many fields are taken from it.
+                        let spread_place = match spread {
+                            &Some(x) => {
+                                let locals_before = self.result.locals.len();
+                                let Some((p, c)) = self.lower_expr_as_place(current, x, true)? else {
+                                    return Ok(None);
+                                };
+                                current = c;
+                                // A base behind a reference, like `..*r`, is not owned here even
+                                // if the reference is a temporary.
+                                let is_temporary = u32::from(p.local.into_raw()) as usize
+                                    >= locals_before
+                                    && !is_behind_deref(&p);
+                                Some((p, is_temporary))
+                            }
+                            None => None,
+                        };
+                        let field_place = |base: &Place, i: usize| {
+                            let local_id = LocalFieldId::from_raw(RawIdx::from(i as u32));
+                            let mut p = base.clone();
+                            p.projection.push(ProjectionElem::Field(FieldId {
+                                parent: variant_id,
+                                local_id,
+                            }));
+                            (p, field_types[local_id].clone().substitute(Interner, &subst))
+                        };
+                        let mut overridden = vec![];
+                        let operands = match &spread_place {
+                            Some((sp, is_temporary)) => operands
+                                .into_iter()
+                                .enumerate()
+                                .map(|(i, x)| match x {
+                                    Some(x) => {
+                                        if *is_temporary {
+                                            overridden.push(field_place(sp, i));
+                                        }
+                                        x
+                                    }
+                                    None => {
+                                        let (p, ty) = field_place(sp, i);
+                                        if is_behind_deref(sp) {
+                                            Operand::Copy(p)
+                                        } else {
+                                            self.push_move_or_copy(p, &ty)
+                                        }
+                                    }
+                                })
+                                .collect(),
+                            None => operands.into_iter().collect::<Option<_>>().ok_or(
+                                MirLowerError::TypeError("missing field in record literal"),
+                            )?,
+                        };
                         self.push_assignment(
                             current,
                             place,
                             Rvalue::Aggregate(
-                                AggregateKind::Adt(variant_id, subst),
-                                match spread_place {
-                                    Some(sp) => operands.into_iter().enumerate().map(|(i, x)| {
-                                        match x {
-                                            Some(x) => x,
-                                            None => {
-                                                let mut p = sp.clone();
-                                                p.projection.push(ProjectionElem::Field(FieldId {
-                                                    parent: variant_id,
-                                                    local_id: LocalFieldId::from_raw(RawIdx::from(i as u32)),
-                                                }));
-                                                Operand::Copy(p)
-                                            },
-                                        }
-                                    }).collect(),
-                                    None => operands.into_iter().collect::<Option<_>>().ok_or(
-                                        MirLowerError::TypeError("missing field in record literal"),
-                                    )?,
-                                },
+                                AggregateKind::Adt(variant_id, subst.clone()),
+                                operands,
                             ),
                             expr_id.into(),
                         );
+                        // A temporary base is gone after this expression, so the fields which
+                        // weren't taken from it are dropped here.
+                        for (p, ty) in overridden {
+                            if self
+                                .db
+                                .needs_drop(ty, self.db.trait_environment_for_body(self.owner))
+                            {
+                                let unwind = self.cleanup_block(&self.live_drop_locals(0));
+                                let next = self.new_basic_block();
+                                self.set_terminator(
+                                    current,
+                                    Terminator::Drop { place: p, target: next, unwind },
+                                );
+                                current = next;
+                            }
+                        }
                         Ok(Some(current))
                     }
                     VariantId::UnionId(union_id) => {
+                        let union_name = || self.db.union_data(union_id).name.to_string();
+                        if spread.is_some() {
+                            return Err(MirLowerError::UnionLiteralWithSpread(union_name()));
+                        }
                         let [RecordLitField { name, expr }] = fields.as_ref() else {
-                            not_supported!("Union record literal with more than one field");
+                            return Err(MirLowerError::UnionLiteralFieldCount {
+                                union: union_name(),
+                                fields: fields.len(),
+                            });
                         };
-                        let local_id =
-                            variant_data.field(name).ok_or(MirLowerError::UnresolvedField)?;
+                        let local_id = variant_data
+                            .field(name)
+                            .ok_or(MirLowerError::UnresolvedField((*expr).into()))?;
                         let mut place = place;
                         place
                             .projection
@@ -686,7 +1126,15 @@ impl MirLowerCtx<'_> {
                     }
                 }
             }
-            Expr::Await { .. } => not_supported!("await"),
+            Expr::Await { expr } => {
+                // Async functions are lowered like synchronous ones, writing the output of their
+                // future to the return slot, so awaiting a call of one is just the call.
+                // FIXME: support awaiting other futures, which needs generator lowering.
+                if !self.is_async_fn_call(*expr) {
+                    not_supported!("await");
+                }
+                self.lower_expr_to_place(*expr, place, current)
+            }
             Expr::Yeet { .. } => not_supported!("yeet"),
             Expr::Async { .. } => not_supported!("async block"),
             Expr::Const { .. } => not_supported!("anonymous const block"),
@@ -694,14 +1142,17 @@ impl MirLowerCtx<'_> {
                 let Some((x, current)) = self.lower_expr_to_some_operand(*expr, current)? else {
                     return Ok(None);
                 };
-                let source_ty = self.infer[*expr].clone();
+                // The operand is lowered with its adjustments, so it is the adjusted type that
+                // is cast.
+                let source_ty = self.expr_ty_after_adjustments(*expr);
                 let target_ty = self.infer[expr_id].clone();
-                self.push_assignment(
-                    current,
-                    place,
-                    Rvalue::Cast(cast_kind(&source_ty, &target_ty)?, x, target_ty),
-                    expr_id.into(),
-                );
+                let rvalue = if source_ty == target_ty && self.is_zst(&target_ty) {
+                    // There is nothing to convert in a cast of a zero sized type to itself.
+                    Operand::const_zst(target_ty).into()
+                } else {
+                    Rvalue::Cast(cast_kind(self.db, &source_ty, &target_ty)?, x, target_ty)
+                };
+                self.push_assignment(current, place, rvalue, expr_id.into());
                 Ok(Some(current))
             }
             Expr::Ref { expr, rawness: _, mutability } => {
@@ -712,15 +1163,37 @@ impl MirLowerCtx<'_> {
                 self.push_assignment(current, place, Rvalue::Ref(bk, p), expr_id.into());
                 Ok(Some(current))
             }
-            Expr::Box { .. } => not_supported!("box expression"),
-            Expr::Field { .. } | Expr::Index { .. } | Expr::UnaryOp { op: hir_def::expr::UnaryOp::Deref, .. } => {
+            Expr::Box { expr } => {
+                let ty = self.expr_ty_after_adjustments(*expr);
+                self.push_assignment(
+                    current,
+                    place.clone(),
+                    Rvalue::ShallowInitBoxWithAlloc(ty),
+                    expr_id.into(),
+                );
+                let Some((operand, current)) = self.lower_expr_to_some_operand(*expr, current)?
+                else {
+                    return Ok(None);
+                };
+                let mut p = place;
+                p.projection.push(ProjectionElem::Deref);
+                self.push_assignment(current, p, operand.into(), expr_id.into());
+                Ok(Some(current))
+            }
+            Expr::Field { .. }
+            | Expr::Index { .. }
+            | Expr::UnaryOp { op: hir_def::expr::UnaryOp::Deref, .. } => {
                 let Some((p, current)) = self.lower_expr_as_place_without_adjust(current, expr_id, true)? else {
                     return Ok(None);
                 };
-                self.push_assignment(current, place, Operand::Copy(p).into(), expr_id.into());
+                let op = self.push_move_or_copy(p, &self.expr_ty(expr_id));
+                self.push_assignment(current, place, op.into(), expr_id.into());
                 Ok(Some(current))
             }
-            Expr::UnaryOp { expr, op: op @ (hir_def::expr::UnaryOp::Not | hir_def::expr::UnaryOp::Neg) } => {
+            Expr::UnaryOp {
+                expr,
+                op: op @ (hir_def::expr::UnaryOp::Not | hir_def::expr::UnaryOp::Neg),
+            } => {
                 let Some((operand, current)) = self.lower_expr_to_some_operand(*expr, current)? else {
                     return Ok(None);
                 };
@@ -736,13 +1209,13 @@ impl MirLowerCtx<'_> {
                     expr_id.into(),
                 );
                 Ok(Some(current))
-            },
+            }
             Expr::BinaryOp { lhs, rhs, op } => {
                 let op = op.ok_or(MirLowerError::IncompleteExpr)?;
-                if let hir_def::expr::BinaryOp::Assignment { op } = op {
-                    if op.is_some() {
-                        not_supported!("assignment with arith op (like +=)");
-                    }
+                if let hir_def::expr::BinaryOp::Assignment { op: Some(op) } = op {
+                    return self.lower_compound_assignment(current, place, expr_id, *lhs, op, *rhs);
+                }
+                if let hir_def::expr::BinaryOp::Assignment { op: None } = op {
                     let Some((lhs_place, current)) =
                         self.lower_expr_as_place(current, *lhs, false)?
                     else {
@@ -754,6 +1227,21 @@ impl MirLowerCtx<'_> {
                     self.push_assignment(current, lhs_place, rhs_op.into(), expr_id.into());
                     return Ok(Some(current));
                 }
+                if let hir_def::expr::BinaryOp::LogicOp(_) = op {
+                    let (then_target, else_target) = self.lower_condition(expr_id, current)?;
+                    for (target, value) in [(then_target, 1), (else_target, 0)] {
+                        if let Some(target) = target {
+                            self.write_bytes_to_place(
+                                target,
+                                place.clone(),
+                                vec![value],
+                                TyBuilder::bool(),
+                                expr_id.into(),
+                            )?;
+                        }
+                    }
+                    return Ok(self.merge_blocks(then_target, else_target));
+                }
                 let Some((lhs_op, current)) = self.lower_expr_to_some_operand(*lhs, current)? else {
                     return Ok(None);
                 };
@@ -763,12 +1251,9 @@ impl MirLowerCtx<'_> {
                 self.push_assignment(
                     current,
                     place,
-                    Rvalue::CheckedBinaryOp(
+                    Rvalue::BinaryOp(
                         match op {
-                            hir_def::expr::BinaryOp::LogicOp(op) => match op {
-                                hir_def::expr::LogicOp::And => BinOp::BitAnd, // FIXME: make these short circuit
-                                hir_def::expr::LogicOp::Or => BinOp::BitOr,
-                            },
+                            hir_def::expr::BinaryOp::LogicOp(_) => unreachable!(), // handled above
                             hir_def::expr::BinaryOp::ArithOp(op) => BinOp::from(op),
                             hir_def::expr::BinaryOp::CmpOp(op) => BinOp::from(op),
                             hir_def::expr::BinaryOp::Assignment { .. } => unreachable!(), // handled above
@@ -809,21 +1294,60 @@ impl MirLowerCtx<'_> {
                     place,
                     Rvalue::Aggregate(
                         AggregateKind::Adt(st.into(), subst.clone()),
-                        self.db.struct_data(st).variant_data.fields().iter().map(|x| {
-                            let o = match x.1.name.as_str() {
-                                Some("start") => lp.take(),
-                                Some("end") => rp.take(),
-                                Some("exhausted") => Some(Operand::from_bytes(vec![0], TyBuilder::bool())),
-                                _ => None,
-                            };
-                            o.ok_or(MirLowerError::UnresolvedField)
-                        }).collect::<Result<_>>()?,
+                        self.db
+                            .struct_data(st)
+                            .variant_data
+                            .fields()
+                            .iter()
+                            .map(|x| {
+                                let o = match x.1.name.as_str() {
+                                    Some("start") => lp.take(),
+                                    Some("end") => rp.take(),
+                                    Some("exhausted") => {
+                                        Some(Operand::from_bytes(vec![0], TyBuilder::bool()))
+                                    }
+                                    _ => None,
+                                };
+                                o.ok_or(MirLowerError::UnresolvedField(expr_id.into()))
+                            })
+                            .collect::<Result<_>>()?,
                     ),
                     expr_id.into(),
                 );
                 Ok(Some(current))
-            },
-            Expr::Closure { .. } => not_supported!("closure"),
+            }
+            Expr::Closure { .. } => {
+                let ty = self.expr_ty(expr_id);
+                let TyKind::Closure(id, _) = ty.kind(Interner) else {
+                    return Err(MirLowerError::TypeError("closure expression without closure type"));
+                };
+                let (captures, _) = closure_captures(self.db, *id);
+                let mut operands = vec![];
+                for capture in captures {
+                    let binding_place = self.binding_place(capture.binding);
+                    let operand = match capture.kind {
+                        CaptureKind::ByRef(m) => {
+                            let temp: Place = self.temp(capture.ty, TempReason::Operand)?.into();
+                            self.push_assignment(
+                                current,
+                                temp.clone(),
+                                Rvalue::Ref(BorrowKind::from_chalk(m), binding_place),
+                                expr_id.into(),
+                            );
+                            Operand::Move(temp)
+                        }
+                        CaptureKind::ByValue => self.push_move_or_copy(binding_place, &capture.ty),
+                    };
+                    operands.push(operand);
+                }
+                self.push_assignment(
+                    current,
+                    place,
+                    Rvalue::Aggregate(AggregateKind::Closure(ty), operands),
+                    expr_id.into(),
+                );
+                Ok(Some(current))
+            }
             Expr::Tuple { exprs, is_assignee_expr: _ } => {
                 let Some(values) = exprs
                         .iter()
@@ -838,10 +1362,7 @@ impl MirLowerCtx<'_> {
                 else {
                     return Ok(None);
                 };
-                let r = Rvalue::Aggregate(
-                    AggregateKind::Tuple(self.expr_ty(expr_id)),
-                    values,
-                );
+                let r = Rvalue::Aggregate(AggregateKind::Tuple(self.expr_ty(expr_id)), values);
                 self.push_assignment(current, place, r, expr_id.into());
                 Ok(Some(current))
             }
@@ -868,14 +1389,29 @@ impl MirLowerCtx<'_> {
                     else {
                         return Ok(None);
                     };
-                    let r = Rvalue::Aggregate(
-                        AggregateKind::Array(elem_ty),
-                        values,
-                    );
+                    let r = Rvalue::Aggregate(AggregateKind::Array(elem_ty), values);
                     self.push_assignment(current, place, r, expr_id.into());
                     Ok(Some(current))
                 }
-                Array::Repeat { .. } => not_supported!("array repeat"),
+                Array::Repeat { initializer, .. } => {
+                    // The length is taken from the type, where it stays a const parameter in
+                    // generic bodies.
+                    let len = match &self.expr_ty(expr_id).data(Interner).kind {
+                        TyKind::Array(_, len) => len.clone(),
+                        _ => {
+                            return Err(MirLowerError::TypeError(
+                                "Array repeat expression with non array type",
+                            ))
+                        }
+                    };
+                    let Some((init, current)) =
+                        self.lower_expr_to_some_operand(*initializer, current)?
+                    else {
+                        return Ok(None);
+                    };
+                    self.push_assignment(current, place, Rvalue::Repeat(init, len), expr_id.into());
+                    Ok(Some(current))
+                }
             },
             Expr::Literal(l) => {
                 let ty = self.expr_ty(expr_id);
@@ -884,7 +1420,64 @@ impl MirLowerCtx<'_> {
                 Ok(Some(current))
             }
             Expr::Underscore => not_supported!("underscore"),
+            Expr::InlineAsm { .. } => Err(MirLowerError::InlineAsm),
+            Expr::OffsetOf { container, fields } => {
+                let offset = self.lower_offset_of(expr_id, container, fields)?;
+                let ty = self.expr_ty(expr_id);
+                let size = self.layout_of(&ty)?.size.bytes_usize();
+                let op = Operand::from_bytes(offset.to_le_bytes()[0..size].to_vec(), ty);
+                self.push_assignment(current, place, op.into(), expr_id.into());
+                Ok(Some(current))
+            }
+        }
+    }
+
+    /// Computes the value of `offset_of!` from the layouts of the container and of the types of the
+    /// fields on the way. The result is a constant, so no code is needed for it.
+    fn lower_offset_of(
+        &self,
+        expr_id: ExprId,
+        container: &TypeRef,
+        fields: &[Name],
+    ) -> Result<usize> {
+        let resolver = self.resolver_for_expr(expr_id);
+        let mut ty = crate::lower::TyLoweringContext::new(self.db, &resolver).lower_ty(container);
+        let mut offset = 0;
+        for field in fields {
+            let layout = self.layout_of(&ty)?;
+            let (index, field_ty) = match ty.kind(Interner) {
+                TyKind::Adt(chalk_ir::AdtId(adt), subst) => {
+                    let variant: VariantId = match *adt {
+                        AdtId::StructId(s) => s.into(),
+                        AdtId::UnionId(u) => u.into(),
+                        AdtId::EnumId(_) => not_supported!("offset_of on enum variants"),
+                    };
+                    let field = variant
+                        .variant_data(self.db.upcast())
+                        .field(field)
+                        .ok_or(MirLowerError::UnresolvedField(expr_id.into()))?;
+                    let field_ty =
+                        self.db.field_types(variant)[field].clone().substitute(Interner, subst);
+                    (u32::from(field.into_raw()) as usize, field_ty)
+                }
+                TyKind::Tuple(_, subst) => {
+                    let index = field
+                        .as_tuple_index()
+                        .ok_or(MirLowerError::UnresolvedField(expr_id.into()))?;
+                    let field_ty = subst
+                        .as_slice(Interner)
+                        .get(index)
+                        .ok_or(MirLowerError::UnresolvedField(expr_id.into()))?
+                        .assert_ty_ref(Interner)
+                        .clone();
+                    (index, field_ty)
+                }
+                _ => return Err(MirLowerError::TypeError("offset_of on a type without fields")),
+            };
+            offset += layout.fields.offset(index).bytes_usize();
+            ty = field_ty;
         }
+        Ok(offset)
     }
 
     fn push_field_projection(&self, place: &mut Place, expr_id: ExprId) -> Result<()> {
@@ -895,8 +1488,13 @@ impl MirLowerCtx<'_> {
                     .ok_or(MirLowerError::TypeError("named field on tuple"))?;
                 place.projection.push(ProjectionElem::TupleField(index))
             } else {
-                let field =
-                    self.infer.field_resolution(expr_id).ok_or(MirLowerError::UnresolvedField)?;
+                let field = self
+                    .infer
+                    .field_resolution(expr_id)
+                    .ok_or(MirLowerError::UnresolvedField(expr_id.into()))?;
+                if let VariantId::EnumVariantId(variant) = field.parent {
+                    place.projection.push(ProjectionElem::Downcast(variant));
+                }
                 place.projection.push(ProjectionElem::Field(field));
             }
         } else {
@@ -906,9 +1504,7 @@ impl MirLowerCtx<'_> {
     }
 
     fn lower_literal_to_operand(&mut self, ty: Ty, l: &Literal) -> Result<Operand> {
-        let size = layout_of_ty(self.db, &ty, self.owner.module(self.db.upcast()).krate())?
-            .size
-            .bytes_usize();
+        let size = self.layout_of(&ty)?.size.bytes_usize();
         let bytes = match l {
             hir_def::expr::Literal::String(b) => {
                 let b = b.as_bytes();
@@ -922,15 +1518,29 @@ impl MirLowerCtx<'_> {
             hir_def::expr::Literal::ByteString(b) => {
                 let mut data = vec![];
                 data.extend(0usize.to_le_bytes());
-                data.extend(b.len().to_le_bytes());
+                // Byte strings are `&[u8; N]` and so thin pointers, unless they are typed as `&[u8]`
+                // (which can happen in patterns) where we need the length as the metadata.
+                if matches!(ty.as_reference().map(|x| x.0.kind(Interner)), Some(TyKind::Slice(_))) {
+                    data.extend(b.len().to_le_bytes());
+                }
                 let mut mm = MemoryMap::default();
                 mm.insert(0, b.to_vec());
                 return Ok(Operand::from_concrete_const(data, mm, ty));
             }
             hir_def::expr::Literal::Char(c) => u32::from(*c).to_le_bytes().into(),
             hir_def::expr::Literal::Bool(b) => vec![*b as u8],
-            hir_def::expr::Literal::Int(x, _) => x.to_le_bytes()[0..size].into(),
-            hir_def::expr::Literal::Uint(x, _) => x.to_le_bytes()[0..size].into(),
+            // Integer literals are stored as 128-bit values, which are truncated to the size of the
+            // type. Only a type error can make the type wider than that.
+            hir_def::expr::Literal::Int(x, _) => x
+                .to_le_bytes()
+                .get(0..size)
+                .ok_or(MirLowerError::TypeError("integer literal wider than 128 bits"))?
+                .into(),
+            hir_def::expr::Literal::Uint(x, _) => x
+                .to_le_bytes()
+                .get(0..size)
+                .ok_or(MirLowerError::TypeError("integer literal wider than 128 bits"))?
+                .into(),
             hir_def::expr::Literal::Float(f, _) => match size {
                 8 => f.into_f64().to_le_bytes().into(),
                 4 => f.into_f32().to_le_bytes().into(),
@@ -943,7 +1553,24 @@ impl MirLowerCtx<'_> {
     }
 
     fn new_basic_block(&mut self) -> BasicBlockId {
-        self.result.basic_blocks.alloc(BasicBlock::default())
+        let block = self.result.basic_blocks.alloc(BasicBlock::default());
+        self.result.source_map.push_block(self.current_source, block);
+        block
+    }
+
+    fn new_cleanup_block(&mut self) -> BasicBlockId {
+        let block =
+            self.result.basic_blocks.alloc(BasicBlock { is_cleanup: true, ..Default::default() });
+        self.result.source_map.push_block(self.current_source, block);
+        block
+    }
+
+    /// Runs `f` with `source` as the expression or pattern being lowered.
+    fn with_source<T>(&mut self, source: MirSpan, f: impl FnOnce(&mut Self) -> T) -> T {
+        let prev = std::mem::replace(&mut self.current_source, source);
+        let result = f(self);
+        self.current_source = prev;
+        result
     }
 
     fn lower_const(
@@ -952,9 +1579,18 @@ impl MirLowerCtx<'_> {
         prev_block: BasicBlockId,
         place: Place,
         subst: Substitution,
+        ty: Ty,
         span: MirSpan,
     ) -> Result<()> {
-        let c = self.db.const_eval(const_id, subst)?;
+        let c = if collect_placeholders(&subst, self.db).is_empty() {
+            let env = self.db.trait_environment_for_body(self.owner);
+            let (const_id, subst) = lookup_impl_const(self.db, env, const_id, subst);
+            self.db.const_eval(const_id, subst)?
+        } else {
+            // The value depends on the generic parameters of this body, e.g. `T::SIZE`, so we
+            // leave it to the interpreter, which knows them.
+            intern_const_scalar(ConstScalar::UnevaluatedConst(const_id, subst), ty)
+        };
         self.write_const_to_place(c, prev_block, place, span)
     }
 
@@ -1008,9 +1644,35 @@ impl MirLowerCtx<'_> {
         func: Operand,
         args: impl Iterator<Item = ExprId>,
         place: Place,
-        mut current: BasicBlockId,
+        current: BasicBlockId,
         is_uninhabited: bool,
+        span: MirSpan,
     ) -> Result<Option<BasicBlockId>> {
+        let Some((args, current)) = self.lower_args(args, current)? else {
+            return Ok(None);
+        };
+        self.lower_call(func, args, place, current, is_uninhabited, span)
+    }
+
+    /// Checks that a call of `callee_ty` with `found` arguments matches its signature, so that a
+    /// call with the wrong arity, which type inference has already reported, fails lowering with
+    /// a clear error instead of confusing the evaluator.
+    fn check_argument_count(&self, callee_ty: &Ty, found: usize) -> Result<()> {
+        let Some(sig) = callee_ty.callable_sig(self.db) else {
+            return Ok(());
+        };
+        let expected = sig.params().len();
+        if found == expected || (sig.is_varargs && found > expected) {
+            return Ok(());
+        }
+        Err(MirLowerError::ArgumentCountMismatch { expected, found })
+    }
+
+    fn lower_args(
+        &mut self,
+        args: impl Iterator<Item = ExprId>,
+        mut current: BasicBlockId,
+    ) -> Result<Option<(Vec<Operand>, BasicBlockId)>> {
         let Some(args) = args
             .map(|arg| {
                 if let Some((temp, c)) = self.lower_expr_to_some_operand(arg, current)? {
@@ -1024,7 +1686,231 @@ impl MirLowerCtx<'_> {
         else {
             return Ok(None);
         };
-        self.lower_call(func, args, place, current, is_uninhabited)
+        Ok(Some((args, current)))
+    }
+
+    /// Lowers the receiver of a method call. A receiver which is autoreferenced but is not a place,
+    /// like `make().get()`, is materialized into a temporary that the reference borrows, which is
+    /// dropped at the end of the enclosing statement.
+    fn lower_method_receiver(
+        &mut self,
+        receiver: ExprId,
+        current: BasicBlockId,
+    ) -> Result<Option<(Operand, BasicBlockId)>> {
+        let adjustments = self.infer.expr_adjustments.get(&receiver).map_or(&[][..], |x| &**x);
+        let (Some((Adjustment { kind: Adjust::Borrow(AutoBorrow::Ref(m)), target }, [])), false) =
+            (adjustments.split_last(), self.is_place_expr(receiver))
+        else {
+            return self.lower_expr_to_some_operand(receiver, current);
+        };
+        let temp: Place = self.temp(self.expr_ty(receiver), TempReason::Operand)?.into();
+        let Some(current) = self.lower_expr_to_place_without_adjust(receiver, temp.clone(), current)?
+        else {
+            return Ok(None);
+        };
+        self.push_drop_scope_temporary(temp.local);
+        let borrow: Place = self.temp(target.clone(), TempReason::Operand)?.into();
+        let bk = BorrowKind::from_chalk(*m);
+        self.push_assignment(current, borrow.clone(), Rvalue::Ref(bk, temp), receiver.into());
+        Ok(Some((Operand::Copy(borrow), current)))
+    }
+
+    /// Lowers the scrutinee of a `match` which is not a place into a temporary. Like in rustc, the
+    /// temporary lives until the end of the enclosing statement, so that it outlives all the arms,
+    /// whose bindings may borrow it.
+    fn lower_scrutinee_to_temporary(
+        &mut self,
+        scrutinee: ExprId,
+        current: BasicBlockId,
+    ) -> Result<Option<(Place, BasicBlockId)>> {
+        let temp: Place =
+            self.temp(self.expr_ty_after_adjustments(scrutinee), TempReason::Operand)?.into();
+        self.push_statement(
+            current,
+            StatementKind::StorageLive(temp.local).with_span(scrutinee.into()),
+        );
+        let Some(current) = self.lower_expr_to_place(scrutinee, temp.clone(), current)? else {
+            return Ok(None);
+        };
+        self.push_drop_scope_temporary(temp.local);
+        if let Some(scope) = self.drop_scopes.last_mut() {
+            scope.storage.push((temp.local, MirSpan::Unknown));
+        }
+        Ok(Some((temp, current)))
+    }
+
+    /// Whether `expr` denotes a place, instead of a value which needs a temporary to be borrowed.
+    fn is_place_expr(&self, expr: ExprId) -> bool {
+        match &self.body.exprs[expr] {
+            Expr::Path(p) => {
+                let resolver = self.resolver_for_expr(expr);
+                matches!(
+                    resolver.resolve_path_in_value_ns_fully(self.db.upcast(), p),
+                    Some(ValueNs::LocalBinding(_) | ValueNs::StaticId(_))
+                )
+            }
+            Expr::Field { .. }
+            | Expr::Index { .. }
+            | Expr::UnaryOp { op: hir_def::expr::UnaryOp::Deref, .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Lowers a call of a closure `callee(args)` to a call of the method of the `Fn*` trait the
+    /// closure implements, like `Fn::call(&callee, (args,))`.
+    fn lower_closure_call(
+        &mut self,
+        closure_ty: Ty,
+        callee: ExprId,
+        args: &[ExprId],
+        place: Place,
+        current: BasicBlockId,
+        expr_id: ExprId,
+    ) -> Result<Option<BasicBlockId>> {
+        let TyKind::Closure(closure, _) = closure_ty.kind(Interner) else {
+            return Err(MirLowerError::TypeError("closure call on a non closure type"));
+        };
+        let (_, fn_trait) = closure_captures(self.db, *closure);
+        let krate = self.owner.module(self.db.upcast()).krate();
+        let method = fn_trait
+            .get_id(self.db, krate)
+            .and_then(|trait_| self.db.trait_data(trait_).method_by_name(&fn_trait.method_name()))
+            .ok_or(MirLowerError::LangItemNotFound(fn_trait.lang_item()))?;
+        let sig = closure_ty
+            .callable_sig(self.db)
+            .ok_or(MirLowerError::TypeError("closure type without a signature"))?;
+        let tuple_ty = TyKind::Tuple(
+            sig.params().len(),
+            Substitution::from_iter(Interner, sig.params().iter().cloned()),
+        )
+        .intern(Interner);
+        let func = Operand::from_bytes(
+            vec![],
+            TyKind::FnDef(
+                CallableDefId::FunctionId(method).to_chalk(self.db),
+                Substitution::from_iter(Interner, [closure_ty.clone(), tuple_ty.clone()]),
+            )
+            .intern(Interner),
+        );
+        let Some((callee_place, current)) = self.lower_expr_as_place(current, callee, true)? else {
+            return Ok(None);
+        };
+        let callee = match fn_trait {
+            FnTrait::FnOnce => self.push_move_or_copy(callee_place, &closure_ty),
+            FnTrait::FnMut | FnTrait::Fn => {
+                let m = match fn_trait {
+                    FnTrait::FnMut => Mutability::Mut,
+                    _ => Mutability::Not,
+                };
+                let ref_ty = TyKind::Ref(m, static_lifetime(), closure_ty).intern(Interner);
+                let temp: Place = self.temp(ref_ty, TempReason::Operand)?.into();
+                self.push_assignment(
+                    current,
+                    temp.clone(),
+                    Rvalue::Ref(BorrowKind::from_chalk(m), callee_place),
+                    callee.into(),
+                );
+                Operand::Move(temp)
+            }
+        };
+        let Some((tuple, current)) = self.lower_args_as_tuple(args, tuple_ty, current, expr_id.into())? else {
+            return Ok(None);
+        };
+        self.lower_call(
+            func,
+            vec![callee, tuple],
+            place,
+            current,
+            self.is_uninhabited(expr_id),
+            expr_id.into(),
+        )
+    }
+
+    fn lower_args_as_tuple(
+        &mut self,
+        args: &[ExprId],
+        tuple_ty: Ty,
+        mut current: BasicBlockId,
+        span: MirSpan,
+    ) -> Result<Option<(Operand, BasicBlockId)>> {
+        let Some(values) = args
+            .iter()
+            .map(|arg| {
+                let Some((o, c)) = self.lower_expr_to_some_operand(*arg, current)? else {
+                    return Ok(None);
+                };
+                current = c;
+                Ok(Some(o))
+            })
+            .collect::<Result<Option<_>>>()?
+        else {
+            return Ok(None);
+        };
+        let tuple: Place = self.temp(tuple_ty.clone(), TempReason::Operand)?.into();
+        self.push_assignment(
+            current,
+            tuple.clone(),
+            Rvalue::Aggregate(AggregateKind::Tuple(tuple_ty), values),
+            span,
+        );
+        Ok(Some((Operand::Move(tuple), current)))
+    }
+
+    /// Lowers `lhs op= rhs`. The place of `lhs` is evaluated once, both when the operation is
+    /// built in and when it calls the method of the `*Assign` trait.
+    fn lower_compound_assignment(
+        &mut self,
+        current: BasicBlockId,
+        place: Place,
+        expr_id: ExprId,
+        lhs: ExprId,
+        op: ArithOp,
+        rhs: ExprId,
+    ) -> Result<Option<BasicBlockId>> {
+        let is_builtin = |ty: Ty| matches!(ty.kind(Interner), TyKind::Scalar(_));
+        if let Some((func_id, generic_args)) = self.infer.method_resolution(expr_id) {
+            if !is_builtin(self.expr_ty(lhs)) || !is_builtin(self.expr_ty(rhs)) {
+                let ty = chalk_ir::TyKind::FnDef(
+                    CallableDefId::FunctionId(func_id).to_chalk(self.db),
+                    generic_args,
+                )
+                .intern(Interner);
+                let func = Operand::from_bytes(vec![], ty);
+                // Inference adjusts `lhs` to the `&mut` borrow the method takes.
+                let Some((lhs_op, current)) = self.lower_expr_to_some_operand(lhs, current)? else {
+                    return Ok(None);
+                };
+                let Some((rhs_op, current)) = self.lower_expr_to_some_operand(rhs, current)? else {
+                    return Ok(None);
+                };
+                return self.lower_call(
+                    func,
+                    vec![lhs_op, rhs_op],
+                    place,
+                    current,
+                    self.is_uninhabited(expr_id),
+                    expr_id.into(),
+                );
+            }
+        }
+        // Like in rustc, the right hand side of a built in operation is evaluated first.
+        let Some((rhs_op, current)) = self.lower_expr_to_some_operand(rhs, current)? else {
+            return Ok(None);
+        };
+        // The `&mut` adjustment of `lhs` is for the trait method, which we don't call.
+        let Some((lhs_place, current)) =
+            self.lower_expr_as_place_without_adjust(current, lhs, false)?
+        else {
+            return Ok(None);
+        };
+        let lhs_op = Operand::Copy(lhs_place.clone());
+        self.push_assignment(
+            current,
+            lhs_place,
+            Rvalue::BinaryOp(BinOp::from(op), lhs_op, rhs_op),
+            expr_id.into(),
+        );
+        Ok(Some(current))
     }
 
     fn lower_call(
@@ -1034,8 +1920,23 @@ impl MirLowerCtx<'_> {
         place: Place,
         current: BasicBlockId,
         is_uninhabited: bool,
+        span: MirSpan,
     ) -> Result<Option<BasicBlockId>> {
+        // Like rustc, we lower the `offset` intrinsic to the `Offset` operation, so that the pointer
+        // arithmetic is visible in MIR.
+        if let [ptr, count] = &args[..] {
+            if self.is_intrinsic_named(&func, "offset") {
+                self.push_assignment(
+                    current,
+                    place,
+                    Rvalue::BinaryOp(BinOp::Offset, ptr.clone(), count.clone()),
+                    span,
+                );
+                return Ok(Some(current));
+            }
+        }
         let b = if is_uninhabited { None } else { Some(self.new_basic_block()) };
+        let cleanup = self.cleanup_block(&self.live_drop_locals(0));
         self.set_terminator(
             current,
             Terminator::Call {
@@ -1043,13 +1944,30 @@ impl MirLowerCtx<'_> {
                 args,
                 destination: place,
                 target: b,
-                cleanup: None,
+                cleanup,
                 from_hir_call: true,
+                span,
             },
         );
         Ok(b)
     }
 
+    fn is_intrinsic_named(&self, func: &Operand, name: &str) -> bool {
+        let Operand::Constant(c) = func else {
+            return false;
+        };
+        let TyKind::FnDef(def, _) = c.data(Interner).ty.kind(Interner) else {
+            return false;
+        };
+        match self.db.lookup_intern_callable_def((*def).into()) {
+            CallableDefId::FunctionId(f) => {
+                is_intrinsic(self.db, f)
+                    && self.db.function_data(f).name.as_text().as_deref() == Some(name)
+            }
+            _ => false,
+        }
+    }
+
     fn is_unterminated(&mut self, source: BasicBlockId) -> bool {
         self.result.basic_blocks[source].terminator.is_none()
     }
@@ -1066,6 +1984,14 @@ impl MirLowerCtx<'_> {
         self.infer[e].clone()
     }
 
+    /// The resolver of the scope of `expr`, which is inside the scope of `self.resolver`.
+    fn resolver_for_expr(&self, expr: ExprId) -> Resolver {
+        let mut resolver = self.resolver.clone();
+        // The copy is dropped instead of being reset, so the guard isn't needed.
+        let _ = resolver.update_to_inner_scope(self.db.upcast(), self.owner, expr);
+        resolver
+    }
+
     fn expr_ty_after_adjustments(&self, e: ExprId) -> Ty {
         let mut ty = None;
         if let Some(x) = self.infer.expr_adjustments.get(&e) {
@@ -1077,7 +2003,10 @@ impl MirLowerCtx<'_> {
     }
 
     fn push_statement(&mut self, block: BasicBlockId, statement: Statement) {
-        self.result.basic_blocks[block].statements.push(statement);
+        let statements = &mut self.result.basic_blocks[block].statements;
+        let location = Location { block, statement_index: statements.len() };
+        statements.push(statement);
+        self.result.source_map.push_statement(self.current_source, location);
     }
 
     fn push_assignment(
@@ -1094,48 +2023,72 @@ impl MirLowerCtx<'_> {
         match &self.discr_temp {
             Some(x) => x.clone(),
             None => {
-                let tmp: Place =
-                    self.temp(TyBuilder::discr_ty()).expect("discr_ty is never unsized").into();
+                let tmp: Place = self
+                    .temp(TyBuilder::discr_ty(), TempReason::Discriminant)
+                    .expect("discr_ty is never unsized")
+                    .into();
                 self.discr_temp = Some(tmp.clone());
                 tmp
             }
         }
     }
 
+    /// Lowers a loop, or a labeled block if `is_block` is set, whose body is lowered by `f` from
+    /// the `begin` block. The loop is the target of the `break`s and `continue`s lowered by `f`
+    /// until it returns, also when it fails.
     fn lower_loop(
         &mut self,
         prev_block: BasicBlockId,
         place: Place,
         label: Option<LabelId>,
+        is_block: bool,
         f: impl FnOnce(&mut MirLowerCtx<'_>, BasicBlockId) -> Result<()>,
     ) -> Result<Option<BasicBlockId>> {
         let begin = self.new_basic_block();
-        let prev = mem::replace(
-            &mut self.current_loop_blocks,
-            Some(LoopBlocks { begin, end: None, place }),
-        );
-        let prev_label = if let Some(label) = label {
-            // We should generate the end now, to make sure that it wouldn't change later. It is
-            // bad as we may emit end (unneccessary unreachable block) for unterminating loop, but
-            // it should not affect correctness.
-            self.current_loop_end()?;
-            self.labeled_loop_blocks.insert(
-                self.body.labels[label].name.clone(),
-                self.current_loop_blocks.as_ref().unwrap().clone(),
-            )
-        } else {
-            None
-        };
+        let depth = self.loop_blocks.len();
+        self.loop_blocks.push(LoopBlocks {
+            begin,
+            end: None,
+            place,
+            drop_scope_index: self.drop_scopes.len(),
+            label: label.map(|label| self.body.labels[label].name.clone()),
+            is_block,
+        });
         self.set_goto(prev_block, begin);
-        f(self, begin)?;
-        let my = mem::replace(&mut self.current_loop_blocks, prev)
-            .ok_or(MirLowerError::ImplementationError("current_loop_blocks is corrupt"))?;
-        if let Some(prev) = prev_label {
-            self.labeled_loop_blocks.insert(self.body.labels[label.unwrap()].name.clone(), prev);
+        let result = f(self, begin);
+        if self.loop_blocks.len() != depth + 1 {
+            return Err(MirLowerError::ImplementationError("loop_blocks is corrupt"));
         }
+        let my = self.loop_blocks.pop().unwrap();
+        result?;
         Ok(my.end)
     }
 
+    /// The index in `loop_blocks` of the target of a `break` or `continue` with `label`, which is
+    /// the innermost loop without one. Fails with `without_loop` if there is no such loop.
+    fn loop_index(&self, label: Option<&Name>, without_loop: MirLowerError) -> Result<usize> {
+        match label {
+            Some(label) => self
+                .loop_blocks
+                .iter()
+                .rposition(|x| x.label.as_ref() == Some(label))
+                .ok_or(MirLowerError::UnresolvedLabel),
+            None => self.loop_blocks.iter().rposition(|x| !x.is_block).ok_or(without_loop),
+        }
+    }
+
+    /// The end block of the loop at `index` in `loop_blocks`, which is allocated on first use.
+    fn loop_end(&mut self, index: usize) -> BasicBlockId {
+        match self.loop_blocks[index].end {
+            Some(end) => end,
+            None => {
+                let end = self.new_basic_block();
+                self.loop_blocks[index].end = Some(end);
+                end
+            }
+        }
+    }
+
     fn has_adjustments(&self, expr_id: ExprId) -> bool {
         !self.infer.expr_adjustments.get(&expr_id).map(|x| x.is_empty()).unwrap_or(true)
     }
@@ -1157,24 +2110,101 @@ impl MirLowerCtx<'_> {
         }
     }
 
-    fn current_loop_end(&mut self) -> Result<BasicBlockId> {
-        let r = match self
-            .current_loop_blocks
-            .as_mut()
-            .ok_or(MirLowerError::ImplementationError("Current loop access out of loop"))?
-            .end
-        {
-            Some(x) => x,
-            None => {
-                let s = self.new_basic_block();
-                self.current_loop_blocks
-                    .as_mut()
-                    .ok_or(MirLowerError::ImplementationError("Current loop access out of loop"))?
-                    .end = Some(s);
-                s
+    /// Lowers `cond`, the condition of an `if` or a `while`, returning the blocks where it holds and
+    /// where it fails, or `None` for the ones which are unreachable. `&&` and `||` short circuit,
+    /// and the bindings of the `let`s chained with `&&` are bound in the block where it holds.
+    fn lower_condition(
+        &mut self,
+        cond: ExprId,
+        current: BasicBlockId,
+    ) -> Result<(Option<BasicBlockId>, Option<BasicBlockId>)> {
+        match self.body.exprs[cond] {
+            Expr::BinaryOp { lhs, rhs, op: Some(hir_def::expr::BinaryOp::LogicOp(op)) } => {
+                let (then_lhs, else_lhs) = self.lower_condition(lhs, current)?;
+                Ok(match op {
+                    hir_def::expr::LogicOp::And => {
+                        let Some(then_lhs) = then_lhs else {
+                            return Ok((None, else_lhs));
+                        };
+                        let (then_target, else_rhs) = self.lower_condition(rhs, then_lhs)?;
+                        (then_target, self.merge_blocks(else_lhs, else_rhs))
+                    }
+                    hir_def::expr::LogicOp::Or => {
+                        let Some(else_lhs) = else_lhs else {
+                            return Ok((then_lhs, None));
+                        };
+                        let (then_rhs, else_target) = self.lower_condition(rhs, else_lhs)?;
+                        (self.merge_blocks(then_lhs, then_rhs), else_target)
+                    }
+                })
+            }
+            Expr::Let { pat, expr } => {
+                let Some((cond_place, current)) = self.lower_expr_as_place(current, expr, true)? else {
+                    return Ok((None, None));
+                };
+                let (then_target, else_target) = self.pattern_match(
+                    current,
+                    None,
+                    cond_place,
+                    self.expr_ty_after_adjustments(expr),
+                    pat,
+                    BindingAnnotation::Unannotated,
+                )?;
+                Ok((Some(then_target), else_target))
+            }
+            _ => {
+                let Some((discr, current)) = self.lower_expr_to_some_operand(cond, current)? else {
+                    return Ok((None, None));
+                };
+                let then_target = self.new_basic_block();
+                let else_target = self.new_basic_block();
+                self.set_terminator(
+                    current,
+                    Terminator::SwitchInt {
+                        discr,
+                        targets: SwitchTargets::static_if(1, then_target, else_target),
+                    },
+                );
+                Ok((Some(then_target), Some(else_target)))
             }
+        }
+    }
+
+    /// Patterns of the `let`s chained with `&&` in the condition `cond`.
+    fn condition_let_pats(&self, cond: ExprId) -> Vec<PatId> {
+        match &self.body.exprs[cond] {
+            Expr::BinaryOp {
+                lhs,
+                rhs,
+                op: Some(hir_def::expr::BinaryOp::LogicOp(hir_def::expr::LogicOp::And)),
+            } => {
+                let mut pats = self.condition_let_pats(*lhs);
+                pats.extend(self.condition_let_pats(*rhs));
+                pats
+            }
+            Expr::Let { pat, .. } => vec![*pat],
+            _ => vec![],
+        }
+    }
+
+    /// The end block of the innermost loop or labeled block, i.e. the one being lowered.
+    fn current_loop_end(&mut self) -> Result<BasicBlockId> {
+        let index = self
+            .loop_blocks
+            .len()
+            .checked_sub(1)
+            .ok_or(MirLowerError::ImplementationError("Current loop access out of loop"))?;
+        Ok(self.loop_end(index))
+    }
+
+    /// Whether `expr` is a call of an async function or method.
+    fn is_async_fn_call(&self, expr: ExprId) -> bool {
+        let func = match &self.body.exprs[expr] {
+            Expr::Call { callee, .. } => self.expr_ty_after_adjustments(*callee).as_fn_def(self.db),
+            Expr::MethodCall { .. } => self.infer.method_resolution(expr).map(|(func, _)| func),
+            _ => None,
         };
-        Ok(r)
+        func.map_or(false, |func| self.db.function_data(func).has_async_kw())
     }
 
     fn is_uninhabited(&self, expr_id: ExprId) -> bool {
@@ -1213,58 +2243,380 @@ impl MirLowerCtx<'_> {
         self.push_statement(current, StatementKind::StorageLive(l).with_span(span));
     }
 
+    /// Pushes `StorageDead` statements for the bindings of `pats`, in reverse declaration order.
+    fn push_storage_dead(&mut self, current: BasicBlockId, pats: &[PatId]) {
+        let mut bindings = vec![];
+        for &pat in pats {
+            self.body.walk_bindings_in_pat(pat, |b| bindings.push((b, pat)));
+        }
+        for (b, pat) in bindings.into_iter().rev() {
+            let l = self.result.binding_locals[b];
+            self.push_statement(
+                current,
+                StatementKind::StorageDead(l).with_span(MirSpan::PatId(pat)),
+            );
+        }
+    }
+
     fn resolve_lang_item(&self, item: LangItem) -> Result<LangItemTarget> {
         let crate_id = self.owner.module(self.db.upcast()).krate();
         self.db.lang_item(crate_id, item).ok_or(MirLowerError::LangItemNotFound(item))
     }
 
+    /// Lowers the leading arms of a match on an integer, `char` or `bool` whose patterns are
+    /// literals, or of a match on an enum whose patterns are unit variants, (or or-patterns of
+    /// those) without guards into a single `SwitchInt`, instead of comparing the scrutinee against
+    /// each of them in turn. Returns the block in which the rest of the arms should be matched, and
+    /// the number of arms that were handled.
+    fn lower_match_arms_as_switch(
+        &mut self,
+        current: BasicBlockId,
+        cond_place: &Place,
+        cond_ty: &Ty,
+        arms: &[MatchArm],
+        place: &Place,
+        end: &mut Option<BasicBlockId>,
+    ) -> Result<(BasicBlockId, usize)> {
+        let scrutinee = match cond_ty.kind(Interner) {
+            TyKind::Scalar(
+                chalk_ir::Scalar::Int(_)
+                | chalk_ir::Scalar::Uint(_)
+                | chalk_ir::Scalar::Char
+                | chalk_ir::Scalar::Bool,
+            ) => SwitchScrutinee::Scalar { size: self.layout_of(cond_ty)?.size.bytes_usize() },
+            TyKind::Adt(chalk_ir::AdtId(AdtId::EnumId(_)), _) => SwitchScrutinee::Discriminant,
+            _ => return Ok((current, 0)),
+        };
+        let mut arm_values = vec![];
+        for arm in arms {
+            if arm.guard.is_some() {
+                break;
+            }
+            let mut values = vec![];
+            if !self.switch_values_of_pat(arm.pat, &scrutinee, &mut values)? {
+                break;
+            }
+            arm_values.push(values);
+        }
+        // A single value is tested just as well by matching the pattern.
+        if arm_values.iter().map(|x| x.len()).sum::<usize>() < 2 {
+            return Ok((current, 0));
+        }
+        let discr = match scrutinee {
+            SwitchScrutinee::Scalar { .. } => cond_place.clone(),
+            SwitchScrutinee::Discriminant => {
+                let tmp = self.discr_temp_place();
+                self.push_assignment(
+                    current,
+                    tmp.clone(),
+                    Rvalue::Discriminant(cond_place.clone()),
+                    arms[0].pat.into(),
+                );
+                tmp
+            }
+        };
+        let mut targets = vec![];
+        let mut seen = FxHashSet::default();
+        for (arm, values) in arms.iter().zip(&arm_values) {
+            let then = self.new_basic_block();
+            // Values already matched by an earlier arm can never reach this one.
+            targets.extend(values.iter().filter(|v| seen.insert(**v)).map(|v| (*v, then)));
+            if let Some(block) = self.lower_expr_to_place(arm.expr, place.clone(), then)? {
+                let r = end.get_or_insert_with(|| self.new_basic_block());
+                self.set_goto(block, *r);
+            }
+        }
+        let otherwise = self.new_basic_block();
+        self.set_terminator(
+            current,
+            Terminator::SwitchInt {
+                discr: Operand::Copy(discr),
+                targets: SwitchTargets::new(targets, otherwise),
+            },
+        );
+        Ok((otherwise, arm_values.len()))
+    }
+
+    /// Collects the values of the scrutinee matched by `pat`, as they are seen by `SwitchInt`,
+    /// returning `false` if `pat` is not made of literals or unit variants only.
+    fn switch_values_of_pat(
+        &self,
+        pat: PatId,
+        scrutinee: &SwitchScrutinee,
+        values: &mut Vec<u128>,
+    ) -> Result<bool> {
+        match (&self.body.pats[pat], scrutinee) {
+            (Pat::Lit(l), &SwitchScrutinee::Scalar { size }) => {
+                let value = match &self.body.exprs[*l] {
+                    Expr::Literal(Literal::Int(x, _)) => *x as u128,
+                    Expr::Literal(Literal::Uint(x, _)) => *x,
+                    Expr::Literal(Literal::Char(c)) => u32::from(*c) as u128,
+                    Expr::Literal(Literal::Bool(b)) => *b as u128,
+                    _ => return Ok(false),
+                };
+                values.push(u128::from_le_bytes(pad16(&value.to_le_bytes()[0..size], false)));
+                Ok(true)
+            }
+            (Pat::Path(_), SwitchScrutinee::Discriminant) => {
+                match self.infer.variant_resolution_for_pat(pat) {
+                    Some(VariantId::EnumVariantId(v)) => {
+                        values.push(self.db.const_eval_discriminant(v)? as u128);
+                        Ok(true)
+                    }
+                    _ => Ok(false),
+                }
+            }
+            (Pat::Or(pats), _) => {
+                for &pat in pats.iter() {
+                    if !self.switch_values_of_pat(pat, scrutinee, values)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
     fn lower_block_to_place(
+        &mut self,
+        block_expr: ExprId,
+        statements: &[hir_def::expr::Statement],
+        current: BasicBlockId,
+        tail: Option<ExprId>,
+        place: Place,
+    ) -> Result<Option<Idx<BasicBlock>>> {
+        let guard = self.resolver.update_to_inner_scope(self.db.upcast(), self.owner, block_expr);
+        self.drop_scopes.push(DropScope::default());
+        let r = self.lower_block_statements_to_place(statements, current, tail, place);
+        let r = match r {
+            Ok(Some(current)) => Ok(Some(self.pop_drop_scope(current))),
+            r => {
+                self.drop_scopes.pop();
+                r
+            }
+        };
+        self.resolver.reset_to_guard(guard);
+        r
+    }
+
+    /// Registers the bindings of `pat` whose values need to be dropped at the end of the current
+    /// scope.
+    // FIXME: bindings of function parameters and match arms are never dropped.
+    fn push_drop_scope_bindings(&mut self, pat: PatId) {
+        let mut locals = vec![];
+        self.body.walk_bindings_in_pat(pat, |b| {
+            let local = self.result.binding_locals[b];
+            let ty = self.result.locals[local].ty.clone();
+            if self.db.needs_drop(ty, self.db.trait_environment_for_body(self.owner)) {
+                locals.push(local);
+            }
+        });
+        if let Some(scope) = self.drop_scopes.last_mut() {
+            scope.locals.extend(locals);
+        }
+    }
+
+    /// Registers a temporary whose value needs to be dropped at the end of the current scope.
+    fn push_drop_scope_temporary(&mut self, local: LocalId) {
+        let ty = self.result.locals[local].ty.clone();
+        if !self.db.needs_drop(ty, self.db.trait_environment_for_body(self.owner)) {
+            return;
+        }
+        if let Some(scope) = self.drop_scopes.last_mut() {
+            scope.locals.push(local);
+        }
+    }
+
+    /// Lowers a statement with `f` in a drop scope of its own, so that the temporaries created in
+    /// it are dropped at its end.
+    fn lower_in_statement_scope(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<Option<BasicBlockId>>,
+    ) -> Result<Option<BasicBlockId>> {
+        self.drop_scopes.push(DropScope::default());
+        match f(self) {
+            Ok(Some(current)) => Ok(Some(self.pop_drop_scope(current))),
+            r => {
+                self.drop_scopes.pop();
+                r
+            }
+        }
+    }
+
+    /// Locals of the drop scopes starting from `scope_index`, from the outermost to the innermost.
+    fn live_drop_locals(&self, scope_index: usize) -> Vec<LocalId> {
+        self.drop_scopes[scope_index..].iter().flat_map(|x| x.locals.iter().copied()).collect()
+    }
+
+    /// Returns the block to unwind to when something panics while `locals` are alive, which drops
+    /// them in reverse order and continues unwinding. A panic in one of these drops aborts. `None`
+    /// if there is nothing to clean up, or if panics abort anyway.
+    fn cleanup_block(&mut self, locals: &[LocalId]) -> Option<BasicBlockId> {
+        let mut cleanup = None;
+        for &local in locals {
+            cleanup = self.cleanup_block_dropping(cleanup, local);
+        }
+        cleanup
+    }
+
+    /// Returns the block to unwind to when `local` is alive in addition to the locals which the
+    /// `cleanup` block drops. The blocks are shared, so each unwind path is only built once.
+    fn cleanup_block_dropping(
+        &mut self,
+        cleanup: Option<BasicBlockId>,
+        local: LocalId,
+    ) -> Option<BasicBlockId> {
+        if self.panic_strategy == PanicStrategy::Abort {
+            return None;
+        }
+        let (resume, abort) = match self.resume_and_abort {
+            Some(it) => it,
+            None => {
+                let resume = self.new_cleanup_block();
+                self.set_terminator(resume, Terminator::Resume);
+                let abort = self.new_cleanup_block();
+                self.set_terminator(abort, Terminator::Abort);
+                *self.resume_and_abort.insert((resume, abort))
+            }
+        };
+        let next = cleanup.unwrap_or(resume);
+        if let Some(&block) = self.cleanup_blocks.get(&(next, local)) {
+            return Some(block);
+        }
+        let block = self.new_cleanup_block();
+        self.set_terminator(
+            block,
+            Terminator::Drop { place: local.into(), target: next, unwind: Some(abort) },
+        );
+        self.cleanup_blocks.insert((next, local), block);
+        Some(block)
+    }
+
+    /// Drops the locals of the drop scopes starting from `scope_index` and ends their storage
+    /// without leaving them, for expressions like `break`, `continue` and `return` that jump out
+    /// of them.
+    fn drop_scopes_from(&mut self, current: BasicBlockId, scope_index: usize) -> BasicBlockId {
+        let locals = self.live_drop_locals(0);
+        let outer_len = self.drop_scopes[..scope_index].iter().map(|x| x.locals.len()).sum();
+        let (outer, locals) = locals.split_at(outer_len);
+        let current = self.emit_drops(current, outer, locals);
+        let storage: Vec<_> = self.drop_scopes[scope_index..]
+            .iter()
+            .rev()
+            .flat_map(|x| x.storage.iter().rev().copied())
+            .collect();
+        for (local, span) in storage {
+            self.push_statement(current, StatementKind::StorageDead(local).with_span(span));
+        }
+        current
+    }
+
+    /// Leaves the innermost drop scope, dropping its locals.
+    fn pop_drop_scope(&mut self, current: BasicBlockId) -> BasicBlockId {
+        let Some(scope) = self.drop_scopes.pop() else {
+            return current;
+        };
+        let outer = self.live_drop_locals(0);
+        let current = self.emit_drops(current, &outer, &scope.locals);
+        for &(local, span) in scope.storage.iter().rev() {
+            self.push_statement(current, StatementKind::StorageDead(local).with_span(span));
+        }
+        current
+    }
+
+    /// Drops `locals` in reverse order, unwinding into the drops of the rest of them and of `outer`
+    /// if one of the drops panics.
+    fn emit_drops(
+        &mut self,
+        mut current: BasicBlockId,
+        outer: &[LocalId],
+        locals: &[LocalId],
+    ) -> BasicBlockId {
+        // The unwind path of the drop of `locals[i]` drops `outer` and `locals[..i]`.
+        let mut unwinds = Vec::with_capacity(locals.len());
+        let mut unwind = self.cleanup_block(outer);
+        for &local in locals {
+            unwinds.push(unwind);
+            unwind = self.cleanup_block_dropping(unwind, local);
+        }
+        for (&local, unwind) in locals.iter().zip(unwinds).rev() {
+            let next = self.new_basic_block();
+            self.set_terminator(
+                current,
+                Terminator::Drop { place: local.into(), target: next, unwind },
+            );
+            current = next;
+        }
+        current
+    }
+
+    fn lower_block_statements_to_place(
         &mut self,
         statements: &[hir_def::expr::Statement],
         mut current: BasicBlockId,
         tail: Option<ExprId>,
         place: Place,
     ) -> Result<Option<Idx<BasicBlock>>> {
+        // Items defined in the block aren't statements of the body, they are only visible through
+        // the block scope of the resolver, so there is nothing to skip here.
         for statement in statements.iter() {
             match statement {
                 hir_def::expr::Statement::Let { pat, initializer, else_branch, type_ref: _ } => {
                     if let Some(expr_id) = initializer {
-                        let else_block;
-                        let Some((init_place, c)) =
-                            self.lower_expr_as_place(current, *expr_id, true)?
+                        let Some(c) = self.lower_in_statement_scope(|this| {
+                            let Some((init_place, current)) =
+                                this.lower_expr_as_place(current, *expr_id, true)?
+                            else {
+                                return Ok(None);
+                            };
+                            let usage = match this.body.pats[*pat] {
+                                Pat::Wild => ValueUsage::Discarded,
+                                Pat::Bind { id, subpat: None } => ValueUsage::Bound(id),
+                                _ => ValueUsage::Used,
+                            };
+                            this.result.source_map.set_place_usage(*expr_id, usage);
+                            let (current, else_block) = this.pattern_match(
+                                current,
+                                None,
+                                init_place,
+                                this.expr_ty_after_adjustments(*expr_id),
+                                *pat,
+                                BindingAnnotation::Unannotated,
+                            )?;
+                            match (else_block, else_branch) {
+                                (None, _) => (),
+                                (Some(else_block), None) => {
+                                    this.set_terminator(else_block, Terminator::Unreachable);
+                                }
+                                (Some(else_block), Some(else_branch)) => {
+                                    if let Some((_, b)) =
+                                        this.lower_expr_as_place(else_block, *else_branch, true)?
+                                    {
+                                        this.set_terminator(b, Terminator::Unreachable);
+                                    }
+                                }
+                            }
+                            Ok(Some(current))
+                        })?
                         else {
                             return Ok(None);
                         };
                         current = c;
-                        (current, else_block) = self.pattern_match(
-                            current,
-                            None,
-                            init_place,
-                            self.expr_ty_after_adjustments(*expr_id),
-                            *pat,
-                            BindingAnnotation::Unannotated,
-                        )?;
-                        match (else_block, else_branch) {
-                            (None, _) => (),
-                            (Some(else_block), None) => {
-                                self.set_terminator(else_block, Terminator::Unreachable);
-                            }
-                            (Some(else_block), Some(else_branch)) => {
-                                if let Some((_, b)) =
-                                    self.lower_expr_as_place(else_block, *else_branch, true)?
-                                {
-                                    self.set_terminator(b, Terminator::Unreachable);
-                                }
-                            }
-                        }
                     } else {
                         self.body.walk_bindings_in_pat(*pat, |b| {
                             self.push_storage_live(b, current);
                         });
                     }
+                    self.push_drop_scope_bindings(*pat);
                 }
                 hir_def::expr::Statement::Expr { expr, has_semi: _ } => {
-                    let Some((_, c)) = self.lower_expr_as_place(current, *expr, true)? else {
+                    let Some(c) = self.lower_in_statement_scope(|this| {
+                        let current = this.lower_expr_as_place(current, *expr, true)?;
+                        this.result.source_map.set_place_usage(*expr, ValueUsage::Discarded);
+                        Ok(current.map(|(_, c)| c))
+                    })?
+                    else {
                         return Ok(None);
                     };
                     current = c;
@@ -1278,7 +2630,12 @@ impl MirLowerCtx<'_> {
     }
 }
 
-fn cast_kind(source_ty: &Ty, target_ty: &Ty) -> Result<CastKind> {
+/// Whether `place` is behind a reference or a pointer, and so is not owned by its local.
+fn is_behind_deref(place: &Place) -> bool {
+    place.projection.iter().any(|x| matches!(x, ProjectionElem::Deref))
+}
+
+fn cast_kind(db: &dyn HirDatabase, source_ty: &Ty, target_ty: &Ty) -> Result<CastKind> {
     Ok(match (source_ty.kind(Interner), target_ty.kind(Interner)) {
         (TyKind::Scalar(s), TyKind::Scalar(t)) => match (s, t) {
             (chalk_ir::Scalar::Float(_), chalk_ir::Scalar::Float(_)) => CastKind::FloatToFloat,
@@ -1295,7 +2652,14 @@ fn cast_kind(source_ty: &Ty, target_ty: &Ty) -> Result<CastKind> {
         (TyKind::Scalar(_), TyKind::Adt(..)) | (TyKind::Adt(..), TyKind::Scalar(_)) => {
             CastKind::IntToInt
         }
-        (a, b) => not_supported!("Unknown cast between {a:?} and {b:?}"),
+        // Other casts, like ones between arrays which need a transmute even if their elements can
+        // be cast, are invalid.
+        _ => {
+            return Err(MirLowerError::UnsupportedCast {
+                from: source_ty.display(db).to_string(),
+                to: target_ty.display(db).to_string(),
+            })
+        }
     })
 }
 
@@ -1310,9 +2674,23 @@ pub fn mir_body_query(db: &dyn HirDatabase, def: DefWithBodyId) -> Result<Arc<Mi
             db.enum_data(it.parent).variants[it.local_id].name.to_string()
         }
     });
+    if let DefWithBodyId::FunctionId(it) = def {
+        if db.function_data(it).attrs.by_key("naked").exists() {
+            return Err(MirLowerError::NakedFunction);
+        }
+    }
     let body = db.body(def);
     let infer = db.infer(def);
-    let result = lower_to_mir(db, def, &body, &infer, body.body_expr)?;
+    let panic_strategy = PanicStrategy::of_crate(db, def.module(db.upcast()).krate());
+    let result = lower_to_mir(
+        db,
+        def,
+        &body,
+        &infer,
+        body.body_expr,
+        panic_strategy,
+        CoverageMode::Off,
+    )?;
     Ok(Arc::new(result))
 }
 
@@ -1324,6 +2702,46 @@ pub fn mir_body_recover(
     Err(MirLowerError::Loop)
 }
 
+pub fn mir_body_for_closure_query(
+    db: &dyn HirDatabase,
+    closure: ClosureId,
+) -> Result<Arc<MirBody>> {
+    let (owner, expr) = db.lookup_intern_closure(closure.into());
+    let body = db.body(owner);
+    let infer = db.infer(owner);
+    let Expr::Closure { args, body: root, .. } = &body[expr] else {
+        return Err(MirLowerError::TypeError("closure id doesn't point to a closure expression"));
+    };
+    let closure_ty = infer[expr].clone();
+    let sig = closure_ty
+        .callable_sig(db)
+        .ok_or(MirLowerError::TypeError("closure type without a signature"))?;
+    let (captures, fn_trait) = infer.closure_info(closure);
+    // Like in rustc, the closure takes itself, which holds the captures, as the first parameter, the
+    // same way the method of the `Fn*` trait it implements takes `self`.
+    let env_ty = match fn_trait {
+        FnTrait::Fn => TyKind::Ref(Mutability::Not, static_lifetime(), closure_ty).intern(Interner),
+        FnTrait::FnMut => {
+            TyKind::Ref(Mutability::Mut, static_lifetime(), closure_ty).intern(Interner)
+        }
+        FnTrait::FnOnce => closure_ty,
+    };
+    let signature = BodySignature {
+        resolver: owner.resolver(db.upcast()),
+        env: Some(ClosureEnv { ty: env_ty, captures, fn_trait }),
+        params: args.iter().copied().zip(sig.params().iter().cloned()).collect(),
+        return_ty: sig.ret().clone(),
+    };
+    let panic_strategy = PanicStrategy::of_crate(db, owner.module(db.upcast()).krate());
+    let result =
+        lower_body_with_params(db, owner, &body, &infer, *root, signature, panic_strategy)?;
+    Ok(Arc::new(result))
+}
+
+/// Lowers `root_expr` of the body of `owner` to MIR. With [`CoverageMode::Branches`], like rustc's
+/// `-C instrument-coverage`, the lowered body counts how often each branch is taken: every block
+/// which a `SwitchInt` jumps to starts with a [`StatementKind::Coverage`] statement of its own
+/// counter, attributed to the expression or pattern the block was lowered for.
 pub fn lower_to_mir(
     db: &dyn HirDatabase,
     owner: DefWithBodyId,
@@ -1332,47 +2750,133 @@ pub fn lower_to_mir(
     // FIXME: root_expr should always be the body.body_expr, but since `X` in `[(); X]` doesn't have its own specific body yet, we
     // need to take this input explicitly.
     root_expr: ExprId,
+    panic_strategy: PanicStrategy,
+    coverage: CoverageMode,
+) -> Result<MirBody> {
+    let params = if let DefWithBodyId::FunctionId(fid) = owner {
+        let substs = TyBuilder::placeholder_subst(db, fid);
+        let callable_sig = db.callable_item_signature(fid.into()).substitute(Interner, &substs);
+        body.params.iter().copied().zip(callable_sig.params().iter().cloned()).collect()
+    } else {
+        if !body.params.is_empty() {
+            return Err(MirLowerError::TypeError("Unexpected parameter for non function body"));
+        }
+        vec![]
+    };
+    let signature = BodySignature {
+        resolver: owner.resolver(db.upcast()),
+        env: None,
+        params,
+        return_ty: infer[root_expr].clone(),
+    };
+    let mut result =
+        lower_body_with_params(db, owner, body, infer, root_expr, signature, panic_strategy)?;
+    if coverage == CoverageMode::Branches {
+        insert_coverage_counters(&mut result);
+    }
+    Ok(result)
+}
+
+/// Lowers `body`, a speculative body of `owner` which isn't stored in the database, with the
+/// resolver of the scope it is in. The lowered body has no spans, as they would point into the
+/// body of `owner` instead of the syntax `body` was lowered from.
+pub fn lower_speculative_body_to_mir(
+    db: &dyn HirDatabase,
+    owner: DefWithBodyId,
+    body: &Body,
+    infer: &InferenceResult,
+    resolver: Resolver,
+) -> Result<MirBody> {
+    let root_expr = body.body_expr;
+    let signature =
+        BodySignature { resolver, env: None, params: vec![], return_ty: infer[root_expr].clone() };
+    let panic_strategy = PanicStrategy::of_crate(db, owner.module(db.upcast()).krate());
+    let mut result =
+        lower_body_with_params(db, owner, body, infer, root_expr, signature, panic_strategy)?;
+    for (_, block) in result.basic_blocks.iter_mut() {
+        for statement in &mut block.statements {
+            statement.span = MirSpan::Unknown;
+        }
+        if let Some(Terminator::Call { span, .. }) = &mut block.terminator {
+            *span = MirSpan::Unknown;
+        }
+    }
+    result.source_map = MirSourceMap::default();
+    Ok(result)
+}
+
+/// The scope, the parameters and the return type of a body being lowered.
+struct BodySignature<'a> {
+    /// The resolver of the scope the body is in, which is the one of its owner unless it is a
+    /// speculative body.
+    resolver: Resolver,
+    /// The environment of the closure if this is the body of one, which is passed before the
+    /// other parameters.
+    env: Option<ClosureEnv<'a>>,
+    /// The patterns of the parameters with their types.
+    params: Vec<(PatId, Ty)>,
+    return_ty: Ty,
+}
+
+/// The first parameter of the body of a closure, through which it accesses its captures.
+struct ClosureEnv<'a> {
+    ty: Ty,
+    captures: &'a [CapturedItem],
+    fn_trait: FnTrait,
+}
+
+/// Lowers `root_expr` as a body with the parameters and return type of `signature`.
+fn lower_body_with_params(
+    db: &dyn HirDatabase,
+    owner: DefWithBodyId,
+    body: &Body,
+    infer: &InferenceResult,
+    root_expr: ExprId,
+    signature: BodySignature<'_>,
+    panic_strategy: PanicStrategy,
 ) -> Result<MirBody> {
+    let BodySignature { resolver, env, params, return_ty } = signature;
     if let Some((_, x)) = infer.type_mismatches().next() {
         return Err(MirLowerError::TypeMismatch(x.clone()));
     }
+    let p = profile::span("mir_lower_locals");
     let mut basic_blocks = Arena::new();
     let start_block =
         basic_blocks.alloc(BasicBlock { statements: vec![], terminator: None, is_cleanup: false });
     let mut locals = Arena::new();
     // 0 is return local
-    locals.alloc(Local { ty: infer[root_expr].clone() });
+    locals.alloc(Local { ty: return_ty, kind: LocalKind::ReturnSlot });
     let mut binding_locals: ArenaMap<BindingId, LocalId> = ArenaMap::new();
-    // 1 to param_len is for params
-    let param_locals: Vec<LocalId> = if let DefWithBodyId::FunctionId(fid) = owner {
-        let substs = TyBuilder::placeholder_subst(db, fid);
-        let callable_sig = db.callable_item_signature(fid.into()).substitute(Interner, &substs);
-        body.params
-            .iter()
-            .zip(callable_sig.params().iter())
-            .map(|(&x, ty)| {
-                let local_id = locals.alloc(Local { ty: ty.clone() });
-                if let Pat::Bind { id, subpat: None } = body[x] {
+    // 1 to param_len is for the environment of the closure, if any, and then the params
+    let env_local =
+        env.as_ref().map(|env| locals.alloc(Local { ty: env.ty.clone(), kind: LocalKind::Arg }));
+    let param_locals: Vec<LocalId> = env_local
+        .into_iter()
+        .chain(params.iter().map(|(x, ty)| {
+            let binding = match body[*x] {
+                Pat::Bind { id, subpat: None }
                     if matches!(
                         body.bindings[id].mode,
                         BindingAnnotation::Unannotated | BindingAnnotation::Mutable
-                    ) {
-                        binding_locals.insert(id, local_id);
-                    }
+                    ) =>
+                {
+                    Some(id)
                 }
-                local_id
-            })
-            .collect()
-    } else {
-        if !body.params.is_empty() {
-            return Err(MirLowerError::TypeError("Unexpected parameter for non function body"));
-        }
-        vec![]
-    };
+                _ => None,
+            };
+            let kind = binding.map_or(LocalKind::Arg, LocalKind::UserBinding);
+            let local_id = locals.alloc(Local { ty: ty.clone(), kind });
+            if let Some(id) = binding {
+                binding_locals.insert(id, local_id);
+            }
+            local_id
+        }))
+        .collect();
     // and then rest of bindings
     for (id, _) in body.bindings.iter() {
         if !binding_locals.contains_idx(id) {
-            binding_locals.insert(id, locals.alloc(Local { ty: infer[id].clone() }));
+            let local = Local { ty: infer[id].clone(), kind: LocalKind::UserBinding(id) };
+            binding_locals.insert(id, locals.alloc(local));
         }
     }
     let mir = MirBody {
@@ -1382,20 +2886,43 @@ pub fn lower_to_mir(
         binding_locals,
         param_locals,
         owner,
-        arg_count: body.params.len(),
+        arg_count: params.len() + usize::from(env.is_some()),
+        source_map: Default::default(),
     };
+    drop(p.detail(|| format!("{} locals", mir.locals.len())));
+    let p = profile::span("mir_lower_exprs");
     let mut ctx = MirLowerCtx {
         result: mir,
         db,
         infer,
         body,
         owner,
-        current_loop_blocks: None,
-        labeled_loop_blocks: Default::default(),
+        loop_blocks: vec![],
         discr_temp: None,
+        resolver,
+        drop_scopes: vec![],
+        panic_strategy,
+        cleanup_blocks: FxHashMap::default(),
+        resume_and_abort: None,
+        current_source: MirSpan::Unknown,
+        captures: FxHashMap::default(),
     };
+    if let (Some(env), Some(env_local)) = (&env, env_local) {
+        for (i, capture) in env.captures.iter().enumerate() {
+            let mut place: Place = env_local.into();
+            if env.fn_trait != FnTrait::FnOnce {
+                place.projection.push(ProjectionElem::Deref);
+            }
+            place.projection.push(ProjectionElem::ClosureField(i));
+            if let CaptureKind::ByRef(_) = capture.kind {
+                place.projection.push(ProjectionElem::Deref);
+            }
+            ctx.captures.insert(capture.binding, place);
+        }
+    }
     let mut current = start_block;
-    for (&param, local) in body.params.iter().zip(ctx.result.param_locals.clone().into_iter()) {
+    let param_locals = ctx.result.param_locals[usize::from(env.is_some())..].to_vec();
+    for (&(param, _), local) in params.iter().zip(param_locals) {
         if let Pat::Bind { id, .. } = body[param] {
             if local == ctx.result.binding_locals[id] {
                 continue;
@@ -1417,5 +2944,56 @@ pub fn lower_to_mir(
     if let Some(b) = ctx.lower_expr_to_place(root_expr, return_slot().into(), current)? {
         ctx.result.basic_blocks[b].terminator = Some(Terminator::Return);
     }
+    // Drops are emitted while lowering the expressions that end their scopes, so they are counted
+    // in this span.
+    drop(p.detail(|| ctx.result.stats().to_string()));
+    if cfg!(debug_assertions) {
+        let _p = profile::span("mir_check_types");
+        if let Err(e) = ctx.result.check_types(db) {
+            ::stdx::never!("MIR lower implementation bug: mistyped assignment {:?}", e);
+        }
+        if let Err(e) = ctx.result.apply_projection_checks(db) {
+            ::stdx::never!("MIR lower implementation bug: invalid projection {:?}", e);
+        }
+    }
     Ok(ctx.result)
 }
+
+/// Starts every block which a `SwitchInt` jumps to with a `Coverage` statement, numbering the
+/// counters in the order of the blocks.
+fn insert_coverage_counters(body: &mut MirBody) {
+    let mut branch_entries: Vec<BasicBlockId> = body
+        .basic_blocks
+        .iter()
+        .filter_map(|(_, block)| match &block.terminator {
+            Some(Terminator::SwitchInt { targets, .. }) if !block.is_cleanup => {
+                Some(targets.all_targets())
+            }
+            _ => None,
+        })
+        .flatten()
+        .copied()
+        .collect();
+    branch_entries.sort_by_key(|b| b.into_raw());
+    branch_entries.dedup();
+    let block_spans: FxHashMap<BasicBlockId, MirSpan> = body
+        .source_map
+        .blocks
+        .iter()
+        .flat_map(|(span, blocks)| blocks.iter().map(|&b| (b, *span)))
+        .collect();
+    // The other statements of the instrumented blocks move by one.
+    let instrumented: FxHashSet<BasicBlockId> = branch_entries.iter().copied().collect();
+    for location in body.source_map.statements.values_mut().flatten() {
+        if instrumented.contains(&location.block) {
+            location.statement_index += 1;
+        }
+    }
+    for (counter, block) in branch_entries.into_iter().enumerate() {
+        let span = block_spans.get(&block).copied().unwrap_or(MirSpan::Unknown);
+        body.basic_blocks[block]
+            .statements
+            .insert(0, StatementKind::Coverage(counter).with_span(span));
+        body.source_map.push_statement(span, Location { block, statement_index: 0 });
+    }
+}
@@ -17,7 +17,7 @@ impl MirLowerCtx<'_> {
         prev_block: BasicBlockId,
     ) -> Result<Option<(Place, BasicBlockId)>> {
         let ty = self.expr_ty(expr_id);
-        let place = self.temp(ty)?;
+        let place = self.temp(ty, TempReason::Operand)?;
         let Some(current) = self.lower_expr_to_place_without_adjust(expr_id, place.into(), prev_block)? else {
             return Ok(None);
         };
@@ -32,7 +32,7 @@ impl MirLowerCtx<'_> {
     ) -> Result<Option<(Place, BasicBlockId)>> {
         let ty =
             adjustments.last().map(|x| x.target.clone()).unwrap_or_else(|| self.expr_ty(expr_id));
-        let place = self.temp(ty)?;
+        let place = self.temp(ty, TempReason::Operand)?;
         let Some(current) = self.lower_expr_to_place_with_adjust(expr_id, place.into(), prev_block, adjustments)? else {
             return Ok(None);
         };
@@ -105,10 +105,10 @@ impl MirLowerCtx<'_> {
         expr_id: ExprId,
         upgrade_rvalue: bool,
     ) -> Result<Option<(Place, BasicBlockId)>> {
-        match self.infer.expr_adjustments.get(&expr_id) {
-            Some(a) => self.lower_expr_as_place_with_adjust(current, expr_id, upgrade_rvalue, a),
-            None => self.lower_expr_as_place_without_adjust(current, expr_id, upgrade_rvalue),
-        }
+        self.with_source(expr_id.into(), |this| match this.infer.expr_adjustments.get(&expr_id) {
+            Some(a) => this.lower_expr_as_place_with_adjust(current, expr_id, upgrade_rvalue, a),
+            None => this.lower_expr_as_place_without_adjust(current, expr_id, upgrade_rvalue),
+        })
     }
 
     pub(super) fn lower_expr_as_place_without_adjust(
@@ -116,6 +116,17 @@ impl MirLowerCtx<'_> {
         current: BasicBlockId,
         expr_id: ExprId,
         upgrade_rvalue: bool,
+    ) -> Result<Option<(Place, BasicBlockId)>> {
+        self.with_source(expr_id.into(), |this| {
+            this.lower_expr_as_place_without_adjust_inner(current, expr_id, upgrade_rvalue)
+        })
+    }
+
+    fn lower_expr_as_place_without_adjust_inner(
+        &mut self,
+        current: BasicBlockId,
+        expr_id: ExprId,
+        upgrade_rvalue: bool,
     ) -> Result<Option<(Place, BasicBlockId)>> {
         let try_rvalue = |this: &mut MirLowerCtx<'_>| {
             if !upgrade_rvalue {
@@ -125,7 +136,7 @@ impl MirLowerCtx<'_> {
         };
         match &self.body.exprs[expr_id] {
             Expr::Path(p) => {
-                let resolver = resolver_for_expr(self.db.upcast(), self.owner, expr_id);
+                let resolver = self.resolver_for_expr(expr_id);
                 let Some(pr) = resolver.resolve_path_in_value_ns(self.db.upcast(), p) else {
                     return Err(MirLowerError::unresolved_path(self.db, p));
                 };
@@ -135,17 +146,31 @@ impl MirLowerCtx<'_> {
                 };
                 match pr {
                     ValueNs::LocalBinding(pat_id) => {
-                        Ok(Some((self.result.binding_locals[pat_id].into(), current)))
+                        Ok(Some((self.binding_place(pat_id), current)))
+                    }
+                    ValueNs::StaticId(s) => {
+                        let ref_ty = static_ref_ty(self.db, s);
+                        let mut temp: Place = self.temp(ref_ty, TempReason::Deref)?.into();
+                        self.push_assignment(
+                            current,
+                            temp.clone(),
+                            Operand::Static(s).into(),
+                            expr_id.into(),
+                        );
+                        temp.projection.push(ProjectionElem::Deref);
+                        Ok(Some((temp, current)))
                     }
                     _ => try_rvalue(self),
                 }
             }
             Expr::UnaryOp { expr, op } => match op {
                 hir_def::expr::UnaryOp::Deref => {
-                    if !matches!(
-                        self.expr_ty(*expr).kind(Interner),
-                        TyKind::Ref(..) | TyKind::Raw(..)
-                    ) {
+                    let is_builtin_deref = match self.expr_ty(*expr).kind(Interner) {
+                        TyKind::Ref(..) | TyKind::Raw(..) => true,
+                        TyKind::Adt(adt, _) => is_box(adt.0, self.db),
+                        _ => false,
+                    };
+                    if !is_builtin_deref {
                         let Some((p, current)) = self.lower_expr_as_place(current, *expr, true)? else {
                             return Ok(None);
                         };
@@ -221,10 +246,19 @@ impl MirLowerCtx<'_> {
                 else {
                     return Ok(None);
                 };
-                let l_index = self.temp(self.expr_ty_after_adjustments(*index))?;
+                // The adjustments of the base are not applied, so the array or slice may still be
+                // behind references, which have to be dereferenced before it can be indexed.
+                let mut base_ty = self.expr_ty(*base);
+                while let TyKind::Ref(_, _, inner) = base_ty.kind(Interner) {
+                    p_base.projection.push(ProjectionElem::Deref);
+                    base_ty = inner.clone();
+                }
+                let l_index =
+                    self.temp(self.expr_ty_after_adjustments(*index), TempReason::Operand)?;
                 let Some(current) = self.lower_expr_to_place(*index, l_index.into(), current)? else {
                     return Ok(None);
                 };
+                let current = self.push_bounds_check(current, &p_base, l_index, expr_id.into())?;
                 p_base.projection.push(ProjectionElem::Index(l_index));
                 Ok(Some((p_base, current)))
             }
@@ -232,6 +266,33 @@ impl MirLowerCtx<'_> {
         }
     }
 
+    /// Asserts that `index` is less than the length of the array or slice at `base`, like the
+    /// bounds check rustc emits before a builtin index. Returns the block which does the indexing.
+    fn push_bounds_check(
+        &mut self,
+        current: BasicBlockId,
+        base: &Place,
+        index: LocalId,
+        span: MirSpan,
+    ) -> Result<BasicBlockId> {
+        let len: Place = self.temp(TyBuilder::usize(), TempReason::Operand)?.into();
+        self.push_assignment(current, len.clone(), Rvalue::Len(base.clone()), span);
+        let in_bounds: Place = self.temp(TyBuilder::bool(), TempReason::Operand)?.into();
+        self.push_assignment(
+            current,
+            in_bounds.clone(),
+            Rvalue::BinaryOp(BinOp::Lt, Operand::Copy(index.into()), Operand::Copy(len)),
+            span,
+        );
+        let target = self.new_basic_block();
+        let cleanup = self.cleanup_block(&self.live_drop_locals(0));
+        self.set_terminator(
+            current,
+            Terminator::Assert { cond: Operand::Copy(in_bounds), expected: true, target, cleanup },
+        );
+        Ok(target)
+    }
+
     fn lower_overloaded_index(
         &mut self,
         current: BasicBlockId,
@@ -242,16 +303,24 @@ impl MirLowerCtx<'_> {
         span: MirSpan,
         index_fn: (FunctionId, Substitution),
     ) -> Result<Option<(Place, BasicBlockId)>> {
-        let (mutability, borrow_kind) = match base_ty.as_reference() {
-            Some((_, _, mutability)) => {
-                (mutability, BorrowKind::Mut { allow_two_phase_borrow: false })
+        let (mutability, ref_place) = match base_ty.as_reference() {
+            // The autoref adjustment of the base has already borrowed it.
+            Some((_, _, mutability)) => (mutability, place),
+            None => {
+                let ref_ty =
+                    TyKind::Ref(Mutability::Not, static_lifetime(), base_ty).intern(Interner);
+                let ref_place: Place = self.temp(ref_ty, TempReason::Deref)?.into();
+                self.push_assignment(
+                    current,
+                    ref_place.clone(),
+                    Rvalue::Ref(BorrowKind::Shared, place),
+                    span,
+                );
+                (Mutability::Not, ref_place)
             }
-            None => (Mutability::Not, BorrowKind::Shared),
         };
         let result_ref = TyKind::Ref(mutability, static_lifetime(), result_ty).intern(Interner);
-        let ref_place: Place = self.temp(base_ty)?.into();
-        self.push_assignment(current, ref_place.clone(), Rvalue::Ref(borrow_kind, place), span);
-        let mut result: Place = self.temp(result_ref)?.into();
+        let mut result: Place = self.temp(result_ref, TempReason::Deref)?.into();
         let index_fn_op = Operand::const_zst(
             TyKind::FnDef(
                 self.db.intern_callable_def(CallableDefId::FunctionId(index_fn.0)).into(),
@@ -259,7 +328,7 @@ impl MirLowerCtx<'_> {
             )
             .intern(Interner),
         );
-        let Some(current) = self.lower_call(index_fn_op, vec![Operand::Copy(ref_place), index_operand], result.clone(), current, false)? else {
+        let Some(current) = self.lower_call(index_fn_op, vec![Operand::Copy(ref_place), index_operand], result.clone(), current, false, span)? else {
             return Ok(None);
         };
         result.projection.push(ProjectionElem::Deref);
@@ -287,7 +356,7 @@ impl MirLowerCtx<'_> {
         };
         let ty_ref = TyKind::Ref(chalk_mut, static_lifetime(), source_ty.clone()).intern(Interner);
         let target_ty_ref = TyKind::Ref(chalk_mut, static_lifetime(), target_ty).intern(Interner);
-        let ref_place: Place = self.temp(ty_ref)?.into();
+        let ref_place: Place = self.temp(ty_ref, TempReason::Deref)?.into();
         self.push_assignment(current, ref_place.clone(), Rvalue::Ref(borrow_kind, place), span);
         let deref_trait = self
             .resolve_lang_item(trait_lang_item)?
@@ -305,8 +374,8 @@ impl MirLowerCtx<'_> {
             )
             .intern(Interner),
         );
-        let mut result: Place = self.temp(target_ty_ref)?.into();
-        let Some(current) = self.lower_call(deref_fn_op, vec![Operand::Copy(ref_place)], result.clone(), current, false)? else {
+        let mut result: Place = self.temp(target_ty_ref, TempReason::Deref)?.into();
+        let Some(current) = self.lower_call(deref_fn_op, vec![Operand::Copy(ref_place)], result.clone(), current, false, span)? else {
             return Ok(None);
         };
         result.projection.push(ProjectionElem::Deref);
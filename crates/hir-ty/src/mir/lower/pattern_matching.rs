@@ -1,6 +1,11 @@
 //! MIR lowering for patterns
 
+use hir_def::Lookup;
+
+use crate::method_resolution::TyFingerprint;
+
 use super::*;
+use crate::consteval::usize_const;
 
 macro_rules! not_supported {
     ($x: expr) => {
@@ -25,6 +30,27 @@ impl MirLowerCtx<'_> {
     /// wouldn't be `None` as well. Note that this function will add jumps to the beginning of the `current_else` block,
     /// so it should be an empty block.
     pub(super) fn pattern_match(
+        &mut self,
+        current: BasicBlockId,
+        current_else: Option<BasicBlockId>,
+        cond_place: Place,
+        cond_ty: Ty,
+        pattern: PatId,
+        binding_mode: BindingAnnotation,
+    ) -> Result<(BasicBlockId, Option<BasicBlockId>)> {
+        self.with_source(pattern.into(), |this| {
+            this.pattern_match_inner(
+                current,
+                current_else,
+                cond_place,
+                cond_ty,
+                pattern,
+                binding_mode,
+            )
+        })
+    }
+
+    fn pattern_match_inner(
         &mut self,
         mut current: BasicBlockId,
         mut current_else: Option<BasicBlockId>,
@@ -60,8 +86,10 @@ impl MirLowerCtx<'_> {
             }
             Pat::Or(pats) => {
                 let then_target = self.new_basic_block();
-                let mut finished = false;
-                for pat in &**pats {
+                let Some((last, pats)) = pats.split_last() else {
+                    return Err(MirLowerError::IncompleteExpr);
+                };
+                for pat in pats {
                     let (next, next_else) = self.pattern_match(
                         current,
                         None,
@@ -75,17 +103,21 @@ impl MirLowerCtx<'_> {
                         Some(t) => {
                             current = t;
                         }
-                        None => {
-                            finished = true;
-                            break;
-                        }
+                        None => return Ok((then_target, current_else)),
                     }
                 }
-                if !finished {
-                    let ce = *current_else.get_or_insert_with(|| self.new_basic_block());
-                    self.set_goto(current, ce);
-                }
-                (then_target, current_else)
+                // Only a failure of the last alternative is a failure of the whole pattern, so it
+                // jumps to the mismatched path directly.
+                let (next, next_else) = self.pattern_match(
+                    current,
+                    current_else,
+                    cond_place,
+                    cond_ty,
+                    *last,
+                    binding_mode,
+                )?;
+                self.set_goto(next, then_target);
+                (then_target, next_else)
             }
             Pat::Record { args, .. } => {
                 let Some(variant) = self.infer.variant_resolution_for_pat(pattern) else {
@@ -103,12 +135,88 @@ impl MirLowerCtx<'_> {
                 )?
             }
             Pat::Range { .. } => not_supported!("range pattern"),
-            Pat::Slice { .. } => not_supported!("slice pattern"),
-            Pat::Path(_) => {
-                let Some(variant) = self.infer.variant_resolution_for_pat(pattern) else {
-                    not_supported!("unresolved variant");
+            Pat::Slice { prefix, slice, suffix } => {
+                pattern_matching_dereference(&mut cond_ty, &mut binding_mode, &mut cond_place);
+                let elem_ty = match cond_ty.kind(Interner) {
+                    TyKind::Array(elem_ty, _) | TyKind::Slice(elem_ty) => elem_ty.clone(),
+                    _ => {
+                        return Err(MirLowerError::TypeError(
+                            "non array or slice type matched with slice pattern",
+                        ))
+                    }
                 };
-                self.pattern_matching_variant(
+                let min_length = (prefix.len() + suffix.len()) as u64;
+                // The length of an array is checked by the type already.
+                if let TyKind::Slice(_) = cond_ty.kind(Interner) {
+                    (current, current_else) = self.pattern_match_slice_length(
+                        current,
+                        current_else,
+                        &cond_place,
+                        min_length,
+                        slice.is_some(),
+                        pattern,
+                    )?;
+                }
+                let elements = prefix.iter().enumerate().map(|(i, pat)| (i, pat, false));
+                let elements = elements
+                    .chain(suffix.iter().rev().enumerate().map(|(i, pat)| (i + 1, pat, true)));
+                for (offset, &pat, from_end) in elements {
+                    let mut elem_place = cond_place.clone();
+                    elem_place.projection.push(ProjectionElem::ConstantIndex {
+                        offset: offset as u64,
+                        min_length,
+                        from_end,
+                    });
+                    (current, current_else) = self.pattern_match(
+                        current,
+                        current_else,
+                        elem_place,
+                        elem_ty.clone(),
+                        pat,
+                        binding_mode,
+                    )?;
+                }
+                // A plain `..` is lowered to a missing pattern, and `rest @ ..` to a binding with a
+                // missing subpattern.
+                let rest = match slice.map(|slice| (slice, &self.body.pats[slice])) {
+                    None | Some((_, Pat::Missing)) => None,
+                    Some((slice, &Pat::Bind { id, subpat: Some(subpat) }))
+                        if matches!(self.body.pats[subpat], Pat::Missing) =>
+                    {
+                        Some((slice, id))
+                    }
+                    Some(_) => not_supported!("rest pattern which is not a binding"),
+                };
+                if let Some((slice, id)) = rest {
+                    let mut rest_place = cond_place.clone();
+                    rest_place.projection.push(ProjectionElem::Subslice {
+                        from: prefix.len() as u64,
+                        to: suffix.len() as u64,
+                        from_end: true,
+                    });
+                    let rest_ty = match cond_ty.kind(Interner) {
+                        TyKind::Array(_, len) => {
+                            let len = try_const_usize(len)
+                                .and_then(|len| len.checked_sub(min_length as u128));
+                            let krate = self.owner.module(self.db.upcast()).krate();
+                            TyKind::Array(elem_ty, usize_const(self.db, len, krate))
+                        }
+                        _ => TyKind::Slice(elem_ty),
+                    }
+                    .intern(Interner);
+                    self.pattern_match_binding(
+                        id,
+                        binding_mode,
+                        rest_place,
+                        rest_ty,
+                        slice,
+                        current,
+                    );
+                }
+                (current, current_else)
+            }
+            Pat::Path(p) => match self.infer.variant_resolution_for_pat(pattern) {
+                Some(variant) => self.pattern_matching_variant(
                     cond_ty,
                     binding_mode,
                     cond_place,
@@ -117,8 +225,40 @@ impl MirLowerCtx<'_> {
                     pattern.into(),
                     current_else,
                     AdtPatternShape::Unit,
-                )?
-            }
+                )?,
+                None => {
+                    let (const_id, subst) = match self.infer.assoc_resolutions_for_pat(pattern) {
+                        Some((hir_def::AssocItemId::ConstId(c), subst)) => (c, subst),
+                        _ => {
+                            match self.resolver.resolve_path_in_value_ns_fully(self.db.upcast(), p)
+                            {
+                                Some(ValueNs::ConstId(c)) => (c, Substitution::empty(Interner)),
+                                Some(_) => {
+                                    not_supported!("path pattern that is not a const or variant")
+                                }
+                                None => return Err(MirLowerError::unresolved_path(self.db, p)),
+                            }
+                        }
+                    };
+                    self.check_structural_match(pattern)?;
+                    let tmp: Place = self.temp(cond_ty.clone(), TempReason::Operand)?.into();
+                    self.lower_const(
+                        const_id,
+                        current,
+                        tmp.clone(),
+                        subst,
+                        cond_ty,
+                        pattern.into(),
+                    )?;
+                    self.pattern_match_const(
+                        current_else,
+                        current,
+                        Operand::Copy(tmp),
+                        cond_place,
+                        pattern,
+                    )?
+                }
+            },
             Pat::Lit(l) => match &self.body.exprs[*l] {
                 Expr::Literal(l) => {
                     let c = self.lower_literal_to_operand(cond_ty, l)?;
@@ -127,36 +267,23 @@ impl MirLowerCtx<'_> {
                 _ => not_supported!("expression path literal"),
             },
             Pat::Bind { id, subpat } => {
-                let target_place = self.result.binding_locals[*id];
-                let mode = self.body.bindings[*id].mode;
                 if let Some(subpat) = subpat {
                     (current, current_else) = self.pattern_match(
                         current,
                         current_else,
                         cond_place.clone(),
-                        cond_ty,
+                        cond_ty.clone(),
                         *subpat,
                         binding_mode,
                     )?
                 }
-                if matches!(mode, BindingAnnotation::Ref | BindingAnnotation::RefMut) {
-                    binding_mode = mode;
-                }
-                self.push_storage_live(*id, current);
-                self.push_assignment(
+                self.pattern_match_binding(
+                    *id,
+                    binding_mode,
+                    cond_place,
+                    cond_ty,
+                    pattern,
                     current,
-                    target_place.into(),
-                    match binding_mode {
-                        BindingAnnotation::Unannotated | BindingAnnotation::Mutable => {
-                            Operand::Copy(cond_place).into()
-                        }
-                        BindingAnnotation::Ref => Rvalue::Ref(BorrowKind::Shared, cond_place),
-                        BindingAnnotation::RefMut => Rvalue::Ref(
-                            BorrowKind::Mut { allow_two_phase_borrow: false },
-                            cond_place,
-                        ),
-                    },
-                    pattern.into(),
                 );
                 (current, current_else)
             }
@@ -196,6 +323,161 @@ impl MirLowerCtx<'_> {
         })
     }
 
+    /// Rejects constants used in `pattern` whose type can't be compared structurally, since
+    /// matching on them would compare the values byte by byte instead of calling `PartialEq`.
+    fn check_structural_match(&self, pattern: PatId) -> Result<()> {
+        let ty = &self.infer[pattern];
+        match self.non_structural_part(ty, &mut FxHashSet::default()) {
+            Some((ty, reason)) => {
+                Err(MirLowerError::NonStructuralMatchConst { pat: pattern, ty, reason })
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Finds the part of `ty` that can't be compared structurally, along with the reason.
+    /// `enclosing` holds the ADTs whose fields are being checked, so that recursive types are not
+    /// checked forever. Other occurrences of an ADT are checked again, as they may have different
+    /// generic arguments.
+    fn non_structural_part(
+        &self,
+        ty: &Ty,
+        enclosing: &mut FxHashSet<AdtId>,
+    ) -> Option<(Ty, NonStructuralMatch)> {
+        match ty.kind(Interner) {
+            TyKind::Scalar(chalk_ir::Scalar::Float(_)) => {
+                Some((ty.clone(), NonStructuralMatch::Float))
+            }
+            TyKind::OpaqueType(..) | TyKind::Alias(chalk_ir::AliasTy::Opaque(_)) => {
+                Some((ty.clone(), NonStructuralMatch::Opaque))
+            }
+            TyKind::Ref(_, _, inner) | TyKind::Array(inner, _) | TyKind::Slice(inner) => {
+                self.non_structural_part(inner, enclosing)
+            }
+            TyKind::Tuple(_, subst) => subst
+                .iter(Interner)
+                .filter_map(|it| it.ty(Interner))
+                .find_map(|it| self.non_structural_part(it, enclosing)),
+            TyKind::Adt(chalk_ir::AdtId(adt), subst) => {
+                if enclosing.contains(adt) {
+                    return None;
+                }
+                if let Some(reason) = self.non_derived_partial_eq(*adt) {
+                    return Some((ty.clone(), reason));
+                }
+                let variants: Vec<VariantId> = match *adt {
+                    AdtId::StructId(it) => vec![it.into()],
+                    AdtId::UnionId(it) => vec![it.into()],
+                    AdtId::EnumId(it) => self
+                        .db
+                        .enum_data(it)
+                        .variants
+                        .iter()
+                        .map(|(local_id, _)| EnumVariantId { parent: it, local_id }.into())
+                        .collect(),
+                };
+                enclosing.insert(*adt);
+                let result = variants.into_iter().find_map(|variant| {
+                    let field_types = self.db.field_types(variant);
+                    let result = field_types.iter().find_map(|(_, field_ty)| {
+                        let field_ty = field_ty.clone().substitute(Interner, subst);
+                        self.non_structural_part(&field_ty, enclosing)
+                    });
+                    result
+                });
+                enclosing.remove(adt);
+                result
+            }
+            _ => None,
+        }
+    }
+
+    /// Checks that `PartialEq` is implemented for `adt` by a builtin derive. Types are accepted
+    /// when there is no `PartialEq` lang item to check against.
+    fn non_derived_partial_eq(&self, adt: AdtId) -> Option<NonStructuralMatch> {
+        let krate = self.owner.module(self.db.upcast()).krate();
+        let partial_eq = self.db.lang_item(krate, LangItem::PartialEq)?.as_trait()?;
+        let impls = self.db.trait_impls_in_deps(krate);
+        let mut impls = impls.for_trait_and_self_ty(partial_eq, TyFingerprint::Adt(adt)).peekable();
+        if impls.peek().is_none() {
+            return Some(NonStructuralMatch::MissingPartialEq);
+        }
+        let derived = impls.any(|impl_| {
+            let file_id = impl_.lookup(self.db.upcast()).id.file_id();
+            file_id.is_builtin_derive(self.db.upcast()).is_some()
+        });
+        (!derived).then_some(NonStructuralMatch::ManualPartialEq)
+    }
+
+    /// Writes the value at `cond_place` to the local of the binding `id`, or a reference to it
+    /// with the `ref` binding modes.
+    fn pattern_match_binding(
+        &mut self,
+        id: BindingId,
+        mut binding_mode: BindingAnnotation,
+        cond_place: Place,
+        cond_ty: Ty,
+        pattern: PatId,
+        current: BasicBlockId,
+    ) {
+        let target_place = self.result.binding_locals[id];
+        let mode = self.body.bindings[id].mode;
+        if matches!(mode, BindingAnnotation::Ref | BindingAnnotation::RefMut) {
+            binding_mode = mode;
+        }
+        self.push_storage_live(id, current);
+        self.push_assignment(
+            current,
+            target_place.into(),
+            match binding_mode {
+                BindingAnnotation::Unannotated | BindingAnnotation::Mutable => {
+                    self.push_move_or_copy(cond_place, &cond_ty).into()
+                }
+                BindingAnnotation::Ref => Rvalue::Ref(BorrowKind::Shared, cond_place),
+                BindingAnnotation::RefMut => {
+                    Rvalue::Ref(BorrowKind::Mut { allow_two_phase_borrow: false }, cond_place)
+                }
+            },
+            pattern.into(),
+        );
+    }
+
+    /// Checks that the slice at `cond_place` has `min_length` elements, or at least that many if
+    /// the slice pattern has a rest pattern.
+    fn pattern_match_slice_length(
+        &mut self,
+        current: BasicBlockId,
+        current_else: Option<BasicBlockId>,
+        cond_place: &Place,
+        min_length: u64,
+        has_rest: bool,
+        pattern: PatId,
+    ) -> Result<(BasicBlockId, Option<BasicBlockId>)> {
+        let then_target = self.new_basic_block();
+        let else_target = current_else.unwrap_or_else(|| self.new_basic_block());
+        let len: Place = self.temp(TyBuilder::usize(), TempReason::Operand)?.into();
+        self.push_assignment(current, len.clone(), Rvalue::Len(cond_place.clone()), pattern.into());
+        let size = self.layout_of(&TyBuilder::usize())?.size.bytes_usize();
+        let min_length =
+            Operand::from_bytes(min_length.to_le_bytes()[0..size].to_vec(), TyBuilder::usize());
+        let op = if has_rest { BinOp::Ge } else { BinOp::Eq };
+        let discr: Place = self.temp(TyBuilder::bool(), TempReason::Discriminant)?.into();
+        self.push_assignment(
+            current,
+            discr.clone(),
+            Rvalue::BinaryOp(op, Operand::Copy(len), min_length),
+            pattern.into(),
+        );
+        self.set_terminator(
+            current,
+            Terminator::SwitchInt {
+                discr: Operand::Copy(discr),
+                targets: SwitchTargets::static_if(1, then_target, else_target),
+            },
+        );
+        Ok((then_target, Some(else_target)))
+    }
+
     fn pattern_match_const(
         &mut self,
         current_else: Option<BasicBlockId>,
@@ -206,11 +488,11 @@ impl MirLowerCtx<'_> {
     ) -> Result<(BasicBlockId, Option<BasicBlockId>)> {
         let then_target = self.new_basic_block();
         let else_target = current_else.unwrap_or_else(|| self.new_basic_block());
-        let discr: Place = self.temp(TyBuilder::bool())?.into();
+        let discr: Place = self.temp(TyBuilder::bool(), TempReason::Discriminant)?.into();
         self.push_assignment(
             current,
             discr.clone(),
-            Rvalue::CheckedBinaryOp(BinOp::Eq, c, Operand::Copy(cond_place)),
+            Rvalue::BinaryOp(BinOp::Eq, c, Operand::Copy(cond_place)),
             pattern.into(),
         );
         let discr = Operand::Copy(discr);
@@ -260,6 +542,8 @@ impl MirLowerCtx<'_> {
                     },
                 );
                 let enum_data = self.db.enum_data(v.parent);
+                let mut variant_place = cond_place.clone();
+                variant_place.projection.push(ProjectionElem::Downcast(v));
                 self.pattern_matching_variant_fields(
                     shape,
                     &enum_data.variants[v.local_id].variant_data,
@@ -267,7 +551,7 @@ impl MirLowerCtx<'_> {
                     subst,
                     next,
                     Some(else_target),
-                    &cond_place,
+                    &variant_place,
                     binding_mode,
                 )?
             }
@@ -307,8 +591,9 @@ impl MirLowerCtx<'_> {
                 let it = args
                     .iter()
                     .map(|x| {
-                        let field_id =
-                            variant_data.field(&x.name).ok_or(MirLowerError::UnresolvedField)?;
+                        let field_id = variant_data
+                            .field(&x.name)
+                            .ok_or(MirLowerError::UnresolvedField(x.pat.into()))?;
                         Ok((
                             PlaceElem::Field(FieldId { parent: v.into(), local_id: field_id }),
                             x.pat,
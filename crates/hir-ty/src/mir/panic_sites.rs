@@ -0,0 +1,212 @@
+//! Finds the places where a body may panic, for the "Show panic sites" IDE command.
+
+use hir_def::{
+    lang_item::{lang_attr, LangItem},
+    FunctionId, ItemContainerId, Lookup,
+};
+use rustc_hash::FxHashSet;
+
+use crate::{
+    db::HirDatabase, mapping::from_chalk, method_resolution::lookup_impl_method, CallableDefId,
+    ConstScalar, Interner, TyKind,
+};
+
+use super::{
+    canonical::successors, BinOp, MirBody, MirSpan, Operand, Place, Rvalue, StatementKind,
+    Terminator,
+};
+
+/// Why a panic site may panic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PanicSiteKind {
+    /// Indexing an array or a slice, which panics if the index is out of bounds.
+    IndexOutOfBounds,
+    /// Integer arithmetic, which panics on overflow.
+    Overflow(BinOp),
+    /// Integer division or remainder, which panics if the divisor is zero.
+    DivisionByZero(BinOp),
+    /// A call to one of the panic functions of the standard library, like through `panic!`.
+    ExplicitPanic,
+    /// A call to a function which never returns normally.
+    DivergingCall(FunctionId),
+    /// A call through a function pointer, a trait object or a generic parameter, which may call
+    /// anything.
+    UnknownCallee,
+    /// A call to a function which has panic sites of its own.
+    Call(FunctionId, Vec<PanicSite>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PanicSite {
+    pub kind: PanicSiteKind,
+    /// The span of the site, in the body that contains it.
+    pub span: MirSpan,
+}
+
+/// Finds the panic sites of `body`, in the order in which they appear in its blocks. The bodies of
+/// the called functions are searched as well, up to `depth` calls deep.
+// FIXME: Lowering emits `Assert` terminators for bounds checks only, so the arithmetic panic sites
+// are found from the operations themselves.
+pub fn panic_sites(db: &dyn HirDatabase, body: &MirBody, depth: usize) -> Vec<PanicSite> {
+    let mut visiting = FxHashSet::default();
+    if let hir_def::DefWithBodyId::FunctionId(f) = body.owner {
+        visiting.insert(f);
+    }
+    PanicSitesCtx { db, visiting }.sites_of_body(body, depth)
+}
+
+struct PanicSitesCtx<'a> {
+    db: &'a dyn HirDatabase,
+    /// Functions whose bodies are being searched, to not loop on recursive calls.
+    visiting: FxHashSet<FunctionId>,
+}
+
+impl PanicSitesCtx<'_> {
+    fn sites_of_body(&mut self, body: &MirBody, depth: usize) -> Vec<PanicSite> {
+        let mut result = vec![];
+        for (_, block) in body.basic_blocks.iter() {
+            for statement in &block.statements {
+                let StatementKind::Assign(place, rvalue) = &statement.kind else { continue };
+                if let Some(kind) = arithmetic_panic(body, place, rvalue) {
+                    result.push(PanicSite { kind, span: statement.span });
+                }
+            }
+            match &block.terminator {
+                Some(Terminator::Call { func, span, .. }) => {
+                    if let Some(kind) = self.call_panic(body, func, depth) {
+                        result.push(PanicSite { kind, span: *span });
+                    }
+                }
+                // The condition of a bounds check is computed in the same block, by a statement
+                // with the span of the index expression.
+                Some(Terminator::Assert { cond: Operand::Copy(cond), .. }) => {
+                    let span = block.statements.iter().rev().find_map(|s| match &s.kind {
+                        StatementKind::Assign(p, _) if p == cond => Some(s.span),
+                        _ => None,
+                    });
+                    let span = span.unwrap_or(MirSpan::Unknown);
+                    result.push(PanicSite { kind: PanicSiteKind::IndexOutOfBounds, span });
+                }
+                _ => (),
+            }
+        }
+        result
+    }
+
+    fn call_panic(
+        &mut self,
+        body: &MirBody,
+        func: &Operand,
+        depth: usize,
+    ) -> Option<PanicSiteKind> {
+        let db = self.db;
+        let Operand::Constant(c) = func else {
+            return Some(PanicSiteKind::UnknownCallee);
+        };
+        let TyKind::FnDef(def, subst) = c.data(Interner).ty.kind(Interner) else {
+            return Some(PanicSiteKind::UnknownCallee);
+        };
+        let CallableDefId::FunctionId(f) = from_chalk(db, *def) else {
+            // Constructors of structs and enum variants.
+            return None;
+        };
+        if is_panic_fn(db, f) {
+            return Some(PanicSiteKind::ExplicitPanic);
+        }
+        let env = db.trait_environment_for_body(body.owner);
+        let (imp, _) = lookup_impl_method(db, env, f, subst.clone());
+        let callee = match db.mir_body(imp.into()) {
+            Ok(callee) => callee,
+            // Trait methods we couldn't resolve to an impl.
+            Err(_) if matches!(imp.lookup(db.upcast()).container, ItemContainerId::TraitId(_)) => {
+                return Some(PanicSiteKind::UnknownCallee)
+            }
+            // Extern functions and intrinsics.
+            Err(_) => return None,
+        };
+        if !can_return(&callee) {
+            return Some(PanicSiteKind::DivergingCall(imp));
+        }
+        if depth == 0 || !self.visiting.insert(imp) {
+            return None;
+        }
+        let sites = self.sites_of_body(&callee, depth - 1);
+        self.visiting.remove(&imp);
+        (!sites.is_empty()).then_some(PanicSiteKind::Call(imp, sites))
+    }
+}
+
+fn is_panic_fn(db: &dyn HirDatabase, f: FunctionId) -> bool {
+    use LangItem::*;
+    matches!(
+        lang_attr(db.upcast(), f),
+        Some(
+            Panic
+                | PanicNounwind
+                | PanicFmt
+                | PanicDisplay
+                | ConstPanicFmt
+                | PanicBoundsCheck
+                | PanicImpl
+                | PanicCannotUnwind
+                | BeginPanic
+        )
+    )
+}
+
+/// Whether a `return` is reachable in `body`.
+fn can_return(body: &MirBody) -> bool {
+    let mut visited = FxHashSet::default();
+    let mut stack = vec![body.start_block];
+    while let Some(b) = stack.pop() {
+        if !visited.insert(b) {
+            continue;
+        }
+        let block = &body.basic_blocks[b];
+        if matches!(block.terminator, Some(Terminator::Return)) {
+            return true;
+        }
+        stack.extend(successors(block));
+    }
+    false
+}
+
+fn arithmetic_panic(body: &MirBody, place: &Place, rvalue: &Rvalue) -> Option<PanicSiteKind> {
+    let Rvalue::BinaryOp(op, _, rhs) = rvalue else { return None };
+    // The operands of arithmetic have the type of the result, which is easy to know only when it
+    // is stored in a local directly, which is what lowering does.
+    if !place.projection.is_empty() {
+        return None;
+    }
+    let is_signed = match body.locals[place.local].ty.kind(Interner) {
+        TyKind::Scalar(chalk_ir::Scalar::Int(_)) => true,
+        TyKind::Scalar(chalk_ir::Scalar::Uint(_)) => false,
+        _ => return None,
+    };
+    match op {
+        BinOp::Div | BinOp::Rem => match constant_bytes(rhs) {
+            None => Some(PanicSiteKind::DivisionByZero(op.clone())),
+            Some(b) if b.iter().all(|&x| x == 0) => Some(PanicSiteKind::DivisionByZero(op.clone())),
+            // Dividing the minimum value of a signed type by `-1` overflows.
+            Some(b) if is_signed && b.iter().all(|&x| x == 255) => {
+                Some(PanicSiteKind::Overflow(op.clone()))
+            }
+            Some(_) => None,
+        },
+        BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Shl | BinOp::Shr => {
+            Some(PanicSiteKind::Overflow(op.clone()))
+        }
+        _ => None,
+    }
+}
+
+fn constant_bytes(op: &Operand) -> Option<&[u8]> {
+    let Operand::Constant(c) = op else { return None };
+    match &c.data(Interner).value {
+        chalk_ir::ConstValue::Concrete(c) => match &c.interned {
+            ConstScalar::Bytes(b, _) => Some(b),
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -13,7 +13,8 @@ use crate::{
 };
 
 use super::{
-    AggregateKind, BasicBlockId, BorrowKind, LocalId, MirBody, Operand, Place, Rvalue, UnOp,
+    AggregateKind, BasicBlockId, BorrowKind, LocalId, LocalKind, MirBody, Operand, Place, Rvalue,
+    TempReason, UnOp,
 };
 
 impl MirBody {
@@ -98,11 +99,10 @@ impl<'a> MirPrettyCtx<'a> {
     }
 
     fn with_block(&mut self, f: impl FnOnce(&mut MirPrettyCtx<'_>)) {
-        self.ident += "    ";
         wln!(self, "{{");
+        self.ident += "    ";
         f(self);
         for _ in 0..4 {
-            self.result.pop();
             self.ident.pop();
         }
         wln!(self, "}}");
@@ -122,16 +122,31 @@ impl<'a> MirPrettyCtx<'a> {
 
     fn write_line(&mut self) {
         self.result.push('\n');
-        self.result += &self.ident;
     }
 
+    /// Writes `line`, indenting it if it starts a line. Empty lines are not indented.
     fn write(&mut self, line: &str) {
+        if !line.is_empty() && self.result.ends_with('\n') {
+            self.result += &self.ident;
+        }
         self.result += line;
     }
 
     fn locals(&mut self) {
         for (id, local) in self.body.locals.iter() {
-            wln!(self, "let {}: {};", self.local_name(id), local.ty.display(self.db));
+            w!(self, "let {}: {};", self.local_name(id), local.ty.display(self.db));
+            match local.kind {
+                LocalKind::Temp(reason) => {
+                    let reason = match reason {
+                        TempReason::Operand => "operand",
+                        TempReason::Deref => "deref",
+                        TempReason::Discriminant => "discriminant",
+                        TempReason::Iterator => "iterator",
+                    };
+                    wln!(self, " // {reason} temporary");
+                }
+                LocalKind::ReturnSlot | LocalKind::Arg | LocalKind::UserBinding(_) => wln!(self),
+            }
         }
     }
 
@@ -170,6 +185,7 @@ impl<'a> MirPrettyCtx<'a> {
                             this.place(p);
                             wln!(this, ");");
                         }
+                        StatementKind::Coverage(counter) => wln!(this, "Coverage({counter});"),
                         StatementKind::Nop => wln!(this, "Nop;"),
                     }
                 }
@@ -209,6 +225,17 @@ impl<'a> MirPrettyCtx<'a> {
                                 wln!(this, ",");
                             });
                         }
+                        Terminator::Drop { place, target, unwind } => {
+                            w!(this, "drop(");
+                            this.place(place);
+                            w!(this, ") -> [return: {}", this.basic_block_id(*target));
+                            if let Some(unwind) = unwind {
+                                w!(this, ", unwind: {}", this.basic_block_id(*unwind));
+                            }
+                            wln!(this, "];");
+                        }
+                        Terminator::Resume => wln!(this, "resume;"),
+                        Terminator::Abort => wln!(this, "abort;"),
                         _ => wln!(this, "{:?};", terminator),
                     },
                     None => wln!(this, "<no-terminator>;"),
@@ -234,20 +261,28 @@ impl<'a> MirPrettyCtx<'a> {
                     let variant_data = field.parent.variant_data(this.db.upcast());
                     let name = &variant_data.fields()[field.local_id].name;
                     match field.parent {
-                        hir_def::VariantId::EnumVariantId(e) => {
+                        hir_def::VariantId::EnumVariantId(e)
+                            if !matches!(head.last(), Some(ProjectionElem::Downcast(_))) =>
+                        {
                             w!(this, "(");
                             f(this, local, head);
                             let variant_name =
                                 &this.db.enum_data(e.parent).variants[e.local_id].name;
                             w!(this, " as {}).{}", variant_name, name);
                         }
-                        hir_def::VariantId::StructId(_) | hir_def::VariantId::UnionId(_) => {
+                        _ => {
                             f(this, local, head);
                             w!(this, ".{name}");
                         }
                     }
                 }
-                ProjectionElem::TupleField(x) => {
+                ProjectionElem::Downcast(e) => {
+                    w!(this, "(");
+                    f(this, local, head);
+                    let variant_name = &this.db.enum_data(e.parent).variants[e.local_id].name;
+                    w!(this, " as {})", variant_name);
+                }
+                ProjectionElem::TupleField(x) | ProjectionElem::ClosureField(x) => {
                     f(this, local, head);
                     w!(this, ".{}", x);
                 }
@@ -255,6 +290,16 @@ impl<'a> MirPrettyCtx<'a> {
                     f(this, local, head);
                     w!(this, "[{}]", this.local_name(*l));
                 }
+                &ProjectionElem::ConstantIndex { offset, min_length, from_end } => {
+                    f(this, local, head);
+                    let sign = if from_end { "-" } else { "" };
+                    w!(this, "[{sign}{offset} of {min_length}]");
+                }
+                &ProjectionElem::Subslice { from, to, from_end } => {
+                    f(this, local, head);
+                    let sign = if from_end { "-" } else { "" };
+                    w!(this, "[{from}:{sign}{to}]");
+                }
                 x => {
                     f(this, local, head);
                     w!(this, ".{:?}", x);
@@ -272,6 +317,7 @@ impl<'a> MirPrettyCtx<'a> {
                 self.place(p);
             }
             Operand::Constant(c) => w!(self, "Const({})", c.display(self.db)),
+            Operand::Static(s) => w!(self, "Static({})", self.db.static_data(*s).name),
         }
     }
 
@@ -307,6 +353,16 @@ impl<'a> MirPrettyCtx<'a> {
                 self.operand_list(x);
                 w!(self, ")");
             }
+            Rvalue::Aggregate(AggregateKind::Closure(_), x) => {
+                w!(self, "Closure(");
+                self.operand_list(x);
+                w!(self, ")");
+            }
+            Rvalue::Repeat(op, len) => {
+                w!(self, "[");
+                self.operand(op);
+                w!(self, "; {}]", len.display(self.db));
+            }
             Rvalue::Len(p) => {
                 w!(self, "Len(");
                 self.place(p);
@@ -317,10 +373,17 @@ impl<'a> MirPrettyCtx<'a> {
                 self.operand(op);
                 w!(self, ", {})", ty.display(self.db));
             }
+            Rvalue::BinaryOp(b, o1, o2) => {
+                self.operand(o1);
+                w!(self, " {b} ");
+                self.operand(o2);
+            }
             Rvalue::CheckedBinaryOp(b, o1, o2) => {
+                w!(self, "Checked(");
                 self.operand(o1);
                 w!(self, " {b} ");
                 self.operand(o2);
+                w!(self, ")");
             }
             Rvalue::UnaryOp(u, o) => {
                 let u = match u {
@@ -340,6 +403,9 @@ impl<'a> MirPrettyCtx<'a> {
                 self.operand(op);
                 w!(self, ")");
             }
+            Rvalue::ShallowInitBoxWithAlloc(ty) => {
+                w!(self, "ShallowInitBoxWithAlloc({})", ty.display(self.db));
+            }
             Rvalue::CopyForDeref(p) => {
                 w!(self, "CopyForDeref(");
                 self.place(p);
@@ -0,0 +1,2780 @@
+use std::{sync::Arc, thread};
+
+use base_db::{fixture::WithFixture, FileId};
+use chalk_ir::{ConstValue, IntTy, Mutability, Scalar, UintTy};
+use expect_test::expect;
+use hir_def::{
+    db::DefDatabase,
+    expr::{BinaryOp, Expr, ExprId},
+    FunctionId, ItemContainerId, Lookup,
+};
+use la_arena::Arena;
+use rustc_hash::FxHashSet;
+
+use crate::{
+    consteval::{try_const_usize, ConstEvalError},
+    db::HirDatabase,
+    display::HirDisplay,
+    infer::PointerCast,
+    layout::layout_of_ty,
+    static_lifetime,
+    test_db::TestDB,
+    CallableDefId, ConstScalar, Interner, Substitution, TyKind,
+};
+
+use super::{
+    canonical::successors, interpret_mir, lower_to_mir, optimize, return_slot, AggregateKind,
+    BasicBlock, BasicBlockId, BinOp, BorrowKind, CalleeSet, CastKind, CoverageMode, LocalKind,
+    Location, MirBody, MirConfig, MirEvalError, MirLowerError, MirPass, MirSpan, MutabilityReason,
+    NonStructuralMatch, Operand, PanicStrategy, Place, ProjectionElem, ProjectionError, Rvalue,
+    Statement, StatementKind, SwitchTargets, TempReason, Terminator, ValueUsage,
+};
+
+fn lower_goal(ra_fixture: &str) -> Arc<MirBody> {
+    let (db, file_id) = TestDB::with_single_file(ra_fixture);
+    goal_body(&db, file_id)
+}
+
+fn goal_body(db: &TestDB, file_id: FileId) -> Arc<MirBody> {
+    db.mir_body(goal_id(db, file_id).into()).unwrap()
+}
+
+fn goal_id(db: &TestDB, file_id: FileId) -> FunctionId {
+    let module_id = db.module_for_file(file_id);
+    let def_map = module_id.def_map(db);
+    let scope = &def_map[module_id.local_id].scope;
+    // `goal` is either a free function or a method.
+    let methods = scope.impls().flat_map(|it| db.impl_data(it).items.clone());
+    let func_id = scope
+        .declarations()
+        .filter_map(|x| match x {
+            hir_def::ModuleDefId::FunctionId(x) => Some(x),
+            _ => None,
+        })
+        .chain(methods.filter_map(|x| match x {
+            hir_def::AssocItemId::FunctionId(x) => Some(x),
+            _ => None,
+        }))
+        .find(|&x| db.function_data(x).name.to_string() == "goal")
+        .unwrap();
+    func_id
+}
+
+#[test]
+fn canonical_eq_ignores_numbering() {
+    let body = lower_goal(
+        r#"
+fn goal(x: i32) -> i32 {
+    let y = x + 1;
+    let z = if y > 2 { y } else { 0 };
+    z * 2
+}
+"#,
+    );
+    let signature_len = body.param_locals.len() + 1;
+    let blocks = body.basic_blocks.iter().map(|x| x.0).rev().collect::<Vec<_>>();
+    let locals = body
+        .locals
+        .iter()
+        .take(signature_len)
+        .chain(body.locals.iter().skip(signature_len).rev())
+        .map(|x| x.0)
+        .collect::<Vec<_>>();
+    let shuffled = body.renumber(&blocks, &locals);
+    assert_ne!(*body, shuffled);
+    assert!(body.canonically_eq(&shuffled));
+    assert_eq!(body.canonicalize(), shuffled.canonicalize());
+
+    let other = lower_goal(
+        r#"
+fn goal(x: i32) -> i32 {
+    let y = x + 1;
+    let z = if y > 2 { 0 } else { y };
+    z * 2
+}
+"#,
+    );
+    assert!(!body.canonically_eq(&other));
+    assert_eq!(body.canonicalize().locals[return_slot()], body.locals[return_slot()]);
+}
+
+#[test]
+fn canonical_eq_ignores_lowering_order() {
+    let body = lower_goal(
+        r#"
+fn goal(x: i32) -> i32 {
+    let y = x + 1;
+    let z = if y > 2 { y } else { 0 };
+    z * 2
+}
+"#,
+    );
+    // The same body, as if lowering had created the statements and blocks of each expression in
+    // the opposite order.
+    let mut reordered = (*body).clone();
+    assert!(reordered.source_map.blocks.values().any(|it| it.len() > 1));
+    assert!(reordered.source_map.statements.values().any(|it| it.len() > 1));
+    reordered.source_map.statements.values_mut().for_each(|it| it.reverse());
+    reordered.source_map.blocks.values_mut().for_each(|it| it.reverse());
+    assert_ne!(*body, reordered);
+    assert!(body.canonically_eq(&reordered));
+}
+
+#[test]
+fn non_copy_reads_are_moves() {
+    let body = lower_goal(
+        r#"
+//- minicore: copy
+struct S(i32);
+fn goal(x: i32, s: S) -> i32 {
+    let y = x;
+    let t = s;
+    y
+}
+"#,
+    );
+    let x = body.param_locals[0];
+    let s = body.param_locals[1];
+    let mut copies = vec![];
+    let mut moves = vec![];
+    for (_, block) in body.basic_blocks.iter() {
+        for statement in &block.statements {
+            match &statement.kind {
+                StatementKind::Assign(_, Rvalue::Use(Operand::Copy(p))) => copies.push(p.local),
+                StatementKind::Assign(_, Rvalue::Use(Operand::Move(p))) => moves.push(p.local),
+                _ => (),
+            }
+        }
+    }
+    assert!(copies.contains(&x));
+    assert!(!copies.contains(&s));
+    assert!(moves.contains(&s));
+    assert!(!moves.contains(&x));
+}
+
+#[test]
+fn record_update_moves_non_copy_fields() {
+    let body = lower_goal(
+        r#"
+//- minicore: copy, drop
+struct Guard;
+impl Drop for Guard {
+    fn drop(&mut self) {}
+}
+struct S { n: i32, a: Guard, b: Guard }
+fn make() -> S { loop {} }
+fn goal(g: Guard) -> S {
+    S { a: g, ..make() }
+}
+"#,
+    );
+    let g = body.param_locals[0];
+    let mut calls = 0;
+    let mut dropped = vec![];
+    let mut operands = None;
+    for (_, block) in body.basic_blocks.iter() {
+        for statement in &block.statements {
+            if let StatementKind::Assign(_, Rvalue::Aggregate(_, ops)) = &statement.kind {
+                operands = Some(ops.clone());
+            }
+        }
+        match &block.terminator {
+            Some(Terminator::Call { .. }) => calls += 1,
+            Some(Terminator::Drop { place, .. }) if !block.is_cleanup => {
+                dropped.push(place.clone())
+            }
+            _ => (),
+        }
+    }
+    // The base is evaluated once, even though two fields are taken from it.
+    assert_eq!(calls, 1);
+    let [Operand::Copy(n), Operand::Move(a), Operand::Move(b)] = &operands.unwrap()[..] else {
+        panic!("fields taken from the base should be copied or moved by their type");
+    };
+    assert_eq!(a.local, g);
+    assert_eq!(n.local, b.local);
+    // The overridden field of the temporary base is dropped.
+    assert_eq!(dropped.len(), 1);
+    assert_eq!(dropped[0].local, b.local);
+    assert_ne!(dropped[0], *b);
+}
+
+#[test]
+fn record_update_copies_from_base_behind_reference() {
+    let body = lower_goal(
+        r#"
+//- minicore: copy, drop
+struct Guard;
+impl Drop for Guard {
+    fn drop(&mut self) {}
+}
+struct S { n: i32, a: Guard }
+fn get(s: &S) -> &S { s }
+fn goal(r: &S) -> S {
+    S { a: Guard, ..*get(r) }
+}
+"#,
+    );
+    let mut dropped = vec![];
+    let mut operands = None;
+    for (_, block) in body.basic_blocks.iter() {
+        for statement in &block.statements {
+            if let StatementKind::Assign(_, Rvalue::Aggregate(_, ops)) = &statement.kind {
+                operands = Some(ops.clone());
+            }
+        }
+        if let Some(Terminator::Drop { place, .. }) = &block.terminator {
+            dropped.push(place.clone());
+        }
+    }
+    let [Operand::Copy(n), _] = &operands.unwrap()[..] else {
+        panic!("fields taken from behind a reference should be copied");
+    };
+    assert!(n.projection.contains(&ProjectionElem::Deref));
+    // The overridden field is owned by the referent, not by this body.
+    assert_eq!(dropped, vec![]);
+}
+
+fn blocks(count: usize) -> Vec<BasicBlockId> {
+    let mut arena = Arena::<BasicBlock>::new();
+    (0..count).map(|_| arena.alloc(BasicBlock::default())).collect()
+}
+
+#[test]
+fn retain_blocks_remaps_targets() {
+    let mut body = (*lower_goal("fn goal() {}")).clone();
+    let mut basic_blocks = Arena::new();
+    let b: Vec<BasicBlockId> = (0..5).map(|_| basic_blocks.alloc(BasicBlock::default())).collect();
+    let discr = Operand::Copy(return_slot().into());
+    let terminators = [
+        Terminator::Goto { target: b[2] },
+        Terminator::Unreachable,
+        Terminator::SwitchInt {
+            discr: discr.clone(),
+            targets: SwitchTargets::static_if(1, b[3], b[4]),
+        },
+        Terminator::Return,
+        Terminator::Goto { target: b[0] },
+    ];
+    for (&id, terminator) in b.iter().zip(terminators) {
+        basic_blocks[id].terminator = Some(terminator);
+    }
+    body.basic_blocks = basic_blocks;
+    body.start_block = b[0];
+
+    body.retain_blocks(|it| it != b[1]);
+    // The blocks after the removed one move down by one.
+    let terminators =
+        body.basic_blocks.iter().map(|(_, it)| it.terminator.clone().unwrap()).collect::<Vec<_>>();
+    assert_eq!(
+        terminators,
+        vec![
+            Terminator::Goto { target: b[1] },
+            Terminator::SwitchInt { discr, targets: SwitchTargets::static_if(1, b[2], b[3]) },
+            Terminator::Return,
+            Terminator::Goto { target: b[0] },
+        ]
+    );
+    assert_eq!(body.start_block, b[0]);
+}
+
+#[test]
+fn retain_blocks_keeps_targets_of_kept_blocks() {
+    let mut body = (*lower_goal("fn goal() {}")).clone();
+    let mut basic_blocks = Arena::new();
+    let b: Vec<BasicBlockId> = (0..4).map(|_| basic_blocks.alloc(BasicBlock::default())).collect();
+    let terminators = [
+        Terminator::Goto { target: b[2] },
+        Terminator::Unreachable,
+        Terminator::Goto { target: b[3] },
+        Terminator::Return,
+    ];
+    for (&id, terminator) in b.iter().zip(terminators) {
+        basic_blocks[id].terminator = Some(terminator);
+    }
+    body.basic_blocks = basic_blocks;
+    body.start_block = b[0];
+
+    // The start block and the blocks it jumps to stay, and only the unreachable one is removed.
+    body.retain_blocks(|_| false);
+    let terminators =
+        body.basic_blocks.iter().map(|(_, it)| it.terminator.clone().unwrap()).collect::<Vec<_>>();
+    assert_eq!(
+        terminators,
+        vec![
+            Terminator::Goto { target: b[1] },
+            Terminator::Goto { target: b[2] },
+            Terminator::Return,
+        ]
+    );
+}
+
+#[test]
+fn terminators_mut_redirects_gotos() {
+    let mut body = (*lower_goal("fn goal() {}")).clone();
+    let mut basic_blocks = Arena::new();
+    let b: Vec<BasicBlockId> = (0..4).map(|_| basic_blocks.alloc(BasicBlock::default())).collect();
+    basic_blocks[b[0]].terminator = Some(Terminator::Goto { target: b[1] });
+    basic_blocks[b[1]].terminator = Some(Terminator::Goto { target: b[3] });
+    basic_blocks[b[2]].terminator = Some(Terminator::Return);
+    body.basic_blocks = basic_blocks;
+    body.start_block = b[0];
+
+    // Blocks without a terminator are skipped.
+    assert_eq!(body.terminators_mut().map(|(it, _)| it).collect::<Vec<_>>(), b[..3]);
+    for (_, terminator) in body.terminators_mut() {
+        if let Terminator::Goto { target } = terminator {
+            if *target == b[1] {
+                *target = b[2];
+            }
+        }
+    }
+    assert_eq!(body.basic_blocks[b[0]].terminator, Some(Terminator::Goto { target: b[2] }));
+    assert_eq!(body.basic_blocks[b[1]].terminator, Some(Terminator::Goto { target: b[3] }));
+}
+
+#[test]
+fn switch_targets_one_way() {
+    let b = blocks(1);
+    let targets = SwitchTargets::new([], b[0]);
+    assert_eq!(targets.iter().count(), 0);
+    assert_eq!(targets.otherwise(), b[0]);
+    assert_eq!(targets.all_targets(), &[b[0]]);
+    assert_eq!(targets.target_for_value(0), b[0]);
+    assert_eq!(targets.target_for_value(u128::MAX), b[0]);
+}
+
+#[test]
+fn switch_targets_two_way() {
+    let b = blocks(2);
+    let targets = SwitchTargets::static_if(1, b[0], b[1]);
+    assert_eq!(targets, SwitchTargets::new([(1, b[0])], b[1]));
+    assert_eq!(targets.iter().collect::<Vec<_>>(), vec![(1, b[0])]);
+    assert_eq!(targets.otherwise(), b[1]);
+    assert_eq!(targets.all_targets(), &[b[0], b[1]]);
+    assert_eq!(targets.target_for_value(1), b[0]);
+    assert_eq!(targets.target_for_value(0), b[1]);
+}
+
+#[test]
+fn switch_targets_five_way() {
+    let b = blocks(5);
+    let cases = vec![(10, b[0]), (20, b[1]), (30, b[2]), (40, b[3])];
+    let mut targets = SwitchTargets::new(cases.clone(), b[4]);
+    assert_eq!(targets.iter().collect::<Vec<_>>(), cases);
+    assert_eq!(targets.otherwise(), b[4]);
+    assert_eq!(targets.all_targets(), &b[..]);
+    for &(value, target) in &cases {
+        assert_eq!(targets.target_for_value(value), target);
+    }
+    assert_eq!(targets.target_for_value(25), b[4]);
+
+    // Renumber the blocks in reverse order.
+    for target in targets.all_targets_mut() {
+        *target = b[4 - u32::from(target.into_raw()) as usize];
+    }
+    assert_eq!(
+        targets.iter().collect::<Vec<_>>(),
+        vec![(10, b[4]), (20, b[3]), (30, b[2]), (40, b[1])]
+    );
+    assert_eq!(targets.otherwise(), b[0]);
+    assert_eq!(targets.target_for_value(40), b[1]);
+}
+
+#[test]
+fn terminator_successors() {
+    let b = blocks(5);
+    let place: Place = return_slot().into();
+    let operand = Operand::Copy(place.clone());
+    let cases = [
+        (Terminator::Goto { target: b[0] }, vec![b[0]]),
+        (
+            Terminator::SwitchInt {
+                discr: operand.clone(),
+                targets: SwitchTargets::new([(1, b[1]), (2, b[2])], b[3]),
+            },
+            vec![b[1], b[2], b[3]],
+        ),
+        (Terminator::Resume, vec![]),
+        (Terminator::Abort, vec![]),
+        (Terminator::Return, vec![]),
+        (Terminator::Unreachable, vec![]),
+        (Terminator::GeneratorDrop, vec![]),
+        (Terminator::Drop { place: place.clone(), target: b[1], unwind: None }, vec![b[1]]),
+        (
+            Terminator::DropAndReplace {
+                place: place.clone(),
+                value: operand.clone(),
+                target: b[1],
+                unwind: Some(b[4]),
+            },
+            vec![b[1], b[4]],
+        ),
+        (
+            Terminator::Call {
+                func: operand.clone(),
+                args: vec![],
+                destination: place.clone(),
+                target: Some(b[2]),
+                cleanup: Some(b[4]),
+                from_hir_call: true,
+                span: MirSpan::Unknown,
+            },
+            vec![b[2], b[4]],
+        ),
+        (
+            Terminator::Call {
+                func: operand.clone(),
+                args: vec![],
+                destination: place.clone(),
+                target: None,
+                cleanup: Some(b[4]),
+                from_hir_call: true,
+                span: MirSpan::Unknown,
+            },
+            vec![b[4]],
+        ),
+        (
+            Terminator::Assert {
+                cond: operand.clone(),
+                expected: true,
+                target: b[3],
+                cleanup: None,
+            },
+            vec![b[3]],
+        ),
+        (
+            Terminator::Yield { value: operand, resume: b[0], resume_arg: place, drop: Some(b[1]) },
+            vec![b[0], b[1]],
+        ),
+        (Terminator::FalseEdge { real_target: b[2], imaginary_target: b[3] }, vec![b[2], b[3]]),
+        (Terminator::FalseUnwind { real_target: b[2], unwind: None }, vec![b[2]]),
+    ];
+    for (terminator, expected) in cases {
+        assert_eq!(terminator.successors().collect::<Vec<_>>(), expected, "{terminator:?}");
+    }
+}
+
+#[test]
+fn integer_match_is_a_single_switch() {
+    let body = lower_goal(
+        r#"
+fn goal(x: u32) -> u32 {
+    match x {
+        0 => 10,
+        1 => 11,
+        2 => 12,
+        3 => 13,
+        4 => 14,
+        5 => 15,
+        6 => 16,
+        7 => 17,
+        8 | 9 => 18,
+        _ => 19,
+    }
+}
+"#,
+    );
+    let switches = body
+        .basic_blocks
+        .iter()
+        .filter_map(|(_, block)| match &block.terminator {
+            Some(Terminator::SwitchInt { targets, .. }) => Some(targets),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(switches.len(), 1);
+    assert_eq!(
+        switches[0].iter().map(|(value, _)| value).collect::<Vec<_>>(),
+        (0..10).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn panic_strategy_controls_cleanup_blocks() {
+    let fixture = r#"
+struct Guard;
+impl Drop for Guard {
+    fn drop(&mut self) {}
+}
+fn f() {}
+fn goal() {
+    let _g = Guard;
+    f();
+}
+"#;
+    let body = lower_goal(&format!("//- minicore: drop\n{fixture}"));
+    let cleanup = body
+        .basic_blocks
+        .iter()
+        .find_map(|(_, block)| match &block.terminator {
+            Some(Terminator::Call { cleanup, .. }) => Some(cleanup.expect("call should unwind")),
+            _ => None,
+        })
+        .unwrap();
+    assert!(body.basic_blocks[cleanup].is_cleanup);
+    let Some(Terminator::Drop { target, unwind: Some(abort), .. }) =
+        &body.basic_blocks[cleanup].terminator
+    else {
+        panic!("cleanup block should drop the guard");
+    };
+    assert_eq!(body.basic_blocks[*target].terminator, Some(Terminator::Resume));
+    assert_eq!(body.basic_blocks[*abort].terminator, Some(Terminator::Abort));
+
+    let body = lower_goal(&format!("//- minicore: drop\n//- /main.rs cfg:panic=abort\n{fixture}"));
+    assert!(body.basic_blocks.iter().all(|(_, block)| !block.is_cleanup));
+    assert!(body
+        .basic_blocks
+        .iter()
+        .any(|(_, block)| matches!(block.terminator, Some(Terminator::Drop { unwind: None, .. }))));
+
+    // The strategy passed to the lowering wins over the one of the crate.
+    let (db, file_id) = TestDB::with_single_file(&format!("//- minicore: drop\n{fixture}"));
+    let def = goal_id(&db, file_id).into();
+    let (body, infer) = (db.body(def), db.infer(def));
+    let body = lower_to_mir(
+        &db,
+        def,
+        &body,
+        &infer,
+        body.body_expr,
+        PanicStrategy::Abort,
+        CoverageMode::Off,
+    )
+    .unwrap();
+    assert!(body.basic_blocks.iter().all(|(_, block)| !block.is_cleanup));
+}
+
+#[test]
+fn unwind_paths_share_their_cleanup_blocks() {
+    let body = lower_goal(
+        r#"
+//- minicore: drop
+struct Guard;
+impl Drop for Guard {
+    fn drop(&mut self) {}
+}
+fn f() {}
+fn goal() {
+    let _a = Guard;
+    f();
+    let _b = Guard;
+    f();
+    let _c = Guard;
+    f();
+    {
+        let _d = Guard;
+        f();
+    }
+    f();
+}
+"#,
+    );
+    // One block dropping each guard, plus the `Resume` and `Abort` blocks.
+    let cleanup_blocks = body.basic_blocks.iter().filter(|(_, block)| block.is_cleanup).count();
+    assert_eq!(cleanup_blocks, 4 + 2);
+}
+
+#[test]
+fn entry_arguments_follow_the_signature() {
+    let body = lower_goal(
+        r#"
+fn goal(x: u8, (a, b): (i32, bool)) -> i64 {
+    0
+}
+"#,
+    );
+    let entry = body.entry_arguments();
+    assert_eq!(entry.return_local, return_slot());
+    assert_eq!(entry.return_ty, TyKind::Scalar(Scalar::Int(IntTy::I64)).intern(Interner));
+    let locals: Vec<_> = entry.args.iter().map(|(l, _)| *l).collect();
+    assert_eq!(locals, body.param_locals);
+    assert_eq!(entry.args.len(), body.arg_count);
+    // Arguments are the locals right after the return slot, in the order of the parameters.
+    for (i, (local, _)) in entry.args.iter().enumerate() {
+        assert_eq!(u32::from(local.into_raw()) as usize, i + 1);
+    }
+    assert_eq!(entry.args[0].1, TyKind::Scalar(Scalar::Uint(UintTy::U8)).intern(Interner));
+    assert!(matches!(entry.args[1].1.kind(Interner), TyKind::Tuple(2, _)));
+}
+
+#[test]
+fn locals_record_why_they_exist() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+//- minicore: iterator
+struct Countdown(i32);
+impl Iterator for Countdown {
+    type Item = i32;
+    fn next(&mut self) -> Option<i32> { None }
+}
+struct Counter { n: i32 }
+impl Counter {
+    fn new(n: i32) -> Counter { Counter { n } }
+    fn bump(&mut self) -> &mut Counter { self }
+    fn get(&self) -> i32 { self.n }
+}
+fn goal(start: i32, (base, _): (i32, u8)) -> i32 {
+    let mut sum = base;
+    for x in Countdown(start) {
+        sum = sum + x;
+    }
+    Counter::new(sum).bump().get()
+}
+"#,
+    );
+    let body = goal_body(&db, file_id);
+    assert_eq!(body.locals[return_slot()].kind, LocalKind::ReturnSlot);
+    assert_eq!(body.locals[body.param_locals[1]].kind, LocalKind::Arg);
+    for (binding, &local) in body.binding_locals.iter() {
+        assert_eq!(body.locals[local].kind, LocalKind::UserBinding(binding));
+    }
+    let temps =
+        |reason| body.locals.iter().filter(|(_, l)| l.kind == LocalKind::Temp(reason)).count();
+    // The iterator, the `&mut` to it passed to `next`, and the `Option` it returns.
+    assert_eq!(temps(TempReason::Iterator), 3);
+    assert!(temps(TempReason::Operand) > 0, "{}", body.pretty_print(&db));
+    let others = body
+        .locals
+        .iter()
+        .filter(|(l, _)| !body.binding_locals.values().any(|b| b == l))
+        .filter(|(_, l)| !matches!(l.kind, LocalKind::Temp(_)))
+        .count();
+    assert_eq!(others, 2);
+    assert!(body.pretty_print(&db).contains(" // iterator temporary\n"));
+}
+
+#[test]
+fn only_bindings_that_need_drop_are_dropped() {
+    let body = lower_goal(
+        r#"
+//- minicore: drop
+struct Guard;
+impl Drop for Guard {
+    fn drop(&mut self) {}
+}
+fn goal() {
+    let _a = (1, Guard);
+    let _b = (1, 2);
+}
+"#,
+    );
+    let dropped = body
+        .basic_blocks
+        .iter()
+        .filter_map(|(_, block)| match &block.terminator {
+            Some(Terminator::Drop { place, .. }) if !block.is_cleanup => Some(place.local),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(dropped, vec![*body.binding_locals.iter().next().unwrap().1]);
+}
+
+#[test]
+fn assignments_of_a_binding() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+struct S { f: i32 }
+impl S {
+    fn set(&mut self) {}
+}
+fn goal() {
+    let mut x = S { f: 1 };
+    x = S { f: 2 };
+    x.f = 3;
+    x.set();
+    let r = &mut x;
+    r.f = 4;
+    let y = &x;
+}
+"#,
+    );
+    let body = goal_body(&db, file_id);
+    let hir_body = db.body(body.owner);
+    let binding = |name: &str| {
+        hir_body.bindings.iter().find(|(_, b)| b.name.to_smol_str() == name).unwrap().0
+    };
+    let spans = |name: &str| {
+        let local = body.local_for_binding(binding(name)).unwrap();
+        body.assignments_of(local)
+            .into_iter()
+            .map(|(_, span)| match span {
+                MirSpan::ExprId(e) => match &hir_body[e] {
+                    Expr::BinaryOp { .. } => "assignment",
+                    Expr::Path(_) => "autoref",
+                    Expr::Ref { .. } => "borrow",
+                    _ => "other expression",
+                },
+                MirSpan::PatId(_) => "initialization",
+                MirSpan::Unknown => "unknown",
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(spans("x"), ["initialization", "assignment", "assignment", "autoref", "borrow"]);
+    // Writing through `r` mutates `x`, not `r`.
+    assert_eq!(spans("r"), ["initialization"]);
+    assert_eq!(spans("y"), ["initialization"]);
+}
+
+#[test]
+fn breaks_with_values_jump_to_the_end_of_their_loop() {
+    let body = lower_goal(
+        r#"
+fn compute(x: i32) -> i32 { x }
+fn goal(c: bool, d: bool) -> i32 {
+    let x: i32 = 'outer: loop {
+        loop {
+            if c {
+                break 'outer compute(1);
+            }
+            if d {
+                break 'outer 2;
+            }
+            break;
+        }
+        if d {
+            break 3;
+        }
+    };
+    x
+}
+"#,
+    );
+    let x = *body.binding_locals.values().find(|l| !body.param_locals.contains(l)).unwrap();
+    let reads_x = |block: &BasicBlock| {
+        block.statements.iter().any(|s| {
+            matches!(&s.kind, StatementKind::Assign(p, Rvalue::Use(Operand::Copy(r) | Operand::Move(r)))
+                if p.local == return_slot() && *r == Place::from(x))
+        })
+    };
+    let (end, _) = body.basic_blocks.iter().find(|(_, b)| reads_x(b)).unwrap();
+    let predecessors: Vec<_> = body
+        .basic_blocks
+        .iter()
+        .filter(|(_, b)| matches!(b.terminator, Some(Terminator::Goto { target }) if target == end))
+        .collect();
+    // One for each `break` of the outer loop, which all write to `x` on the way.
+    assert_eq!(predecessors.len(), 3, "{body:?}");
+}
+
+#[test]
+fn zero_sized_fields_are_not_stored() {
+    let body = lower_goal(
+        r#"
+//- minicore: phantom_data
+use core::marker::PhantomData;
+struct Marker;
+struct S<T> { x: i32, p: PhantomData<T>, m: Marker, u: () }
+fn goal(x: i32) -> S<u8> {
+    S { x, p: PhantomData, m: Marker, u: () }
+}
+"#,
+    );
+    // Only the return slot and `x`, without temporaries for the zero sized fields.
+    assert_eq!(body.locals.len(), 2);
+    let statements: Vec<_> = body.basic_blocks.iter().flat_map(|(_, b)| &b.statements).collect();
+    let [statement] = &statements[..] else { panic!("{body:?}") };
+    let StatementKind::Assign(_, Rvalue::Aggregate(AggregateKind::Adt(..), ops)) = &statement.kind else {
+        panic!("{statement:?}")
+    };
+    assert!(matches!(ops[0], Operand::Copy(_)));
+    assert!(ops[1..].iter().all(|op| matches!(op, Operand::Constant(_))));
+}
+
+#[test]
+fn source_map_links_expressions_to_their_statements() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+fn goal(x: i32) -> i32 {
+    let y = x + 1;
+    if y > 2 { y } else { 0 }
+}
+"#,
+    );
+    let body = goal_body(&db, file_id);
+    let hir_body = db.body(body.owner);
+    let find_expr = |f: fn(&Expr) -> bool| hir_body.exprs.iter().find(|(_, e)| f(e)).unwrap().0;
+    let statement = |body: &MirBody, l: Location| {
+        body.basic_blocks[l.block].statements[l.statement_index].clone()
+    };
+
+    let add = find_expr(|e| matches!(e, Expr::BinaryOp { op: Some(BinaryOp::ArithOp(_)), .. }));
+    let locations = body.source_map.statements(add);
+    assert_eq!(locations.len(), 1);
+    let add_statement = statement(&body, locations[0]);
+    assert!(matches!(
+        add_statement.kind,
+        StatementKind::Assign(_, Rvalue::BinaryOp(BinOp::Add, ..))
+    ));
+    assert_eq!(add_statement.span, MirSpan::ExprId(add));
+    // The statements of the operands belong to the operands.
+    assert!(body.source_map.statements(find_expr(|e| matches!(e, Expr::Literal(_)))).is_empty());
+
+    // The branches of the `if` get their own blocks.
+    let if_expr = find_expr(|e| matches!(e, Expr::If { .. }));
+    assert_eq!(body.source_map.blocks(if_expr).len(), 3);
+    assert!(body.source_map.statements(if_expr).is_empty());
+
+    // Renumbering keeps the map pointing at the same statements.
+    let canonical = body.canonicalize();
+    let locations = canonical.source_map.statements(add);
+    assert_eq!(locations.len(), 1);
+    let renumbered = statement(&canonical, locations[0]);
+    assert!(matches!(renumbered.kind, StatementKind::Assign(_, Rvalue::BinaryOp(BinOp::Add, ..))));
+    assert_eq!(renumbered.span, MirSpan::ExprId(add));
+
+    let optimized = db.optimized_mir_body(body.owner).unwrap();
+    assert!(optimized.source_map.statements(add).is_empty());
+}
+
+#[test]
+fn self_parameter_is_named() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+struct S { x: i32 }
+impl S {
+    fn goal(&mut self) -> i32 {
+        self.x
+    }
+}
+"#,
+    );
+    let body = goal_body(&db, file_id);
+    let hir_body = db.body(body.owner);
+    let self_binding =
+        hir_body.bindings.iter().find(|(_, b)| b.name.to_smol_str() == "self").unwrap().0;
+    assert_eq!(body.local_for_binding(self_binding), Some(body.param_locals[0]));
+    let pretty = body.pretty_print(&db);
+    assert!(pretty.contains("let self_1: &mut S;"), "{pretty}");
+}
+
+#[test]
+fn lowering_is_deterministic() {
+    const FIXTURE: &str = r#"
+//- minicore: drop, option
+struct Guard(i32);
+impl Drop for Guard {
+    fn drop(&mut self) {}
+}
+const S: &str = "hello";
+fn f(x: &str) -> usize {
+    x.len()
+}
+fn goal(x: Option<i32>, y: u8) -> usize {
+    let _g = Guard(1);
+    let c: fn(u8) -> u8 = |a| a + 1;
+    let mut n = 0;
+    'outer: loop {
+        'inner: while n < 10 {
+            match c(y) {
+                0 => break 'outer,
+                1 | 2 => continue,
+                _ => n = n + 1,
+            }
+            if let Some(z) = x {
+                if z > 2 {
+                    break 'inner;
+                }
+            }
+        }
+        break;
+    }
+    f(S) + f("world") + n
+}
+"#;
+    let pretty = || {
+        let (db, file_id) = TestDB::with_single_file(FIXTURE);
+        goal_body(&db, file_id).pretty_print(&db)
+    };
+    let expected = pretty();
+    assert_eq!(pretty(), expected);
+    // A fresh thread gets new random keys for the hash maps created on it.
+    assert_eq!(thread::spawn(pretty).join().unwrap(), expected);
+}
+
+fn optimized(body: &MirBody, passes: &[MirPass]) -> MirBody {
+    let mut body = body.clone();
+    optimize(&mut body, passes);
+    body
+}
+
+fn has_switch(body: &MirBody) -> bool {
+    body.basic_blocks
+        .iter()
+        .any(|(_, b)| matches!(b.terminator, Some(Terminator::SwitchInt { .. })))
+}
+
+#[test]
+fn optimizations_shrink_the_cfg() {
+    let (mut db, file_id) = TestDB::with_single_file(
+        r#"
+fn goal(x: i32) -> i32 {
+    let mut y = x;
+    if x > 2 {
+        y = y + 1;
+    }
+    let z = if y > 5 { 1 } else { 1 };
+    loop {
+        if y > 10 {
+            break;
+        }
+        y = y * 2;
+    }
+    y + z
+}
+"#,
+    );
+    let body = goal_body(&db, file_id);
+    let func = body.owner;
+    let optimized_body = db.optimized_mir_body(func).unwrap();
+    assert!(optimized_body.basic_blocks.len() < body.basic_blocks.len());
+    assert_eq!(*optimized_body, optimized(&body, MirPass::ALL));
+    assert_eq!(optimized(&body, &[]), *body);
+
+    db.set_mir_config(MirConfig { disabled_passes: MirPass::ALL.to_vec(), ..MirConfig::default() });
+    assert_eq!(db.optimized_mir_body(func).unwrap(), body);
+}
+
+#[test]
+fn closure_bodies_are_optimized() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+//- minicore: fn
+fn goal(x: i32) -> i32 {
+    let f = |y: i32| {
+        let c = true;
+        if c { y } else { 2 }
+    };
+    f(x)
+}
+"#,
+    );
+    let body = goal_body(&db, file_id);
+    let closure = body
+        .basic_blocks
+        .iter()
+        .flat_map(|(_, block)| &block.statements)
+        .find_map(|statement| match &statement.kind {
+            StatementKind::Assign(_, Rvalue::Aggregate(AggregateKind::Closure(ty), _)) => {
+                match ty.kind(Interner) {
+                    TyKind::Closure(closure, _) => Some(*closure),
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+        .unwrap();
+    let closure_body = db.mir_body_for_closure(closure).unwrap();
+    let optimized_body = db.optimized_mir_body_for_closure(closure).unwrap();
+    assert!(has_switch(&closure_body));
+    assert!(!has_switch(&optimized_body));
+    assert_eq!(*optimized_body, optimized(&closure_body, MirPass::ALL));
+}
+
+#[test]
+fn constant_conditions_are_folded() {
+    let body = lower_goal(
+        r#"
+fn goal(x: i32) -> i32 {
+    let c = true;
+    if c { x } else { 2 }
+}
+"#,
+    );
+    assert!(has_switch(&body));
+    let simplified = optimized(&body, &[MirPass::SimplifyCfg, MirPass::RemoveUnreachableBlocks]);
+    assert!(has_switch(&simplified));
+    let folded = optimized(&body, MirPass::ALL);
+    assert!(!has_switch(&folded));
+    assert_eq!(folded.basic_blocks.len(), 1);
+}
+
+#[test]
+fn single_use_temporaries_are_forwarded() {
+    let body = lower_goal(
+        r#"
+fn goal(x: i32) -> i32 {
+    let y = x + 1;
+    y
+}
+"#,
+    );
+    let count_assignments = |body: &MirBody| {
+        body.basic_blocks
+            .iter()
+            .flat_map(|(_, b)| &b.statements)
+            .filter(|s| matches!(s.kind, StatementKind::Assign(..)))
+            .count()
+    };
+    let forwarded = optimized(&body, &[MirPass::CopyPropagation, MirPass::SimplifyCfg]);
+    assert!(count_assignments(&forwarded) < count_assignments(&body));
+}
+
+#[test]
+fn forwarded_locals_are_not_used() {
+    let body = lower_goal(
+        r#"
+fn goal(x: i32) -> i32 {
+    let y = x + 1;
+    y
+}
+"#,
+    );
+    let used = body.used_locals();
+    assert!(body.locals.iter().all(|(l, _)| used.contains(&l)));
+    let forwarded = optimized(&body, &[MirPass::CopyPropagation]);
+    let used = forwarded.used_locals();
+    let unused: Vec<_> =
+        forwarded.locals.iter().filter(|(l, _)| !used.contains(l)).map(|(l, _)| l).collect();
+    assert_eq!(unused.len(), 1, "{forwarded:?}");
+    // `y` is only mentioned by its storage statements once its value is forwarded to the return slot.
+    assert!(matches!(forwarded.locals[unused[0]].kind, LocalKind::UserBinding(_)));
+    assert!(used.contains(&return_slot()));
+    assert!(used.contains(&forwarded.param_locals[0]));
+
+    let storage_of = |body: &MirBody, local| {
+        body.basic_blocks
+            .iter()
+            .flat_map(|(_, b)| &b.statements)
+            .filter(|s| match s.kind {
+                StatementKind::StorageLive(l) | StatementKind::StorageDead(l) => l == local,
+                _ => false,
+            })
+            .count()
+    };
+    assert_ne!(storage_of(&forwarded, unused[0]), 0);
+    let removed = optimized(&body, &[MirPass::CopyPropagation, MirPass::RemoveDeadLocals]);
+    assert_eq!(storage_of(&removed, unused[0]), 0);
+}
+
+#[test]
+fn loop_invariant_assignments_are_hoisted() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+fn goal(x: i32) -> i32 {
+    let mut i = 0;
+    let mut sum = 0;
+    while i < x {
+        let k = 7;
+        let same = x == 3;
+        if same {
+            sum = sum + k;
+        }
+        i = i + 1;
+    }
+    sum
+}
+"#,
+    );
+    let body = goal_body(&db, file_id);
+    let in_cycle = |body: &MirBody, block: BasicBlockId| {
+        let mut stack: Vec<_> = successors(&body.basic_blocks[block]).collect();
+        let mut visited = FxHashSet::default();
+        while let Some(b) = stack.pop() {
+            if b == block {
+                return true;
+            }
+            if visited.insert(b) {
+                stack.extend(successors(&body.basic_blocks[b]));
+            }
+        }
+        false
+    };
+    let invariant_assignments = |body: &MirBody| {
+        body.basic_blocks
+            .iter()
+            .flat_map(|(b, block)| block.statements.iter().map(move |s| (b, s)))
+            .filter(|(_, s)| {
+                matches!(
+                    &s.kind,
+                    StatementKind::Assign(_, Rvalue::Use(Operand::Constant(_)))
+                        | StatementKind::Assign(_, Rvalue::BinaryOp(BinOp::Eq, ..))
+                )
+            })
+            .map(|(b, _)| in_cycle(body, b))
+            .collect::<Vec<_>>()
+    };
+    assert!(invariant_assignments(&body).contains(&true), "{}", body.pretty_print(&db));
+    let hoisted = optimized(&body, &[MirPass::LoopInvariantCodeMotion]);
+    assert!(!invariant_assignments(&hoisted).contains(&true), "{}", hoisted.pretty_print(&db));
+    assert_eq!(hoisted.stats(), body.stats());
+    // The storage of `k` and `same` ends in every iteration, so they are assigned in the loop
+    // even though their values are invariant.
+    let storage_in_loop = hoisted
+        .basic_blocks
+        .iter()
+        .filter(|(b, _)| in_cycle(&hoisted, *b))
+        .flat_map(|(_, block)| &block.statements)
+        .filter_map(|s| match s.kind {
+            StatementKind::StorageLive(l) | StatementKind::StorageDead(l) => Some(l),
+            _ => None,
+        })
+        .collect::<FxHashSet<_>>();
+    assert_eq!(storage_in_loop.len(), 2);
+    for (b, block) in hoisted.basic_blocks.iter() {
+        for s in &block.statements {
+            if let StatementKind::Assign(p, _) = &s.kind {
+                if storage_in_loop.contains(&p.local) {
+                    assert!(in_cycle(&hoisted, b), "{}", hoisted.pretty_print(&db));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn passes_report_their_effect() {
+    let body = lower_goal(
+        r#"
+fn goal(x: i32) -> i32 {
+    let c = true;
+    let mut y = if c { x } else { 2 };
+    while y < 10 {
+        y = y * 2;
+    }
+    y
+}
+"#,
+    );
+    let mut optimized = (*body).clone();
+    let stats = optimize(&mut optimized, MirPass::ALL);
+    assert_eq!(stats.iter().map(|it| it.pass).collect::<Vec<_>>(), MirPass::ALL);
+    assert_eq!(stats[0].before, body.stats());
+    assert_eq!(stats.last().unwrap().after, optimized.stats());
+    for (prev, next) in stats.iter().zip(&stats[1..]) {
+        assert_eq!(prev.after, next.before);
+    }
+    for it in &stats {
+        assert!(it.after.blocks <= it.before.blocks, "{} added blocks", it.pass.name());
+        assert!(it.after.statements <= it.before.statements, "{} added statements", it.pass.name());
+    }
+    assert!(optimized.stats().blocks < body.stats().blocks);
+}
+
+#[test]
+fn check_types_finds_mistyped_assignment() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+fn goal(x: i32) -> i32 {
+    let y = x + 1;
+    y
+}
+"#,
+    );
+    let body = goal_body(&db, file_id);
+    assert_eq!(body.check_types(&db), Ok(()));
+
+    let mut mistyped = (*body).clone();
+    let u32_ty = TyKind::Scalar(Scalar::Uint(UintTy::U32)).intern(Interner);
+    let statement = Statement {
+        kind: StatementKind::Assign(
+            return_slot().into(),
+            Operand::from_bytes(5u32.to_le_bytes().to_vec(), u32_ty.clone()).into(),
+        ),
+        span: MirSpan::Unknown,
+    };
+    mistyped.basic_blocks[mistyped.start_block].statements.push(statement);
+    let mismatch = mistyped.check_types(&db).unwrap_err();
+    assert_eq!(mismatch.rvalue_ty, u32_ty);
+    assert_eq!(mismatch.place_ty, TyKind::Scalar(Scalar::Int(IntTy::I32)).intern(Interner));
+}
+
+#[test]
+fn consts_in_patterns_must_be_structural_match() {
+    let fixture = |goal: &str| {
+        format!(
+            r#"
+//- minicore: eq, derive
+#[derive(PartialEq)]
+struct Derived(i32);
+struct Manual(i32);
+impl PartialEq for Manual {{
+    fn eq(&self, _: &Self) -> bool {{ true }}
+}}
+const D: (Derived, &[Derived; 1]) = (Derived(1), &[Derived(2)]);
+const M: Option<(u8, Manual)> = None;
+enum Option<T> {{ Some(T), None }}
+use Option::*;
+{goal}
+"#
+        )
+    };
+    let (db, file_id) = TestDB::with_single_file(&fixture(
+        "fn goal(x: (Derived, &[Derived; 1])) -> bool { match x { D => true, _ => false } }",
+    ));
+    assert!(db.mir_body(goal_id(&db, file_id).into()).is_ok());
+
+    let (db, file_id) = TestDB::with_single_file(&fixture(
+        "fn goal(x: Option<(u8, Manual)>) -> bool { match x { M => true, _ => false } }",
+    ));
+    match db.mir_body(goal_id(&db, file_id).into()) {
+        Err(MirLowerError::NonStructuralMatchConst { ty, reason, .. }) => {
+            assert_eq!(reason, NonStructuralMatch::MissingPartialEq);
+            assert_eq!(ty.display_test(&db).to_string(), "Option<(u8, Manual)>");
+        }
+        it => panic!("unexpected lowering result: {it:?}"),
+    }
+}
+
+#[test]
+fn rvalue_types() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+//- minicore: sized
+#[lang = "owned_box"]
+struct Box<T: ?Sized>(*mut T);
+fn goal(x: u8) -> usize {
+    x as usize
+}
+"#,
+    );
+    let body = goal_body(&db, file_id);
+    let x = body.binding_locals[body.binding_locals.iter().next().unwrap().0];
+    let ty = |rvalue: Rvalue| rvalue.ty(&db, &body).map(|it| it.display_test(&db).to_string());
+    let x_place = || super::Place::from(x);
+    let usize_ty = TyKind::Scalar(Scalar::Uint(UintTy::Usize)).intern(Interner);
+
+    assert_eq!(ty(Operand::Copy(x_place()).into()).as_deref(), Some("u8"));
+    assert_eq!(
+        ty(Rvalue::Ref(BorrowKind::Mut { allow_two_phase_borrow: false }, x_place())).as_deref(),
+        Some("&mut u8")
+    );
+    assert_eq!(
+        ty(Rvalue::BinaryOp(BinOp::Lt, Operand::Copy(x_place()), Operand::Copy(x_place())))
+            .as_deref(),
+        Some("bool")
+    );
+    assert_eq!(
+        ty(Rvalue::CheckedBinaryOp(BinOp::Add, Operand::Copy(x_place()), Operand::Copy(x_place())))
+            .as_deref(),
+        Some("(u8, bool)")
+    );
+    assert_eq!(
+        ty(Rvalue::Cast(CastKind::IntToInt, Operand::Copy(x_place()), usize_ty.clone())).as_deref(),
+        Some("usize")
+    );
+    assert_eq!(ty(Rvalue::Discriminant(x_place())).as_deref(), Some("i128"));
+    assert_eq!(
+        ty(Rvalue::Aggregate(
+            AggregateKind::Array(usize_ty.clone()),
+            vec![Operand::Copy(x_place()), Operand::Copy(x_place())],
+        ))
+        .as_deref(),
+        Some("[usize; 2]")
+    );
+    assert_eq!(
+        ty(Rvalue::ShallowInitBox(Operand::Copy(x_place()), usize_ty)).as_deref(),
+        Some("Box<usize>")
+    );
+}
+
+#[test]
+fn checked_binary_ops_yield_the_overflow_flag() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+fn goal() -> (u8, bool) {
+    let a = 250u8;
+    let b = 10u8;
+    (a, false)
+}
+"#,
+    );
+    let body = goal_body(&db, file_id);
+    let [a, b] = body.binding_locals.iter().map(|it| *it.1).collect::<Vec<_>>()[..] else {
+        panic!("expected two bindings")
+    };
+    let eval = |op: BinOp| {
+        let mut body = (*body).clone();
+        for (_, block) in body.basic_blocks.iter_mut() {
+            for statement in &mut block.statements {
+                if let StatementKind::Assign(_, rvalue @ Rvalue::Aggregate(..)) =
+                    &mut statement.kind
+                {
+                    *rvalue = Rvalue::CheckedBinaryOp(
+                        op.clone(),
+                        Operand::Copy(a.into()),
+                        Operand::Copy(b.into()),
+                    );
+                }
+            }
+        }
+        let result = interpret_mir(&db, &body, Substitution::empty(Interner), false).unwrap();
+        let ConstValue::Concrete(c) = &result.data(Interner).value else {
+            panic!("the result is not a concrete value");
+        };
+        let ConstScalar::Bytes(bytes, _) = &c.interned else { panic!("the result has no bytes") };
+        bytes.clone()
+    };
+    assert_eq!(eval(BinOp::Add), vec![4, 1]);
+    assert_eq!(eval(BinOp::Sub), vec![240, 0]);
+    assert_eq!(eval(BinOp::Mul), vec![196, 1]);
+    // The shift amount is masked, so this shifts by 2.
+    assert_eq!(eval(BinOp::Shl), vec![232, 1]);
+}
+
+#[test]
+fn projection_checks_find_field_of_scalar() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+struct S { a: i32 }
+fn goal(s: S, x: i32) -> i32 {
+    s.a + x
+}
+"#,
+    );
+    let body = goal_body(&db, file_id);
+    assert_eq!(body.apply_projection_checks(&db), Ok(()));
+
+    // Reads the field of `s` from `x` instead.
+    let mut invalid = (*body).clone();
+    let [s, x] = invalid.param_locals[..] else { panic!() };
+    let mut found = None;
+    invalid.walk_places(|location, place| {
+        if place.local == s && !place.projection.is_empty() {
+            place.local = x;
+            found = Some((location, place.clone()));
+        }
+    });
+    let (location, place) = found.unwrap();
+    let error = invalid.apply_projection_checks(&db).unwrap_err();
+    assert_eq!(error.location, location);
+    assert_eq!(error.place, place);
+    assert_eq!(error.projection_index, 0);
+    assert_eq!(error.base_ty, TyKind::Scalar(Scalar::Int(IntTy::I32)).intern(Interner));
+    assert_eq!(error.reason, ProjectionError::UnexpectedBaseType);
+}
+
+/// The blocks that jump to `block`.
+fn predecessors(body: &MirBody, block: BasicBlockId) -> Vec<BasicBlockId> {
+    body.basic_blocks
+        .iter()
+        .filter(|(_, b)| successors(b).any(|s| s == block))
+        .map(|x| x.0)
+        .collect()
+}
+
+#[test]
+fn diverging_branches_do_not_reach_the_destination() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+fn f() -> ! { loop {} }
+fn goal(c: bool, n: i32, never: !) -> i32 {
+    let x = if c { 1 } else { return 0; };
+    let y = match n { 0 => f(), 1 => return 2, _ => 5 };
+    let z: i32 = if c { never } else { 3 };
+    x + y + z
+}
+"#,
+    );
+    let body = goal_body(&db, file_id);
+    let hir_body = db.body(body.owner);
+    for name in ["x", "y", "z"] {
+        let (binding, _) =
+            hir_body.bindings.iter().find(|(_, b)| b.name.to_smol_str() == name).unwrap();
+        let local = body.local_for_binding(binding).unwrap();
+        let assignments = body.assignments_of(local);
+        assert_eq!(assignments.len(), 1, "{name}");
+        // The value of the live branch flows into the binding without going through a block
+        // where the diverging branches join it.
+        let (location, _) = assignments[0];
+        let block = &body.basic_blocks[location.block];
+        let StatementKind::Assign(_, Rvalue::Use(Operand::Copy(value) | Operand::Move(value))) =
+            &block.statements[location.statement_index].kind
+        else {
+            panic!("{name} is not initialized from a temporary");
+        };
+        let writes = body.assignments_of(value.local);
+        assert_eq!(writes.len(), 1, "{name}");
+        let mut block = location.block;
+        while block != writes[0].0.block {
+            match predecessors(&body, block)[..] {
+                [pred] => block = pred,
+                ref preds => panic!("{name} is reached from {preds:?}"),
+            }
+        }
+    }
+}
+
+#[test]
+fn while_let_bindings_die_each_iteration() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+//- minicore: option
+struct Stack { items: [i32; 2], len: usize }
+impl Stack {
+    fn pop(&mut self) -> Option<&i32> { loop {} }
+}
+fn goal(mut stack: Stack) -> i32 {
+    let mut acc = 0;
+    while let Some(item) = stack.pop() {
+        acc = acc + *item;
+    }
+    acc
+}
+"#,
+    );
+    let body = goal_body(&db, file_id);
+    let hir_body = db.body(body.owner);
+    let (binding, _) =
+        hir_body.bindings.iter().find(|(_, b)| b.name.to_smol_str() == "item").unwrap();
+    let item = body.local_for_binding(binding).unwrap();
+    let (head, _) = body
+        .basic_blocks
+        .iter()
+        .find(|(_, b)| matches!(b.terminator, Some(Terminator::Call { .. })))
+        .unwrap();
+    let dead_in = body
+        .basic_blocks
+        .iter()
+        .filter(|(_, b)| {
+            b.statements
+                .iter()
+                .any(|s| matches!(s.kind, StatementKind::StorageDead(l) if l == item))
+        })
+        .map(|(id, b)| (id, successors(b).collect::<Vec<_>>()))
+        .collect::<Vec<_>>();
+    // `item` dies at the end of the body, before `pop` is called again, and when the loop exits.
+    assert_eq!(dead_in.len(), 2, "{}", body.pretty_print(&db));
+    assert!(dead_in.iter().any(|(_, succ)| succ[..] == [head]), "{}", body.pretty_print(&db));
+    assert!(dead_in.iter().all(|&(id, _)| id != head));
+}
+
+#[test]
+fn while_let_bindings_die_on_continue_and_break() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+//- minicore: option
+struct Stack { items: [i32; 2], len: usize }
+impl Stack {
+    fn pop(&mut self) -> Option<&i32> { loop {} }
+}
+fn goal(mut stack: Stack) -> i32 {
+    let mut acc = 0;
+    while let Some(item) = stack.pop() {
+        if *item == 0 {
+            continue;
+        }
+        if *item == 1 {
+            break;
+        }
+        acc = acc + *item;
+    }
+    acc
+}
+"#,
+    );
+    let body = goal_body(&db, file_id);
+    let hir_body = db.body(body.owner);
+    let (binding, _) =
+        hir_body.bindings.iter().find(|(_, b)| b.name.to_smol_str() == "item").unwrap();
+    let item = body.local_for_binding(binding).unwrap();
+    let (head, _) = body
+        .basic_blocks
+        .iter()
+        .find(|(_, b)| matches!(b.terminator, Some(Terminator::Call { .. })))
+        .unwrap();
+    let dead_in = body
+        .basic_blocks
+        .iter()
+        // The block binding `item` ends its storage before starting it, see `push_storage_live`.
+        .filter(|(_, b)| {
+            let dead = b.statements.iter().any(|s| s.kind == StatementKind::StorageDead(item));
+            dead && b.statements.iter().all(|s| s.kind != StatementKind::StorageLive(item))
+        })
+        .map(|(_, b)| successors(b).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    // `item` also dies before the `continue` jumps back to `pop`, and before the `break`.
+    assert_eq!(dead_in.len(), 4, "{}", body.pretty_print(&db));
+    let to_head = dead_in.iter().filter(|succ| succ[..] == [head]).count();
+    assert_eq!(to_head, 2, "{}", body.pretty_print(&db));
+}
+
+#[test]
+fn match_scrutinee_temporary_outlives_the_arms() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+struct S { a: i32 }
+fn make() -> S { S { a: 1 } }
+fn goal() -> i32 {
+    let r = match make() {
+        ref s => s.a,
+    };
+    r
+}
+"#,
+    );
+    let body = goal_body(&db, file_id);
+    let (call, scrutinee) = body
+        .basic_blocks
+        .iter()
+        .find_map(|(id, b)| match &b.terminator {
+            Some(Terminator::Call { destination, .. }) => Some((id, destination.local)),
+            _ => None,
+        })
+        .unwrap();
+    let blocks_with = |f: &dyn Fn(&StatementKind) -> bool| {
+        body.basic_blocks
+            .iter()
+            .filter(|(_, b)| b.statements.iter().any(|s| f(&s.kind)))
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>()
+    };
+    let live = blocks_with(&|s| matches!(s, StatementKind::StorageLive(l) if *l == scrutinee));
+    let dead = blocks_with(&|s| matches!(s, StatementKind::StorageDead(l) if *l == scrutinee));
+    let borrow = blocks_with(
+        &|s| matches!(s, StatementKind::Assign(_, Rvalue::Ref(_, p)) if p.local == scrutinee),
+    );
+    assert_eq!(live, [call], "{}", body.pretty_print(&db));
+    // The arm borrowing the scrutinee ends before its storage does.
+    assert_eq!(borrow.len(), 1, "{}", body.pretty_print(&db));
+    assert_eq!(dead.len(), 1, "{}", body.pretty_print(&db));
+    assert_ne!(borrow[0], dead[0]);
+    assert_eq!(successors(&body.basic_blocks[borrow[0]]).collect::<Vec<_>>(), [dead[0]]);
+}
+
+#[test]
+fn pattern_failures_jump_to_the_next_arm_directly() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+struct S { a: i32, b: i32, c: (i32, i32), d: i32, e: i32, f: i32 }
+fn goal(s: S) -> i32 {
+    match s {
+        S { a: 1, b: 2 | 3, c: (4, 5), d: 6, e: 7, f: 8 } => 1,
+        S { a: 1, b, .. } if b > 2 => b,
+        _ => 0,
+    }
+}
+"#,
+    );
+    let body = goal_body(&db, file_id);
+    let is_goto_only = |block: BasicBlockId| {
+        let block = &body.basic_blocks[block];
+        block.statements.is_empty() && matches!(block.terminator, Some(Terminator::Goto { .. }))
+    };
+    let mut failure_targets = vec![];
+    for (_, block) in body.basic_blocks.iter() {
+        if let Some(Terminator::SwitchInt { targets, .. }) = &block.terminator {
+            assert!(!is_goto_only(targets.otherwise()), "{}", body.pretty_print(&db));
+            failure_targets.push(targets.otherwise());
+        }
+    }
+    let otherwise = |block: BasicBlockId| match &body.basic_blocks[block].terminator {
+        Some(Terminator::SwitchInt { targets, .. }) => targets.otherwise(),
+        _ => panic!("{}", body.pretty_print(&db)),
+    };
+    // Every test of the first arm, except the first alternative of `2 | 3`, fails to the start
+    // of the second arm, and both its pattern and its guard fail to the last arm.
+    let second_arm = otherwise(body.start_block);
+    assert_eq!(failure_targets.iter().filter(|&&t| t == second_arm).count(), 7);
+    let last_arm = otherwise(second_arm);
+    assert_eq!(predecessors(&body, last_arm).len(), 2);
+}
+
+#[test]
+fn matches_on_unit_variants_is_a_single_switch() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+//- minicore: matches
+enum Mode { A, B, C }
+fn goal(x: Mode) -> bool {
+    matches!(x, Mode::A | Mode::C)
+}
+"#,
+    );
+    let body = goal_body(&db, file_id);
+    let switches = body
+        .basic_blocks
+        .iter()
+        .filter_map(|(_, b)| match &b.terminator {
+            Some(Terminator::SwitchInt { targets, .. }) => Some(targets),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(switches.len(), 1, "{}", body.pretty_print(&db));
+    let values = switches[0].iter().map(|(v, _)| v).collect::<Vec<_>>();
+    assert_eq!(values, [0, 2]);
+}
+
+#[test]
+fn cast_operand_is_adjusted() {
+    let body = lower_goal(
+        r#"
+fn f(x: &mut i32) -> &mut i32 { x }
+fn goal(x: &mut i32) -> *const i32 {
+    f(x) as *const i32
+}
+"#,
+    );
+    let (operand, target) = body
+        .basic_blocks
+        .iter()
+        .flat_map(|(_, b)| &b.statements)
+        .filter_map(|s| match &s.kind {
+            StatementKind::Assign(_, Rvalue::Cast(CastKind::PtrToPtr, operand, target)) => {
+                Some((operand, target))
+            }
+            _ => None,
+        })
+        .last()
+        .unwrap();
+    // The reference returned by `f` is reborrowed as a raw pointer before the cast, so the cast
+    // written in the source is from the adjusted type.
+    let (Operand::Copy(p) | Operand::Move(p)) = operand else { panic!("constant cast operand") };
+    let i32_ty = TyKind::Scalar(Scalar::Int(IntTy::I32)).intern(Interner);
+    assert_eq!(body.locals[p.local].ty, TyKind::Raw(Mutability::Not, i32_ty).intern(Interner));
+    assert_eq!(&body.locals[p.local].ty, target);
+}
+
+#[test]
+fn mutable_statics_are_borrowed_mutably() {
+    let body = lower_goal(
+        r#"
+static S: i32 = 1;
+static mut M: i32 = 2;
+fn goal() -> i32 {
+    unsafe {
+        M = S;
+        M
+    }
+}
+"#,
+    );
+    let mutabilities: Vec<_> = body
+        .basic_blocks
+        .iter()
+        .flat_map(|(_, b)| &b.statements)
+        .filter_map(|s| match &s.kind {
+            StatementKind::Assign(p, Rvalue::Use(Operand::Static(_))) => {
+                match body.locals[p.local].ty.kind(Interner) {
+                    TyKind::Ref(mutability, ..) => Some(*mutability),
+                    _ => panic!("static operand of a non reference type"),
+                }
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(mutabilities, [Mutability::Mut, Mutability::Not, Mutability::Mut]);
+}
+
+#[test]
+fn rvalue_receiver_is_borrowed_from_a_dropped_temporary() {
+    let body = lower_goal(
+        r#"
+//- minicore: drop
+struct Guard(i32);
+impl Drop for Guard {
+    fn drop(&mut self) {}
+}
+impl Guard {
+    fn bump(&mut self) -> i32 { loop {} }
+}
+fn goal() -> i32 {
+    let x = Guard(1).bump();
+    x
+}
+"#,
+    );
+    let (call_block, temp) = body
+        .basic_blocks
+        .iter()
+        .find_map(|(id, b)| {
+            b.statements.iter().find_map(|s| match &s.kind {
+                StatementKind::Assign(_, Rvalue::Ref(BorrowKind::Mut { .. }, p)) => {
+                    Some((id, p.local))
+                }
+                _ => None,
+            })
+        })
+        .expect("the temporary is not borrowed mutably");
+    assert!(body.binding_locals.iter().all(|(_, &l)| l != temp));
+    // The temporary is dropped after the call, at the end of the `let` statement.
+    let Some(Terminator::Call { target: Some(target), .. }) =
+        &body.basic_blocks[call_block].terminator
+    else {
+        panic!("call without a target");
+    };
+    assert!(matches!(
+        &body.basic_blocks[*target].terminator,
+        Some(Terminator::Drop { place, .. }) if place.local == temp
+    ));
+}
+
+#[test]
+fn closure_body_accesses_captures_through_its_environment() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+//- minicore: fn
+fn goal() -> u8 {
+    let mut n = 0;
+    let mut inc = || {
+        n = n + 1;
+    };
+    inc();
+    n
+}
+"#,
+    );
+    let body = goal_body(&db, file_id);
+    let closure_ty = body
+        .basic_blocks
+        .iter()
+        .flat_map(|(_, block)| &block.statements)
+        .find_map(|statement| match &statement.kind {
+            StatementKind::Assign(_, Rvalue::Aggregate(AggregateKind::Closure(ty), operands)) => {
+                assert_eq!(operands.len(), 1);
+                Some(ty.clone())
+            }
+            _ => None,
+        })
+        .unwrap();
+    let TyKind::Closure(closure, _) = closure_ty.kind(Interner) else { panic!() };
+    let closure_body = db.mir_body_for_closure(*closure).unwrap();
+    // The closure mutates its capture, so it is `FnMut` and takes itself by mutable reference.
+    assert_eq!(closure_body.arg_count, 1);
+    let env = closure_body.param_locals[0];
+    assert_eq!(
+        closure_body.locals[env].ty,
+        TyKind::Ref(Mutability::Mut, static_lifetime(), closure_ty).intern(Interner)
+    );
+    let captured = Place {
+        local: env,
+        projection: vec![
+            ProjectionElem::Deref,
+            ProjectionElem::ClosureField(0),
+            ProjectionElem::Deref,
+        ],
+    };
+    assert!(closure_body.basic_blocks.iter().flat_map(|(_, block)| &block.statements).any(
+        |statement| matches!(&statement.kind, StatementKind::Assign(place, _) if *place == captured)
+    ));
+}
+
+#[test]
+fn closure_aggregates_follow_the_capture_kinds() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+//- minicore: fn
+fn goal() -> i32 {
+    let a = 1;
+    let b = 2;
+    let by_ref = || a + 1;
+    let by_move = move || b + 1;
+    let empty = || 3;
+    by_ref() + by_move() + empty()
+}
+"#,
+    );
+    let body = goal_body(&db, file_id);
+    let statements: Vec<_> =
+        body.basic_blocks.iter().flat_map(|(_, block)| &block.statements).collect();
+    let closures: Vec<_> = statements
+        .iter()
+        .filter_map(|statement| match &statement.kind {
+            StatementKind::Assign(_, Rvalue::Aggregate(AggregateKind::Closure(ty), operands)) => {
+                Some((ty.clone(), operands.clone()))
+            }
+            _ => None,
+        })
+        .collect();
+    let [(_, by_ref), (_, by_move), (empty_ty, empty)] = &closures[..] else {
+        panic!("expected three closures, found {closures:?}");
+    };
+    let binding_local = |name: &str| {
+        let hir_body = db.body(body.owner);
+        let binding = hir_body.bindings.iter().find(|(_, it)| it.name.to_smol_str() == name);
+        body.binding_locals[binding.unwrap().0]
+    };
+    // `a` is only read, so the closure holds a shared reference to it.
+    let [Operand::Move(temp)] = &by_ref[..] else { panic!("{by_ref:?}") };
+    assert!(statements.iter().any(|statement| matches!(
+        &statement.kind,
+        StatementKind::Assign(place, Rvalue::Ref(BorrowKind::Shared, borrowed))
+            if place == temp && borrowed.local == binding_local("a")
+    )));
+    // `move` closures take their captures by value.
+    let [Operand::Copy(moved) | Operand::Move(moved)] = &by_move[..] else { panic!("{by_move:?}") };
+    assert_eq!(moved.local, binding_local("b"));
+    // A closure without captures is a zero-sized value.
+    assert!(empty.is_empty());
+    let krate = db.module_for_file(file_id).krate();
+    assert_eq!(layout_of_ty(&db, empty_ty, krate).unwrap().size.bytes(), 0);
+}
+
+#[test]
+fn naked_functions_are_not_lowered() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+#[naked]
+fn goal() -> i32 {
+    unsafe { core::arch::asm!("mov eax, 5", "ret", options(noreturn)) }
+}
+"#,
+    );
+    let result = db.mir_body(goal_id(&db, file_id).into());
+    assert_eq!(result.map(|_| ()), Err(MirLowerError::NakedFunction));
+}
+
+#[test]
+fn inline_asm_is_not_lowered() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+#[rustc_builtin_macro]
+macro_rules! asm { () => {} }
+fn goal(x: i32) -> i32 {
+    unsafe {
+        asm!("nop");
+    }
+    x
+}
+"#,
+    );
+    let result = db.mir_body(goal_id(&db, file_id).into());
+    assert_eq!(result.map(|_| ()), Err(MirLowerError::InlineAsm));
+}
+
+fn callee_names(db: &TestDB, callees: &CalleeSet) -> Vec<String> {
+    let mut names: Vec<_> = callees
+        .callees
+        .iter()
+        .map(|&it| match it {
+            CallableDefId::FunctionId(it) => db.function_data(it).name.to_string(),
+            CallableDefId::StructId(it) => db.struct_data(it).name.to_string(),
+            CallableDefId::EnumVariantId(it) => {
+                db.enum_data(it.parent).variants[it.local_id].name.to_string()
+            }
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn callees_of_direct_and_indirect_calls() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+fn foo() -> i32 { 1 }
+fn bar(x: i32) -> i32 { x }
+fn baz() -> i32 { 2 }
+fn goal() -> i32 {
+    let p: fn() -> i32 = baz;
+    bar(foo()) + p()
+}
+"#,
+    );
+    let callees = db.mir_callees(goal_id(&db, file_id).into());
+    assert_eq!(callee_names(&db, &callees), ["bar", "foo"]);
+    assert!(callees.has_indirect_calls);
+    // The callees are in the order of the calls, not of their hashes.
+    let functions: Vec<_> = callees
+        .callees
+        .iter()
+        .map(|&it| match it {
+            CallableDefId::FunctionId(it) => db.function_data(it).name.to_string(),
+            _ => panic!("{it:?}"),
+        })
+        .collect();
+    assert_eq!(functions, ["foo", "bar"]);
+}
+
+#[test]
+fn callees_of_constructors_drops_and_methods() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+//- minicore: drop, option, fn
+struct Wrapper(i32);
+struct Record { x: i32 }
+impl Drop for Wrapper {
+    fn drop(&mut self) {}
+}
+trait Tr {
+    fn method(&self) -> i32;
+}
+impl Tr for Record {
+    fn method(&self) -> i32 { self.x }
+}
+fn goal() -> Option<i32> {
+    let w = Wrapper(1);
+    let r = Record { x: w.0 };
+    let double = |x: i32| Some(x * 2);
+    double(r.method())
+}
+"#,
+    );
+    let callees = db.mir_callees(goal_id(&db, file_id).into());
+    assert_eq!(callee_names(&db, &callees), ["Some", "Wrapper", "call", "drop", "method"]);
+    assert!(!callees.has_indirect_calls);
+}
+
+#[test]
+fn callees_of_drop_glue() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+//- minicore: drop, manually_drop
+use core::mem::ManuallyDrop;
+struct Outer { inner: Inner, pair: (i32, [Other; 2]), skipped: ManuallyDrop<Skipped>, e: E }
+struct Inner;
+struct Other;
+struct Skipped;
+struct InVariant;
+enum E { A(i32), B(InVariant) }
+impl Drop for Outer { fn drop(&mut self) {} }
+impl Drop for Inner { fn drop(&mut self) {} }
+impl Drop for Other { fn drop(&mut self) {} }
+impl Drop for Skipped { fn drop(&mut self) {} }
+impl Drop for InVariant { fn drop(&mut self) {} }
+fn goal(x: Outer) {
+    let _y = x;
+}
+"#,
+    );
+    let callees = db.mir_callees(goal_id(&db, file_id).into());
+    let dropped: Vec<_> = callees
+        .callees
+        .iter()
+        .map(|&it| match it {
+            CallableDefId::FunctionId(it) => match it.lookup(&db).container {
+                ItemContainerId::ImplId(imp) => {
+                    db.impl_self_ty(imp).skip_binders().display(&db).to_string()
+                }
+                _ => panic!("{it:?}"),
+            },
+            _ => panic!("{it:?}"),
+        })
+        .collect();
+    assert_eq!(dropped, ["Outer", "Inner", "Other", "InVariant"]);
+}
+
+fn call_targets(body: &MirBody) -> Vec<Option<BasicBlockId>> {
+    body.basic_blocks
+        .iter()
+        .filter_map(|(_, b)| match &b.terminator {
+            Some(Terminator::Call { target, .. }) => Some(*target),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn calls_returning_uninhabited_types_diverge() {
+    let body = lower_goal(
+        r#"
+enum Infallible {}
+fn fail<E>() -> E { loop {} }
+fn goal() -> i32 {
+    let _e: Infallible = fail();
+    5
+}
+"#,
+    );
+    assert_eq!(call_targets(&body), [None]);
+}
+
+#[test]
+fn calls_returning_privately_uninhabited_types_return() {
+    let body = lower_goal(
+        r#"
+mod m {
+    enum Void {}
+    pub struct Opaque(Void);
+    pub struct Wrapper(pub Opaque);
+}
+fn make<T>() -> T { loop {} }
+fn goal() -> i32 {
+    let _w: m::Wrapper = make();
+    5
+}
+"#,
+    );
+    assert!(matches!(call_targets(&body)[..], [Some(_)]));
+}
+
+#[test]
+fn matched_variant_fields_are_read_through_a_downcast() {
+    let body = lower_goal(
+        r#"
+enum E {
+    A(i32),
+    B { x: i64, y: i32 },
+}
+fn goal(e: E) -> i32 {
+    match e {
+        E::A(a) => a,
+        E::B { y, .. } => y,
+    }
+}
+"#,
+    );
+    let reads: Vec<_> = body
+        .basic_blocks
+        .iter()
+        .flat_map(|(_, block)| &block.statements)
+        .filter_map(|statement| match &statement.kind {
+            StatementKind::Assign(_, Rvalue::Use(Operand::Copy(p) | Operand::Move(p)))
+                if p.local == body.param_locals[0] =>
+            {
+                Some(&p.projection[..])
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(reads.len(), 2);
+    for projection in reads {
+        let [ProjectionElem::Downcast(variant), ProjectionElem::Field(field)] = projection else {
+            panic!("{projection:?}");
+        };
+        assert_eq!(field.parent, (*variant).into());
+    }
+}
+
+#[test]
+fn labels_are_only_visible_in_their_loop() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+fn goal() {
+    'a: loop {
+        break 'a;
+    }
+    loop {
+        break 'a;
+    }
+}
+"#,
+    );
+    let result = db.mir_body(goal_id(&db, file_id).into());
+    assert!(matches!(result, Err(MirLowerError::UnresolvedLabel)), "{result:?}");
+}
+
+#[test]
+fn nested_loops_and_labeled_blocks() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+fn goal(a: bool, b: bool, c: bool) -> i32 {
+    'outer: loop {
+        'inner: loop {
+            let x = 'block: {
+                if a {
+                    break 'block 1;
+                }
+                if b {
+                    continue 'inner;
+                }
+                if c {
+                    continue 'outer;
+                }
+                break 'outer 2;
+            };
+            if x == 1 {
+                break;
+            }
+            continue;
+        }
+        break 3;
+    }
+}
+"#,
+    );
+    expect![[r#"
+        // FunctionId(FunctionId(0))
+        {
+            let _0: i32;
+            let a_1: bool;
+            let b_2: bool;
+            let c_3: bool;
+            let x_4: i32;
+            let _5: (); // operand temporary
+            let _6: (); // operand temporary
+            let _7: (); // operand temporary
+            let _8: i32; // operand temporary
+            let _9: (); // operand temporary
+            let _10: !; // operand temporary
+            let _11: (); // operand temporary
+            let _12: !; // operand temporary
+            let _13: (); // operand temporary
+            let _14: !; // operand temporary
+            let _15: !; // operand temporary
+            let _16: (); // operand temporary
+            let _17: bool; // operand temporary
+            let _18: !; // operand temporary
+            let _19: !; // operand temporary
+            let _20: !; // operand temporary
+
+
+            'bb0: {
+                goto 'bb1;
+            }
+
+            'bb1: {
+                goto 'bb2;
+            }
+
+            'bb2: {
+                goto 'bb3;
+            }
+
+            'bb3: {
+                switch a_1 {
+                    1 => 'bb4,
+                    _ => 'bb5,
+                }
+            }
+
+            'bb4: {
+                _8 = Const(1);
+                goto 'bb6;
+            }
+
+            'bb5: {
+                switch b_2 {
+                    1 => 'bb7,
+                    _ => 'bb8,
+                }
+            }
+
+            'bb6: {
+                StorageDead(x_4)
+                StorageLive(x_4)
+                x_4 = _8;
+                _17 = x_4 == Const(1);
+                switch _17 {
+                    1 => 'bb12,
+                    _ => 'bb13,
+                }
+            }
+
+            'bb7: {
+                goto 'bb2;
+            }
+
+            'bb8: {
+                switch c_3 {
+                    1 => 'bb9,
+                    _ => 'bb10,
+                }
+            }
+
+            'bb9: {
+                goto 'bb1;
+            }
+
+            'bb10: {
+                _0 = Const(2);
+                goto 'bb11;
+            }
+
+            'bb11: {
+                Return;
+            }
+
+            'bb12: {
+                _6 = Const(());
+                goto 'bb14;
+            }
+
+            'bb13: {
+                goto 'bb2;
+            }
+
+            'bb14: {
+                _0 = Const(3);
+                goto 'bb11;
+            }
+        }
+    "#]]
+    .assert_eq(&goal_body(&db, file_id).pretty_print(&db));
+}
+
+#[test]
+fn call_arguments_are_unsized_to_trait_objects() {
+    let body = lower_goal(
+        r#"
+//- minicore: coerce_unsized, fmt
+use core::fmt::{Debug, Formatter, Result};
+// Minicore has no `Debug` impls of its own.
+impl Debug for i32 {
+    fn fmt(&self, _: &mut Formatter<'_>) -> Result {
+        loop {}
+    }
+}
+fn takes(_: &dyn Debug) {}
+fn goal() {
+    takes(&5i32);
+}
+"#,
+    );
+    let args = body
+        .basic_blocks
+        .iter()
+        .find_map(|(_, b)| match &b.terminator {
+            Some(Terminator::Call { args, .. }) => Some(args.clone()),
+            _ => None,
+        })
+        .unwrap();
+    let [Operand::Copy(arg) | Operand::Move(arg)] = &args[..] else { panic!("{args:?}") };
+    let unsized_ty = body
+        .basic_blocks
+        .iter()
+        .flat_map(|(_, block)| &block.statements)
+        .find_map(|statement| match &statement.kind {
+            StatementKind::Assign(
+                place,
+                Rvalue::Cast(CastKind::Pointer(PointerCast::Unsize), _, ty),
+            ) if place == arg => Some(ty.clone()),
+            _ => None,
+        })
+        .unwrap();
+    let TyKind::Ref(_, _, pointee) = unsized_ty.kind(Interner) else { panic!() };
+    assert!(matches!(pointee.kind(Interner), TyKind::Dyn(_)));
+}
+
+#[test]
+fn coverage_counters_at_branch_entries() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+fn goal(x: i32, c: bool) -> i32 {
+    let y = if c { 1 } else { 2 };
+    match x {
+        0 => y,
+        1 => 10,
+        _ => 20,
+    }
+}
+"#,
+    );
+    let def = goal_id(&db, file_id).into();
+    let (body, infer) = (db.body(def), db.infer(def));
+    let lower = |coverage| {
+        let panic_strategy = PanicStrategy::Unwind;
+        lower_to_mir(&db, def, &body, &infer, body.body_expr, panic_strategy, coverage).unwrap()
+    };
+    let counters = |mir: &MirBody| -> Vec<(BasicBlockId, usize)> {
+        mir.basic_blocks
+            .iter()
+            .flat_map(|(id, block)| block.statements.iter().map(move |s| (id, &s.kind)))
+            .filter_map(|(id, kind)| match kind {
+                StatementKind::Coverage(counter) => Some((id, *counter)),
+                _ => None,
+            })
+            .collect()
+    };
+    assert_eq!(counters(&lower(CoverageMode::Off)), []);
+
+    let mir = lower(CoverageMode::Branches);
+    let branch_entries: FxHashSet<BasicBlockId> = mir
+        .basic_blocks
+        .iter()
+        .filter_map(|(_, block)| match &block.terminator {
+            Some(Terminator::SwitchInt { targets, .. }) => Some(targets.all_targets()),
+            _ => None,
+        })
+        .flatten()
+        .copied()
+        .collect();
+    // The two branches of the `if` and at least one block per arm of the `match`.
+    assert!(branch_entries.len() >= 5, "{}", mir.pretty_print(&db));
+    let counters = counters(&mir);
+    assert_eq!(counters.len(), branch_entries.len());
+    for (i, &(block, counter)) in counters.iter().enumerate() {
+        assert_eq!(counter, i);
+        assert!(branch_entries.contains(&block));
+        let first = &mir.basic_blocks[block].statements[0];
+        assert_eq!(first.kind, StatementKind::Coverage(counter));
+        assert!(mir
+            .source_map
+            .statements(first.span)
+            .contains(&Location { block, statement_index: 0 }));
+    }
+    // Apart from the counters, the body is unchanged.
+    let mut without_counters = mir;
+    for (_, block) in without_counters.basic_blocks.iter_mut() {
+        block.statements.retain(|s| !matches!(s.kind, StatementKind::Coverage(_)));
+    }
+    assert_eq!(without_counters.basic_blocks, lower(CoverageMode::Off).basic_blocks);
+}
+
+#[test]
+fn expr_value_usage() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+//- minicore: index, slice
+fn make() -> i32 { 1 }
+fn take(x: i32) -> i32 { x }
+fn goal(xs: [i32; 2], i: usize) -> i32 {
+    make();
+    let _ = make();
+    let x = make();
+    take(make());
+    let _ = xs[i];
+    xs[i];
+    i as u8;
+    let y = xs[i] as i64;
+    make()
+}
+"#,
+    );
+    let body = goal_body(&db, file_id);
+    let hir_body = db.body(body.owner);
+    let calls_of = |name: &str| -> Vec<ExprId> {
+        hir_body
+            .exprs
+            .iter()
+            .filter(|(_, e)| match e {
+                Expr::Call { callee, .. } => match &hir_body[*callee] {
+                    Expr::Path(p) => {
+                        p.segments().last().map_or(false, |s| s.name.to_smol_str() == name)
+                    }
+                    _ => false,
+                },
+                _ => false,
+            })
+            .map(|(id, _)| id)
+            .collect()
+    };
+    let x = hir_body.bindings.iter().find(|(_, b)| b.name.to_smol_str() == "x").unwrap().0;
+    let usages: Vec<_> = calls_of("make").into_iter().map(|e| body.expr_value_usage(e)).collect();
+    assert_eq!(
+        usages,
+        [
+            ValueUsage::Discarded,
+            ValueUsage::Discarded,
+            ValueUsage::Bound(x),
+            ValueUsage::Used,
+            ValueUsage::Returned,
+        ]
+    );
+    let [take] = calls_of("take")[..] else { panic!() };
+    assert_eq!(body.expr_value_usage(take), ValueUsage::Discarded);
+    // The temporaries of the bounds checks and casts are not the values of the expressions.
+    let of_kind = |f: fn(&Expr) -> bool| -> Vec<ValueUsage> {
+        hir_body
+            .exprs
+            .iter()
+            .filter(|(_, e)| f(e))
+            .map(|(id, _)| body.expr_value_usage(id))
+            .collect()
+    };
+    assert_eq!(
+        of_kind(|e| matches!(e, Expr::Index { .. })),
+        [ValueUsage::Discarded, ValueUsage::Discarded, ValueUsage::Used]
+    );
+    let y = hir_body.bindings.iter().find(|(_, b)| b.name.to_smol_str() == "y").unwrap().0;
+    assert_eq!(
+        of_kind(|e| matches!(e, Expr::Cast { .. })),
+        [ValueUsage::Discarded, ValueUsage::Bound(y)]
+    );
+    // The block of the body has no assignment of its own.
+    assert_eq!(body.expr_value_usage(hir_body.body_expr), ValueUsage::Unknown);
+}
+
+#[test]
+fn lengths_are_read_for_bounds_checks_and_slice_patterns() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+//- minicore: coerce_unsized, index, slice
+fn goal(i: usize) -> i32 {
+    let a = [1, 2, 3];
+    let s: &[i32] = &a;
+    let [_, tail @ ..] = a;
+    let x = a[i] + s[i] + tail[i];
+    match s {
+        [first, rest @ ..] => x + *first + rest[0],
+        [] => 0,
+    }
+}
+"#,
+    );
+    let body = goal_body(&db, file_id);
+    // The length of an array place, or `None` for a slice place.
+    let len_of = |p: &Place| match p.ty(&db, &body).unwrap().kind(Interner) {
+        TyKind::Array(_, len) => Some(try_const_usize(len).unwrap()),
+        TyKind::Slice(_) => None,
+        ty => panic!("length of {ty:?}"),
+    };
+    let statements = body.basic_blocks.iter().flat_map(|(_, block)| &block.statements);
+    let len_places = statements.clone().filter_map(|statement| match &statement.kind {
+        StatementKind::Assign(_, Rvalue::Len(p)) => Some(len_of(p)),
+        _ => None,
+    });
+    // The bounds checks of `a[i]`, `s[i]` and `tail[i]`, the length tests of the two slice
+    // patterns and the bounds check of `rest[0]`.
+    assert_eq!(len_places.collect::<Vec<_>>(), [Some(3), None, Some(2), None, None, None]);
+    let subslice_places = statements.filter_map(|statement| match &statement.kind {
+        StatementKind::Assign(_, Rvalue::Use(Operand::Copy(p) | Operand::Move(p)))
+        | StatementKind::Assign(_, Rvalue::Ref(_, p))
+            if matches!(p.projection.last(), Some(ProjectionElem::Subslice { .. })) =>
+        {
+            Some(len_of(p))
+        }
+        _ => None,
+    });
+    assert_eq!(subslice_places.collect::<Vec<_>>(), [Some(2), None]);
+    let asserts = body
+        .basic_blocks
+        .iter()
+        .filter(|(_, block)| matches!(block.terminator, Some(Terminator::Assert { .. })));
+    assert_eq!(asserts.count(), 4);
+}
+
+#[test]
+fn trivially_const_bodies() {
+    let is_trivially_const = |ra_fixture: &str| lower_goal(ra_fixture).is_trivially_const();
+    assert!(is_trivially_const(
+        r#"
+fn goal(x: i32, y: i32) -> i32 {
+    let z = if x > y { x - y } else { y - x };
+    z * 2 + 1
+}
+"#,
+    ));
+    assert!(!is_trivially_const(
+        r#"
+fn goal(mut x: i32) -> i32 {
+    let mut r = 0;
+    while x > 0 {
+        r = r + x;
+        x = x - 1;
+    }
+    r
+}
+"#,
+    ));
+    assert!(!is_trivially_const(
+        r#"
+fn double(x: i32) -> i32 { x * 2 }
+fn goal(x: i32) -> i32 { double(x) + 1 }
+"#,
+    ));
+    assert!(!is_trivially_const(
+        r#"
+fn goal<T: Copy>(x: T) -> (T, T) { (x, x) }
+"#,
+    ));
+}
+
+#[test]
+fn projected_types_through_a_generic_struct() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+//- minicore: coerce_unsized, index, slice
+struct Wrapper<T> { inner: T }
+fn goal(w: &Wrapper<(i32, [u8; 4])>, i: usize) -> u8 {
+    w.inner.1[i]
+}
+"#,
+    );
+    let body = goal_body(&db, file_id);
+    let krate = db.module_for_file(file_id).krate();
+    let place = body
+        .basic_blocks
+        .iter()
+        .flat_map(|(_, block)| &block.statements)
+        .find_map(|statement| match &statement.kind {
+            StatementKind::Assign(_, Rvalue::Use(Operand::Copy(p) | Operand::Move(p)))
+                if matches!(p.projection.last(), Some(ProjectionElem::Index(_))) =>
+            {
+                Some(p.clone())
+            }
+            _ => None,
+        })
+        .unwrap();
+    let mut ty = body.locals[place.local].ty.clone();
+    let steps: Vec<_> = place
+        .projection
+        .iter()
+        .map(|proj| {
+            ty = proj.projected_ty(&db, ty.clone(), krate).unwrap();
+            ty.display(&db).to_string()
+        })
+        .collect();
+    assert_eq!(steps, ["Wrapper<(i32, [u8; 4])>", "(i32, [u8; 4])", "[u8; 4]", "u8"]);
+    assert_eq!(place.ty(&db, &body), Some(ty));
+
+    let [deref, field, tuple_field, index] = &place.projection[..] else { panic!() };
+    let local_ty = body.locals[place.local].ty.clone();
+    let wrapper_ty = deref.projected_ty(&db, local_ty, krate).unwrap();
+    let array_ty = [field, tuple_field]
+        .iter()
+        .try_fold(wrapper_ty.clone(), |ty, proj| proj.projected_ty(&db, ty, krate))
+        .unwrap();
+    let subslice = ProjectionElem::Subslice { from: 1, to: 1, from_end: true };
+    let subslice_ty = subslice.projected_ty(&db, array_ty.clone(), krate).unwrap();
+    assert_eq!(subslice_ty.display(&db).to_string(), "[u8; 2]");
+    // Projections which don't apply to the type of the place.
+    let unexpected = Err(ProjectionError::UnexpectedBaseType);
+    assert_eq!(tuple_field.projected_ty(&db, wrapper_ty.clone(), krate), unexpected);
+    assert_eq!(index.projected_ty(&db, wrapper_ty, krate), unexpected);
+    assert_eq!(deref.projected_ty(&db, array_ty.clone(), krate), unexpected);
+    let out_of_bounds = ProjectionElem::Subslice { from: 3, to: 2, from_end: false };
+    let error = out_of_bounds.projected_ty(&db, array_ty, krate);
+    assert_eq!(error, Err(ProjectionError::SubsliceOutOfRange));
+}
+
+#[test]
+fn closure_with_expression_body() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+//- minicore: fn
+fn goal() -> i32 {
+    let add_one = |x: i32| x + 1;
+    add_one(2)
+}
+"#,
+    );
+    let body = goal_body(&db, file_id);
+    let closure_ty = body
+        .basic_blocks
+        .iter()
+        .flat_map(|(_, block)| &block.statements)
+        .find_map(|statement| match &statement.kind {
+            StatementKind::Assign(_, Rvalue::Aggregate(AggregateKind::Closure(ty), _)) => {
+                Some(ty.clone())
+            }
+            _ => None,
+        })
+        .unwrap();
+    let TyKind::Closure(closure, _) = closure_ty.kind(Interner) else { panic!() };
+    let closure_body = db.mir_body_for_closure(*closure).unwrap();
+    let (_, closure_expr) = db.lookup_intern_closure((*closure).into());
+    let hir_body = db.body(closure_body.owner);
+    let Expr::Closure { body: body_expr, .. } = &hir_body[closure_expr] else { panic!() };
+    assert!(matches!(hir_body[*body_expr], Expr::BinaryOp { .. }));
+    // The body is lowered like the tail of a block, writing the sum to the return slot.
+    let x = closure_body.param_locals[1];
+    let assignments: Vec<_> = closure_body
+        .basic_blocks
+        .iter()
+        .flat_map(|(_, block)| &block.statements)
+        .filter_map(|statement| match &statement.kind {
+            StatementKind::Assign(place, rvalue) => Some((place, rvalue, statement.span)),
+            _ => None,
+        })
+        .collect();
+    match &assignments[..] {
+        [(place, Rvalue::BinaryOp(BinOp::Add, Operand::Copy(lhs), _), span)] => {
+            assert_eq!(**place, return_slot().into());
+            assert_eq!(*lhs, x.into());
+            assert_eq!(*span, MirSpan::ExprId(*body_expr));
+        }
+        _ => panic!("unexpected assignments {assignments:?}"),
+    }
+}
+
+#[test]
+fn bodies_with_unused_closures_are_lowered() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+//- minicore: fn
+fn goal() -> i32 {
+    let mut total = 0;
+    let _unused = |y: i32| y * 2;
+    let f = |x: i32| x + 1;
+    total = total + f(2);
+    total
+}
+"#,
+    );
+    let goal = goal_id(&db, file_id);
+    assert!(db.mir_body(goal.into()).is_ok());
+    // The analyses on the MIR see the bindings of the body, like `total` which needs `mut`.
+    let borrowck = db.borrowck(goal.into()).unwrap();
+    let total = borrowck
+        .mir_body
+        .locals
+        .iter()
+        .find_map(|(local, it)| match it.kind {
+            LocalKind::UserBinding(binding) => {
+                let hir_body = db.body(goal.into());
+                (hir_body.bindings[binding].name.to_smol_str() == "total").then_some(local)
+            }
+            _ => None,
+        })
+        .unwrap();
+    assert!(matches!(borrowck.mutability_of_locals[total], MutabilityReason::Mut { .. }));
+}
+
+#[test]
+fn tail_await_writes_the_return_slot() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+//- minicore: future
+async fn inner(x: i32) -> i32 {
+    x + 1
+}
+struct S;
+impl S {
+    async fn method(&self) -> i32 {
+        inner(2).await * 10
+    }
+}
+async fn goal() -> i32 {
+    S.method().await
+}
+"#,
+    );
+    let body = goal_body(&db, file_id);
+    assert!(body.basic_blocks.iter().any(|(_, block)| matches!(
+        &block.terminator,
+        Some(Terminator::Call { destination, .. }) if *destination == return_slot().into()
+    )));
+    let result = interpret_mir(&db, &body, Substitution::empty(Interner), false).unwrap();
+    let ConstValue::Concrete(c) = &result.data(Interner).value else {
+        panic!("the result is not a concrete value");
+    };
+    let ConstScalar::Bytes(bytes, _) = &c.interned else { panic!("the result has no bytes") };
+    assert_eq!(bytes[..], 30i32.to_le_bytes());
+}
+
+#[test]
+fn const_params_of_bodies_interpreted_without_generic_args() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+fn goal<const N: usize>() -> usize {
+    N + 1
+}
+"#,
+    );
+    let body = db.mir_body(goal_id(&db, file_id).into()).unwrap();
+    let result = interpret_mir(&db, &body, Substitution::empty(Interner), false);
+    let Err(MirEvalError::ConstEvalError(e)) = result else { panic!("{result:?}") };
+    let expected = MirLowerError::ConstParamInRuntimeContext("N".to_string());
+    assert_eq!(*e, ConstEvalError::MirLowerError(expected.clone()));
+    assert_eq!(expected.to_string(), "the value of const parameter `N` is not known here");
+}
+
+#[test]
+fn repeat_length_stays_a_const_param() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+fn goal<const N: usize>() -> [u8; N] {
+    [0; N]
+}
+"#,
+    );
+    let body = db.mir_body(goal_id(&db, file_id).into()).unwrap();
+    let lengths: Vec<_> = body
+        .basic_blocks
+        .iter()
+        .flat_map(|(_, block)| &block.statements)
+        .filter_map(|statement| match &statement.kind {
+            StatementKind::Assign(_, Rvalue::Repeat(_, len)) => Some(len),
+            _ => None,
+        })
+        .collect();
+    let [len] = lengths[..] else { panic!("{lengths:?}") };
+    assert!(matches!(len.data(Interner).value, chalk_ir::ConstValue::Placeholder(_)));
+    assert_eq!(try_const_usize(len), None);
+}
+
+#[test]
+fn logic_ops_only_reach_the_rhs_through_the_lhs() {
+    let reachable = |body: &MirBody, from: BasicBlockId| {
+        let mut stack = vec![from];
+        let mut visited = FxHashSet::default();
+        while let Some(b) = stack.pop() {
+            if visited.insert(b) {
+                stack.extend(successors(&body.basic_blocks[b]));
+            }
+        }
+        visited
+    };
+    for (op, rhs_value) in [("&&", 1), ("||", 0)] {
+        let body = lower_goal(&format!(
+            r#"
+fn side() -> bool {{ true }}
+fn goal(a: bool) -> bool {{
+    a {op} side()
+}}
+"#
+        ));
+        let call_block = body
+            .basic_blocks
+            .iter()
+            .find(|(_, block)| matches!(block.terminator, Some(Terminator::Call { .. })))
+            .map(|(b, _)| b)
+            .unwrap();
+        let Some(Terminator::SwitchInt { targets, .. }) =
+            &body.basic_blocks[body.start_block].terminator
+        else {
+            panic!("the lhs of `{op}` doesn't branch");
+        };
+        let short_circuit = targets.target_for_value(1 - rhs_value);
+        assert!(
+            reachable(&body, targets.target_for_value(rhs_value)).contains(&call_block),
+            "{op}"
+        );
+        assert!(!reachable(&body, short_circuit).contains(&call_block), "{op}");
+    }
+}
+
+#[test]
+fn unit_values_are_zero_sized() {
+    let (db, file_id) = TestDB::with_single_file(
+        r#"
+fn takes(_: ()) {}
+fn goal() {
+    let unit = ();
+    takes(());
+}
+"#,
+    );
+    let body = goal_body(&db, file_id);
+    let blocks = body.basic_blocks.iter().map(|(_, block)| block);
+    let aggregates: Vec<_> = blocks
+        .clone()
+        .flat_map(|block| &block.statements)
+        .filter_map(|statement| match &statement.kind {
+            StatementKind::Assign(_, Rvalue::Aggregate(AggregateKind::Tuple(ty), operands)) => {
+                Some((ty, operands))
+            }
+            _ => None,
+        })
+        .collect();
+    // The binding is initialized with an aggregate of no operands, which stores no bytes.
+    let [(unit_ty, operands)] = aggregates[..] else { panic!("{aggregates:?}") };
+    assert!(operands.is_empty());
+    let krate = db.module_for_file(file_id).krate();
+    assert_eq!(layout_of_ty(&db, unit_ty, krate).unwrap().size.bytes(), 0);
+    // As an argument, `()` is a constant without bytes instead of a temporary.
+    let args: Vec<_> = blocks
+        .filter_map(|block| match &block.terminator {
+            Some(Terminator::Call { args, .. }) => Some(&args[..]),
+            _ => None,
+        })
+        .collect();
+    let [[Operand::Constant(c)]] = &args[..] else { panic!("{args:?}") };
+    let chalk_ir::ConstValue::Concrete(c) = &c.data(Interner).value else { panic!("{c:?}") };
+    assert!(matches!(&c.interned, crate::ConstScalar::Bytes(bytes, _) if bytes.is_empty()));
+}
+
+#[test]
+fn identical_literals_share_memory_map_allocations() {
+    let body = lower_goal(
+        r#"
+fn goal() {
+    let a = "hello";
+    let b = b"hello";
+    let c = "world";
+}
+"#,
+    );
+    let allocations: Vec<_> = body
+        .basic_blocks
+        .iter()
+        .flat_map(|(_, block)| &block.statements)
+        .filter_map(|statement| match &statement.kind {
+            StatementKind::Assign(_, Rvalue::Use(Operand::Constant(c))) => Some(c),
+            _ => None,
+        })
+        .flat_map(|c| match &c.data(Interner).value {
+            ConstValue::Concrete(c) => match &c.interned {
+                ConstScalar::Bytes(_, memory_map) => memory_map.memory.values().cloned().collect(),
+                _ => vec![],
+            },
+            _ => vec![],
+        })
+        .collect();
+    // The string and the byte string are different constants with the same bytes. Interned
+    // allocations are compared by their address.
+    let [hello, byte_hello, world] = &allocations[..] else { panic!("{allocations:?}") };
+    assert_eq!(hello, byte_hello);
+    assert_ne!(hello, world);
+}
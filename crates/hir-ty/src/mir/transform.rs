@@ -0,0 +1,728 @@
+//! Optimization passes that run on lowered MIR before it is handed to the evaluator.
+//!
+//! The passes are deliberately conservative: they only perform rewrites that are obviously
+//! semantics preserving for the evaluator, and they never touch the MIR that borrowck and the
+//! diagnostics see, which is always the raw output of lowering.
+
+use std::{collections::hash_map::Entry, mem, sync::Arc};
+
+use chalk_ir::ConstValue;
+use hir_def::DefWithBodyId;
+use la_arena::ArenaMap;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::{db::HirDatabase, ClosureId, ConstScalar, Interner};
+
+use super::{
+    canonical::{for_each_successor_mut, successors},
+    pad16, return_slot, BasicBlockId, BinOp, LocalId, MirBody, MirBodyStats, MirLowerError,
+    MirSourceMap, Operand, Place, ProjectionElem, Rvalue, StatementKind, Terminator, UnOp,
+};
+
+/// A single optimization pass over a [`MirBody`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MirPass {
+    /// Replaces reads of temporaries that are only ever assigned a single constant with that
+    /// constant.
+    ConstPropagation,
+    /// Folds `_t = rvalue; place = move _t` into `place = rvalue` when `_t` is used nowhere else.
+    CopyPropagation,
+    /// Moves assignments of pure rvalues which compute the same value in every iteration of a
+    /// loop to the block from which the loop is entered.
+    LoopInvariantCodeMotion,
+    /// Removes the storage statements of locals which are mentioned nowhere else, like the
+    /// temporaries whose values copy propagation forwarded.
+    RemoveDeadLocals,
+    /// Redirects jumps to blocks that are identical to an earlier block to that earlier block.
+    DeduplicateBlocks,
+    /// Removes `Nop` statements, folds switches on constants or with a single target, threads
+    /// jumps through empty blocks and merges blocks that are only reachable through a `goto`,
+    /// removing the blocks which are left unreachable.
+    SimplifyCfg,
+    /// Removes the blocks which are not reachable from the start block.
+    RemoveUnreachableBlocks,
+}
+
+impl MirPass {
+    /// All passes, in the order in which they run.
+    pub const ALL: &'static [MirPass] = &[
+        MirPass::ConstPropagation,
+        MirPass::CopyPropagation,
+        MirPass::LoopInvariantCodeMotion,
+        MirPass::RemoveDeadLocals,
+        MirPass::DeduplicateBlocks,
+        MirPass::SimplifyCfg,
+        MirPass::RemoveUnreachableBlocks,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MirPass::ConstPropagation => "const-propagation",
+            MirPass::CopyPropagation => "copy-propagation",
+            MirPass::LoopInvariantCodeMotion => "loop-invariant-code-motion",
+            MirPass::RemoveDeadLocals => "remove-dead-locals",
+            MirPass::DeduplicateBlocks => "deduplicate-blocks",
+            MirPass::SimplifyCfg => "simplify-cfg",
+            MirPass::RemoveUnreachableBlocks => "remove-unreachable-blocks",
+        }
+    }
+
+    /// The pass called `name`, as returned by [`MirPass::name`].
+    pub fn from_name(name: &str) -> Option<MirPass> {
+        MirPass::ALL.iter().copied().find(|p| p.name() == name)
+    }
+
+    fn run(self, body: &mut MirBody) {
+        match self {
+            MirPass::ConstPropagation => propagate_constants(body),
+            MirPass::CopyPropagation => propagate_copies(body),
+            MirPass::LoopInvariantCodeMotion => hoist_loop_invariants(body),
+            MirPass::RemoveDeadLocals => remove_dead_locals(body),
+            MirPass::DeduplicateBlocks => deduplicate_blocks(body),
+            MirPass::SimplifyCfg => simplify_cfg(body),
+            MirPass::RemoveUnreachableBlocks => remove_unreachable_blocks(body),
+        }
+    }
+}
+
+pub fn optimized_mir_body_query(
+    db: &dyn HirDatabase,
+    def: DefWithBodyId,
+) -> Result<Arc<MirBody>, MirLowerError> {
+    let body = db.mir_body(def)?;
+    let _p = profile::span("optimized_mir_body_query").detail(|| body.stats().to_string());
+    Ok(run_enabled_passes(db, body))
+}
+
+/// Like [`optimized_mir_body_query`], for the body of a closure, which the evaluator runs as part
+/// of the optimized body of its parent.
+pub fn optimized_mir_body_for_closure_query(
+    db: &dyn HirDatabase,
+    closure: ClosureId,
+) -> Result<Arc<MirBody>, MirLowerError> {
+    let body = db.mir_body_for_closure(closure)?;
+    let _p =
+        profile::span("optimized_mir_body_for_closure_query").detail(|| body.stats().to_string());
+    Ok(run_enabled_passes(db, body))
+}
+
+/// Runs the passes which the MIR config doesn't disable on `body`.
+pub(crate) fn run_enabled_passes(db: &dyn HirDatabase, body: Arc<MirBody>) -> Arc<MirBody> {
+    let disabled = db.mir_config().disabled_passes;
+    let passes: Vec<_> = MirPass::ALL.iter().copied().filter(|p| !disabled.contains(p)).collect();
+    if passes.is_empty() {
+        return body;
+    }
+    let mut body = (*body).clone();
+    optimize(&mut body, &passes);
+    Arc::new(body)
+}
+
+pub fn optimized_mir_body_recover(
+    _db: &dyn HirDatabase,
+    _cycle: &[String],
+    _def: &DefWithBodyId,
+) -> Result<Arc<MirBody>, MirLowerError> {
+    Err(MirLowerError::Loop)
+}
+
+/// The size of a body before and after a pass ran on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PassStats {
+    pub pass: MirPass,
+    pub before: MirBodyStats,
+    pub after: MirBodyStats,
+}
+
+/// Runs `passes` on `body`, in the given order, and returns how each of them changed its size.
+pub fn optimize(body: &mut MirBody, passes: &[MirPass]) -> Vec<PassStats> {
+    if !passes.is_empty() {
+        body.source_map = MirSourceMap::default();
+    }
+    passes
+        .iter()
+        .map(|&pass| {
+            let before = body.stats();
+            let p = profile::span("mir_pass");
+            pass.run(body);
+            let after = body.stats();
+            drop(p.detail(|| format!("{}: {before} -> {after}", pass.name())));
+            PassStats { pass, before, after }
+        })
+        .collect()
+}
+
+/// How a local is used in a body.
+#[derive(Debug, Default, Clone)]
+struct LocalUses {
+    /// Number of statements assigning to the whole local.
+    assignments: usize,
+    /// Number of operands that copy or move the whole local.
+    reads: usize,
+    /// Number of all the other uses, like borrows, projections, drops and call destinations.
+    others: usize,
+}
+
+impl LocalUses {
+    fn of(body: &mut MirBody) -> ArenaMap<LocalId, LocalUses> {
+        let mut result: ArenaMap<LocalId, LocalUses> =
+            body.locals.iter().map(|(l, _)| (l, LocalUses::default())).collect();
+        body.walk_places(|_, p| {
+            result[p.local].others += 1;
+            for proj in &p.projection {
+                if let ProjectionElem::Index(l) = proj {
+                    result[*l].others += 1;
+                }
+            }
+        });
+        walk_operands(body, |op| {
+            if let Operand::Copy(p) | Operand::Move(p) = op {
+                if p.projection.is_empty() {
+                    result[p.local].others -= 1;
+                    result[p.local].reads += 1;
+                }
+            }
+        });
+        for (_, block) in body.basic_blocks.iter() {
+            for statement in &block.statements {
+                if let StatementKind::Assign(p, _) = &statement.kind {
+                    if p.projection.is_empty() {
+                        result[p.local].others -= 1;
+                        result[p.local].assignments += 1;
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Calls `f` on every operand of the statements and terminators of this body.
+fn walk_operands(body: &mut MirBody, mut f: impl FnMut(&mut Operand)) {
+    for (_, block) in body.basic_blocks.iter_mut() {
+        for statement in &mut block.statements {
+            if let StatementKind::Assign(_, r) = &mut statement.kind {
+                match r {
+                    Rvalue::ShallowInitBox(o, _)
+                    | Rvalue::UnaryOp(_, o)
+                    | Rvalue::Cast(_, o, _)
+                    | Rvalue::Repeat(o, _)
+                    | Rvalue::Use(o) => f(o),
+                    Rvalue::BinaryOp(_, o1, o2) | Rvalue::CheckedBinaryOp(_, o1, o2) => {
+                        f(o1);
+                        f(o2);
+                    }
+                    Rvalue::Aggregate(_, ops) => ops.iter_mut().for_each(&mut f),
+                    Rvalue::CopyForDeref(_)
+                    | Rvalue::Discriminant(_)
+                    | Rvalue::Len(_)
+                    | Rvalue::Ref(_, _)
+                    | Rvalue::ShallowInitBoxWithAlloc(_) => (),
+                }
+            }
+        }
+        match &mut block.terminator {
+            Some(Terminator::SwitchInt { discr: o, .. })
+            | Some(Terminator::DropAndReplace { value: o, .. })
+            | Some(Terminator::Assert { cond: o, .. })
+            | Some(Terminator::Yield { value: o, .. }) => f(o),
+            Some(Terminator::Call { func, args, .. }) => {
+                f(func);
+                args.iter_mut().for_each(&mut f);
+            }
+            _ => (),
+        }
+    }
+}
+
+fn mentions_local(rvalue: &Rvalue, local: LocalId) -> bool {
+    let place_mentions = |p: &Place| {
+        p.local == local
+            || p.projection.iter().any(|x| matches!(x, ProjectionElem::Index(l) if *l == local))
+    };
+    let operand_mentions = |o: &Operand| match o {
+        Operand::Copy(p) | Operand::Move(p) => place_mentions(p),
+        Operand::Constant(_) | Operand::Static(_) => false,
+    };
+    match rvalue {
+        Rvalue::ShallowInitBox(o, _)
+        | Rvalue::UnaryOp(_, o)
+        | Rvalue::Cast(_, o, _)
+        | Rvalue::Repeat(o, _)
+        | Rvalue::Use(o) => operand_mentions(o),
+        Rvalue::BinaryOp(_, o1, o2) | Rvalue::CheckedBinaryOp(_, o1, o2) => {
+            operand_mentions(o1) || operand_mentions(o2)
+        }
+        Rvalue::Aggregate(_, ops) => ops.iter().any(operand_mentions),
+        Rvalue::CopyForDeref(p) | Rvalue::Discriminant(p) | Rvalue::Len(p) | Rvalue::Ref(_, p) => {
+            place_mentions(p)
+        }
+        Rvalue::ShallowInitBoxWithAlloc(_) => false,
+    }
+}
+
+fn propagate_constants(body: &mut MirBody) {
+    // Propagating a constant can make the local it is assigned to a constant as well.
+    while propagate_constants_once(body) {}
+}
+
+/// Returns whether anything changed.
+fn propagate_constants_once(body: &mut MirBody) -> bool {
+    let uses = LocalUses::of(body);
+    let param_locals = body.param_locals.clone();
+    let mut constants: ArenaMap<LocalId, Operand> = ArenaMap::default();
+    for (_, block) in body.basic_blocks.iter_mut() {
+        for statement in &mut block.statements {
+            let StatementKind::Assign(p, Rvalue::Use(c @ Operand::Constant(_))) = &statement.kind else {
+                continue;
+            };
+            let l = p.local;
+            let u = &uses[l];
+            if !p.projection.is_empty()
+                || l == return_slot()
+                || param_locals.contains(&l)
+                || u.assignments != 1
+                || u.others != 0
+            {
+                continue;
+            }
+            constants.insert(l, c.clone());
+            statement.kind = StatementKind::Nop;
+        }
+    }
+    if constants.iter().next().is_none() {
+        return false;
+    }
+    walk_operands(body, |op| {
+        if let Operand::Copy(p) | Operand::Move(p) = op {
+            if p.projection.is_empty() {
+                if let Some(c) = constants.get(p.local) {
+                    *op = c.clone();
+                }
+            }
+        }
+    });
+    true
+}
+
+fn propagate_copies(body: &mut MirBody) {
+    let uses = LocalUses::of(body);
+    let param_locals = body.param_locals.clone();
+    for (_, block) in body.basic_blocks.iter_mut() {
+        for i in 1..block.statements.len() {
+            let (first, second) = block.statements.split_at_mut(i);
+            let (first, second) = (&mut first[i - 1], &mut second[0]);
+            let StatementKind::Assign(temp, rvalue) = &first.kind else { continue };
+            let StatementKind::Assign(dest, Rvalue::Use(Operand::Copy(read) | Operand::Move(read))) = &second.kind else {
+                continue;
+            };
+            let t = temp.local;
+            if !temp.projection.is_empty()
+                || read != temp
+                || t == return_slot()
+                || param_locals.contains(&t)
+                || dest.local == t
+                || mentions_local(rvalue, dest.local)
+            {
+                continue;
+            }
+            let u = &uses[t];
+            if u.assignments != 1 || u.reads != 1 || u.others != 0 {
+                continue;
+            }
+            let StatementKind::Assign(dest, _) = mem::replace(&mut second.kind, StatementKind::Nop) else {
+                unreachable!()
+            };
+            let StatementKind::Assign(_, rvalue) = mem::replace(&mut first.kind, StatementKind::Nop) else {
+                unreachable!()
+            };
+            second.kind = StatementKind::Assign(dest, rvalue);
+        }
+    }
+}
+
+fn remove_dead_locals(body: &mut MirBody) {
+    let used = body.used_locals();
+    for (_, block) in body.basic_blocks.iter_mut() {
+        block.statements.retain(|s| match s.kind {
+            StatementKind::StorageLive(l) | StatementKind::StorageDead(l) => used.contains(&l),
+            _ => true,
+        });
+    }
+}
+
+/// The immediate dominator of each block reachable from the start block, which is its own
+/// immediate dominator. `order` is the reverse postorder of the reachable blocks. This is the
+/// iterative algorithm from "A Simple, Fast Dominance Algorithm" by Cooper, Harvey and Kennedy.
+fn immediate_dominators(
+    order: &[BasicBlockId],
+    predecessors: &ArenaMap<BasicBlockId, Vec<BasicBlockId>>,
+) -> ArenaMap<BasicBlockId, BasicBlockId> {
+    let index: ArenaMap<BasicBlockId, usize> =
+        order.iter().enumerate().map(|(i, &b)| (b, i)).collect();
+    let mut idom: ArenaMap<BasicBlockId, BasicBlockId> = ArenaMap::default();
+    idom.insert(order[0], order[0]);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &b in &order[1..] {
+            let mut new_idom = None;
+            for &p in &predecessors[b] {
+                if idom.get(p).is_none() {
+                    continue;
+                }
+                new_idom = Some(match new_idom {
+                    None => p,
+                    Some(mut other) => {
+                        let mut p = p;
+                        while p != other {
+                            while index[p] > index[other] {
+                                p = idom[p];
+                            }
+                            while index[other] > index[p] {
+                                other = idom[other];
+                            }
+                        }
+                        p
+                    }
+                });
+            }
+            if let Some(new_idom) = new_idom {
+                if idom.get(b) != Some(&new_idom) {
+                    idom.insert(b, new_idom);
+                    changed = true;
+                }
+            }
+        }
+    }
+    idom
+}
+
+fn dominates(
+    idom: &ArenaMap<BasicBlockId, BasicBlockId>,
+    a: BasicBlockId,
+    mut b: BasicBlockId,
+) -> bool {
+    loop {
+        if a == b {
+            return true;
+        }
+        match idom.get(b) {
+            Some(&parent) if parent != b => b = parent,
+            _ => return false,
+        }
+    }
+}
+
+/// The natural loops of the body, as their header and the blocks they consist of, innermost
+/// loops first. Back edges to the same header are considered to be the same loop.
+fn natural_loops(
+    body: &MirBody,
+    order: &[BasicBlockId],
+    predecessors: &ArenaMap<BasicBlockId, Vec<BasicBlockId>>,
+) -> Vec<(BasicBlockId, FxHashSet<BasicBlockId>)> {
+    let idom = immediate_dominators(order, predecessors);
+    let mut loops: Vec<(BasicBlockId, FxHashSet<BasicBlockId>)> = vec![];
+    for &b in order {
+        for header in successors(&body.basic_blocks[b]) {
+            if !dominates(&idom, header, b) {
+                continue;
+            }
+            let index = match loops.iter().position(|(h, _)| *h == header) {
+                Some(index) => index,
+                None => {
+                    loops.push((header, [header].into_iter().collect()));
+                    loops.len() - 1
+                }
+            };
+            let blocks = &mut loops[index].1;
+            let mut stack = vec![b];
+            while let Some(x) = stack.pop() {
+                if blocks.insert(x) {
+                    stack.extend(predecessors[x].iter().copied());
+                }
+            }
+        }
+    }
+    loops.sort_by_key(|(header, blocks)| (blocks.len(), header.into_raw()));
+    loops
+}
+
+/// Whether evaluating `rvalue` has no effect besides producing its value, and can't fail.
+fn is_pure_rvalue(rvalue: &Rvalue, mut is_invariant: impl FnMut(&Operand) -> bool) -> bool {
+    match rvalue {
+        Rvalue::Use(o) | Rvalue::UnaryOp(UnOp::Not, o) => is_invariant(o),
+        Rvalue::BinaryOp(op, o1, o2) => {
+            matches!(
+                op,
+                BinOp::BitXor
+                    | BinOp::BitAnd
+                    | BinOp::BitOr
+                    | BinOp::Eq
+                    | BinOp::Lt
+                    | BinOp::Le
+                    | BinOp::Ne
+                    | BinOp::Ge
+                    | BinOp::Gt
+            ) && is_invariant(o1)
+                && is_invariant(o2)
+        }
+        Rvalue::Aggregate(_, ops) => ops.iter().all(is_invariant),
+        _ => false,
+    }
+}
+
+fn hoist_loop_invariants(body: &mut MirBody) {
+    let order = body.reverse_postorder();
+    let mut predecessors: ArenaMap<BasicBlockId, Vec<BasicBlockId>> =
+        order.iter().map(|&b| (b, vec![])).collect();
+    for &b in &order {
+        for s in successors(&body.basic_blocks[b]) {
+            predecessors[s].push(b);
+        }
+    }
+    let loops = natural_loops(body, &order, &predecessors);
+    if loops.is_empty() {
+        return;
+    }
+    let uses = LocalUses::of(body);
+    let mut moved = FxHashSet::default();
+    walk_operands(body, |op| {
+        if let Operand::Move(p) = op {
+            moved.insert(p.local);
+        }
+    });
+    let param_locals = body.param_locals.clone();
+    for (header, blocks) in loops {
+        // The hoisted assignments go to the end of the only block from which the loop is entered,
+        // if it jumps to the header unconditionally, so that they run exactly once before the loop
+        // is entered.
+        let mut entries = predecessors[header].iter().filter(|p| !blocks.contains(p));
+        let (Some(&preheader), None) = (entries.next(), entries.next()) else { continue };
+        if header == body.start_block
+            || body.basic_blocks[preheader].is_cleanup
+            || !matches!(body.basic_blocks[preheader].terminator, Some(Terminator::Goto { .. }))
+        {
+            continue;
+        }
+        let mut assigned_in_loop = FxHashSet::default();
+        // The locals whose storage starts or ends in the loop, like the bindings declared in its
+        // body, are uninitialized at the start of each iteration, so they are never hoisted.
+        let mut storage_in_loop = FxHashSet::default();
+        for &b in &blocks {
+            for statement in &body.basic_blocks[b].statements {
+                match &statement.kind {
+                    StatementKind::Assign(p, _) => {
+                        assigned_in_loop.insert(p.local);
+                    }
+                    StatementKind::StorageLive(l) | StatementKind::StorageDead(l) => {
+                        storage_in_loop.insert(*l);
+                    }
+                    _ => (),
+                }
+            }
+        }
+        // An operand is invariant if it is a constant or a local that is not assigned in the loop
+        // and can't be changed through a reference or a projection, since it is only ever used
+        // as a whole. Moves are never hoisted, since a move that happens once before the loop
+        // instead of in every iteration would duplicate the moved value.
+        let mut hoisted = vec![];
+        loop {
+            let mut changed = false;
+            for &b in order.iter().filter(|b| blocks.contains(b)) {
+                let block = &mut body.basic_blocks[b];
+                if block.is_cleanup {
+                    continue;
+                }
+                let mut i = 0;
+                while i < block.statements.len() {
+                    let StatementKind::Assign(p, rvalue) = &block.statements[i].kind else {
+                        i += 1;
+                        continue;
+                    };
+                    let l = p.local;
+                    let is_invariant = |o: &Operand| match o {
+                        Operand::Constant(_) => true,
+                        Operand::Copy(p) => {
+                            p.projection.is_empty()
+                                && uses[p.local].others == 0
+                                && !assigned_in_loop.contains(&p.local)
+                        }
+                        Operand::Move(_) | Operand::Static(_) => false,
+                    };
+                    if !p.projection.is_empty()
+                        || l == return_slot()
+                        || param_locals.contains(&l)
+                        || uses[l].assignments != 1
+                        || uses[l].others != 0
+                        || moved.contains(&l)
+                        || storage_in_loop.contains(&l)
+                        || !is_pure_rvalue(rvalue, is_invariant)
+                    {
+                        i += 1;
+                        continue;
+                    }
+                    hoisted.push(block.statements.remove(i));
+                    assigned_in_loop.remove(&l);
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        body.basic_blocks[preheader].statements.extend(hoisted);
+    }
+}
+
+fn reachable_blocks(body: &MirBody) -> FxHashSet<BasicBlockId> {
+    let mut reachable = FxHashSet::default();
+    let mut stack = vec![body.start_block];
+    while let Some(b) = stack.pop() {
+        if reachable.insert(b) {
+            stack.extend(successors(&body.basic_blocks[b]));
+        }
+    }
+    reachable
+}
+
+/// Number of edges into each block from reachable blocks, counting the entry to the body as an
+/// edge into the start block.
+fn predecessor_counts(body: &MirBody) -> ArenaMap<BasicBlockId, usize> {
+    let mut result: ArenaMap<BasicBlockId, usize> =
+        body.basic_blocks.iter().map(|(b, _)| (b, 0)).collect();
+    result[body.start_block] += 1;
+    for b in reachable_blocks(body) {
+        for s in successors(&body.basic_blocks[b]) {
+            result[s] += 1;
+        }
+    }
+    result
+}
+
+/// The value of `op`, if it is a constant that can be used as the discriminant of a switch.
+fn constant_discriminant(op: &Operand) -> Option<u128> {
+    let Operand::Constant(c) = op else { return None };
+    match &c.data(Interner).value {
+        ConstValue::Concrete(c) => match &c.interned {
+            ConstScalar::Bytes(b, _) if b.len() <= 16 => Some(u128::from_le_bytes(pad16(b, false))),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn simplify_cfg(body: &mut MirBody) {
+    for (_, block) in body.basic_blocks.iter_mut() {
+        block.statements.retain(|s| s.kind != StatementKind::Nop);
+        if let Some(Terminator::SwitchInt { discr, targets }) = &block.terminator {
+            let target = match constant_discriminant(discr) {
+                Some(value) => Some(targets.target_for_value(value)),
+                None => {
+                    let all = targets.all_targets();
+                    all.iter().all(|t| *t == all[0]).then(|| all[0])
+                }
+            };
+            if let Some(target) = target {
+                block.terminator = Some(Terminator::Goto { target });
+            }
+        }
+    }
+    // Jump threading: a jump to an empty block ending in a `goto` can go to its target directly.
+    let forwarded: ArenaMap<BasicBlockId, BasicBlockId> = body
+        .basic_blocks
+        .iter()
+        .map(|(start, _)| {
+            let mut visited = FxHashSet::default();
+            let mut current = start;
+            while visited.insert(current) {
+                let block = &body.basic_blocks[current];
+                match &block.terminator {
+                    Some(Terminator::Goto { target }) if block.statements.is_empty() => {
+                        current = *target;
+                    }
+                    _ => break,
+                }
+            }
+            (start, current)
+        })
+        .collect();
+    for (_, block) in body.basic_blocks.iter_mut() {
+        if let Some(terminator) = &mut block.terminator {
+            for_each_successor_mut(terminator, |b| *b = forwarded[*b]);
+        }
+    }
+    // Merge blocks into their only predecessor, if it jumps to them unconditionally.
+    let mut predecessors = predecessor_counts(body);
+    let mut reachable: Vec<BasicBlockId> = reachable_blocks(body).into_iter().collect();
+    reachable.sort_by_key(|b| b.into_raw());
+    for b in reachable {
+        loop {
+            let Some(Terminator::Goto { target }) = body.basic_blocks[b].terminator else {
+                break;
+            };
+            if target == b
+                || predecessors[target] != 1
+                || body.basic_blocks[target].is_cleanup != body.basic_blocks[b].is_cleanup
+            {
+                break;
+            }
+            let target_block = &mut body.basic_blocks[target];
+            let statements = mem::take(&mut target_block.statements);
+            let terminator = target_block.terminator.replace(Terminator::Unreachable);
+            predecessors[target] = 0;
+            let block = &mut body.basic_blocks[b];
+            block.statements.extend(statements);
+            block.terminator = terminator;
+        }
+    }
+    // The merged blocks are left unreachable. Blocks jumping to them are unreachable too, so
+    // removing all of the unreachable blocks doesn't leave dangling jumps.
+    let reachable = reachable_blocks(body);
+    body.retain_blocks(|b| reachable.contains(&b));
+}
+
+fn deduplicate_blocks(body: &mut MirBody) {
+    let mut buckets: FxHashMap<_, Vec<BasicBlockId>> = FxHashMap::default();
+    let mut replacement: ArenaMap<BasicBlockId, BasicBlockId> = ArenaMap::default();
+    for (id, block) in body.basic_blocks.iter() {
+        let Some(terminator) = &block.terminator else { continue };
+        let key = (block.statements.len(), block.is_cleanup, mem::discriminant(terminator));
+        let bucket = match buckets.entry(key) {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(vec![]),
+        };
+        let same = bucket.iter().copied().find(|&other| {
+            let other = &body.basic_blocks[other];
+            other.terminator.as_ref() == Some(terminator)
+                && other
+                    .statements
+                    .iter()
+                    .map(|s| &s.kind)
+                    .eq(block.statements.iter().map(|s| &s.kind))
+        });
+        match same {
+            Some(other) => {
+                replacement.insert(id, other);
+            }
+            None => bucket.push(id),
+        }
+    }
+    if replacement.iter().next().is_none() {
+        return;
+    }
+    for (_, block) in body.basic_blocks.iter_mut() {
+        if let Some(terminator) = &mut block.terminator {
+            for_each_successor_mut(terminator, |b| {
+                if let Some(r) = replacement.get(*b) {
+                    *b = *r;
+                }
+            });
+        }
+    }
+}
+
+fn remove_unreachable_blocks(body: &mut MirBody) {
+    let reachable = reachable_blocks(body);
+    body.retain_blocks(|b| reachable.contains(&b));
+}
@@ -0,0 +1,324 @@
+//! A sanity check of lowered MIR, which verifies that every assignment stores a value of the type
+//! of its destination. It is run in debug builds only, to find bugs in lowering.
+
+use base_db::CrateId;
+use chalk_ir::Mutability;
+use hir_def::{lang_item::LangItem, HasModule};
+
+use crate::{
+    consteval::{try_const_usize, usize_const},
+    db::HirDatabase,
+    infer::closure_captures,
+    lang_items::is_box,
+    static_lifetime, GenericArgData, Interner, Substitution, Ty, TyBuilder, TyExt, TyKind,
+};
+
+use super::{
+    static_ref_ty, AggregateKind, BinOp, BorrowKind, Location, MirBody, MirSpan, Operand, Place,
+    PlaceElem, ProjectionElem, Rvalue, StatementKind, UnOp,
+};
+
+/// An assignment whose value has a different type than its destination.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MirTypeMismatch {
+    pub location: Location,
+    pub span: MirSpan,
+    pub place_ty: Ty,
+    pub rvalue_ty: Ty,
+}
+
+/// A place with a projection which doesn't apply to the type of the place it projects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MirInvalidProjection {
+    /// The location of the statement or terminator which mentions the place.
+    pub location: Location,
+    pub place: Place,
+    /// The index of the projection in the projections of `place`.
+    pub projection_index: usize,
+    /// The type of the place which the projection projects.
+    pub base_ty: Ty,
+    pub reason: ProjectionError,
+}
+
+/// Why a projection doesn't apply to the type it projects, see [`PlaceElem::projected_ty`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectionError {
+    /// The projection doesn't apply to this kind of type, like a `Deref` of an integer, or a
+    /// `Field` or a `Downcast` of another ADT.
+    UnexpectedBaseType,
+    /// A tuple or closure field which the type doesn't have.
+    FieldOutOfRange(usize),
+    /// A subslice of an array of unknown length, or one which is out of its bounds.
+    SubsliceOutOfRange,
+    /// The generic arguments of the type are malformed, like a `Box` whose first one is not a
+    /// type.
+    InvalidGenericArgs,
+}
+
+impl Place {
+    /// The type of this place, or `None` if a projection doesn't apply to the type it projects.
+    pub fn ty(&self, db: &dyn HirDatabase, body: &MirBody) -> Option<Ty> {
+        let krate = body.owner.module(db.upcast()).krate();
+        let ty = body.locals[self.local].ty.clone();
+        self.projection.iter().try_fold(ty, |ty, proj| proj.projected_ty(db, ty, krate).ok())
+    }
+}
+
+impl PlaceElem {
+    /// The type of a place of type `base_ty` after this projection, or why the projection doesn't
+    /// apply to `base_ty`. `krate` is the crate of the body, in which the lengths of the subslices
+    /// of arrays are interned.
+    pub fn projected_ty(
+        &self,
+        db: &dyn HirDatabase,
+        base_ty: Ty,
+        krate: CrateId,
+    ) -> Result<Ty, ProjectionError> {
+        use ProjectionError::*;
+        Ok(match self {
+            ProjectionElem::Deref => match base_ty.kind(Interner) {
+                TyKind::Ref(_, _, inner) | TyKind::Raw(_, inner) => inner.clone(),
+                TyKind::Adt(adt, subst) if is_box(adt.0, db) => {
+                    subst.at(Interner, 0).ty(Interner).ok_or(InvalidGenericArgs)?.clone()
+                }
+                _ => return Err(UnexpectedBaseType),
+            },
+            // The field of an enum variant is only found through the variant, so the parent of
+            // the field is checked against the type as well.
+            ProjectionElem::Field(f) => match base_ty.kind(Interner) {
+                TyKind::Adt(adt, subst) if f.parent.adt_id() == adt.0 => {
+                    db.field_types(f.parent)[f.local_id].clone().substitute(Interner, subst)
+                }
+                _ => return Err(UnexpectedBaseType),
+            },
+            &ProjectionElem::TupleField(f) => match base_ty.kind(Interner) {
+                TyKind::Tuple(_, subst) => subst
+                    .as_slice(Interner)
+                    .get(f)
+                    .ok_or(FieldOutOfRange(f))?
+                    .ty(Interner)
+                    .ok_or(InvalidGenericArgs)?
+                    .clone(),
+                _ => return Err(UnexpectedBaseType),
+            },
+            &ProjectionElem::ClosureField(f) => match base_ty.kind(Interner) {
+                TyKind::Closure(c, _) => {
+                    closure_captures(db, *c).0.get(f).ok_or(FieldOutOfRange(f))?.ty.clone()
+                }
+                _ => return Err(UnexpectedBaseType),
+            },
+            ProjectionElem::Index(_) | ProjectionElem::ConstantIndex { .. } => {
+                match base_ty.kind(Interner) {
+                    TyKind::Array(inner, _) | TyKind::Slice(inner) => inner.clone(),
+                    _ => return Err(UnexpectedBaseType),
+                }
+            }
+            &ProjectionElem::Subslice { from, to, from_end } => match base_ty.kind(Interner) {
+                TyKind::Slice(_) => base_ty,
+                TyKind::Array(inner, len) => {
+                    let end = if from_end {
+                        let len = try_const_usize(len).ok_or(SubsliceOutOfRange)? as u64;
+                        len.checked_sub(to).ok_or(SubsliceOutOfRange)?
+                    } else {
+                        to
+                    };
+                    let len = end.checked_sub(from).ok_or(SubsliceOutOfRange)?;
+                    TyKind::Array(inner.clone(), usize_const(db, Some(len as u128), krate))
+                        .intern(Interner)
+                }
+                _ => return Err(UnexpectedBaseType),
+            },
+            ProjectionElem::Downcast(v) => match base_ty.kind(Interner) {
+                TyKind::Adt(adt, _) if hir_def::AdtId::from(v.parent) == adt.0 => base_ty,
+                _ => return Err(UnexpectedBaseType),
+            },
+            ProjectionElem::OpaqueCast(t) => t.clone(),
+        })
+    }
+}
+
+impl Operand {
+    /// The type of the value of this operand.
+    pub fn ty(&self, db: &dyn HirDatabase, body: &MirBody) -> Option<Ty> {
+        Some(match self {
+            Operand::Copy(p) | Operand::Move(p) => p.ty(db, body)?,
+            Operand::Constant(c) => c.data(Interner).ty.clone(),
+            &Operand::Static(s) => static_ref_ty(db, s),
+        })
+    }
+}
+
+impl Rvalue {
+    /// The type of the value of this rvalue, or `None` if it can't be known from the rvalue alone.
+    pub fn ty(&self, db: &dyn HirDatabase, body: &MirBody) -> Option<Ty> {
+        Some(match self {
+            Rvalue::Use(o) | Rvalue::UnaryOp(UnOp::Not | UnOp::Neg, o) => o.ty(db, body)?,
+            Rvalue::Ref(kind, p) => {
+                let mutability = match kind {
+                    BorrowKind::Mut { .. } => Mutability::Mut,
+                    BorrowKind::Shared | BorrowKind::Shallow | BorrowKind::Unique => {
+                        Mutability::Not
+                    }
+                };
+                TyKind::Ref(mutability, static_lifetime(), p.ty(db, body)?).intern(Interner)
+            }
+            Rvalue::Len(_) => TyBuilder::usize(),
+            Rvalue::Repeat(op, len) => {
+                TyKind::Array(op.ty(db, body)?, len.clone()).intern(Interner)
+            }
+            Rvalue::Cast(_, _, ty) => ty.clone(),
+            Rvalue::BinaryOp(op, lhs, _) => binary_op_ty(db, body, op, lhs)?,
+            Rvalue::CheckedBinaryOp(op, lhs, _) => {
+                let ty = binary_op_ty(db, body, op, lhs)?;
+                TyKind::Tuple(2, Substitution::from_iter(Interner, [ty, TyBuilder::bool()]))
+                    .intern(Interner)
+            }
+            Rvalue::Discriminant(_) => TyBuilder::discr_ty(),
+            Rvalue::Aggregate(kind, ops) => match kind {
+                AggregateKind::Array(elem) => {
+                    let krate = body.owner.module(db.upcast()).krate();
+                    let len = usize_const(db, Some(ops.len() as u128), krate);
+                    TyKind::Array(elem.clone(), len).intern(Interner)
+                }
+                AggregateKind::Tuple(ty) | AggregateKind::Closure(ty) => ty.clone(),
+                AggregateKind::Adt(variant, subst) => {
+                    TyKind::Adt(chalk_ir::AdtId(variant.adt_id()), subst.clone()).intern(Interner)
+                }
+                // The generic arguments of the union are not stored in the aggregate.
+                AggregateKind::Union(..) => return None,
+            },
+            Rvalue::ShallowInitBox(_, ty) | Rvalue::ShallowInitBoxWithAlloc(ty) => {
+                let krate = body.owner.module(db.upcast()).krate();
+                let box_id = db.lang_item(krate, LangItem::OwnedBox)?.as_struct()?;
+                TyBuilder::adt(db, box_id.into())
+                    .push(ty.clone())
+                    .fill_with_defaults(db, || TyKind::Error.intern(Interner))
+                    .build()
+            }
+            Rvalue::CopyForDeref(p) => p.ty(db, body)?,
+        })
+    }
+}
+
+/// The type of the value of `lhs op rhs`.
+fn binary_op_ty(db: &dyn HirDatabase, body: &MirBody, op: &BinOp, lhs: &Operand) -> Option<Ty> {
+    Some(match op {
+        BinOp::Eq | BinOp::Lt | BinOp::Le | BinOp::Ne | BinOp::Ge | BinOp::Gt => TyBuilder::bool(),
+        BinOp::Add
+        | BinOp::Sub
+        | BinOp::Mul
+        | BinOp::Div
+        | BinOp::Rem
+        | BinOp::BitXor
+        | BinOp::BitAnd
+        | BinOp::BitOr
+        | BinOp::Shl
+        | BinOp::Shr
+        | BinOp::Offset => lhs.ty(db, body)?,
+    })
+}
+
+impl MirBody {
+    /// Checks that each assignment in this body stores a value of the type of its destination,
+    /// and returns the first one that doesn't. Lifetimes are not compared, and assignments whose
+    /// types can't be computed are skipped.
+    pub fn check_types(&self, db: &dyn HirDatabase) -> Result<(), MirTypeMismatch> {
+        for (block, data) in self.basic_blocks.iter() {
+            for (statement_index, statement) in data.statements.iter().enumerate() {
+                let StatementKind::Assign(place, rvalue) = &statement.kind else { continue };
+                let (Some(place_ty), Some(rvalue_ty)) =
+                    (place.ty(db, self), rvalue.ty(db, self))
+                else {
+                    continue;
+                };
+                if !types_match(&place_ty, &rvalue_ty) {
+                    return Err(MirTypeMismatch {
+                        location: Location { block, statement_index },
+                        span: statement.span,
+                        place_ty,
+                        rvalue_ty,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that each projection of each place in this body applies to the type of the place it
+    /// projects, like that a `Field` projects a struct, and returns the first place with one that
+    /// doesn't. The projections of places of unknown types are skipped.
+    pub fn apply_projection_checks(
+        &self,
+        db: &dyn HirDatabase,
+    ) -> Result<(), MirInvalidProjection> {
+        let krate = self.owner.module(db.upcast()).krate();
+        let mut result = Ok(());
+        self.for_each_place(|location, place| {
+            if result.is_err() {
+                return;
+            }
+            let mut ty = self.locals[place.local].ty.clone();
+            for (projection_index, proj) in place.projection.iter().enumerate() {
+                if ty.is_unknown() {
+                    return;
+                }
+                match proj.projected_ty(db, ty.clone(), krate) {
+                    Ok(projected) => ty = projected,
+                    Err(reason) => {
+                        result = Err(MirInvalidProjection {
+                            location,
+                            place: place.clone(),
+                            projection_index,
+                            base_ty: ty,
+                            reason,
+                        });
+                        return;
+                    }
+                }
+            }
+        });
+        result
+    }
+}
+
+/// Whether `a` and `b` are the same type, ignoring lifetimes. Unknown types and constants, which
+/// come from code that didn't type check, match anything.
+fn types_match(a: &Ty, b: &Ty) -> bool {
+    let (a, b) = (a.kind(Interner), b.kind(Interner));
+    match (a, b) {
+        (TyKind::Error, _) | (_, TyKind::Error) => true,
+        // `!` coerces to anything.
+        (_, TyKind::Never) => true,
+        (TyKind::Ref(m1, _, t1), TyKind::Ref(m2, _, t2))
+        | (TyKind::Raw(m1, t1), TyKind::Raw(m2, t2)) => m1 == m2 && types_match(t1, t2),
+        (TyKind::Slice(t1), TyKind::Slice(t2)) => types_match(t1, t2),
+        (TyKind::Array(t1, c1), TyKind::Array(t2, c2)) => {
+            let lengths_match = match (try_const_usize(c1), try_const_usize(c2)) {
+                (Some(l1), Some(l2)) => l1 == l2,
+                _ => true,
+            };
+            lengths_match && types_match(t1, t2)
+        }
+        (TyKind::Adt(id1, s1), TyKind::Adt(id2, s2)) => id1 == id2 && substs_match(s1, s2),
+        (TyKind::Tuple(n1, s1), TyKind::Tuple(n2, s2)) => n1 == n2 && substs_match(s1, s2),
+        (TyKind::FnDef(id1, s1), TyKind::FnDef(id2, s2)) => id1 == id2 && substs_match(s1, s2),
+        (TyKind::Closure(id1, s1), TyKind::Closure(id2, s2)) => id1 == id2 && substs_match(s1, s2),
+        (a, b) => a == b,
+    }
+}
+
+fn substs_match(a: &Substitution, b: &Substitution) -> bool {
+    let (a, b) = (a.as_slice(Interner), b.as_slice(Interner));
+    a.len() == b.len()
+        && a.iter().zip(b).all(|(a, b)| match (a.data(Interner), b.data(Interner)) {
+            (GenericArgData::Ty(a), GenericArgData::Ty(b)) => types_match(a, b),
+            (GenericArgData::Lifetime(_), GenericArgData::Lifetime(_)) => true,
+            (GenericArgData::Const(a), GenericArgData::Const(b)) => {
+                match (try_const_usize(a), try_const_usize(b)) {
+                    (Some(a), Some(b)) => a == b,
+                    _ => true,
+                }
+            }
+            _ => false,
+        })
+}
@@ -0,0 +1,137 @@
+//! Whether the value of an expression is used, found by following the place it is lowered to.
+
+use hir_def::expr::{BindingId, ExprId};
+use rustc_hash::FxHashSet;
+
+use super::{
+    return_slot, LocalId, LocalKind, MirBody, MirSpan, Operand, Place, ProjectionElem, Rvalue,
+    StatementKind, Terminator,
+};
+
+/// Where the value of an expression ends up, see [`MirBody::expr_value_usage`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueUsage {
+    /// The value is written to a temporary which is never read, like the value of an expression
+    /// statement or of the initializer of `let _ = ...`. Dropping it doesn't count as a read.
+    Discarded,
+    /// The value is stored in a binding, or in a part of it.
+    Bound(BindingId),
+    /// The value is returned from the body, or is a part of the returned value.
+    Returned,
+    /// The value is read by another operation, like a call it is passed to or an arithmetic
+    /// operation.
+    Used,
+    /// The expression has no assignment of its own, like a block whose value is written by its
+    /// tail expression or a place which is only borrowed, or it diverges.
+    Unknown,
+}
+
+impl MirBody {
+    /// Where the value of `expr` ends up. The temporaries which only move the value to another
+    /// place are followed, so that e.g. the value of a call assigned to a binding is
+    /// [`ValueUsage::Bound`] even if the call writes to a temporary first. This uses the source
+    /// map, so it is always [`ValueUsage::Unknown`] in optimized bodies.
+    pub fn expr_value_usage(&self, expr: ExprId) -> ValueUsage {
+        let Some(destination) = self.source_map.destination(expr) else {
+            let usage = self.source_map.place_usages.get(&expr);
+            return usage.copied().unwrap_or(ValueUsage::Unknown);
+        };
+        // Lowering an expression may write other temporaries with its span too, like the length
+        // and the comparison of a bounds check, so only a write to its destination counts.
+        let span = MirSpan::ExprId(expr);
+        let assigns_destination = self.basic_blocks.iter().any(|(_, block)| {
+            block.statements.iter().any(|statement| match &statement.kind {
+                StatementKind::Assign(place, _) => statement.span == span && place == destination,
+                _ => false,
+            }) || matches!(
+                &block.terminator,
+                Some(Terminator::Call { destination: place, span: call_span, .. })
+                    if *call_span == span && place == destination
+            )
+        });
+        if !assigns_destination {
+            return ValueUsage::Unknown;
+        }
+        self.place_usage(destination, &mut FxHashSet::default())
+    }
+
+    fn place_usage(&self, place: &Place, visited: &mut FxHashSet<LocalId>) -> ValueUsage {
+        // A value written to a part of a local is used like the local.
+        let local = place.local;
+        if local == return_slot() {
+            return ValueUsage::Returned;
+        }
+        if let LocalKind::UserBinding(binding) = self.locals[local].kind {
+            return ValueUsage::Bound(binding);
+        }
+        // Temporaries moved in a cycle are assigned in a loop, whose value is read in it.
+        if !visited.insert(local) {
+            return ValueUsage::Used;
+        }
+        let mut reads = self.reads_of(local);
+        match (reads.next(), reads.next()) {
+            (None, _) => ValueUsage::Discarded,
+            (Some(Some(forwarded_to)), None) => self.place_usage(forwarded_to, visited),
+            (Some(_), _) => ValueUsage::Used,
+        }
+    }
+
+    /// The reads of `local` or of its parts. A read which moves or copies the whole local to
+    /// another place is that place, the other reads are `None`.
+    fn reads_of(&self, local: LocalId) -> impl Iterator<Item = Option<&Place>> + '_ {
+        let mentions = move |p: &Place| {
+            p.local == local
+                || p.projection.iter().any(|x| matches!(x, ProjectionElem::Index(l) if *l == local))
+        };
+        let operand_mentions = move |o: &Operand| match o {
+            Operand::Copy(p) | Operand::Move(p) => mentions(p),
+            Operand::Constant(_) | Operand::Static(_) => false,
+        };
+        // Writing to a part of the local is not a read of it, but using it as an index is.
+        let index_mentions = move |p: &Place| {
+            p.projection.iter().any(|x| matches!(x, ProjectionElem::Index(l) if *l == local))
+        };
+        self.basic_blocks.iter().flat_map(move |(_, block)| {
+            let statement_reads = block.statements.iter().filter_map(move |statement| {
+                let StatementKind::Assign(dest, rvalue) = &statement.kind else { return None };
+                let rvalue_reads = match rvalue {
+                    Rvalue::Use(Operand::Copy(p) | Operand::Move(p))
+                        if p.local == local && p.projection.is_empty() =>
+                    {
+                        return Some(Some(dest));
+                    }
+                    Rvalue::ShallowInitBox(o, _)
+                    | Rvalue::UnaryOp(_, o)
+                    | Rvalue::Cast(_, o, _)
+                    | Rvalue::Repeat(o, _)
+                    | Rvalue::Use(o) => operand_mentions(o),
+                    Rvalue::BinaryOp(_, o1, o2) | Rvalue::CheckedBinaryOp(_, o1, o2) => {
+                        operand_mentions(o1) || operand_mentions(o2)
+                    }
+                    Rvalue::Aggregate(_, ops) => ops.iter().any(operand_mentions),
+                    Rvalue::CopyForDeref(p)
+                    | Rvalue::Discriminant(p)
+                    | Rvalue::Len(p)
+                    | Rvalue::Ref(_, p) => mentions(p),
+                    Rvalue::ShallowInitBoxWithAlloc(_) => false,
+                };
+                (rvalue_reads || index_mentions(dest)).then_some(None)
+            });
+            let terminator_reads = match &block.terminator {
+                Some(Terminator::SwitchInt { discr: o, .. })
+                | Some(Terminator::Assert { cond: o, .. })
+                | Some(Terminator::Yield { value: o, .. }) => operand_mentions(o),
+                Some(Terminator::DropAndReplace { place, value, .. }) => {
+                    index_mentions(place) || operand_mentions(value)
+                }
+                Some(Terminator::Call { func, args, destination, .. }) => {
+                    operand_mentions(func)
+                        || args.iter().any(operand_mentions)
+                        || index_mentions(destination)
+                }
+                _ => false,
+            };
+            statement_reads.chain(terminator_reads.then_some(None))
+        })
+    }
+}
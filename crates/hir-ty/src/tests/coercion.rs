@@ -57,6 +57,19 @@ fn test() {
     );
 }
 
+#[test]
+fn cast_ref_to_raw_pointer_coerces() {
+    check(
+        r"
+fn f(x: &mut i32) -> &mut i32 { x }
+fn test(x: &mut i32) {
+    let p = f(x) as *const i32;
+         // ^^^^ adjustments: Deref(None), Borrow(RawPtr(Not))
+}
+",
+    );
+}
+
 #[test]
 fn custom_coerce_unsized() {
     check(
@@ -2,6 +2,8 @@ use std::sync::Arc;
 
 use base_db::{fixture::WithFixture, SourceDatabaseExt};
 
+use hir_def::{db::DefDatabase, expr::Expr};
+
 use crate::{db::HirDatabase, test_db::TestDB};
 
 use super::visit_module;
@@ -49,3 +51,48 @@ fn typing_whitespace_inside_a_function_should_not_invalidate_types() {
         assert!(!format!("{events:?}").contains("infer"), "{events:#?}")
     }
 }
+
+#[test]
+fn editing_a_function_invalidates_the_mir_of_its_closures() {
+    let (mut db, pos) = TestDB::with_position(
+        "
+        //- /lib.rs
+        fn foo() -> i32 {
+            let c = |x: i32| x + $01;
+            c(2)
+        }
+    ",
+    );
+    let closure_mir = |db: &TestDB| {
+        let module = db.module_for_file(pos.file_id);
+        let crate_def_map = module.def_map(db);
+        let mut result = vec![];
+        visit_module(db, &crate_def_map, module.local_id, &mut |def| {
+            let body = db.body(def);
+            for (expr, _) in body.exprs.iter().filter(|(_, it)| matches!(it, Expr::Closure { .. }))
+            {
+                let closure = db.intern_closure((def, expr));
+                result.push(db.mir_body_for_closure(closure.into()).unwrap());
+            }
+        });
+        result
+    };
+    let before = closure_mir(&db);
+    assert_eq!(before.len(), 1);
+
+    let new_text = "
+        fn foo() -> i32 {
+            let c = |x: i32| x + 2;
+            c(2)
+        }
+    "
+    .to_string();
+
+    db.set_file_text(pos.file_id, Arc::new(new_text));
+
+    let mut after = vec![];
+    let events = db.log_executed(|| after = closure_mir(&db));
+    let events = format!("{events:?}");
+    assert!(events.contains("mir_body_for_closure"), "{events}");
+    assert_ne!(before, after);
+}
@@ -3640,6 +3640,22 @@ fn main() {
     )
 }
 
+#[test]
+fn array_length_expressions() {
+    check_types(
+        r#"
+const BLOCK: usize = 8;
+fn make() -> [u8; BLOCK * 2] { loop {} }
+fn main() {
+    let a = make();
+      //^ [u8; BLOCK * 2]
+    let b: [u8; (2 * 8) >> 1] = [0; 8];
+      //^ [u8; 8]
+}
+"#,
+    );
+}
+
 #[test]
 fn fn_returning_unit() {
     check_infer_with_mismatches(
@@ -185,7 +185,7 @@ pub enum FnTrait {
 }
 
 impl FnTrait {
-    const fn lang_item(self) -> LangItem {
+    pub const fn lang_item(self) -> LangItem {
         match self {
             FnTrait::FnOnce => LangItem::FnOnce,
             FnTrait::FnMut => LangItem::FnMut,
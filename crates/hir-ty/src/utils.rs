@@ -326,6 +326,21 @@ fn parent_generic_def(db: &dyn DefDatabase, def: GenericDefId) -> Option<Generic
     }
 }
 
+/// Returns whether `func` is a compiler intrinsic, i.e. it has the `"rust-intrinsic"` ABI itself or
+/// is declared in an `extern "rust-intrinsic"` block.
+pub(crate) fn is_intrinsic(db: &dyn HirDatabase, func: FunctionId) -> bool {
+    match &db.function_data(func).abi {
+        Some(abi) => *abi == Interned::new_str("rust-intrinsic"),
+        None => match func.lookup(db.upcast()).container {
+            hir_def::ItemContainerId::ExternBlockId(block) => {
+                let id = block.lookup(db.upcast()).id;
+                id.item_tree(db.upcast())[id.value].abi.as_deref() == Some("rust-intrinsic")
+            }
+            _ => false,
+        },
+    }
+}
+
 pub fn is_fn_unsafe_to_call(db: &dyn HirDatabase, func: FunctionId) -> bool {
     let data = db.function_data(func);
     if data.has_unsafe_kw() {
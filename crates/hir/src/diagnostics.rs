@@ -10,9 +10,10 @@ use cfg::{CfgExpr, CfgOptions};
 use either::Either;
 use hir_def::path::ModPath;
 use hir_expand::{name::Name, HirFileId, InFile};
+use hir_ty::mir::NonStructuralMatch;
 use syntax::{ast, AstPtr, SyntaxNodePtr, TextRange};
 
-use crate::{AssocItem, Field, Local, MacroKind, Type};
+use crate::{AssocItem, Field, Function, Local, MacroKind, Static, Type};
 
 macro_rules! diagnostics {
     ($($diag:ident,)*) => {
@@ -45,6 +46,7 @@ diagnostics![
     MissingMatchArms,
     MissingUnsafe,
     NeedMut,
+    NonStructuralMatchConst,
     NoSuchField,
     PrivateAssocItem,
     PrivateField,
@@ -58,6 +60,7 @@ diagnostics![
     UnresolvedMethodCall,
     UnresolvedModule,
     UnresolvedProcMacro,
+    UnevaluableConst,
     UnusedMut,
 ];
 
@@ -223,3 +226,39 @@ pub struct NeedMut {
 pub struct UnusedMut {
     pub local: Local,
 }
+
+#[derive(Debug)]
+pub struct NonStructuralMatchConst {
+    pub pat: InFile<AstPtr<ast::Pat>>,
+    /// The part of the constant's type that can't be compared structurally.
+    pub ty: Type,
+    pub reason: NonStructuralMatch,
+}
+
+/// A constant whose initializer depends on something that only exists at runtime, like a foreign
+/// function, through the calls in `frames`.
+#[derive(Debug)]
+pub struct UnevaluableConst {
+    /// The name of the constant, or the constant itself if it has no name.
+    pub node: InFile<SyntaxNodePtr>,
+    pub name: Option<Name>,
+    pub frames: Vec<DependencyFrame>,
+    pub cause: RuntimeOnlyCause,
+}
+
+/// A function called on the way from a constant to the cause of an [`UnevaluableConst`].
+#[derive(Debug)]
+pub struct DependencyFrame {
+    pub function: Function,
+    /// The call of `function`, if it is known.
+    pub call_site: Option<InFile<SyntaxNodePtr>>,
+    /// Whether `function` calls itself, directly or through other functions.
+    pub recursive: bool,
+}
+
+#[derive(Debug)]
+pub enum RuntimeOnlyCause {
+    ForeignFunction(Function),
+    ExternStatic(Static),
+    InlineAsm,
+}
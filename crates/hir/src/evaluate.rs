@@ -0,0 +1,88 @@
+//! Evaluation of expressions which aren't part of the source, like the ones typed by the user in
+//! the "evaluate expression" window of a debugger.
+
+use hir_def::body::Body;
+use hir_expand::name::AsName;
+use hir_ty::{
+    consteval::{eval_speculative_body, ConstEvalError},
+    display::HexifiedConst,
+    mir::MirEvalError,
+    Interner,
+};
+use rustc_hash::FxHashSet;
+use syntax::{
+    ast::{self, HasName},
+    AstNode,
+};
+
+use crate::{db::HirDatabase, HirDisplay, Name, PathResolution, SemanticsScope, Type};
+
+/// The number of basic blocks the evaluation of an expression may execute. The user waits for the
+/// result, so we give up on expressions which are expensive to evaluate.
+const EXECUTION_LIMIT: usize = 10_000;
+
+/// The result of [`evaluate_expression`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedValue {
+    pub value: String,
+    pub ty: Type,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvalError {
+    /// The text isn't a single expression, or it contains items, macro calls, closures or async
+    /// blocks, which can't be evaluated outside of the source.
+    InvalidExpression,
+    /// The scope isn't in a body, which the expression would be lowered as a part of.
+    NotInBody,
+    /// The expression refers to a local of the body enclosing the scope, which has no value.
+    LocalNotInScope(Name),
+    ConstEvalError(ConstEvalError),
+}
+
+/// Evaluates `text` as an expression in `scope`, which must be in a body. The expression may use
+/// the items, imports and generic parameters visible in `scope`, but not the locals of the body.
+pub fn evaluate_expression(
+    db: &dyn HirDatabase,
+    scope: &SemanticsScope<'_>,
+    text: &str,
+) -> Result<RenderedValue, EvalError> {
+    if !db.mir_config().enable {
+        return Err(EvalError::ConstEvalError(ConstEvalError::MirEvalError(
+            MirEvalError::Disabled,
+        )));
+    }
+    let (owner, scope_id) = scope.resolver().expr_scope().ok_or(EvalError::NotInBody)?;
+    let expr = Body::parse_speculative_expr(text).ok_or(EvalError::InvalidExpression)?;
+    // The snippet is lowered without the locals of the enclosing body, so one of them would
+    // silently resolve to an item with the same name, or not at all. The names bound by the
+    // snippet itself shadow them.
+    let bound = expr
+        .syntax()
+        .descendants()
+        .filter_map(ast::IdentPat::cast)
+        .filter_map(|it| Some(it.name()?.as_name()))
+        .collect::<FxHashSet<_>>();
+    let paths =
+        expr.syntax().descendants().filter_map(ast::PathExpr::cast).filter_map(|it| it.path());
+    for path in paths {
+        if let Some(PathResolution::Local(local)) = scope.speculative_resolve(&path) {
+            let name = local.name(db);
+            if !bound.contains(&name) {
+                return Err(EvalError::LocalNotInScope(name));
+            }
+        }
+    }
+    let body = Body::speculative_expr(db.upcast(), owner, expr);
+    let c = eval_speculative_body(db, owner, scope_id, &body, EXECUTION_LIMIT)
+        .map_err(EvalError::ConstEvalError)?;
+    let ty = Type::new_with_resolver(db, scope.resolver(), c.data(Interner).ty.clone());
+    let value = format!("{}", HexifiedConst(c).display(db));
+    // Like in `Const::render_eval`, the parts we can't render are not worth showing to the user.
+    if value.contains("not-supported>") {
+        return Err(EvalError::ConstEvalError(ConstEvalError::MirEvalError(
+            MirEvalError::NotSupported("rendering complex values".to_string()),
+        )));
+    }
+    Ok(RenderedValue { value, ty })
+}
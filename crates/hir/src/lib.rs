@@ -32,6 +32,7 @@ pub mod db;
 pub mod symbols;
 
 mod display;
+mod evaluate;
 
 use std::{iter, ops::ControlFlow, sync::Arc};
 
@@ -40,7 +41,7 @@ use base_db::{CrateDisplayName, CrateId, CrateOrigin, Edition, FileId, ProcMacro
 use either::Either;
 use hir_def::{
     adt::VariantData,
-    body::{BodyDiagnostic, SyntheticSyntax},
+    body::{BodyDiagnostic, BodySourceMap, SyntheticSyntax},
     expr::{BindingAnnotation, BindingId, ExprOrPatId, LabelId, Pat},
     generics::{LifetimeParamData, TypeOrConstParamData, TypeParamProvenance},
     item_tree::ItemTreeNode,
@@ -58,9 +59,9 @@ use hir_def::{
 use hir_expand::{name::name, MacroCallKind};
 use hir_ty::{
     all_super_traits, autoderef,
-    consteval::{try_const_usize, unknown_const_as_generic, ConstEvalError, ConstExt},
+    consteval::{self, try_const_usize, unknown_const_as_generic, ConstEvalError, ConstExt},
     diagnostics::BodyValidationDiagnostic,
-    display::HexifiedConst,
+    display::{render_const_limited, HexifiedConst},
     layout::layout_of_ty,
     method_resolution::{self, TyFingerprint},
     mir::{self, interpret_mir},
@@ -85,13 +86,15 @@ use crate::db::{DefDatabase, HirDatabase};
 pub use crate::{
     attrs::{HasAttrs, Namespace},
     diagnostics::{
-        AnyDiagnostic, BreakOutsideOfLoop, ExpectedFunction, InactiveCode, IncoherentImpl,
-        IncorrectCase, InvalidDeriveTarget, MacroError, MalformedDerive, MismatchedArgCount,
-        MissingFields, MissingMatchArms, MissingUnsafe, NeedMut, NoSuchField, PrivateAssocItem,
-        PrivateField, ReplaceFilterMapNextWithFindMap, TypeMismatch, UnimplementedBuiltinMacro,
+        AnyDiagnostic, BreakOutsideOfLoop, DependencyFrame, ExpectedFunction, InactiveCode,
+        IncoherentImpl, IncorrectCase, InvalidDeriveTarget, MacroError, MalformedDerive,
+        MismatchedArgCount, MissingFields, MissingMatchArms, MissingUnsafe, NeedMut, NoSuchField,
+        NonStructuralMatchConst, PrivateAssocItem, PrivateField, ReplaceFilterMapNextWithFindMap,
+        RuntimeOnlyCause, TypeMismatch, UnevaluableConst, UnimplementedBuiltinMacro,
         UnresolvedExternCrate, UnresolvedField, UnresolvedImport, UnresolvedMacroCall,
         UnresolvedMethodCall, UnresolvedModule, UnresolvedProcMacro, UnusedMut,
     },
+    evaluate::{evaluate_expression, EvalError, RenderedValue},
     has_source::HasSource,
     semantics::{PathResolution, Semantics, SemanticsScope, TypeInfo, VisibleTraits},
 };
@@ -129,8 +132,8 @@ pub use {
         ExpandResult, HirFileId, InFile, MacroFile, Origin,
     },
     hir_ty::{
-        display::{HirDisplay, HirDisplayError, HirWrite},
-        mir::MirEvalError,
+        display::{ConstRenderLimits, HirDisplay, HirDisplayError, HirWrite, RenderedConst},
+        mir::{BinOp, MirConfig, MirEvalError, MirPass, NonStructuralMatch},
         PointerCast, Safety,
     },
 };
@@ -1521,32 +1524,24 @@ impl DefWithBody {
             }
         }
 
-        let hir_body = db.body(self.into());
-
-        if let Ok(borrowck_result) = db.borrowck(self.into()) {
+        let borrowck_result =
+            if db.mir_config().enable { db.borrowck(self.into()).ok() } else { None };
+        if let Some(borrowck_result) = borrowck_result {
             let mir_body = &borrowck_result.mir_body;
             let mol = &borrowck_result.mutability_of_locals;
-            for (binding_id, _) in hir_body.bindings.iter() {
-                let need_mut = &mol[mir_body.binding_locals[binding_id]];
+            // Only bindings can be declared `mut`, so the temporaries introduced by lowering are
+            // never reported.
+            for (local_id, mir_local) in mir_body.locals.iter() {
+                let mir::LocalKind::UserBinding(binding_id) = mir_local.kind else { continue };
+                let need_mut = &mol[local_id];
                 let local = Local { parent: self.into(), binding_id };
                 match (need_mut, local.is_mut(db)) {
                     (mir::MutabilityReason::Mut { .. }, true)
                     | (mir::MutabilityReason::Not, false) => (),
                     (mir::MutabilityReason::Mut { spans }, false) => {
                         for span in spans {
-                            let span: InFile<SyntaxNodePtr> = match span {
-                                mir::MirSpan::ExprId(e) => match source_map.expr_syntax(*e) {
-                                    Ok(s) => s.map(|x| x.into()),
-                                    Err(_) => continue,
-                                },
-                                mir::MirSpan::PatId(p) => match source_map.pat_syntax(*p) {
-                                    Ok(s) => s.map(|x| match x {
-                                        Either::Left(e) => e.into(),
-                                        Either::Right(e) => e.into(),
-                                    }),
-                                    Err(_) => continue,
-                                },
-                                mir::MirSpan::Unknown => continue,
+                            let Some(span) = mir_span_to_source(&source_map, *span) else {
+                                continue;
                             };
                             acc.push(NeedMut { local, span }.into());
                         }
@@ -1556,6 +1551,17 @@ impl DefWithBody {
             }
         }
 
+        if let Err(mir::MirLowerError::NonStructuralMatchConst { pat, ty, reason }) =
+            db.mir_body(self.into())
+        {
+            if let Ok(InFile { file_id, value: Either::Left(pat) }) = source_map.pat_syntax(pat) {
+                let ty = Type::new(db, DefWithBodyId::from(self), ty);
+                acc.push(
+                    NonStructuralMatchConst { pat: InFile::new(file_id, pat), ty, reason }.into(),
+                );
+            }
+        }
+
         for diagnostic in BodyValidationDiagnostic::collect(db, self.into()) {
             match diagnostic {
                 BodyValidationDiagnostic::RecordMissingFields {
@@ -1660,6 +1666,12 @@ impl DefWithBody {
             }
         }
 
+        if let DefWithBody::Const(konst) = self {
+            if db.mir_config().enable {
+                acc.extend(konst.unevaluable_diagnostic(db).map(AnyDiagnostic::from));
+            }
+        }
+
         let def: ModuleDef = match self {
             DefWithBody::Function(it) => it.into(),
             DefWithBody::Static(it) => it.into(),
@@ -1798,12 +1810,107 @@ impl Function {
     }
 
     pub fn eval(self, db: &dyn HirDatabase) -> Result<(), MirEvalError> {
+        if !db.mir_config().enable {
+            return Err(MirEvalError::Disabled);
+        }
         let body = db
-            .mir_body(self.id.into())
+            .optimized_mir_body(self.id.into())
             .map_err(|e| MirEvalError::MirLowerError(self.id.into(), e))?;
         interpret_mir(db, &body, Substitution::empty(Interner), false)?;
         Ok(())
     }
+
+    /// The places where this function may panic, looking into the functions it calls up to `depth`
+    /// calls deep.
+    pub fn panic_sites(self, db: &dyn HirDatabase, depth: usize) -> Vec<PanicSite> {
+        if !db.mir_config().enable {
+            return vec![];
+        }
+        let Ok(body) = db.mir_body(self.id.into()) else {
+            return vec![];
+        };
+        PanicSite::from_mir(db, self.id.into(), mir::panic_sites(db, &body, depth))
+    }
+
+    /// The functions and constructors the body of this function may call, as found from its MIR.
+    pub fn callees(self, db: &dyn HirDatabase) -> Callees {
+        if !db.mir_config().enable {
+            return Callees { has_indirect_calls: true, ..Callees::default() };
+        }
+        let set = db.mir_callees(self.id.into());
+        let mut result =
+            Callees { has_indirect_calls: set.has_indirect_calls, ..Callees::default() };
+        for &callee in &set.callees {
+            match callee {
+                CallableDefId::FunctionId(it) => result.functions.push(it.into()),
+                CallableDefId::StructId(it) => result.constructors.push(Struct::from(it).into()),
+                CallableDefId::EnumVariantId(it) => {
+                    result.constructors.push(Variant::from(it).into())
+                }
+            }
+        }
+        result
+    }
+}
+
+/// The calls of a function, see [`Function::callees`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Callees {
+    /// The called functions, with trait methods resolved to the method of the impl if possible, and
+    /// the `drop` functions of the values the function drops.
+    pub functions: Vec<Function>,
+    /// The tuple structs and tuple-like variants the function constructs.
+    pub constructors: Vec<VariantDef>,
+    /// Whether the function calls through a function pointer or a trait object, or its body can't
+    /// be analyzed, so it may call anything.
+    pub has_indirect_calls: bool,
+}
+
+/// A place where a function may panic, see [`Function::panic_sites`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PanicSite {
+    pub kind: PanicSiteKind,
+    /// `None` if the site doesn't come from the source, like the checks of a desugaring.
+    pub source: Option<InFile<SyntaxNodePtr>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PanicSiteKind {
+    IndexOutOfBounds,
+    Overflow(BinOp),
+    DivisionByZero(BinOp),
+    ExplicitPanic,
+    DivergingCall(Function),
+    UnknownCallee,
+    /// A call to a function, with the panic sites in its body.
+    Call(Function, Vec<PanicSite>),
+}
+
+impl PanicSite {
+    fn from_mir(
+        db: &dyn HirDatabase,
+        owner: DefWithBodyId,
+        sites: Vec<mir::PanicSite>,
+    ) -> Vec<PanicSite> {
+        let (_, source_map) = db.body_with_source_map(owner);
+        sites
+            .into_iter()
+            .map(|site| {
+                let kind = match site.kind {
+                    mir::PanicSiteKind::IndexOutOfBounds => PanicSiteKind::IndexOutOfBounds,
+                    mir::PanicSiteKind::Overflow(op) => PanicSiteKind::Overflow(op),
+                    mir::PanicSiteKind::DivisionByZero(op) => PanicSiteKind::DivisionByZero(op),
+                    mir::PanicSiteKind::ExplicitPanic => PanicSiteKind::ExplicitPanic,
+                    mir::PanicSiteKind::DivergingCall(f) => PanicSiteKind::DivergingCall(f.into()),
+                    mir::PanicSiteKind::UnknownCallee => PanicSiteKind::UnknownCallee,
+                    mir::PanicSiteKind::Call(f, sites) => {
+                        PanicSiteKind::Call(f.into(), PanicSite::from_mir(db, f.into(), sites))
+                    }
+                };
+                PanicSite { kind, source: mir_span_to_source(&source_map, site.span) }
+            })
+            .collect()
+    }
 }
 
 // Note: logically, this belongs to `hir_ty`, but we are not using it there yet.
@@ -1946,18 +2053,70 @@ impl Const {
         Type::new_with_resolver_inner(db, &resolver, ty)
     }
 
-    pub fn render_eval(self, db: &dyn HirDatabase) -> Result<String, ConstEvalError> {
+    /// Renders the value of this constant within the default limits, with the full value as well
+    /// if something was elided.
+    pub fn render_eval(self, db: &dyn HirDatabase) -> Result<RenderedConst, ConstEvalError> {
+        self.render_eval_with_limits(db, ConstRenderLimits::default())
+    }
+
+    /// Renders the value of this constant, eliding what exceeds `limits`. The full value is
+    /// rendered as well if something was elided.
+    pub fn render_eval_with_limits(
+        self,
+        db: &dyn HirDatabase,
+        limits: ConstRenderLimits,
+    ) -> Result<RenderedConst, ConstEvalError> {
+        if !db.mir_config().enable {
+            return Err(ConstEvalError::MirEvalError(MirEvalError::Disabled));
+        }
         let c = db.const_eval(self.id, Substitution::empty(Interner))?;
-        let r = format!("{}", HexifiedConst(c).display(db));
+        let r = render_const_limited(db, &HexifiedConst(c), limits);
         // We want to see things like `<utf8-error>` and `<layout-error>` as they are probably bug in our
-        // implementation, but there is no need to show things like `<enum-not-supported>` or `<ref-not-supported>` to
-        // the user.
-        if r.contains("not-supported>") {
+        // implementation, but there is no need to show things like `<ref-not-supported>` to the user.
+        if r.full.as_ref().unwrap_or(&r.short).contains("not-supported>") {
             return Err(ConstEvalError::MirEvalError(MirEvalError::NotSupported(
                 "rendering complex constants".to_string(),
             )));
         }
-        return Ok(r);
+        Ok(r)
+    }
+
+    /// The diagnostic for a constant whose evaluation fails because it depends on something that
+    /// only exists at runtime. Other evaluation failures are not reported, as they are more likely
+    /// to be a limitation of the evaluator than an error in the code.
+    fn unevaluable_diagnostic(self, db: &dyn HirDatabase) -> Option<UnevaluableConst> {
+        let error = db.const_eval(self.id, Substitution::empty(Interner)).err()?;
+        let dependency = error.runtime_dependency(db)?;
+        let source = self.source(db)?;
+        let node = match source.value.name() {
+            Some(name) => source.with_value(SyntaxNodePtr::new(name.syntax())),
+            None => source.with_value(SyntaxNodePtr::new(source.value.syntax())),
+        };
+        let frames = dependency
+            .frames
+            .iter()
+            .map(|frame| {
+                let call_site = frame.call_site.and_then(|(owner, span)| {
+                    let (_, source_map) = db.body_with_source_map(owner);
+                    mir_span_to_source(&source_map, span)
+                });
+                DependencyFrame {
+                    function: frame.func.into(),
+                    call_site,
+                    recursive: frame.recursive,
+                }
+            })
+            .collect();
+        let cause = match dependency.cause {
+            consteval::RuntimeOnlyCause::ForeignFunction(it) => {
+                RuntimeOnlyCause::ForeignFunction(it.into())
+            }
+            consteval::RuntimeOnlyCause::ExternStatic(it) => {
+                RuntimeOnlyCause::ExternStatic(it.into())
+            }
+            consteval::RuntimeOnlyCause::InlineAsm => RuntimeOnlyCause::InlineAsm,
+        };
+        Some(UnevaluableConst { node, name: self.name(db), frames, cause })
     }
 }
 
@@ -2593,6 +2752,22 @@ impl Local {
         body[self.binding_id].mode == BindingAnnotation::Mutable
     }
 
+    /// The source of every place where this local is written, including its initialization:
+    /// assignments to it or to its fields, and `&mut` borrows of it (e.g. by method autoref).
+    pub fn mutation_spans(self, db: &dyn HirDatabase) -> Vec<InFile<SyntaxNodePtr>> {
+        if !db.mir_config().enable {
+            return vec![];
+        }
+        let Ok(mir_body) = db.mir_body(self.parent) else { return vec![] };
+        let Some(local) = mir_body.local_for_binding(self.binding_id) else { return vec![] };
+        let (_, source_map) = db.body_with_source_map(self.parent);
+        mir_body
+            .assignments_of(local)
+            .into_iter()
+            .filter_map(|(_, span)| mir_span_to_source(&source_map, span))
+            .collect()
+    }
+
     pub fn is_ref(self, db: &dyn HirDatabase) -> bool {
         let body = db.body(self.parent);
         matches!(body[self.binding_id].mode, BindingAnnotation::Ref | BindingAnnotation::RefMut)
@@ -3510,6 +3685,13 @@ impl Type {
         }
     }
 
+    /// Renders this type with the constants in it, like array lengths, evaluated. The full form
+    /// keeps the expressions they are written as, like `BLOCK * 2` in `[u8; BLOCK * 2]`, if any
+    /// was evaluated.
+    pub fn render_eval(&self, db: &dyn HirDatabase) -> RenderedConst {
+        render_const_limited(db, &self.ty, ConstRenderLimits::default())
+    }
+
     pub fn autoderef<'a>(&'a self, db: &'a dyn HirDatabase) -> impl Iterator<Item = Type> + 'a {
         self.autoderef_(db).map(move |ty| self.derived(ty))
     }
@@ -4052,6 +4234,20 @@ impl Callable {
     }
 }
 
+fn mir_span_to_source(
+    source_map: &BodySourceMap,
+    span: mir::MirSpan,
+) -> Option<InFile<SyntaxNodePtr>> {
+    match span {
+        mir::MirSpan::ExprId(e) => Some(source_map.expr_syntax(e).ok()?.map(|x| x.into())),
+        mir::MirSpan::PatId(p) => Some(source_map.pat_syntax(p).ok()?.map(|x| match x {
+            Either::Left(e) => e.into(),
+            Either::Right(e) => e.into(),
+        })),
+        mir::MirSpan::Unknown => None,
+    }
+}
+
 fn closure_source(db: &dyn HirDatabase, closure: ClosureId) -> Option<ast::ClosureExpr> {
     let (owner, expr_id) = db.lookup_intern_closure(closure.into());
     let (_, source_map) = db.body_with_source_map(owner);
@@ -125,6 +125,9 @@ impl RootDatabase {
             // HirDatabase
             hir::db::InferQueryQuery
             hir::db::MirBodyQuery
+            hir::db::OptimizedMirBodyQuery
+            hir::db::MirBodyForClosureQuery
+            hir::db::OptimizedMirBodyForClosureQuery
             hir::db::BorrowckQuery
             hir::db::TyQuery
             hir::db::ValueTyQuery
@@ -132,9 +135,11 @@ impl RootDatabase {
             hir::db::ConstParamTyQuery
             hir::db::ConstEvalQuery
             hir::db::ConstEvalDiscriminantQuery
+            hir::db::EvalUnevaluatedConstQuery
             hir::db::ImplTraitQuery
             hir::db::FieldTypesQuery
             hir::db::LayoutOfAdtQuery
+            hir::db::NeedsDropQuery
             hir::db::TargetDataLayoutQuery
             hir::db::CallableItemSignatureQuery
             hir::db::ReturnTypeImplTraitsQuery
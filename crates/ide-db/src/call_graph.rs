@@ -0,0 +1,93 @@
+//! Walks the call graph between functions, as found from their MIR by [`Function::callees`].
+
+use hir::Function;
+use rustc_hash::FxHashSet;
+
+use crate::RootDatabase;
+
+/// The functions found by [`reachable_functions`].
+#[derive(Debug, Default)]
+pub struct ReachableFunctions {
+    /// The reachable functions in breadth first order, without the function the search starts
+    /// from.
+    pub functions: Vec<Function>,
+    /// Whether one of the searched bodies calls through a function pointer or a trait object, in
+    /// which case more functions may be reachable.
+    pub has_indirect_calls: bool,
+}
+
+/// Finds the functions `function` may call, directly or through at most `depth - 1` other calls.
+pub fn reachable_functions(
+    db: &RootDatabase,
+    function: Function,
+    depth: usize,
+) -> ReachableFunctions {
+    let mut result = ReachableFunctions::default();
+    let mut seen = FxHashSet::default();
+    seen.insert(function);
+    let mut frontier = vec![function];
+    for _ in 0..depth {
+        let mut next = vec![];
+        for caller in frontier {
+            let callees = caller.callees(db);
+            result.has_indirect_calls |= callees.has_indirect_calls;
+            for callee in callees.functions {
+                if seen.insert(callee) {
+                    result.functions.push(callee);
+                    next.push(callee);
+                }
+            }
+        }
+        frontier = next;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use base_db::fixture::WithFixture;
+    use hir::{ModuleDef, Semantics};
+
+    use crate::RootDatabase;
+
+    use super::reachable_functions;
+
+    fn check(ra_fixture: &str, depth: usize, expected: &[&str], has_indirect_calls: bool) {
+        let (db, file_id) = RootDatabase::with_single_file(ra_fixture);
+        let sema = Semantics::new(&db);
+        let module = sema.to_module_def(file_id).unwrap();
+        let goal = module
+            .declarations(&db)
+            .into_iter()
+            .find_map(|it| match it {
+                ModuleDef::Function(f) if f.name(&db).to_smol_str() == "goal" => Some(f),
+                _ => None,
+            })
+            .unwrap();
+        let reachable = reachable_functions(&db, goal, depth);
+        let mut names: Vec<_> =
+            reachable.functions.iter().map(|it| it.name(&db).to_smol_str()).collect();
+        names.sort();
+        assert_eq!(names, expected);
+        assert_eq!(reachable.has_indirect_calls, has_indirect_calls);
+    }
+
+    #[test]
+    fn walks_calls_up_to_depth() {
+        let fixture = r#"
+fn leaf() -> i32 { 1 }
+fn indirect() -> i32 {
+    let p: fn() -> i32 = leaf;
+    p()
+}
+fn middle() -> i32 { leaf() + indirect() }
+fn recursive(n: i32) -> i32 { if n == 0 { middle() } else { recursive(n - 1) } }
+fn goal() -> i32 { recursive(3) + goal() }
+"#;
+        check(fixture, 0, &[], false);
+        check(fixture, 1, &["recursive"], false);
+        check(fixture, 2, &["middle", "recursive"], false);
+        check(fixture, 3, &["indirect", "leaf", "middle", "recursive"], false);
+        check(fixture, 4, &["indirect", "leaf", "middle", "recursive"], true);
+    }
+}
@@ -3,7 +3,7 @@
 use std::collections::VecDeque;
 
 use base_db::{FileId, SourceDatabaseExt};
-use hir::{Crate, ItemInNs, ModuleDef, Name, Semantics};
+use hir::{db::HirDatabase, Crate, ItemInNs, ModuleDef, Name, Semantics};
 use syntax::{
     ast::{self, make},
     AstToken, SyntaxKind, SyntaxToken, TokenAtOffset,
@@ -109,3 +109,10 @@ pub fn is_editable_crate(krate: Crate, db: &RootDatabase) -> bool {
     let source_root_id = db.file_source_root(root_file);
     !db.source_root(source_root_id).is_library
 }
+
+/// Whether the MIR based features (const evaluation, borrow checking diagnostics, ...) should run
+/// for `krate`, as configured by [`hir::MirConfig`].
+pub fn mir_enabled_for(krate: Crate, db: &RootDatabase) -> bool {
+    let config = db.mir_config();
+    config.enable && (!config.local_crates_only || is_editable_crate(krate, db))
+}
@@ -8,6 +8,7 @@ mod apply_change;
 
 pub mod active_parameter;
 pub mod assists;
+pub mod call_graph;
 pub mod defs;
 pub mod famous_defs;
 pub mod helpers;
@@ -141,6 +142,7 @@ impl RootDatabase {
         db.set_local_roots_with_durability(Default::default(), Durability::HIGH);
         db.set_library_roots_with_durability(Default::default(), Durability::HIGH);
         db.set_enable_proc_attr_macros(false);
+        db.set_mir_config(Default::default());
         db.update_lru_capacity(lru_capacity);
         db
     }
@@ -247,5 +249,6 @@ impl SnippetCap {
 
 #[cfg(test)]
 mod tests {
+    mod evaluate;
     mod sourcegen_lints;
 }
@@ -0,0 +1,115 @@
+use base_db::fixture::WithFixture;
+use hir::{evaluate_expression, EvalError, HirDisplay, Semantics};
+use syntax::{AstNode, TokenAtOffset};
+
+use crate::RootDatabase;
+
+/// Evaluates `text` at the cursor of the fixture, and renders the value with its type.
+fn eval(ra_fixture: &str, text: &str) -> Result<String, EvalError> {
+    let (db, position) = RootDatabase::with_position(ra_fixture);
+    let sema = Semantics::new(&db);
+    let file = sema.parse(position.file_id);
+    let token = match file.syntax().token_at_offset(position.offset) {
+        TokenAtOffset::Single(it) | TokenAtOffset::Between(it, _) => it,
+        TokenAtOffset::None => panic!("no token at the cursor"),
+    };
+    let scope = sema.scope_at_offset(&token.parent().unwrap(), position.offset).unwrap();
+    let value = evaluate_expression(&db, &scope, text)?;
+    Ok(format!("{}: {}", value.value, value.ty.display(&db)))
+}
+
+#[test]
+fn renders_the_value_and_its_type() {
+    let fixture = r#"
+const C: i32 = 5;
+struct S;
+impl S {
+    const K: i32 = 7;
+}
+fn f() {
+    $0
+}
+"#;
+    assert_eq!(eval(fixture, "C + S::K * 2").as_deref(), Ok("19 (0x13): i32"));
+    assert_eq!(eval(fixture, "(C as u8, C > 2)").as_deref(), Ok("(5, true): (u8, bool)"));
+}
+
+#[test]
+fn resolves_the_items_of_enclosing_blocks() {
+    let fixture = r#"
+mod m {
+    pub const C: u8 = 3;
+}
+fn f() {
+    use m::C;
+    const D: u8 = 4;
+    {
+        $0
+    }
+}
+"#;
+    assert_eq!(eval(fixture, "C * D").as_deref(), Ok("12 (0xC): u8"));
+}
+
+#[test]
+fn evaluates_blocks() {
+    let fixture = r#"
+const C: i32 = 5;
+fn f() {
+    $0
+}
+"#;
+    assert_eq!(eval(fixture, "if C > 2 { 1 } else { 2 }").as_deref(), Ok("1: i32"));
+    assert_eq!(eval(fixture, "match C { 5 => { 10 } _ => 0 }").as_deref(), Ok("10 (0xA): i32"));
+    assert_eq!(eval(fixture, "{ let y = C; y * 2 }").as_deref(), Ok("10 (0xA): i32"));
+}
+
+#[test]
+fn locals_are_not_in_scope() {
+    let fixture = r#"
+fn f(x: i32) {
+    let y = 2;
+    $0y;
+}
+"#;
+    let name = |e: Result<String, EvalError>| match e {
+        Err(EvalError::LocalNotInScope(name)) => name.to_string(),
+        e => panic!("{e:?}"),
+    };
+    assert_eq!(name(eval(fixture, "x + 1")), "x");
+    assert_eq!(name(eval(fixture, "y")), "y");
+    // The bindings of the snippet shadow the locals.
+    assert_eq!(eval(fixture, "{ let y = 3; y + 1 }").as_deref(), Ok("4: i32"));
+}
+
+#[test]
+fn generic_params_are_in_scope() {
+    let fixture = r#"
+fn f<const N: usize>() {
+    $0
+}
+"#;
+    let Err(EvalError::ConstEvalError(e)) = eval(fixture, "N + 1") else { panic!() };
+    assert!(format!("{e:?}").contains("ConstParamInRuntimeContext(\"N\")"), "{e:?}");
+}
+
+#[test]
+fn rejects_items_and_macro_calls() {
+    let fixture = r#"
+fn f() {
+    $0
+}
+"#;
+    for text in ["{ struct S; 1 }", "m!()", "1; 2", "let x = 1", "(|| 1)()", "async { 1 }"] {
+        assert_eq!(eval(fixture, text), Err(EvalError::InvalidExpression), "{text}");
+    }
+}
+
+#[test]
+fn requires_a_body() {
+    let fixture = r#"
+const C: i32 = 5;
+$0
+"#;
+    assert_eq!(eval(fixture, "C + 1"), Err(EvalError::NotInBody));
+}
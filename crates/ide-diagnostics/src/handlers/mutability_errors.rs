@@ -764,6 +764,30 @@ fn main() {
       //^^^^^ 💡 weak: variable does not need to be mutable
     f(x);
 }
+"#,
+        );
+    }
+
+    #[test]
+    fn self_receivers() {
+        check_diagnostics(
+            r#"
+struct Foo { x: i32 }
+impl Foo {
+    fn by_value(self) {
+        self.x = 2;
+      //^^^^^^^^^^ 💡 error: cannot mutate immutable variable `self`
+    }
+    fn by_mut_value(mut self) {
+        self.x = 2;
+    }
+    fn unused(mut self) {}
+            //^^^^^^^^ 💡 weak: variable does not need to be mutable
+    fn by_ref(&mut self) {
+        self.x = 2;
+        *self = Foo { x: 3 };
+    }
+}
 "#,
         );
     }
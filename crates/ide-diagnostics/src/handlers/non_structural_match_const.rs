@@ -0,0 +1,173 @@
+use hir::{HirDisplay, NonStructuralMatch};
+
+use crate::{Diagnostic, DiagnosticsContext};
+
+// Diagnostic: non-structural-match-const
+//
+// This diagnostic is triggered if a constant is used in a pattern, but its type can't be compared
+// structurally, e.g. because it implements `PartialEq` by hand.
+pub(crate) fn non_structural_match_const(
+    ctx: &DiagnosticsContext<'_>,
+    d: &hir::NonStructuralMatchConst,
+) -> Diagnostic {
+    let ty = d.ty.display(ctx.sema.db);
+    let reason = match d.reason {
+        NonStructuralMatch::Float => format!("it contains the floating point type `{ty}`"),
+        NonStructuralMatch::ManualPartialEq => {
+            format!("`{ty}` implements `PartialEq` without deriving it")
+        }
+        NonStructuralMatch::MissingPartialEq => format!("`{ty}` doesn't implement `PartialEq`"),
+        NonStructuralMatch::Opaque => format!("it contains the opaque type `{ty}`"),
+    };
+    Diagnostic::new(
+        "non-structural-match-const",
+        format!("constant can't be used in a pattern: {reason}"),
+        ctx.sema.diagnostics_display_range(d.pat.clone().map(|it| it.into())).range,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::check_diagnostics;
+
+    #[test]
+    fn manual_partial_eq() {
+        check_diagnostics(
+            r#"
+//- minicore: eq, derive
+struct Manual(i32);
+impl PartialEq for Manual {
+    fn eq(&self, _: &Self) -> bool { true }
+}
+const M: Manual = Manual(1);
+fn f(x: Manual) {
+    match x {
+        M => {}
+      //^ error: constant can't be used in a pattern: `Manual` implements `PartialEq` without deriving it
+        _ => {}
+    }
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn missing_partial_eq() {
+        check_diagnostics(
+            r#"
+//- minicore: eq
+struct NoEq(i32);
+const N: NoEq = NoEq(1);
+fn f(x: NoEq) {
+    if let N = x {}
+         //^ error: constant can't be used in a pattern: `NoEq` doesn't implement `PartialEq`
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn derived_partial_eq() {
+        check_diagnostics(
+            r#"
+//- minicore: eq, derive
+#[derive(PartialEq)]
+struct Inner(i32);
+#[derive(PartialEq)]
+enum Derived {
+    A(Inner),
+    B(&'static Inner, (u8, [i32; 2])),
+}
+const D: Derived = Derived::A(Inner(1));
+fn f(x: Derived) {
+    match x {
+        D => {}
+        _ => {}
+    }
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn field_with_manual_partial_eq() {
+        check_diagnostics(
+            r#"
+//- minicore: eq, derive
+struct Manual(i32);
+impl PartialEq for Manual {
+    fn eq(&self, _: &Self) -> bool { true }
+}
+#[derive(PartialEq)]
+struct Outer(u8, Manual);
+const O: Outer = Outer(1, Manual(2));
+fn f(x: Outer) {
+    match x {
+        O => {}
+      //^ error: constant can't be used in a pattern: `Manual` implements `PartialEq` without deriving it
+        _ => {}
+    }
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn float() {
+        check_diagnostics(
+            r#"
+//- minicore: eq, derive
+#[derive(PartialEq)]
+struct WithFloat(f32);
+const F: WithFloat = WithFloat(1.0);
+fn f(x: WithFloat) {
+    match x {
+        F => {}
+      //^ error: constant can't be used in a pattern: it contains the floating point type `f32`
+        _ => {}
+    }
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn float_in_second_instance_of_generic() {
+        check_diagnostics(
+            r#"
+//- minicore: eq, derive
+#[derive(PartialEq)]
+struct Wrapper<T>(T);
+const W: (Wrapper<u8>, Wrapper<f32>) = (Wrapper(1), Wrapper(1.0));
+fn f(x: (Wrapper<u8>, Wrapper<f32>)) {
+    match x {
+        W => {}
+      //^ error: constant can't be used in a pattern: it contains the floating point type `f32`
+        _ => {}
+    }
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn recursive_type() {
+        check_diagnostics(
+            r#"
+//- minicore: eq, derive
+#[derive(PartialEq)]
+enum List<T> {
+    Nil,
+    Cons(T, &'static List<T>),
+}
+const L: List<u8> = List::Cons(1, &List::Nil);
+fn f(x: List<u8>) {
+    match x {
+        L => {}
+        _ => {}
+    }
+}
+"#,
+        );
+    }
+}
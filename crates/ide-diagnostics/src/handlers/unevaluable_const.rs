@@ -0,0 +1,204 @@
+use hir::RuntimeOnlyCause;
+
+use crate::{Diagnostic, DiagnosticsContext};
+
+// Diagnostic: unevaluable-const
+//
+// This diagnostic is triggered if the initializer of a constant depends on something that only
+// exists at runtime, like a call to a foreign function or a read of a foreign static, through the
+// functions it calls.
+pub(crate) fn unevaluable_const(
+    ctx: &DiagnosticsContext<'_>,
+    d: &hir::UnevaluableConst,
+) -> Diagnostic {
+    let db = ctx.sema.db;
+    let name = d.name.as_ref().map_or_else(|| "_".to_string(), |it| it.to_string());
+    let cause = match d.cause {
+        RuntimeOnlyCause::ForeignFunction(f) => format!("calls foreign function `{}`", f.name(db)),
+        RuntimeOnlyCause::ExternStatic(s) => {
+            let mutability = if s.is_mut(db) { "mut " } else { "" };
+            format!("reads foreign `static {mutability}{}`", s.name(db))
+        }
+        RuntimeOnlyCause::InlineAsm => "uses inline assembly".to_string(),
+    };
+    let mut message = format!("cannot evaluate `{name}`: ");
+    for (i, frame) in d.frames.iter().enumerate() {
+        message += if i == 0 { "depends on " } else { "calls " };
+        message += &format!("`{}()`", frame.function.name(db));
+        if frame.recursive {
+            message += " (recursively)";
+        }
+        message += ", which ";
+    }
+    message += &cause;
+    let related = d
+        .frames
+        .iter()
+        .filter_map(|frame| {
+            let range = ctx.sema.diagnostics_display_range(frame.call_site.clone()?);
+            Some((range, format!("`{}()` is called here", frame.function.name(db))))
+        })
+        .collect();
+    Diagnostic::new(
+        "unevaluable-const",
+        message,
+        ctx.sema.diagnostics_display_range(d.node.clone()).range,
+    )
+    .with_related(related)
+    .experimental()
+}
+
+#[cfg(test)]
+mod tests {
+    use ide_db::{
+        assists::AssistResolveStrategy,
+        base_db::{fixture::WithFixture, SourceDatabaseExt},
+        RootDatabase,
+    };
+    use test_utils::extract_annotations;
+
+    use crate::{tests::check_diagnostics, DiagnosticsConfig};
+
+    /// Checks that the calls linked by the only diagnostic are the ranges annotated with `call:`.
+    fn check_call_sites(ra_fixture: &str) {
+        let (db, file_id) = RootDatabase::with_single_file(ra_fixture);
+        let diagnostics = crate::diagnostics(
+            &db,
+            &DiagnosticsConfig::test_sample(),
+            &AssistResolveStrategy::All,
+            file_id,
+        );
+        let [diagnostic] = &diagnostics[..] else {
+            panic!("expected a single diagnostic, found {diagnostics:?}");
+        };
+        let expected: Vec<_> = extract_annotations(&db.file_text(file_id))
+            .into_iter()
+            .filter_map(|(range, text)| Some((range, text.strip_prefix("call: ")?.to_string())))
+            .collect();
+        let mut actual: Vec<_> = diagnostic
+            .related
+            .iter()
+            .map(|(frange, message)| {
+                assert_eq!(frange.file_id, file_id);
+                (frange.range, message.clone())
+            })
+            .collect();
+        actual.sort_by_key(|(range, _)| range.start());
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn extern_call_through_two_functions() {
+        check_diagnostics(
+            r#"
+extern "C" {
+    fn getenv(name: *const u8) -> *const u8;
+}
+fn read_env() -> usize {
+    unsafe { getenv(0 as *const u8) as usize }
+}
+fn config_size() -> usize {
+    read_env() + 1
+}
+const CONFIG_SIZE: usize = config_size();
+    //^^^^^^^^^^^ error: cannot evaluate `CONFIG_SIZE`: depends on `config_size()`, which calls `read_env()`, which calls foreign function `getenv`
+const DIRECT: usize = unsafe { getenv(0 as *const u8) as usize };
+    //^^^^^^ error: cannot evaluate `DIRECT`: calls foreign function `getenv`
+"#,
+        );
+    }
+
+    #[test]
+    fn inline_asm_through_two_functions() {
+        check_diagnostics(
+            r#"
+#[rustc_builtin_macro]
+macro_rules! asm { () => {} }
+#[macro_export]
+#[rustc_builtin_macro]
+macro_rules! format_args { () => {} }
+fn read_counter() -> u64 {
+    unsafe { asm!("rdtsc") };
+    5
+}
+fn elapsed() -> u64 {
+    read_counter() * 2
+}
+const ELAPSED: u64 = elapsed();
+    //^^^^^^^ error: cannot evaluate `ELAPSED`: depends on `elapsed()`, which calls `read_counter()`, which uses inline assembly
+"#,
+        );
+    }
+
+    #[test]
+    fn extern_static_through_two_functions() {
+        check_diagnostics(
+            r#"
+extern "C" {
+    static mut errno: i32;
+}
+fn last_error() -> i32 {
+    unsafe { errno }
+}
+fn failed() -> bool {
+    last_error() != 0
+}
+const FAILED: bool = failed();
+    //^^^^^^ error: cannot evaluate `FAILED`: depends on `failed()`, which calls `last_error()`, which reads foreign `static mut errno`
+"#,
+        );
+    }
+
+    #[test]
+    fn recursion_is_collapsed() {
+        check_diagnostics(
+            r#"
+extern "C" {
+    fn getpid() -> i32;
+}
+fn even(n: i32) -> i32 {
+    if n == 0 { unsafe { getpid() } } else { odd(n - 1) }
+}
+fn odd(n: i32) -> i32 {
+    even(n - 1)
+}
+const PID: i32 = even(6);
+    //^^^ error: cannot evaluate `PID`: depends on `even()` (recursively), which calls foreign function `getpid`
+"#,
+        );
+    }
+
+    #[test]
+    fn links_call_sites() {
+        check_call_sites(
+            r#"
+extern "C" {
+    fn getenv(name: *const u8) -> *const u8;
+}
+fn read_env() -> usize {
+    unsafe { getenv(0 as *const u8) as usize }
+}
+fn config_size() -> usize {
+    read_env() + 1
+  //^^^^^^^^^^ call: `read_env()` is called here
+}
+const CONFIG_SIZE: usize = config_size();
+    //^^^^^^^^^^^ error
+                         //^^^^^^^^^^^^^ call: `config_size()` is called here
+"#,
+        );
+    }
+
+    #[test]
+    fn other_failures_are_not_reported() {
+        check_diagnostics(
+            r#"
+//- minicore: panic
+const fn fails() -> i32 {
+    panic!("no")
+}
+const FAILS: i32 = fails();
+"#,
+        );
+    }
+}
@@ -148,6 +148,7 @@ mod baz {}
                                 },
                             ],
                         ),
+                        related: [],
                     },
                 ]
             "#]],
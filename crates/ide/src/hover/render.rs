@@ -7,10 +7,11 @@ use hir::{
     MirEvalError, Semantics, TypeInfo,
 };
 use ide_db::{
-    base_db::SourceDatabase,
+    base_db::{SourceDatabase, SourceDatabaseExt},
     defs::Definition,
     famous_defs::FamousDefs,
     generated::lints::{CLIPPY_LINTS, DEFAULT_LINTS, FEATURES},
+    helpers::mir_enabled_for,
     syntax_helpers::insert_whitespace_into_node,
     RootDatabase,
 };
@@ -382,6 +383,8 @@ pub(super) fn definition(
     config: &HoverConfig,
 ) -> Option<Markup> {
     let mod_path = definition_mod_path(db, &def);
+    // The label with nothing elided, if the label is shortened.
+    let mut full_label = None;
     let (label, docs) = match def {
         Definition::Macro(it) => label_and_docs(db, it),
         Definition::Field(it) => label_and_layout_info_and_docs(db, it, |&it| {
@@ -404,7 +407,7 @@ pub(super) fn definition(
         }),
         Definition::Module(it) => label_and_docs(db, it),
         Definition::Function(it) => label_and_layout_info_and_docs(db, it, |_| {
-            if !config.interpret_tests {
+            if !config.interpret_tests || !mir_enabled_for(it.module(db).krate(), db) {
                 return None;
             }
             match it.eval(db) {
@@ -413,7 +416,18 @@ pub(super) fn definition(
                     let name = &db.function_data(f).name;
                     Some(format!("error: fail to lower {name} due {e:?}"))
                 }
-                Err(e) => Some(format!("error: {e:?}")),
+                Err(e) => match e.panic_message() {
+                    Some((message, Some(location))) => {
+                        let root = db.source_root(db.file_source_root(location.file_id));
+                        let file = root.path_for_file(&location.file_id);
+                        let file =
+                            file.map_or_else(|| "<unknown file>".to_owned(), |it| it.to_string());
+                        let (line, column) = (location.line, location.column);
+                        Some(format!("error: panicked at {file}:{line}:{column}: {message}"))
+                    }
+                    Some((message, None)) => Some(format!("error: panicked: {message}")),
+                    None => Some(format!("error: {e:?}")),
+                },
             }
         }),
         Definition::Adt(it) => label_and_layout_info_and_docs(db, it, |&it| {
@@ -431,10 +445,17 @@ pub(super) fn definition(
             }
         }),
         Definition::Const(it) => label_value_and_docs(db, it, |it| {
-            let body = it.render_eval(db);
+            let body = mir_enabled_for(it.module(db).krate(), db)
+                .then(|| it.render_eval(db).ok())
+                .flatten();
             match body {
-                Ok(x) => Some(x),
-                Err(_) => {
+                Some(x) => {
+                    if let Some(full) = x.full {
+                        full_label = Some(format!("{} = {full}", it.display(db)));
+                    }
+                    Some(x.short)
+                }
+                None => {
                     let source = it.source(db)?;
                     let mut body = source.value.body()?.syntax().clone();
                     if source.file_id.is_macro() {
@@ -484,7 +505,7 @@ pub(super) fn definition(
             item.docs(db)
         })
         .map(Into::into);
-    markup(docs, label, mod_path)
+    markup_with_full_desc(docs, label, full_label, mod_path)
 }
 
 fn type_info(
@@ -516,7 +537,8 @@ fn type_info(
         )
         .into()
     } else {
-        Markup::fenced_block(&original.display(sema.db))
+        let original = original.render_eval(sema.db);
+        markup_with_full_desc(None, original.short, original.full, None)?
     };
     res.actions.push(HoverAction::goto_type_from_targets(sema.db, targets));
     Some(res)
@@ -578,7 +600,7 @@ fn label_value_and_docs<D, E, V>(
 ) -> (String, Option<hir::Documentation>)
 where
     D: HasAttrs + HirDisplay,
-    E: Fn(&D) -> Option<V>,
+    E: FnOnce(&D) -> Option<V>,
     V: Display,
 {
     let label = if let Some(value) = value_extractor(&def) {
@@ -598,6 +620,16 @@ fn definition_mod_path(db: &RootDatabase, def: &Definition) -> Option<String> {
 }
 
 fn markup(docs: Option<String>, desc: String, mod_path: Option<String>) -> Option<Markup> {
+    markup_with_full_desc(docs, desc, None, mod_path)
+}
+
+/// Like [`markup`], with `full_desc` shown after `desc` when `desc` is a shortened form of it.
+fn markup_with_full_desc(
+    docs: Option<String>,
+    desc: String,
+    full_desc: Option<String>,
+    mod_path: Option<String>,
+) -> Option<Markup> {
     let mut buf = String::new();
 
     if let Some(mod_path) = mod_path {
@@ -606,6 +638,9 @@ fn markup(docs: Option<String>, desc: String, mod_path: Option<String>) -> Optio
         }
     }
     format_to!(buf, "```rust\n{}\n```", desc);
+    if let Some(full_desc) = full_desc {
+        format_to!(buf, "\n___\n\nIn full:\n```rust\n{}\n```", full_desc);
+    }
 
     if let Some(doc) = docs {
         format_to!(buf, "\n___\n\n{}", doc);
@@ -632,9 +667,10 @@ fn find_std_module(famous_defs: &FamousDefs<'_, '_>, name: &str) -> Option<hir::
 
 fn local(db: &RootDatabase, it: hir::Local) -> Option<Markup> {
     let ty = it.ty(db);
-    let ty = ty.display_truncated(db, None);
+    let full_ty = ty.render_eval(db).full;
+    let ty = ty.display_truncated(db, None).to_string();
     let is_mut = if it.is_mut(db) { "mut " } else { "" };
-    let desc = match it.primary_source(db).into_ident_pat() {
+    let desc = |ty: &str| match it.primary_source(db).into_ident_pat() {
         Some(ident) => {
             let name = it.name(db);
             let let_kw = if ident
@@ -650,7 +686,7 @@ fn local(db: &RootDatabase, it: hir::Local) -> Option<Markup> {
         }
         None => format!("{is_mut}self: {ty}"),
     };
-    markup(None, desc, None)
+    markup_with_full_desc(None, desc(&ty), full_ty.as_deref().map(desc), None)
 }
 
 struct KeywordHint {
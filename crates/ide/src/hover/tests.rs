@@ -1,8 +1,16 @@
 use expect_test::{expect, Expect};
-use ide_db::base_db::{FileLoader, FileRange};
-use syntax::TextRange;
+use hir::{
+    db::{ConstEvalQuery, HirDatabase},
+    MirConfig,
+};
+use ide_db::base_db::{
+    fixture::ChangeFixture,
+    salsa::debug::{DebugQueryTable, TableEntry},
+    FileLoader, FileRange,
+};
+use syntax::{TextRange, TextSize};
 
-use crate::{fixture, HoverConfig, HoverDocFormat};
+use crate::{fixture, AnalysisHost, HoverConfig, HoverDocFormat};
 
 const HOVER_BASE_CONFIG: HoverConfig = HoverConfig {
     links_in_hover: false,
@@ -761,6 +769,99 @@ fn hover_local_var_edge() {
     )
 }
 
+#[test]
+fn hover_evaluates_consts_in_types() {
+    check(
+        r#"
+const BLOCK: usize = 8;
+fn make() -> [u8; BLOCK * 2] { loop {} }
+fn main() {
+    let buf$0 = make();
+}
+"#,
+        expect![[r#"
+            *buf*
+
+            ```rust
+            let buf: [u8; 16]
+            ```
+
+            ---
+
+            In full:
+
+            ```rust
+            let buf: [u8; BLOCK * 2]
+            ```
+        "#]],
+    );
+    check(
+        r#"
+struct ArrayVec<T, const CAP: usize>([T; CAP]);
+const fn double(x: usize) -> usize { x * 2 }
+const CAP: usize = double(8);
+fn make() -> ArrayVec<i32, { CAP }> { loop {} }
+fn main() {
+    let v$0 = make();
+}
+"#,
+        expect![[r#"
+            *v*
+
+            ```rust
+            let v: ArrayVec<i32, 16>
+            ```
+
+            ---
+
+            In full:
+
+            ```rust
+            let v: ArrayVec<i32, CAP>
+            ```
+        "#]],
+    );
+    check_hover_range(
+        r#"
+const BLOCK: usize = 8;
+fn make() -> [u8; BLOCK * 2] { loop {} }
+fn main() {
+    let buf = $0make()$0;
+}
+"#,
+        expect![[r#"
+            ```rust
+            [u8; 16]
+            ```
+            ___
+
+            In full:
+            ```rust
+            [u8; BLOCK * 2]
+            ```"#]],
+    );
+}
+
+#[test]
+fn hover_shows_consts_in_types_that_fail_to_evaluate() {
+    check(
+        r#"
+const BLOCK: usize = 0 - 1;
+fn make() -> [u8; BLOCK * 2] { loop {} }
+fn main() {
+    let buf$0 = make();
+}
+"#,
+        expect![[r#"
+            *buf*
+
+            ```rust
+            let buf: [u8; BLOCK * 2]
+            ```
+        "#]],
+    );
+}
+
 #[test]
 fn hover_for_param_edge() {
     check(
@@ -4034,6 +4135,87 @@ const FOO$0: f64 = 1.0f64;
     );
 }
 
+#[test]
+fn hover_const_eval_aggregates() {
+    check(
+        r#"
+struct Point { x: i32, y: i32 }
+const ORIGIN$0: Point = Point { x: 1 - 1, y: 0 };
+"#,
+        expect![[r#"
+            *ORIGIN*
+
+            ```rust
+            test
+            ```
+
+            ```rust
+            const ORIGIN: Point = Point { x: 0, y: 0 }
+            ```
+        "#]],
+    );
+    check(
+        r#"
+enum Mode { Slow, Fast }
+const DEFAULT_MODE$0: Mode = { let slow = false; if slow { Mode::Slow } else { Mode::Fast } };
+"#,
+        expect![[r#"
+            *DEFAULT_MODE*
+
+            ```rust
+            test
+            ```
+
+            ```rust
+            const DEFAULT_MODE: Mode = Mode::Fast
+            ```
+        "#]],
+    );
+    check(
+        r#"
+//- minicore: option
+enum Shape { Circle(u8), Rect { w: u8, h: u8 } }
+const RECT: Shape = Shape::Rect { w: 1 + 1, h: 3 };
+const SHAPES$0: (Shape, Option<Shape>, Option<Shape>) = (RECT, Some(Shape::Circle(1)), None);
+"#,
+        expect![[r#"
+            *SHAPES*
+
+            ```rust
+            test
+            ```
+
+            ```rust
+            const SHAPES: (Shape, Option<Shape>, Option<Shape>) = (Shape::Rect { w: 2, h: 3 }, Option::Some(Shape::Circle(1)), Option::None)
+            ```
+        "#]],
+    );
+    check(
+        r#"
+const TABLE$0: [u16; 12] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 10 + 2];
+"#,
+        expect![[r#"
+            *TABLE*
+
+            ```rust
+            test
+            ```
+
+            ```rust
+            const TABLE: [u16; 12] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, …; 12]
+            ```
+
+            ---
+
+            In full:
+
+            ```rust
+            const TABLE: [u16; 12] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]
+            ```
+        "#]],
+    );
+}
+
 #[test]
 fn hover_const_eval_in_generic_trait() {
     // Doesn't compile, but we shouldn't crash.
@@ -4065,6 +4247,63 @@ fn test() {
     );
 }
 
+#[test]
+fn hover_const_eval_only_for_local_crates() {
+    let mut host = AnalysisHost::default();
+    let change_fixture = ChangeFixture::parse(
+        r#"
+//- /dep.rs crate:dep new_source_root:library
+pub const DEP: u32 = 2 + 3;
+//- /main.rs crate:main deps:dep new_source_root:local
+const LOCAL: u32 = 2 + 3;
+fn main() {
+    let _ = (dep::DEP, LOCAL);
+}
+"#,
+    );
+    host.raw_database_mut()
+        .set_mir_config(MirConfig { local_crates_only: true, ..MirConfig::default() });
+    host.apply_change(change_fixture.change);
+    let analysis = host.analysis();
+    let main_file = change_fixture.files[1];
+    let text = analysis.file_text(main_file).unwrap();
+    let hover = |name: &str| {
+        let offset = TextSize::try_from(text.rfind(name).unwrap()).unwrap();
+        let range = FileRange { file_id: main_file, range: TextRange::empty(offset) };
+        analysis.hover(&HOVER_BASE_CONFIG, range).unwrap().unwrap().info.markup.to_string()
+    };
+
+    assert!(hover("DEP").contains("const DEP: u32 = 2 + 3"));
+    assert!(hover("LOCAL").contains("const LOCAL: u32 = 5"));
+    let evaluated = analysis
+        .with_db(|db| ConstEvalQuery.in_db(db).entries::<Vec<TableEntry<_, _>>>().len())
+        .unwrap();
+    assert_eq!(evaluated, 1);
+}
+
+#[test]
+fn hover_keeps_consts_in_types_with_mir_disabled() {
+    let mut host = AnalysisHost::default();
+    let change_fixture = ChangeFixture::parse(
+        r#"
+const BLOCK: usize = 8;
+fn make() -> [u8; BLOCK * 2] { loop {} }
+fn main() {
+    let buf = make();
+}
+"#,
+    );
+    host.raw_database_mut().set_mir_config(MirConfig { enable: false, ..MirConfig::default() });
+    host.apply_change(change_fixture.change);
+    let analysis = host.analysis();
+    let file_id = change_fixture.files[0];
+    let text = analysis.file_text(file_id).unwrap();
+    let offset = TextSize::try_from(text.find("buf").unwrap()).unwrap();
+    let range = FileRange { file_id, range: TextRange::empty(offset) };
+    let hover = analysis.hover(&HOVER_BASE_CONFIG, range).unwrap().unwrap();
+    assert!(hover.info.markup.to_string().contains("let buf: [u8; BLOCK * 2]"));
+}
+
 #[test]
 fn hover_const_pat() {
     check(
@@ -5843,3 +6082,38 @@ fn test() {
         "#]],
     );
 }
+
+#[test]
+fn hover_interpreted_test_panic() {
+    let (analysis, position) = fixture::position(
+        r#"
+//- minicore: panic
+fn check(x: i32) {
+    if x > 2 {
+        panic!("too big");
+    }
+}
+#[test]
+fn t$0est() {
+    check(3);
+}
+"#,
+    );
+    let hover = analysis
+        .hover(
+            &HoverConfig { interpret_tests: true, ..HOVER_BASE_CONFIG },
+            FileRange { file_id: position.file_id, range: TextRange::empty(position.offset) },
+        )
+        .unwrap()
+        .unwrap();
+    expect![[r#"
+
+        ```rust
+        test
+        ```
+
+        ```rust
+        fn test() // error: panicked at /main.rs:3:9: too big
+        ```"#]]
+    .assert_eq(&hover.info.markup.to_string());
+}
@@ -43,6 +43,7 @@ mod markdown_remove;
 mod matching_brace;
 mod moniker;
 mod move_item;
+mod panic_sites;
 mod parent_module;
 mod references;
 mod rename;
@@ -312,6 +313,10 @@ impl Analysis {
         self.with_db(|db| view_mir::view_mir(db, position))
     }
 
+    pub fn panic_sites(&self, position: FilePosition) -> Cancellable<String> {
+        self.with_db(|db| panic_sites::panic_sites(db, position))
+    }
+
     pub fn view_item_tree(&self, file_id: FileId) -> Cancellable<String> {
         self.with_db(|db| view_item_tree::view_item_tree(db, file_id))
     }
@@ -0,0 +1,160 @@
+use hir::{InFile, PanicSite, PanicSiteKind, Semantics};
+use ide_db::{
+    base_db::{FilePosition, FileRange, SourceDatabaseExt},
+    LineIndexDatabase, RootDatabase,
+};
+use stdx::format_to;
+use syntax::{algo::find_node_at_offset, ast, AstNode};
+
+/// How many calls deep the panic sites of callees are searched.
+const CALL_DEPTH: usize = 2;
+
+// Feature: Show Panic Sites
+//
+// Lists the places where the function under the cursor may panic: indexing, arithmetic that may
+// overflow or divide by zero, explicit panics, and calls to functions that may panic, together
+// with the panic sites of those functions. Calls that can't be resolved to a single function, like
+// calls through a trait object, are listed as they may call anything.
+//
+// |===
+// | Editor  | Action Name
+//
+// | VS Code | **rust-analyzer: Show Panic Sites**
+// |===
+pub(crate) fn panic_sites(db: &RootDatabase, position: FilePosition) -> String {
+    fn_panic_sites(db, position).unwrap_or_else(|| "Not inside a function".to_string())
+}
+
+fn fn_panic_sites(db: &RootDatabase, position: FilePosition) -> Option<String> {
+    let sema = Semantics::new(db);
+    let source_file = sema.parse(position.file_id);
+
+    let fn_ = find_node_at_offset::<ast::Fn>(source_file.syntax(), position.offset)?;
+    let function = sema.to_def(&fn_)?;
+    let sites = function.panic_sites(db, CALL_DEPTH);
+    if sites.is_empty() {
+        return Some(format!("`{}` has no panic sites", function.name(db)));
+    }
+    let mut buf = String::new();
+    render_sites(&sema, &sites, 0, &mut buf);
+    Some(buf)
+}
+
+fn render_sites(
+    sema: &Semantics<'_, RootDatabase>,
+    sites: &[PanicSite],
+    indent: usize,
+    buf: &mut String,
+) {
+    let db = sema.db;
+    for site in sites {
+        let location = site.source.clone().and_then(|source| source_location(sema, source));
+        let location = location.as_deref().unwrap_or("<unknown>");
+        let description = match &site.kind {
+            PanicSiteKind::IndexOutOfBounds => "index may be out of bounds".to_string(),
+            PanicSiteKind::Overflow(op) => format!("`{op}` may overflow"),
+            PanicSiteKind::DivisionByZero(op) => format!("`{op}` may divide by zero"),
+            PanicSiteKind::ExplicitPanic => "explicit panic".to_string(),
+            PanicSiteKind::DivergingCall(f) => {
+                format!("calls `{}`, which never returns", f.name(db))
+            }
+            PanicSiteKind::UnknownCallee => "unknown callee, may panic".to_string(),
+            PanicSiteKind::Call(f, _) => format!("calls `{}`, which may panic", f.name(db)),
+        };
+        format_to!(buf, "{:indent$}{location}: {description}\n", "");
+        if let PanicSiteKind::Call(_, sites) = &site.kind {
+            render_sites(sema, sites, indent + 4, buf);
+        }
+    }
+}
+
+/// Renders `source` as `line:column`, followed by the text of the site.
+fn source_location(
+    sema: &Semantics<'_, RootDatabase>,
+    source: InFile<syntax::SyntaxNodePtr>,
+) -> Option<String> {
+    let db = sema.db;
+    let root = sema.parse_or_expand(source.file_id)?;
+    let node = source.value.to_node(&root);
+    let FileRange { file_id, range } = source.with_value(&node).original_file_range(db);
+    let line_index = db.line_index(file_id);
+    let line_col = line_index.line_col(range.start());
+    let text = &db.file_text(file_id)[range];
+    let text = text.lines().next().unwrap_or_default();
+    let ellipsis = if text.len() < range.len().into() { "…" } else { "" };
+    Some(format!("{}:{} `{text}{ellipsis}`", line_col.line + 1, line_col.col + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::{expect, Expect};
+
+    use crate::fixture;
+
+    fn check(ra_fixture: &str, expect: Expect) {
+        let (analysis, position) = fixture::position(ra_fixture);
+        let sites = analysis.panic_sites(position).unwrap();
+        expect.assert_eq(&sites)
+    }
+
+    #[test]
+    fn lists_sites_of_callees() {
+        check(
+            r#"
+#[lang = "panic"]
+fn panic(_: &str) -> ! { loop {} }
+
+enum Option<T> { Some(T), None }
+
+fn unwrap(x: Option<i32>) -> i32 {
+    match x {
+        Option::Some(v) => v,
+        Option::None => panic("unwrap on None"),
+    }
+}
+
+fn is_zero(x: i32) -> bool {
+    x == 0
+}
+
+fn f$0oo(xs: [i32; 4], i: usize, o: Option<i32>) -> i32 {
+    let a = xs[i];
+    let b = unwrap(o);
+    if is_zero(b) { a } else { a / b }
+}
+"#,
+            expect![[r#"
+                18:13 `xs[i]`: index may be out of bounds
+                19:13 `unwrap(o)`: calls `unwrap`, which may panic
+                    9:25 `panic("unwrap on None")`: explicit panic
+                20:32 `a / b`: `/` may divide by zero
+            "#]],
+        );
+    }
+
+    #[test]
+    fn no_sites() {
+        check(
+            r#"
+fn f$0oo(x: i32) -> bool {
+    x == 0
+}
+"#,
+            expect!["`foo` has no panic sites"],
+        );
+    }
+
+    #[test]
+    fn unknown_callee() {
+        check(
+            r#"
+fn f$0oo(f: fn(i32) -> i32) -> i32 {
+    f(2)
+}
+"#,
+            expect![[r#"
+                2:5 `f(2)`: unknown callee, may panic
+            "#]],
+        );
+    }
+}
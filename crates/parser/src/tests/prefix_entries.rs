@@ -35,6 +35,9 @@ fn pat() {
     check(PrefixEntryPoint::Pat, "fn f() {}", "fn");
     // FIXME: This one is wrong, we should consume only one pattern.
     check(PrefixEntryPoint::Pat, ".. ..", ".. ..");
+    check(PrefixEntryPoint::Pat, "A | B => x", "A");
+    check(PrefixEntryPoint::PatTop, "A | B => x", "A | B");
+    check(PrefixEntryPoint::PatTop, "| A | B if x", "| A | B");
 }
 
 #[test]
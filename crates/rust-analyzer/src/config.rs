@@ -10,6 +10,7 @@
 use std::{fmt, iter, path::PathBuf};
 
 use flycheck::FlycheckConfig;
+use hir::{MirConfig, MirPass};
 use ide::{
     AssistConfig, CallableSnippets, CompletionConfig, DiagnosticsConfig, ExprFillDefaultMode,
     HighlightConfig, HighlightRelatedConfig, HoverConfig, HoverDocFormat, InlayHintsConfig,
@@ -419,6 +420,21 @@ config_data! {
         /// Number of syntax trees rust-analyzer keeps in memory. Defaults to 128.
         lru_capacity: Option<usize>                 = "null",
 
+        /// Names of the MIR optimization passes which don't run before the MIR is evaluated,
+        /// e.g. `const-propagation`.
+        mir_disabledPasses: Vec<String>             = "[]",
+        /// Whether to run the MIR based analyses: const evaluation, mutability diagnostics and
+        /// test interpretation.
+        mir_enable: bool                            = "true",
+        /// Maximum number of bytes the MIR interpreter may allocate when evaluating a constant.
+        /// The default `null` means no limit.
+        mir_eval_memoryLimit: Option<usize>         = "null",
+        /// Maximum number of MIR basic blocks executed when evaluating a constant.
+        mir_eval_stepLimit: usize                   = "100000",
+        /// Only run the MIR based analyses on the crates of the workspace, and not on their
+        /// dependencies.
+        mir_localCratesOnly: bool                   = "false",
+
         /// Whether to show `can't find Cargo.toml` error message.
         notifications_cargoTomlNotFound: bool      = "true",
 
@@ -813,6 +829,17 @@ impl Config {
                 serde_json::Error::custom("expected a non-empty string"),
             ));
         }
+        for name in &self.data.mir_disabledPasses {
+            if MirPass::from_name(name).is_none() {
+                let known = MirPass::ALL.iter().map(|it| it.name()).join(", ");
+                error_sink.push((
+                    "/mir/disabledPasses".to_string(),
+                    serde_json::Error::custom(format!(
+                        "unknown MIR pass `{name}`, expected one of {known}"
+                    )),
+                ));
+            }
+        }
     }
 
     pub fn json_schema() -> serde_json::Value {
@@ -1089,6 +1116,22 @@ impl Config {
         self.data.lru_capacity
     }
 
+    pub fn mir_config(&self) -> MirConfig {
+        MirConfig {
+            enable: self.data.mir_enable,
+            local_crates_only: self.data.mir_localCratesOnly,
+            eval_step_limit: self.data.mir_eval_stepLimit,
+            memory_limit: self.data.mir_eval_memoryLimit,
+            disabled_passes: self
+                .data
+                .mir_disabledPasses
+                .iter()
+                .filter_map(|name| MirPass::from_name(name))
+                .collect(),
+            ..MirConfig::default()
+        }
+    }
+
     pub fn proc_macro_srv(&self) -> Option<(AbsPathBuf, /* is path explicitly set */ bool)> {
         if !self.data.procMacro_enable {
             return None;
@@ -2388,4 +2431,19 @@ mod tests {
     fn remove_ws(text: &str) -> String {
         text.replace(char::is_whitespace, "")
     }
+
+    #[test]
+    fn unknown_mir_passes_are_reported() {
+        let mut config =
+            Config::new(AbsPathBuf::try_from(project_root()).unwrap(), Default::default(), vec![]);
+        let error = config
+            .update(serde_json::json!({
+                "mir": { "disabledPasses": ["simplify-cfg", "inline"] }
+            }))
+            .unwrap_err();
+        assert_eq!(error.errors.len(), 1);
+        assert_eq!(error.errors[0].0, "/mir/disabledPasses");
+        assert!(error.errors[0].1.to_string().contains("`inline`"));
+        assert_eq!(config.mir_config().disabled_passes, [MirPass::SimplifyCfg]);
+    }
 }
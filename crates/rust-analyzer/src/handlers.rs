@@ -162,6 +162,16 @@ pub(crate) fn handle_view_mir(
     Ok(res)
 }
 
+pub(crate) fn handle_show_panic_sites(
+    snap: GlobalStateSnapshot,
+    params: lsp_types::TextDocumentPositionParams,
+) -> Result<String> {
+    let _p = profile::span("handle_show_panic_sites");
+    let position = from_proto::file_position(&snap, params)?;
+    let res = snap.analysis.panic_sites(position)?;
+    Ok(res)
+}
+
 pub(crate) fn handle_view_file_text(
     snap: GlobalStateSnapshot,
     params: lsp_types::TextDocumentIdentifier,
@@ -1353,7 +1363,15 @@ pub(crate) fn publish_diagnostics(
             }),
             source: Some("rust-analyzer".to_string()),
             message: d.message,
-            related_information: None,
+            related_information: (!d.related.is_empty()).then(|| {
+                d.related
+                    .into_iter()
+                    .filter_map(|(frange, message)| {
+                        let location = to_proto::location(snap, frange).ok()?;
+                        Some(lsp_types::DiagnosticRelatedInformation { location, message })
+                    })
+                    .collect()
+            }),
             tags: if d.unused { Some(vec![DiagnosticTag::UNNECESSARY]) } else { None },
             data: None,
         })
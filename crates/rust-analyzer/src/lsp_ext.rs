@@ -90,6 +90,14 @@ impl Request for ViewMir {
     const METHOD: &'static str = "rust-analyzer/viewMir";
 }
 
+pub enum ShowPanicSites {}
+
+impl Request for ShowPanicSites {
+    type Params = lsp_types::TextDocumentPositionParams;
+    type Result = String;
+    const METHOD: &'static str = "rust-analyzer/showPanicSites";
+}
+
 pub enum ViewFileText {}
 
 impl Request for ViewFileText {
@@ -15,7 +15,7 @@
 use std::{collections::hash_map::Entry, iter, mem, sync::Arc};
 
 use flycheck::{FlycheckConfig, FlycheckHandle};
-use hir::db::DefDatabase;
+use hir::db::{DefDatabase, HirDatabase};
 use ide::Change;
 use ide_db::{
     base_db::{
@@ -90,6 +90,10 @@ impl GlobalState {
                 .raw_database_mut()
                 .set_enable_proc_attr_macros(self.config.expand_proc_attr_macros());
         }
+
+        if self.analysis_host.raw_database().mir_config() != self.config.mir_config() {
+            self.analysis_host.raw_database_mut().set_mir_config(self.config.mir_config());
+        }
     }
 
     pub(crate) fn current_status(&self) -> lsp_ext::ServerStatusParams {
@@ -10,7 +10,10 @@
 //! Available flags:
 //!     add:
 //!     as_ref: sized
+//!     assert: panic
 //!     bool_impl: option, fn
+//!     box: sized
+//!     cell: copy
 //!     clone: sized
 //!     coerce_unsized: unsize
 //!     copy: clone
@@ -31,11 +34,17 @@
 //!     infallible:
 //!     iterator: option
 //!     iterators: iterator, fn
+//!     manually_drop:
+//!     matches:
 //!     non_zero:
+//!     offset_of:
 //!     option:
 //!     ord: eq, option
+//!     panic: fmt, coerce_unsized
+//!     phantom_data:
 //!     pin:
 //!     range:
+//!     refcell: cell, deref_mut, drop, panic
 //!     result:
 //!     send: sized
 //!     sized:
@@ -77,6 +86,11 @@ pub mod marker {
     pub trait Unsize<T: ?Sized> {}
     // endregion:unsize
 
+    // region:phantom_data
+    #[lang = "phantom_data"]
+    pub struct PhantomData<T: ?Sized>;
+    // endregion:phantom_data
+
     // region:copy
     #[lang = "copy"]
     pub trait Copy: Clone {}
@@ -150,6 +164,155 @@ pub mod clone {
 }
 // endregion:clone
 
+// region:cell
+pub mod cell {
+    #[lang = "unsafe_cell"]
+    #[repr(transparent)]
+    pub struct UnsafeCell<T: ?Sized> {
+        value: T,
+    }
+
+    impl<T> UnsafeCell<T> {
+        pub const fn new(value: T) -> UnsafeCell<T> {
+            UnsafeCell { value }
+        }
+    }
+
+    impl<T: ?Sized> UnsafeCell<T> {
+        pub const fn get(&self) -> *mut T {
+            self as *const UnsafeCell<T> as *const T as *mut T
+        }
+    }
+
+    #[repr(transparent)]
+    pub struct Cell<T: ?Sized> {
+        value: UnsafeCell<T>,
+    }
+
+    impl<T> Cell<T> {
+        pub const fn new(value: T) -> Cell<T> {
+            Cell { value: UnsafeCell::new(value) }
+        }
+
+        pub fn set(&self, val: T) {
+            unsafe { *self.value.get() = val };
+        }
+    }
+
+    impl<T: Copy> Cell<T> {
+        pub fn get(&self) -> T {
+            unsafe { *self.value.get() }
+        }
+    }
+
+    // region:refcell
+    /// The number of shared borrows, or `-1` while the value is mutably borrowed.
+    type BorrowFlag = isize;
+
+    pub struct RefCell<T: ?Sized> {
+        borrow: Cell<BorrowFlag>,
+        value: UnsafeCell<T>,
+    }
+
+    impl<T> RefCell<T> {
+        pub const fn new(value: T) -> RefCell<T> {
+            RefCell { borrow: Cell::new(0), value: UnsafeCell::new(value) }
+        }
+    }
+
+    impl<T: ?Sized> RefCell<T> {
+        #[track_caller]
+        pub fn borrow(&self) -> Ref<'_, T> {
+            let b = self.borrow.get();
+            if b < 0 {
+                crate::panicking::panic("already mutably borrowed");
+            }
+            self.borrow.set(b + 1);
+            Ref { value: unsafe { &*self.value.get() }, borrow: &self.borrow }
+        }
+
+        #[track_caller]
+        pub fn borrow_mut(&self) -> RefMut<'_, T> {
+            if self.borrow.get() != 0 {
+                crate::panicking::panic("already borrowed");
+            }
+            self.borrow.set(-1);
+            RefMut { value: unsafe { &mut *self.value.get() }, borrow: &self.borrow }
+        }
+    }
+
+    pub struct Ref<'b, T: ?Sized + 'b> {
+        value: &'b T,
+        borrow: &'b Cell<BorrowFlag>,
+    }
+
+    impl<T: ?Sized> crate::ops::Deref for Ref<'_, T> {
+        type Target = T;
+        fn deref(&self) -> &T {
+            self.value
+        }
+    }
+
+    impl<T: ?Sized> crate::ops::Drop for Ref<'_, T> {
+        fn drop(&mut self) {
+            self.borrow.set(self.borrow.get() - 1);
+        }
+    }
+
+    pub struct RefMut<'b, T: ?Sized + 'b> {
+        value: &'b mut T,
+        borrow: &'b Cell<BorrowFlag>,
+    }
+
+    impl<T: ?Sized> crate::ops::Deref for RefMut<'_, T> {
+        type Target = T;
+        fn deref(&self) -> &T {
+            self.value
+        }
+    }
+
+    impl<T: ?Sized> crate::ops::DerefMut for RefMut<'_, T> {
+        fn deref_mut(&mut self) -> &mut T {
+            self.value
+        }
+    }
+
+    impl<T: ?Sized> crate::ops::Drop for RefMut<'_, T> {
+        fn drop(&mut self) {
+            self.borrow.set(0);
+        }
+    }
+    // endregion:refcell
+}
+// endregion:cell
+
+pub mod mem {
+    // region:manually_drop
+    #[lang = "manually_drop"]
+    #[repr(transparent)]
+    pub struct ManuallyDrop<T: ?Sized> {
+        value: T,
+    }
+
+    impl<T> ManuallyDrop<T> {
+        pub const fn new(value: T) -> ManuallyDrop<T> {
+            ManuallyDrop { value }
+        }
+    }
+
+    pub fn forget<T>(t: T) {
+        let _ = ManuallyDrop::new(t);
+    }
+    // endregion:manually_drop
+
+    // region:offset_of
+    #[rustc_builtin_macro]
+    pub macro offset_of($Container:ty, $($fields:tt).+ $(,)?) {
+        // The actual implementation is in the compiler
+    }
+    // endregion:offset_of
+}
+
 pub mod convert {
     // region:from
     pub trait From<T>: Sized {
@@ -589,6 +752,30 @@ pub mod cmp {
     #[lang = "partial_ord"]
     pub trait PartialOrd<Rhs: ?Sized = Self>: PartialEq<Rhs> {
         fn partial_cmp(&self, other: &Rhs) -> Option<Ordering>;
+        fn lt(&self, other: &Rhs) -> bool {
+            match self.partial_cmp(other) {
+                Some(Ordering::Less) => true,
+                _ => false,
+            }
+        }
+        fn le(&self, other: &Rhs) -> bool {
+            match self.partial_cmp(other) {
+                Some(Ordering::Less | Ordering::Equal) => true,
+                _ => false,
+            }
+        }
+        fn gt(&self, other: &Rhs) -> bool {
+            match self.partial_cmp(other) {
+                Some(Ordering::Greater) => true,
+                _ => false,
+            }
+        }
+        fn ge(&self, other: &Rhs) -> bool {
+            match self.partial_cmp(other) {
+                Some(Ordering::Greater | Ordering::Equal) => true,
+                _ => false,
+            }
+        }
     }
 
     pub trait Ord: Eq + PartialOrd<Self> {
@@ -623,9 +810,151 @@ pub mod fmt {
     pub trait Display {
         fn fmt(&self, f: &mut Formatter<'_>) -> Result;
     }
+
+    extern "rust-intrinsic" {
+        fn transmute<T, U>(x: T) -> U;
+    }
+
+    struct Opaque;
+
+    pub struct ArgumentV1<'a> {
+        value: &'a Opaque,
+        formatter: fn(&Opaque, &mut Formatter<'_>) -> Result,
+    }
+
+    impl<'a> ArgumentV1<'a> {
+        pub fn new<'b, T>(x: &'b T, f: fn(&T, &mut Formatter<'_>) -> Result) -> ArgumentV1<'b> {
+            unsafe { ArgumentV1 { formatter: transmute(f), value: transmute(x) } }
+        }
+    }
+
+    pub struct Arguments<'a> {
+        pieces: &'a [&'static str],
+        args: &'a [ArgumentV1<'a>],
+    }
+
+    impl<'a> Arguments<'a> {
+        pub const fn new_v1(
+            pieces: &'a [&'static str],
+            args: &'a [ArgumentV1<'a>],
+        ) -> Arguments<'a> {
+            Arguments { pieces, args }
+        }
+
+        pub const fn new_const(pieces: &'a [&'static str]) -> Arguments<'a> {
+            Arguments { pieces, args: &[] }
+        }
+    }
+
+    // region:derive
+    #[rustc_builtin_macro]
+    pub macro Debug($item:item) {}
+    // endregion:derive
 }
 // endregion:fmt
 
+// region:fmt
+#[macro_export]
+#[rustc_builtin_macro]
+macro_rules! const_format_args {
+    ($fmt:expr) => {{ /* compiler built-in */ }};
+    ($fmt:expr, $($args:tt)*) => {{ /* compiler built-in */ }};
+}
+
+#[macro_export]
+#[rustc_builtin_macro]
+macro_rules! format_args {
+    ($fmt:expr) => {{ /* compiler built-in */ }};
+    ($fmt:expr, $($args:tt)*) => {{ /* compiler built-in */ }};
+}
+// endregion:fmt
+
+// region:panic
+pub mod panic {
+    pub macro panic_2015 {
+        () => (
+            $crate::panicking::panic("explicit panic")
+        ),
+        ($($t:tt)+) => (
+            $crate::panicking::panic_fmt($crate::const_format_args!($($t)+))
+        ),
+    }
+
+    pub macro panic_2021 {
+        () => (
+            $crate::panicking::panic("explicit panic")
+        ),
+        ($($t:tt)+) => (
+            $crate::panicking::panic_fmt($crate::const_format_args!($($t)+))
+        ),
+    }
+}
+
+pub mod panicking {
+    #[track_caller]
+    #[lang = "panic_fmt"]
+    pub const fn panic_fmt(_fmt: crate::fmt::Arguments<'_>) -> ! {
+        loop {}
+    }
+
+    #[track_caller]
+    #[lang = "panic"]
+    pub const fn panic(expr: &'static str) -> ! {
+        panic_fmt(crate::fmt::Arguments::new_const(&[expr]))
+    }
+
+    // region:assert
+    #[track_caller]
+    pub fn assert_failed<T, U>(left: &T, right: &U) -> !
+    where
+        T: crate::fmt::Debug + ?Sized,
+        U: crate::fmt::Debug + ?Sized,
+    {
+        panic_fmt(crate::const_format_args!(
+            "assertion failed: `(left == right)`\n  left: `{:?}`,\n right: `{:?}`",
+            left,
+            right
+        ))
+    }
+    // endregion:assert
+}
+
+#[macro_export]
+#[rustc_builtin_macro(core_panic)]
+macro_rules! panic {
+    ($($arg:tt)*) => {
+        /* compiler built-in */
+    };
+}
+// endregion:panic
+
+// region:assert
+#[macro_export]
+macro_rules! assert_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        match (&$left, &$right) {
+            (left_val, right_val) => {
+                if !(*left_val == *right_val) {
+                    $crate::panicking::assert_failed(&*left_val, &*right_val);
+                }
+            }
+        }
+    };
+}
+// endregion:assert
+
+// region:matches
+#[macro_export]
+macro_rules! matches {
+    ($expression:expr, $pattern:pat $(if $guard:expr)? $(,)?) => {
+        match $expression {
+            $pattern $(if $guard)? => true,
+            _ => false
+        }
+    };
+}
+// endregion:matches
+
 // region:slice
 pub mod slice {
     #[lang = "slice"]
@@ -648,6 +977,7 @@ pub mod option {
     }
 
     impl<T> Option<T> {
+        #[track_caller]
         pub const fn unwrap(self) -> T {
             match self {
                 Some(val) => val,
@@ -729,6 +1059,35 @@ pub mod pin {
 }
 // endregion:pin
 
+// region:box
+pub mod boxed {
+    #[lang = "owned_box"]
+    #[fundamental]
+    pub struct Box<T: ?Sized> {
+        pointer: *mut T,
+    }
+    impl<T> Box<T> {
+        pub fn new(x: T) -> Box<T> {
+            box x
+        }
+    }
+    // region:deref
+    impl<T: ?Sized> crate::ops::Deref for Box<T> {
+        type Target = T;
+        fn deref(&self) -> &T {
+            &**self
+        }
+    }
+    // endregion:deref
+    // region:coerce_unsized
+    impl<T: ?Sized + crate::marker::Unsize<U>, U: ?Sized> crate::ops::CoerceUnsized<Box<U>>
+        for Box<T>
+    {
+    }
+    // endregion:coerce_unsized
+}
+// endregion:box
+
 // region:future
 pub mod future {
     use crate::{
@@ -953,6 +1312,7 @@ pub mod error {
 pub mod prelude {
     pub mod v1 {
         pub use crate::{
+            boxed::Box,                         // :box
             clone::Clone,                       // :clone
             cmp::{Eq, PartialEq},               // :eq
             cmp::{Ord, PartialOrd},             // :ord
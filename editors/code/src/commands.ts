@@ -413,11 +413,15 @@ export function syntaxTree(ctx: CtxInit): Cmd {
     };
 }
 
-function viewHirOrMir(ctx: CtxInit, xir: "hir" | "mir"): Cmd {
-    const viewXir = xir === "hir" ? "viewHir" : "viewMir";
-    const requestType = xir === "hir" ? ra.viewHir : ra.viewMir;
+function viewHirOrMir(ctx: CtxInit, xir: "hir" | "mir" | "panic-sites"): Cmd {
+    const viewXir = { hir: "viewHir", mir: "viewMir", "panic-sites": "showPanicSites" }[xir];
+    const requestType = { hir: ra.viewHir, mir: ra.viewMir, "panic-sites": ra.showPanicSites }[
+        xir
+    ];
+    // The list of panic sites is plain text, not Rust code to highlight.
+    const extension = xir === "panic-sites" ? "txt" : "rs";
     const tdcp = new (class implements vscode.TextDocumentContentProvider {
-        readonly uri = vscode.Uri.parse(`rust-analyzer-${xir}://${viewXir}/${xir}.rs`);
+        readonly uri = vscode.Uri.parse(`rust-analyzer-${xir}://${viewXir}/${xir}.${extension}`);
         readonly eventEmitter = new vscode.EventEmitter<vscode.Uri>();
         constructor() {
             vscode.workspace.onDidChangeTextDocument(
@@ -495,6 +499,14 @@ export function viewMir(ctx: CtxInit): Cmd {
     return viewHirOrMir(ctx, "mir");
 }
 
+// Opens the virtual file that will list the places where the function containing the cursor
+// position may panic
+//
+// The contents of the file come from the `TextDocumentContentProvider`
+export function showPanicSites(ctx: CtxInit): Cmd {
+    return viewHirOrMir(ctx, "panic-sites");
+}
+
 export function viewFileText(ctx: CtxInit): Cmd {
     const tdcp = new (class implements vscode.TextDocumentContentProvider {
         readonly uri = vscode.Uri.parse("rust-analyzer-file-text://viewFileText/file.rs");